@@ -1,4 +1,6 @@
 use super::memory::MemoryRegion;
+use super::option_bytes::OptionBytesRegion;
+use super::spi_flash::SpiFlash;
 use crate::{serialize::hex_option, CoreType};
 use serde::{Deserialize, Serialize};
 
@@ -81,6 +83,16 @@ pub struct Chip {
     pub jtag: Option<Jtag>,
     /// The default binary format for this chip
     pub default_binary_format: Option<BinaryFormat>,
+    /// Option bytes, UICR, OTP and fuse regions of the chip.
+    ///
+    /// These regions are not written by the flash algorithms, but
+    /// through a chip family specific sequence.
+    #[serde(default)]
+    pub option_bytes: Vec<OptionBytesRegion>,
+    /// External SPI NOR flashes, which are programmed through their memory
+    /// mapped controller instead of a flash algorithm.
+    #[serde(default)]
+    pub spi_flash: Vec<SpiFlash>,
 }
 
 impl Chip {
@@ -102,6 +114,8 @@ impl Chip {
             rtt_scan_ranges: None,
             jtag: None,
             default_binary_format: Some(BinaryFormat::Raw),
+            option_bytes: vec![],
+            spi_flash: vec![],
         }
     }
 }
@@ -1,4 +1,4 @@
-use crate::CoreAccessOptions;
+use crate::{CoreAccessOptions, MemoryRange, MemoryRegion};
 
 use super::chip::Chip;
 use super::flash_algorithm::RawFlashAlgorithm;
@@ -264,6 +264,29 @@ impl ChipFamily {
                     memory
                 );
             }
+
+            for region in &variant.option_bytes {
+                region
+                    .validate()
+                    .map_err(|e| format!("Variant {}: {}", variant.name, e))?;
+            }
+
+            for spi_flash in &variant.spi_flash {
+                spi_flash
+                    .validate()
+                    .map_err(|e| format!("Variant {}: {}", variant.name, e))?;
+
+                let covered = variant.memory_map.iter().any(|region| match region {
+                    MemoryRegion::Nvm(nvm) => nvm.range.contains_range(&spi_flash.address_range),
+                    _ => false,
+                });
+                if !covered {
+                    return Err(format!(
+                        "Variant {}: SPI flash {} is not covered by a NVM region",
+                        variant.name, spi_flash.name
+                    ));
+                }
+            }
         }
 
         Ok(())
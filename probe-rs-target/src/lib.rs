@@ -16,7 +16,9 @@ mod chip_family;
 mod flash_algorithm;
 mod flash_properties;
 mod memory;
+mod option_bytes;
 pub(crate) mod serialize;
+mod spi_flash;
 
 pub use chip::{
     ArmCoreAccessOptions, BinaryFormat, Chip, Core, CoreAccessOptions, Jtag,
@@ -31,3 +33,7 @@ pub use memory::{
     GenericRegion, MemoryRange, MemoryRegion, NvmRegion, PageInfo, RamRegion, SectorDescription,
     SectorInfo,
 };
+pub use option_bytes::{
+    OptionBytesField, OptionBytesFieldValue, OptionBytesKind, OptionBytesRegion,
+};
+pub use spi_flash::{RegisterWrite, SpiFlash, SpiFlashController, Stm32QuadspiOptions};
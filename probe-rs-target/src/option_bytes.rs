@@ -0,0 +1,285 @@
+use crate::serialize::{hex_range, hex_u_int};
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// The kind of configuration memory described by an [`OptionBytesRegion`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptionBytesKind {
+    /// Option bytes or configuration words, e.g. the STM32 option bytes.
+    OptionBytes,
+    /// A user information configuration register block, e.g. the nRF UICR.
+    Uicr,
+    /// One-time programmable memory rows.
+    Otp,
+    /// Fuses, which can usually only be changed from 1 to 0.
+    Fuses,
+}
+
+/// A named value of an [`OptionBytesField`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OptionBytesFieldValue {
+    /// The name of the value, e.g. `Level1`.
+    pub name: String,
+    /// The raw value of the field.
+    #[serde(serialize_with = "hex_u_int")]
+    pub value: u64,
+    /// A description of what this value does.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// True if writing this value cannot be undone, e.g. STM32 RDP level 2.
+    #[serde(default)]
+    pub irreversible: bool,
+}
+
+/// A bitfield inside an [`OptionBytesRegion`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OptionBytesField {
+    /// The name of the field, e.g. `RDP`.
+    pub name: String,
+    /// A description of the field.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Offset of the 32-bit word containing the field, relative to the start of the region.
+    #[serde(serialize_with = "hex_u_int")]
+    pub offset: u64,
+    /// Position of the least significant bit of the field inside the word.
+    pub bit_offset: u8,
+    /// Width of the field in bits.
+    pub bit_width: u8,
+    /// Named values of the field.
+    #[serde(default)]
+    pub values: Vec<OptionBytesFieldValue>,
+}
+
+impl OptionBytesField {
+    /// The mask of the field, not shifted to its position in the word.
+    pub fn mask(&self) -> u32 {
+        if self.bit_width >= 32 {
+            u32::MAX
+        } else {
+            (1 << self.bit_width) - 1
+        }
+    }
+
+    /// Extract the value of the field from the raw contents of its region.
+    ///
+    /// Returns `None` if `data` is too short to contain the field.
+    pub fn extract(&self, data: &[u8]) -> Option<u32> {
+        let word = self.word(data)?;
+
+        Some((word >> self.bit_offset) & self.mask())
+    }
+
+    /// Insert `value` into the raw contents of the region, leaving all other bits untouched.
+    ///
+    /// Returns `None` if `data` is too short to contain the field, or if `value` does not fit.
+    pub fn insert(&self, data: &mut [u8], value: u32) -> Option<()> {
+        if value & !self.mask() != 0 {
+            return None;
+        }
+
+        let word = self.word(data)?;
+        let mask = self.mask() << self.bit_offset;
+        let word = (word & !mask) | (value << self.bit_offset);
+
+        let offset = self.offset as usize;
+        data[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+
+        Some(())
+    }
+
+    /// Look up a named value of this field.
+    pub fn value_by_name(&self, name: &str) -> Option<&OptionBytesFieldValue> {
+        self.values
+            .iter()
+            .find(|value| value.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Look up the name of a raw field value.
+    pub fn value_by_raw(&self, raw: u32) -> Option<&OptionBytesFieldValue> {
+        self.values.iter().find(|value| value.value == raw as u64)
+    }
+
+    fn word(&self, data: &[u8]) -> Option<u32> {
+        let offset = self.offset as usize;
+        let bytes = data.get(offset..offset + 4)?;
+
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+/// Describes a region of special-purpose non-volatile memory, such as
+/// option bytes, UICR, OTP rows or fuses.
+///
+/// These regions cannot be written by the regular flash algorithms, and are
+/// programmed using a chip family specific sequence instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OptionBytesRegion {
+    /// A name to describe the region, e.g. `UICR`.
+    pub name: String,
+    /// The kind of configuration memory.
+    pub kind: OptionBytesKind,
+    /// Address range of the region.
+    #[serde(serialize_with = "hex_range")]
+    pub range: Range<u64>,
+    /// True if any write to this region cannot be undone.
+    #[serde(default)]
+    pub irreversible: bool,
+    /// The bitfields contained in the region.
+    #[serde(default)]
+    pub fields: Vec<OptionBytesField>,
+}
+
+impl OptionBytesRegion {
+    /// Size of the region in bytes.
+    pub fn size(&self) -> u64 {
+        self.range.end - self.range.start
+    }
+
+    /// Look up a field by its name.
+    pub fn field(&self, name: &str) -> Option<&OptionBytesField> {
+        self.fields
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns true if changing the contents of the region from `old` to `new`
+    /// cannot be undone.
+    pub fn is_irreversible_change(&self, old: &[u8], new: &[u8]) -> bool {
+        if old == new {
+            return false;
+        }
+
+        if self.irreversible {
+            return true;
+        }
+
+        self.fields.iter().any(|field| {
+            let new_value = field.extract(new);
+
+            new_value != field.extract(old)
+                && new_value
+                    .and_then(|raw| field.value_by_raw(raw))
+                    .map(|value| value.irreversible)
+                    .unwrap_or(false)
+        })
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.range.start % 4 != 0 || self.size() % 4 != 0 || self.size() == 0 {
+            return Err(format!(
+                "option bytes region {} must be a non-empty, word aligned range",
+                self.name
+            ));
+        }
+
+        for field in &self.fields {
+            if field.bit_width == 0 || field.bit_offset as u32 + field.bit_width as u32 > 32 {
+                return Err(format!(
+                    "field {} of option bytes region {} does not fit into a 32-bit word",
+                    field.name, self.name
+                ));
+            }
+
+            if field.offset % 4 != 0 || field.offset + 4 > self.size() {
+                return Err(format!(
+                    "field {} of option bytes region {} is not word aligned or outside of the region",
+                    field.name, self.name
+                ));
+            }
+
+            for value in &field.values {
+                if value.value > field.mask() as u64 {
+                    return Err(format!(
+                        "value {} of field {} in option bytes region {} does not fit into the field",
+                        value.name, field.name, self.name
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rdp_region() -> OptionBytesRegion {
+        OptionBytesRegion {
+            name: "OPTR".into(),
+            kind: OptionBytesKind::OptionBytes,
+            range: 0x4002_2020..0x4002_2024,
+            irreversible: false,
+            fields: vec![
+                OptionBytesField {
+                    name: "RDP".into(),
+                    description: None,
+                    offset: 0,
+                    bit_offset: 0,
+                    bit_width: 8,
+                    values: vec![
+                        OptionBytesFieldValue {
+                            name: "Level0".into(),
+                            value: 0xAA,
+                            description: None,
+                            irreversible: false,
+                        },
+                        OptionBytesFieldValue {
+                            name: "Level2".into(),
+                            value: 0xCC,
+                            description: None,
+                            irreversible: true,
+                        },
+                    ],
+                },
+                OptionBytesField {
+                    name: "nBOOT1".into(),
+                    description: None,
+                    offset: 0,
+                    bit_offset: 23,
+                    bit_width: 1,
+                    values: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn extract_and_insert_field() {
+        let region = rdp_region();
+        let mut data = 0xFFEF_F8AAu32.to_le_bytes();
+
+        let rdp = region.field("rdp").unwrap();
+        assert_eq!(rdp.extract(&data), Some(0xAA));
+
+        rdp.insert(&mut data, 0xBB).unwrap();
+        assert_eq!(u32::from_le_bytes(data), 0xFFEF_F8BB);
+
+        let boot1 = region.field("nBOOT1").unwrap();
+        boot1.insert(&mut data, 0).unwrap();
+        assert_eq!(u32::from_le_bytes(data), 0xFF6F_F8BB);
+
+        assert!(boot1.insert(&mut data, 2).is_none());
+    }
+
+    #[test]
+    fn irreversible_change() {
+        let region = rdp_region();
+        let old = 0xFFEF_F8AAu32.to_le_bytes();
+
+        assert!(!region.is_irreversible_change(&old, &0xFFEF_F8BBu32.to_le_bytes()));
+        assert!(region.is_irreversible_change(&old, &0xFFEF_F8CCu32.to_le_bytes()));
+        assert!(!region.is_irreversible_change(&old, &old));
+    }
+
+    #[test]
+    fn validate_field_outside_region() {
+        let mut region = rdp_region();
+        region.fields[0].offset = 4;
+
+        assert!(region.validate().is_err());
+    }
+}
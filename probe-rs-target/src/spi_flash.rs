@@ -0,0 +1,87 @@
+use crate::serialize::{hex_option, hex_range, hex_u_int};
+use core::ops::Range;
+use serde::{Deserialize, Serialize};
+
+/// Describes an external SPI NOR flash, which is memory mapped through a SPI flash controller.
+///
+/// Instead of a flash algorithm compiled for the chip, the flash is programmed by
+/// driving the controller from the debugger. The layout of the flash, e.g. the sector
+/// sizes and the erase instructions, is discovered through the JEDEC SFDP tables of the flash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SpiFlash {
+    /// A name to describe the flash, e.g. `QSPI`.
+    pub name: String,
+    /// The address range in which the flash is memory mapped.
+    ///
+    /// The range must be covered by a NVM region of the memory map. The size of
+    /// the flash is discovered through SFDP, and may be smaller than this range.
+    #[serde(serialize_with = "hex_range")]
+    pub address_range: Range<u64>,
+    /// The controller used to access the flash.
+    pub controller: SpiFlashController,
+    /// Register writes which are executed before the controller is used, e.g.
+    /// to enable the clock of the controller and configure the pins.
+    #[serde(default)]
+    pub init: Vec<RegisterWrite>,
+}
+
+/// The SPI flash controllers which can be driven by the generic SPI flash algorithm.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpiFlashController {
+    /// The QUADSPI peripheral of STM32F7, STM32H7, STM32L4 and others.
+    Stm32Quadspi(Stm32QuadspiOptions),
+    /// The SSI peripheral of the RP2040, used for the boot flash.
+    Rp2040Ssi,
+}
+
+/// Options for the [`SpiFlashController::Stm32Quadspi`] controller.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Stm32QuadspiOptions {
+    /// Base address of the QUADSPI registers.
+    #[serde(serialize_with = "hex_u_int")]
+    pub registers: u64,
+    /// The clock prescaler, the SPI clock is the kernel clock divided by `prescaler + 1`.
+    #[serde(default = "default_quadspi_prescaler")]
+    pub prescaler: u8,
+    /// Use the second flash bank, connected to the BK2 pins.
+    #[serde(default)]
+    pub flash_select: bool,
+}
+
+fn default_quadspi_prescaler() -> u8 {
+    7
+}
+
+/// A write to a memory mapped register.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RegisterWrite {
+    /// The address of the 32-bit register.
+    #[serde(serialize_with = "hex_u_int")]
+    pub address: u64,
+    /// The value to write.
+    #[serde(serialize_with = "hex_u_int")]
+    pub value: u32,
+    /// If set, only the bits in the mask are modified, and all other bits of the register are preserved.
+    #[serde(default, serialize_with = "hex_option")]
+    pub mask: Option<u32>,
+}
+
+impl SpiFlash {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.address_range.is_empty() {
+            return Err(format!(
+                "SPI flash {} must have a non-empty address range",
+                self.name
+            ));
+        }
+
+        if let Some(write) = self.init.iter().find(|write| write.address % 4 != 0) {
+            return Err(format!(
+                "init register {:#010x} of SPI flash {} is not word aligned",
+                write.address, self.name
+            ));
+        }
+
+        Ok(())
+    }
+}
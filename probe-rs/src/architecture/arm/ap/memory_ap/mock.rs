@@ -77,7 +77,8 @@ impl ApAccess for MockMemoryAp {
                 let csw = CSW::try_from(csw).unwrap();
 
                 let (new_drw, offset) = match csw.SIZE {
-                    DataSize::U32 => {
+                    // A 64-bit access consists of two accesses to DRW, the lower word first.
+                    DataSize::U32 | DataSize::U64 => {
                         let bytes: [u8; 4] = self
                             .memory
                             .get(offset..offset + 4)
@@ -167,7 +168,7 @@ impl ApAccess for MockMemoryAp {
 
                 let bit_offset = (address % 4) * 8;
                 match csw.SIZE {
-                    DataSize::U32 => {
+                    DataSize::U32 | DataSize::U64 => {
                         self.memory[address as usize..address as usize + 4]
                             .copy_from_slice(&value.to_le_bytes());
                         Ok(4)
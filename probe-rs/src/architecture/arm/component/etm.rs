@@ -0,0 +1,270 @@
+//! Module for configuring an ETMv4 (Embedded Trace Macrocell) trace unit.
+//!
+//! The ETM generates instruction trace, which can be decoded with
+//! [`EtmV4Decoder`](crate::architecture::arm::etm::EtmV4Decoder).
+//!
+//! See the ARM Embedded Trace Macrocell Architecture Specification ETMv4 (ARM IHI 0064) for more
+//! info.
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use super::DebugComponentInterface;
+use crate::architecture::arm::{memory::CoresightComponent, ArmError, ArmProbeInterface};
+use crate::memory_mapped_bitfield_register;
+
+const REGISTER_OFFSET_TRCPRGCTLR: u32 = 0x004;
+const REGISTER_OFFSET_TRCEVENTCTL0R: u32 = 0x020;
+const REGISTER_OFFSET_TRCEVENTCTL1R: u32 = 0x024;
+const REGISTER_OFFSET_TRCSTALLCTLR: u32 = 0x02C;
+const REGISTER_OFFSET_TRCTSCTLR: u32 = 0x030;
+const REGISTER_OFFSET_TRCSYNCPR: u32 = 0x034;
+const REGISTER_OFFSET_TRCCCCTLR: u32 = 0x038;
+const REGISTER_OFFSET_TRCBBCTLR: u32 = 0x03C;
+const REGISTER_OFFSET_TRCTRACEIDR: u32 = 0x040;
+const REGISTER_OFFSET_TRCVIIECTLR: u32 = 0x084;
+const REGISTER_OFFSET_TRCVISSCTLR: u32 = 0x088;
+const REGISTER_OFFSET_TRCACVR: u32 = 0x400;
+const REGISTER_OFFSET_TRCACATR: u32 = 0x480;
+const REGISTER_OFFSET_TRCOSLAR: u32 = 0x300;
+const REGISTER_OFFSET_TRCLAR: u32 = 0xFB0;
+
+/// The trace ID used if none is configured, which differs from [`ITM_TRACE_ID`](super::ITM_TRACE_ID).
+pub const DEFAULT_ETM_TRACE_ID: u8 = 0x10;
+
+/// The configuration of the instruction trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EtmConfig {
+    /// The ID of the trace source, used to separate it from other trace sources in the trace
+    /// memory. Valid IDs are 0x01 to 0x6F.
+    pub trace_id: u8,
+    /// Trace the target address of every taken branch instead of only indirect ones. This makes
+    /// the trace larger, but more robust against code which doesn't match the program.
+    pub branch_broadcast: bool,
+    /// Trace the number of cycles between instructions.
+    pub cycle_accurate: bool,
+    /// Only trace the instructions in these address ranges, or all instructions if empty.
+    pub address_ranges: Vec<Range<u64>>,
+}
+
+impl Default for EtmConfig {
+    fn default() -> Self {
+        Self {
+            trace_id: DEFAULT_ETM_TRACE_ID,
+            branch_broadcast: false,
+            cycle_accurate: false,
+            address_ranges: Vec::new(),
+        }
+    }
+}
+
+/// An ETMv4 trace unit on target.
+pub struct Etm<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> Etm<'a> {
+    /// Creates a new ETM component representation.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Etm {
+            component,
+            interface,
+        }
+    }
+
+    /// Returns the major and minor version of the ETM architecture.
+    pub fn version(&mut self) -> Result<(u8, u8), ArmError> {
+        let idr1 = Trcidr1::load(self.component, self.interface)?;
+        Ok((idr1.trcarchmaj(), idr1.trcarchmin()))
+    }
+
+    /// Returns whether cycle count packets don't contain a commit count (TRCIDR0.COMMOPT), which
+    /// is needed to decode them.
+    pub fn commit_opt(&mut self) -> Result<bool, ArmError> {
+        Ok(Trcidr0::load(self.component, self.interface)?.commopt())
+    }
+
+    /// Unlocks the registers of the ETM.
+    pub fn unlock(&mut self) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCLAR, 0xC5AC_CE55)?;
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCOSLAR, 0)
+    }
+
+    /// Disables the ETM and applies `config`. Tracing starts with [`Etm::enable`].
+    pub fn configure(&mut self, config: &EtmConfig) -> Result<(), ArmError> {
+        let idr4 = Trcidr4::load(self.component, self.interface)?;
+        if config.address_ranges.len() > idr4.numacpairs() as usize {
+            return Err(ArmError::OutOfBounds);
+        }
+
+        self.unlock()?;
+        self.disable()?;
+
+        let mut configr = Trcconfigr(0);
+        configr.set_bb(config.branch_broadcast);
+        configr.set_cci(config.cycle_accurate);
+        configr.store(self.component, self.interface)?;
+
+        for offset in [
+            REGISTER_OFFSET_TRCEVENTCTL0R,
+            REGISTER_OFFSET_TRCEVENTCTL1R,
+            REGISTER_OFFSET_TRCSTALLCTLR,
+            REGISTER_OFFSET_TRCTSCTLR,
+            REGISTER_OFFSET_TRCVISSCTLR,
+        ] {
+            self.component.write_reg(self.interface, offset, 0)?;
+        }
+        // Generate a synchronization every 2^12 bytes of trace.
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCSYNCPR, 0xC)?;
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_TRCCCCTLR,
+            if config.cycle_accurate { 0x10 } else { 0 },
+        )?;
+        // Branch broadcasting outside of no range, i.e. everywhere.
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCBBCTLR, 0)?;
+        self.component.write_reg(
+            self.interface,
+            REGISTER_OFFSET_TRCTRACEIDR,
+            u32::from(config.trace_id & 0x7F),
+        )?;
+
+        // Every range uses a pair of address comparators.
+        let mut include = 0;
+        for (pair, range) in config.address_ranges.iter().enumerate() {
+            let (low, high) = (pair as u32 * 2, pair as u32 * 2 + 1);
+            self.write_comparator(low, range.start)?;
+            self.write_comparator(high, range.end.saturating_sub(1))?;
+            include |= 1 << pair;
+        }
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCVIIECTLR, include)?;
+
+        let mut victlr = Trcvictlr(0);
+        // Resource 1 is always active, so instructions are traced whenever they are included.
+        victlr.set_event(1);
+        victlr.set_ssstatus(true);
+        victlr.store(self.component, self.interface)
+    }
+
+    fn write_comparator(&mut self, comparator: u32, address: u64) -> Result<(), ArmError> {
+        let offset = REGISTER_OFFSET_TRCACVR + comparator * 8;
+        self.component
+            .write_reg(self.interface, offset, address as u32)?;
+        self.component
+            .write_reg(self.interface, offset + 4, (address >> 32) as u32)?;
+        // Compare instruction addresses in all security states and exception levels.
+        let offset = REGISTER_OFFSET_TRCACATR + comparator * 8;
+        self.component.write_reg(self.interface, offset, 0)?;
+        self.component.write_reg(self.interface, offset + 4, 0)
+    }
+
+    /// Starts tracing.
+    pub fn enable(&mut self) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCPRGCTLR, 1)?;
+        self.wait_for_idle(false)
+    }
+
+    /// Stops tracing, and waits until all trace was emitted.
+    pub fn disable(&mut self) -> Result<(), ArmError> {
+        self.component
+            .write_reg(self.interface, REGISTER_OFFSET_TRCPRGCTLR, 0)?;
+        self.wait_for_idle(true)
+    }
+
+    fn wait_for_idle(&mut self, idle: bool) -> Result<(), ArmError> {
+        let start = Instant::now();
+        while Trcstatr::load(self.component, self.interface)?.idle() != idle {
+            if start.elapsed() > Duration::from_millis(100) {
+                return Err(ArmError::Timeout);
+            }
+        }
+        Ok(())
+    }
+}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcstatr(u32);
+    0x00C, "TRCSTATR",
+    impl From;
+    pub pmstable, _: 1;
+    pub idle, _: 0;
+}
+
+impl DebugComponentInterface for Trcstatr {}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcconfigr(u32);
+    0x010, "TRCCONFIGR",
+    impl From;
+    pub dv, set_dv: 17;
+    pub da, set_da: 16;
+    pub vmidopt, set_vmidopt: 15;
+    pub u8, qe, set_qe: 14, 13;
+    pub rs, set_rs: 12;
+    pub ts, set_ts: 11;
+    pub u8, cond, set_cond: 10, 8;
+    pub vmid, set_vmid: 7;
+    pub cid, set_cid: 6;
+    pub cci, set_cci: 4;
+    pub bb, set_bb: 3;
+    pub u8, instp0, set_instp0: 2, 1;
+}
+
+impl DebugComponentInterface for Trcconfigr {}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcvictlr(u32);
+    0x080, "TRCVICTLR",
+    impl From;
+    pub u8, exlevel_ns, set_exlevel_ns: 23, 20;
+    pub u8, exlevel_s, set_exlevel_s: 19, 16;
+    pub trcerr, set_trcerr: 11;
+    pub trcreset, set_trcreset: 10;
+    pub ssstatus, set_ssstatus: 9;
+    pub u8, event, set_event: 7, 0;
+}
+
+impl DebugComponentInterface for Trcvictlr {}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcidr0(u32);
+    0x1E0, "TRCIDR0",
+    impl From;
+    pub commopt, _: 29;
+    pub u8, tssize, _: 28, 24;
+    pub trccci, _: 7;
+    pub trccond, _: 6;
+    pub trcbb, _: 5;
+}
+
+impl DebugComponentInterface for Trcidr0 {}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcidr1(u32);
+    0x1E4, "TRCIDR1",
+    impl From;
+    pub u8, trcarchmaj, _: 11, 8;
+    pub u8, trcarchmin, _: 7, 4;
+    pub u8, revision, _: 3, 0;
+}
+
+impl DebugComponentInterface for Trcidr1 {}
+
+memory_mapped_bitfield_register! {
+    pub struct Trcidr4(u32);
+    0x1F0, "TRCIDR4",
+    impl From;
+    pub u8, numacpairs, _: 3, 0;
+}
+
+impl DebugComponentInterface for Trcidr4 {}
@@ -8,6 +8,9 @@ use crate::{Error, MemoryMappedRegister};
 
 pub const _ITM_PID: [u8; 8] = [0x1, 0xB0, 0x3b, 0x0, 0x4, 0x0, 0x0, 0x0];
 
+/// The trace bus ID (ATID) of the ITM, which separates its data from other trace sources.
+pub const ITM_TRACE_ID: u8 = 13;
+
 /// An interface to control the ITM (Instrumentation Trace Macrocell) of a MCU.
 ///
 /// The ITM generates trace information as packets. There are four sources that can generate packets.
@@ -69,7 +72,7 @@ impl<'a> Itm<'a> {
         value |= 1 << 2; // SYNENA: Enable sync pulses, note DWT_CTRL.SYNCTAP must be configured.
         value |= 1 << 3; // TXENA: forward DWT packets to ITM
         value |= 1 << 11; // GTSFREQ: generate global timestamp every 8192 cycles
-        value |= 13 << 16; // 7 bits trace bus ID
+        value |= (ITM_TRACE_ID as u32) << 16; // 7 bits trace bus ID
         self.component
             .write_reg(self.interface, REGISTER_OFFSET_ITM_TCR, value)?;
 
@@ -1,7 +1,9 @@
 //! Types and functions for interacting with CoreSight Components
 
 mod dwt;
+mod etm;
 mod itm;
+mod mtb;
 mod scs;
 mod swo;
 mod tmc;
@@ -17,12 +19,14 @@ use crate::architecture::arm::core::armv6m::Demcr;
 use crate::architecture::arm::{ArmProbeInterface, SwoConfig, SwoMode};
 use crate::{Core, Error, MemoryInterface, MemoryMappedRegister};
 
-pub use self::itm::Itm;
+pub use self::itm::{Itm, ITM_TRACE_ID};
 pub use dwt::Dwt;
+pub use etm::{Etm, EtmConfig, DEFAULT_ETM_TRACE_ID};
+pub use mtb::{executed_ranges, parse_packets, Mtb, MtbPacket};
 pub use scs::Scs;
 pub use swo::Swo;
 pub use tmc::TraceMemoryController;
-pub use tpiu::Tpiu;
+pub use tpiu::{Tpiu, TpiuDeformatter};
 pub use trace_funnel::TraceFunnel;
 
 /// Specifies the data sink (destination) for trace data.
@@ -277,6 +281,7 @@ pub(crate) fn setup_tracing(
 /// # Args
 /// * `interface` - The interface with the debug probe.
 /// * `components` - The CoreSight debug components identified in the system.
+/// * `source` - The ATID of the trace source to extract, e.g. [`ITM_TRACE_ID`].
 ///
 /// # Note
 /// This function will read any available trace data in trace memory without blocking. At most,
@@ -289,6 +294,7 @@ pub(crate) fn setup_tracing(
 pub(crate) fn read_trace_memory(
     interface: &mut dyn ArmProbeInterface,
     components: &[CoresightComponent],
+    source: u8,
 ) -> Result<Vec<u8>, ArmError> {
     let mut tmc =
         TraceMemoryController::new(interface, find_component(components, PeripheralType::Tmc)?);
@@ -323,27 +329,25 @@ pub(crate) fn read_trace_memory(
     }
 
     // The TMC formats data into frames, as it contains trace data from multiple data sources. We
-    // need to deserialize the frames and pull out only the data source of interest. For now, all
-    // we care about is the ITM data.
+    // need to deserialize the frames and pull out only the data source of interest.
 
     let mut id = 0.into();
-    let mut itm_trace = Vec::new();
+    let mut trace = Vec::new();
 
     // Process each formatted frame and extract the multiplexed trace data.
     for frame_buffer in etf_trace.chunks_exact(16) {
         let mut frame = tmc::Frame::new(frame_buffer, id);
         for (id, data) in &mut frame {
             match id.into() {
-                // ITM ATID, see Itm::tx_enable()
-                13 => itm_trace.push(data),
+                id if id == source => trace.push(data),
                 0 => (),
-                id => tracing::warn!("Unexpected trace source ATID {id}: {data}, ignoring"),
+                id => tracing::debug!("Trace source ATID {id}: {data}, ignoring"),
             }
         }
         id = frame.id();
     }
 
-    Ok(itm_trace)
+    Ok(trace)
 }
 
 /// Configures DWT trace unit `unit` to begin tracing `address`.
@@ -0,0 +1,311 @@
+//! Module for interacting with the Micro Trace Buffer (MTB) of Cortex-M0+ cores.
+//!
+//! The MTB records the source and destination address of every non-sequential change of the
+//! program flow into a circular buffer in SRAM. Together with the program, this is enough to
+//! reconstruct the executed instructions, e.g. the ones leading up to a fault.
+//!
+//! See the CoreSight MTB-M0+ technical reference manual (ARM DDI 0486) for more info.
+
+use std::ops::RangeInclusive;
+
+use super::DebugComponentInterface;
+use crate::architecture::arm::{memory::CoresightComponent, ArmError, ArmProbeInterface};
+use crate::memory_mapped_bitfield_register;
+
+/// The size of a packet in the trace buffer, in bytes.
+const PACKET_SIZE: u32 = 8;
+
+/// The smallest trace buffer supported by the MTB, in bytes.
+const MIN_BUFFER_SIZE: u32 = 16;
+
+/// The source address of a return from an exception handler, which is the `EXC_RETURN` value
+/// and not the address of the returning instruction.
+const EXC_RETURN_MIN: u32 = 0xF000_0000;
+
+/// The longest sequential execution that is reconstructed from two packets. Longer ranges can only
+/// result from a corrupt or overwritten buffer.
+const MAX_SEQUENTIAL_RANGE: u32 = 0x1_0000;
+
+/// A Micro Trace Buffer unit on target.
+pub struct Mtb<'a> {
+    component: &'a CoresightComponent,
+    interface: &'a mut dyn ArmProbeInterface,
+}
+
+impl<'a> Mtb<'a> {
+    /// Creates a new MTB component representation.
+    pub fn new(
+        interface: &'a mut dyn ArmProbeInterface,
+        component: &'a CoresightComponent,
+    ) -> Self {
+        Mtb {
+            component,
+            interface,
+        }
+    }
+
+    /// Returns the address of the SRAM which the trace buffer is placed in.
+    pub fn base(&mut self) -> Result<u32, ArmError> {
+        Ok(Base::load(self.component, self.interface)?.into())
+    }
+
+    /// Places the trace buffer at `offset` bytes from the [`base`](Self::base) of the SRAM.
+    ///
+    /// The size is rounded down to a power of two of at least 16 bytes, and the offset has to be
+    /// aligned to the size. The firmware must not use this part of the SRAM while tracing.
+    pub fn configure(&mut self, offset: u32, size: u32) -> Result<(), ArmError> {
+        let size = size.max(MIN_BUFFER_SIZE);
+        let mask = size.ilog2() - MIN_BUFFER_SIZE.ilog2();
+        let size = 1 << size.ilog2();
+        if offset % size != 0 {
+            return Err(ArmError::MemoryNotAligned {
+                address: offset as u64,
+                alignment: size as usize,
+            });
+        }
+
+        let mut master = Master::load(self.component, self.interface)?;
+        master.set_en(false);
+        master.set_mask(mask as u8);
+        master.store(self.component, self.interface)?;
+
+        let mut position = Position(0);
+        position.set_pointer(offset / PACKET_SIZE);
+        position.set_wrap(false);
+        position.store(self.component, self.interface)
+    }
+
+    /// Sets the position in the trace buffer at which the trace stops, or the core halts if
+    /// `halt` is set. `None` disables the watermark.
+    pub fn set_watermark(&mut self, watermark: Option<u32>, halt: bool) -> Result<(), ArmError> {
+        let mut flow = Flow(0);
+        if let Some(watermark) = watermark {
+            flow.set_watermark(watermark / PACKET_SIZE);
+            flow.set_autohalt(halt);
+            flow.set_autostop(!halt);
+        }
+        flow.store(self.component, self.interface)
+    }
+
+    /// Starts tracing.
+    pub fn start(&mut self) -> Result<(), ArmError> {
+        let mut master = Master::load(self.component, self.interface)?;
+        master.set_en(true);
+        master.store(self.component, self.interface)
+    }
+
+    /// Stops tracing.
+    pub fn stop(&mut self) -> Result<(), ArmError> {
+        let mut master = Master::load(self.component, self.interface)?;
+        master.set_en(false);
+        master.store(self.component, self.interface)
+    }
+
+    /// Reads the packets in the trace buffer, from the oldest to the newest one.
+    ///
+    /// Tracing should be stopped before, so that the buffer doesn't change while it is read.
+    pub fn read_packets(&mut self) -> Result<Vec<MtbPacket>, ArmError> {
+        let base = self.base()?;
+        let master = Master::load(self.component, self.interface)?;
+        let position = Position::load(self.component, self.interface)?;
+
+        let size = MIN_BUFFER_SIZE << master.mask();
+        let pointer = position.pointer() * PACKET_SIZE;
+        let start = pointer - pointer % size;
+
+        let mut buffer = vec![0; (size / 4) as usize];
+        let mut memory = self.interface.memory_interface(self.component.ap)?;
+        memory.read_32((base + start) as u64, &mut buffer)?;
+
+        Ok(parse_packets(
+            &buffer,
+            (pointer - start) / PACKET_SIZE,
+            position.wrap(),
+        ))
+    }
+}
+
+/// A change of the program flow recorded by the MTB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtbPacket {
+    /// The address of the branch, or the `EXC_RETURN` value for a return from an exception.
+    pub source: u32,
+    /// The address of the first instruction executed after the branch.
+    pub destination: u32,
+    /// The change of flow is the entry to or the return from an exception (the A-bit).
+    pub exception: bool,
+    /// This is the first packet after tracing was started (the S-bit).
+    pub start: bool,
+}
+
+impl MtbPacket {
+    fn from_words(source: u32, destination: u32) -> Self {
+        Self {
+            source: source & !1,
+            destination: destination & !1,
+            exception: source & 1 != 0,
+            start: destination & 1 != 0,
+        }
+    }
+}
+
+/// Parses the packets of a trace buffer, from the oldest to the newest one.
+///
+/// `next` is the index of the packet which is written next, and `wrapped` is set if the buffer
+/// wrapped around at least once, in which case the oldest packet is at `next`.
+pub fn parse_packets(buffer: &[u32], next: u32, wrapped: bool) -> Vec<MtbPacket> {
+    let packets = buffer
+        .chunks_exact(2)
+        .map(|words| MtbPacket::from_words(words[0], words[1]));
+
+    let next = next as usize;
+    if wrapped {
+        packets
+            .clone()
+            .skip(next)
+            .chain(packets.take(next))
+            .collect()
+    } else {
+        packets.take(next).collect()
+    }
+}
+
+/// Reconstructs the address ranges which were executed sequentially between the packets.
+///
+/// Every range contains the addresses of the first and the last instruction executed, in
+/// chronological order. `pc` is the current program counter of the halted core, which ends the
+/// range after the newest packet. Ranges which can not be reconstructed are left out.
+pub fn executed_ranges(packets: &[MtbPacket], pc: Option<u32>) -> Vec<RangeInclusive<u32>> {
+    let mut ranges = Vec::new();
+
+    for (packet, next) in packets.iter().zip(packets.iter().skip(1)) {
+        // After tracing was restarted, the flow in between is unknown.
+        if next.start {
+            continue;
+        }
+
+        let end = if next.exception && next.source >= EXC_RETURN_MIN {
+            // The address of the returning instruction isn't recorded.
+            None
+        } else if next.exception {
+            // The interrupted instruction at the source address wasn't executed yet.
+            next.source.checked_sub(2)
+        } else {
+            Some(next.source)
+        };
+
+        match end {
+            Some(end)
+                if end >= packet.destination && end - packet.destination < MAX_SEQUENTIAL_RANGE =>
+            {
+                ranges.push(packet.destination..=end)
+            }
+            _ => ranges.push(packet.destination..=packet.destination),
+        }
+    }
+
+    if let (Some(last), Some(pc)) = (packets.last(), pc) {
+        if pc >= last.destination && pc - last.destination < MAX_SEQUENTIAL_RANGE {
+            ranges.push(last.destination..=pc);
+        }
+    }
+
+    ranges
+}
+
+memory_mapped_bitfield_register! {
+    pub struct Position(u32);
+    0x000, "MTB_POSITION",
+    impl From;
+    pub pointer, set_pointer: 31, 3;
+    pub wrap, set_wrap: 2;
+}
+
+impl DebugComponentInterface for Position {}
+
+memory_mapped_bitfield_register! {
+    pub struct Master(u32);
+    0x004, "MTB_MASTER",
+    impl From;
+    pub en, set_en: 31;
+    pub haltreq, set_haltreq: 9;
+    pub rampriv, set_rampriv: 8;
+    pub sfrwpriv, set_sfrwpriv: 7;
+    pub tstopen, set_tstopen: 6;
+    pub tstarten, set_tstarten: 5;
+    pub u8, mask, set_mask: 4, 0;
+}
+
+impl DebugComponentInterface for Master {}
+
+memory_mapped_bitfield_register! {
+    pub struct Flow(u32);
+    0x008, "MTB_FLOW",
+    impl From;
+    pub watermark, set_watermark: 31, 3;
+    pub autohalt, set_autohalt: 1;
+    pub autostop, set_autostop: 0;
+}
+
+impl DebugComponentInterface for Flow {}
+
+memory_mapped_bitfield_register! {
+    pub struct Base(u32);
+    0x00C, "MTB_BASE",
+    impl From;
+}
+
+impl DebugComponentInterface for Base {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn packet(source: u32, destination: u32) -> MtbPacket {
+        MtbPacket::from_words(source, destination)
+    }
+
+    #[test]
+    fn wrapped_buffer_starts_at_the_oldest_packet() {
+        let buffer = [0x100, 0x200, 0x300, 0x400, 0x500, 0x600];
+
+        assert_eq!(
+            parse_packets(&buffer, 1, true),
+            [
+                packet(0x300, 0x400),
+                packet(0x500, 0x600),
+                packet(0x100, 0x200)
+            ]
+        );
+        assert_eq!(parse_packets(&buffer, 1, false), [packet(0x100, 0x200)]);
+    }
+
+    #[test]
+    fn packet_flags_are_decoded() {
+        let packet = packet(0x1001, 0x2001);
+
+        assert_eq!(packet.source, 0x1000);
+        assert_eq!(packet.destination, 0x2000);
+        assert!(packet.exception);
+        assert!(packet.start);
+    }
+
+    #[test]
+    fn ranges_between_branches() {
+        let packets = [
+            packet(0x100, 0x201),
+            packet(0x210, 0x300),
+            // Exception entry, 0x308 was interrupted.
+            packet(0x309, 0x400),
+            // Exception return.
+            packet(0xFFFF_FFF9, 0x308),
+            // Restart of the trace.
+            packet(0x500, 0x601),
+        ];
+
+        assert_eq!(
+            executed_ranges(&packets, Some(0x604)),
+            [0x200..=0x210, 0x300..=0x306, 0x400..=0x400, 0x600..=0x604]
+        );
+    }
+}
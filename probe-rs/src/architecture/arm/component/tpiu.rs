@@ -1,4 +1,7 @@
+use std::collections::BTreeMap;
+
 use super::super::memory::romtable::CoresightComponent;
+use super::tmc::{Frame, Id};
 use crate::architecture::arm::ArmProbeInterface;
 use crate::Error;
 
@@ -10,6 +13,13 @@ const REGISTER_OFFSET_TPIU_ACPR: u32 = 0x10;
 const REGISTER_OFFSET_TPIU_SPPR: u32 = 0xF0;
 const REGISTER_OFFSET_TPIU_FFCR: u32 = 0x304;
 
+/// The size of a formatter frame.
+const FRAME_SIZE: usize = 16;
+/// The full frame synchronization packet, which precedes a frame.
+const FULL_SYNC: [u8; 4] = [0xFF, 0xFF, 0xFF, 0x7F];
+/// The halfword synchronization packet, which a trace port outputs when it has no data.
+const HALFWORD_SYNC: [u8; 2] = [0xFF, 0x7F];
+
 /// TPIU unit
 ///
 /// Trace port interface unit unit.
@@ -62,3 +72,156 @@ impl<'a> Tpiu<'a> {
         Ok(())
     }
 }
+
+/// Splits the formatter frames captured from the trace port, e.g. with a logic analyzer, into the
+/// data of every trace source.
+///
+/// The start of the frames is found with the full frame synchronization packets, which the
+/// formatter outputs periodically in continuous mode. Data before the first one is discarded.
+#[derive(Debug)]
+pub struct TpiuDeformatter {
+    buffer: Vec<u8>,
+    synchronized: bool,
+    id: Id,
+}
+
+impl Default for TpiuDeformatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TpiuDeformatter {
+    /// Creates a deformatter, which waits for a synchronization packet.
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            synchronized: false,
+            id: 0.into(),
+        }
+    }
+
+    /// Returns whether the start of the frames is known.
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized
+    }
+
+    /// Adds the captured `data`, and returns the data of the trace sources in the frames it
+    /// completed, by trace ID (ATID).
+    pub fn push(&mut self, data: &[u8]) -> BTreeMap<u8, Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut sources = BTreeMap::<u8, Vec<u8>>::new();
+        let mut position = 0;
+        loop {
+            let rest = &self.buffer[position..];
+
+            if !self.synchronized {
+                match rest.windows(FULL_SYNC.len()).position(|w| w == FULL_SYNC) {
+                    Some(offset) => {
+                        position += offset + FULL_SYNC.len();
+                        self.synchronized = true;
+                        // The source of the data before the first ID is unknown.
+                        self.id = 0.into();
+                    }
+                    None => {
+                        // Keep the start of a synchronization packet split across captures.
+                        position += rest.len().saturating_sub(FULL_SYNC.len() - 1);
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            // Frames never start with 0xFF, as it would be the reserved ID 0x7F.
+            if rest.first() == Some(&0xFF) {
+                if rest.starts_with(&FULL_SYNC) {
+                    position += FULL_SYNC.len();
+                } else if rest.starts_with(&HALFWORD_SYNC) {
+                    position += HALFWORD_SYNC.len();
+                } else if FULL_SYNC.starts_with(rest) {
+                    break;
+                } else {
+                    tracing::warn!("Lost the synchronization of the TPIU frames");
+                    self.synchronized = false;
+                }
+                continue;
+            }
+
+            if rest.len() < FRAME_SIZE {
+                break;
+            }
+            let mut frame = Frame::new(&rest[..FRAME_SIZE], self.id);
+            for (id, byte) in &mut frame {
+                // The null ID marks padding, and IDs from 0x70 on are reserved.
+                let id = u8::from(id);
+                if (0x01..0x70).contains(&id) {
+                    sources.entry(id).or_default().push(byte);
+                }
+            }
+            self.id = frame.id();
+            position += FRAME_SIZE;
+        }
+
+        self.buffer.drain(..position);
+        sources
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Formats a frame of the source `id`, with 14 bytes of `data`.
+    fn frame(id: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![(id << 1) | 1];
+        frame.extend_from_slice(data);
+        // The lowest bits of the data bytes at even positions are stored in the last byte. Its
+        // lowest bit is 0, so the ID applies to the byte after it.
+        let mut auxiliary = 0;
+        for index in (2..FRAME_SIZE - 1).step_by(2) {
+            auxiliary |= (frame[index] & 1) << (index / 2);
+            frame[index] &= !1;
+        }
+        frame.push(auxiliary);
+        frame
+    }
+
+    #[test]
+    fn frames_are_split_by_source() {
+        let itm = (1..=14).collect::<Vec<u8>>();
+        let etm = (0x81..=0x8E).collect::<Vec<u8>>();
+
+        let mut capture = vec![0x12, 0x34];
+        capture.extend_from_slice(&FULL_SYNC);
+        capture.extend(frame(13, &itm));
+        capture.extend_from_slice(&HALFWORD_SYNC);
+        capture.extend_from_slice(&FULL_SYNC);
+        capture.extend(frame(16, &etm));
+
+        let mut deformatter = TpiuDeformatter::new();
+        let mut sources = deformatter.push(&capture[..9]);
+        assert!(sources.is_empty());
+        assert!(deformatter.is_synchronized());
+        sources.extend(deformatter.push(&capture[9..]));
+
+        assert_eq!(sources, BTreeMap::from([(13, itm), (16, etm)]));
+    }
+
+    #[test]
+    fn synchronization_is_recovered() {
+        let data = [0x55; 14];
+
+        let mut capture = FULL_SYNC.to_vec();
+        capture.extend_from_slice(&[0xFF, 0x00]);
+        capture.extend(frame(13, &[0xAA; 14]));
+        capture.extend_from_slice(&FULL_SYNC);
+        capture.extend(frame(13, &data));
+
+        let mut deformatter = TpiuDeformatter::new();
+        assert_eq!(
+            deformatter.push(&capture),
+            BTreeMap::from([(13, data.to_vec())])
+        );
+    }
+}
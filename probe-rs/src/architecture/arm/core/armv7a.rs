@@ -815,6 +815,51 @@ impl<'probe> CoreInterface for Armv7a<'probe> {
 
         Ok(())
     }
+
+    fn read_pc_samples(&mut self, samples: &mut [u64]) -> Result<usize, Error> {
+        let address = Dbgdidr::get_mmio_address_from_base(self.base_address)?;
+        let dbgdidr = Dbgdidr(self.memory.read_word_32(address)?);
+
+        let address = if dbgdidr.pcsr_imp() {
+            DbgpcsrLegacy::get_mmio_address_from_base(self.base_address)?
+        } else {
+            let address = Dbgdevid::get_mmio_address_from_base(self.base_address)?;
+            let dbgdevid = Dbgdevid(self.memory.read_word_32(address)?);
+            if !dbgdidr.devid_imp() || dbgdevid.pcsample() == 0 {
+                return Err(Error::NotImplemented("non-halting PC sampling"));
+            }
+
+            Dbgpcsr::get_mmio_address_from_base(self.base_address)?
+        };
+
+        let mut values = vec![0; samples.len()];
+        self.memory.read_32_repeated(address, &mut values)?;
+
+        // Before v7.1 debug, the samples are offset by 8 bytes in ARM state, and 4 bytes in
+        // Thumb state.
+        let has_offset = dbgdidr.version() < 0b0101;
+
+        // DBGPCSR reads as all ones while the core is halted, or if sampling is prohibited.
+        let mut count = 0;
+        for value in values.into_iter().filter(|&value| value != u32::MAX) {
+            // The low bits encode the instruction set: `0bx1` for Thumb, `0b00` for ARM.
+            let (pc, offset) = if value & 0b1 != 0 {
+                (value & !0b1, 4)
+            } else {
+                (value & !0b11, 8)
+            };
+            let pc = if has_offset {
+                pc.wrapping_sub(offset)
+            } else {
+                pc
+            };
+
+            samples[count] = pc.into();
+            count += 1;
+        }
+
+        Ok(count)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv7a<'probe> {
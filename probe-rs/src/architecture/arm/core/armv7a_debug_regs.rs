@@ -296,6 +296,41 @@ memory_mapped_bitfield_register! {
     pub revision, _: 3, 0;
 }
 
+memory_mapped_bitfield_register! {
+    /// DBGPCSR - Program Counter Sampling Register, implemented as register 40
+    pub struct Dbgpcsr(u32);
+    0x0A0, "DBGPCSR",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// DBGPCSR - Program Counter Sampling Register, implemented as register 33
+    ///
+    /// Reads of DBGITR return DBGPCSR, if `DBGDIDR.pcsr_imp` is set.
+    pub struct DbgpcsrLegacy(u32);
+    0x084, "DBGPCSR",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// DBGDEVID - Debug Device ID Register
+    pub struct Dbgdevid(u32);
+    0xFC8, "DBGDEVID",
+    impl From;
+
+    /// Level of support for program counter sampling using DBGPCSR.
+    ///
+    /// 0b0000
+    /// DBGPCSR is not implemented as register 40.
+    ///
+    /// 0b0001
+    /// DBGPCSR is implemented as register 40.
+    ///
+    /// 0b0010
+    /// DBGPCSR, DBGCIDSR and DBGVIDSR are implemented as registers 40 to 42.
+    pub pcsample, _: 3, 0;
+}
+
 memory_mapped_bitfield_register! {
     /// DBGDRCR - Debug Run Control Register
     pub struct Dbgdrcr(u32);
@@ -1161,6 +1161,34 @@ impl<'probe> CoreInterface for Armv8a<'probe> {
 
         Ok(())
     }
+
+    fn read_pc_samples(&mut self, samples: &mut [u64]) -> Result<usize, Error> {
+        let address = Eddevid::get_mmio_address_from_base(self.base_address)?;
+        let eddevid = Eddevid(self.memory.read_word_32(address)?);
+        if eddevid.pcsample() == 0 {
+            return Err(Error::NotImplemented("non-halting PC sampling"));
+        }
+
+        // EDPCSRlo, EDCIDSR, EDVIDSR and EDPCSRhi are read in one transfer. Reading EDPCSRlo
+        // first captures the other registers for the same sample.
+        let address = Edpcsrlo::get_mmio_address_from_base(self.base_address)?;
+        let mut sample = [0; 4];
+
+        let mut count = 0;
+        for _ in 0..samples.len() {
+            self.memory.read_32(address, &mut sample)?;
+
+            // EDPCSRlo reads as all ones while the core is halted, or if sampling is prohibited.
+            if sample[0] == u32::MAX {
+                continue;
+            }
+
+            samples[count] = u64::from(sample[3]) << 32 | u64::from(sample[0]);
+            count += 1;
+        }
+
+        Ok(count)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv8a<'probe> {
@@ -145,6 +145,26 @@ impl Edscr {
     }
 }
 
+memory_mapped_bitfield_register! {
+    /// EDPCSRlo - External Debug Program Counter Sample Register, low word
+    ///
+    /// Reading this register captures EDPCSRhi, EDCIDSR and EDVIDSR, which follow it.
+    pub struct Edpcsrlo(u32);
+    0x0A0, "EDPCSRlo",
+    impl From;
+}
+
+memory_mapped_bitfield_register! {
+    /// EDDEVID - External Debug Device ID register 0
+    pub struct Eddevid(u32);
+    0xFC8, "EDDEVID",
+    impl From;
+
+    /// Indicates the level of PC Sample-based Profiling support using external debug
+    /// registers. `0b0000` means that it is not implemented.
+    pub pcsample, _: 3, 0;
+}
+
 memory_mapped_bitfield_register! {
     /// EDLAR - Lock Access Register
     pub struct Edlar(u32);
@@ -525,6 +525,17 @@ impl<'probe> CoreInterface for Armv8m<'probe> {
             .write_word_32(Demcr::get_mmio_address(), demcr.into())?;
         Ok(())
     }
+
+    fn read_pc_samples(&mut self, samples: &mut [u64]) -> Result<usize, Error> {
+        let mut demcr = Demcr(self.memory.read_word_32(Demcr::get_mmio_address())?);
+        if !demcr.trcena() {
+            demcr.set_trcena(true);
+            self.memory
+                .write_word_32(Demcr::get_mmio_address(), demcr.into())?;
+        }
+
+        super::cortex_m::read_pc_samples(&mut *self.memory, samples)
+    }
 }
 
 impl<'probe> MemoryInterface for Armv8m<'probe> {
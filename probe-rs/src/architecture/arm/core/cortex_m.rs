@@ -142,6 +142,40 @@ impl IdPfr1 {
     }
 }
 
+memory_mapped_bitfield_register! {
+    /// DWT_PCSR - Program Counter Sample Register
+    pub struct DwtPcsr(u32);
+    0xE000_101C, "DWT_PCSR",
+    impl From;
+}
+
+/// Samples the program counter by reading `DWT_PCSR` repeatedly, see
+/// [`crate::Core::read_pc_samples`].
+///
+/// The DWT has to be enabled with `DEMCR.TRCENA` before.
+pub(crate) fn read_pc_samples(
+    memory: &mut dyn ArmProbe,
+    samples: &mut [u64],
+) -> Result<usize, Error> {
+    let mut values = vec![0; samples.len()];
+    memory.read_32_repeated(DwtPcsr::get_mmio_address(), &mut values)?;
+
+    // DWT_PCSR is RAZ if PC sampling is not implemented. Code never runs at address 0, as that
+    // is where the vector table starts.
+    if !values.is_empty() && values.iter().all(|&value| value == 0) {
+        return Err(Error::NotImplemented("PC sampling"));
+    }
+
+    // DWT_PCSR reads as all ones while the core is halted or in Debug state.
+    let mut count = 0;
+    for value in values.into_iter().filter(|&value| value != u32::MAX) {
+        samples[count] = value.into();
+        count += 1;
+    }
+
+    Ok(count)
+}
+
 pub(crate) fn read_core_reg(memory: &mut dyn ArmProbe, addr: RegisterId) -> Result<u32, Error> {
     // Write the DCRSR value to select the register we want to read.
     let mut dcrsr_val = Dcrsr(0);
@@ -43,6 +43,13 @@ impl ExceptionInterface for ArmV6MExceptionHandler {
             stackframe_registers,
         )
     }
+
+    fn exception_name(&self, exception_number: u32) -> Option<String> {
+        Some(format!(
+            "{:?}",
+            armv6m::ExceptionReason::from(exception_number)
+        ))
+    }
 }
 
 /// Exception handling for cores based on the ARMv7-M and ARMv7-EM architectures.
@@ -84,6 +91,13 @@ impl ExceptionInterface for ArmV7MExceptionHandler {
                 .expanded_description(memory_interface)?
         ))
     }
+
+    fn exception_name(&self, exception_number: u32) -> Option<String> {
+        Some(format!(
+            "{:?}",
+            armv7m::ExceptionReason::from(exception_number)
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -119,6 +133,15 @@ mod test {
         assert_eq!(description, "ThreadMode")
     }
 
+    #[test]
+    fn exception_handler_exception_names() {
+        let handler = ArmV6MExceptionHandler {};
+
+        assert_eq!(handler.exception_name(15).unwrap(), "SysTick");
+        assert_eq!(handler.exception_name(19).unwrap(), "ExternalInterrupt(3)");
+        assert_eq!(handler.exception_name(5).unwrap(), "Reserved");
+    }
+
     #[test]
     fn exception_handler_no_exception_details() {
         let handler = ArmV6MExceptionHandler {};
@@ -318,4 +318,8 @@ impl<'probe> ExceptionInterface for crate::architecture::arm::core::armv7m::Armv
     ) -> Result<Option<ExceptionInfo>, Error> {
         exception_details(self, memory_interface, stackframe_registers)
     }
+
+    fn exception_name(&self, exception_number: u32) -> Option<String> {
+        Some(format!("{:?}", ExceptionReason::from(exception_number)))
+    }
 }
@@ -453,6 +453,10 @@ impl ExceptionInterface for ArmV8MExceptionHandler {
         ))
     }
 
+    fn exception_name(&self, exception_number: u32) -> Option<String> {
+        Some(format!("{:?}", ExceptionReason::from(exception_number)))
+    }
+
     fn exception_details(
         &self,
         memory_interface: &mut dyn MemoryInterface,
@@ -147,7 +147,7 @@ impl CortexMState {
         }
     }
 
-    fn initialize(&mut self) {
+    pub(crate) fn initialize(&mut self) {
         self.initialized = true;
     }
 
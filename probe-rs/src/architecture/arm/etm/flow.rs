@@ -0,0 +1,443 @@
+//! Reconstruction of the executed instructions from trace elements and the program.
+
+use super::TraceElement;
+use crate::InstructionSet;
+
+/// The longest run of instructions without a branch which is followed. Longer runs can only
+/// result from a program which doesn't match the traced one.
+const MAX_INSTRUCTIONS: usize = 0x1_0000;
+
+/// The effect of an instruction on the program flow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Branch {
+    /// The instruction is not a branch.
+    None,
+    /// A branch to a target encoded in the instruction.
+    Direct {
+        /// The address of the branch target.
+        target: u64,
+        /// The instruction set at the branch target.
+        instruction_set: InstructionSet,
+    },
+    /// A branch to a target from a register or memory.
+    Indirect,
+}
+
+/// The program flow could not be reconstructed.
+#[derive(Debug, thiserror::Error)]
+pub enum FlowError {
+    /// The traced address is not part of the program.
+    #[error("The program contains no code at address {0:#010x}")]
+    NoCode(u64),
+    /// No branch was found to apply an atom to.
+    #[error("No branch found after the instruction at address {0:#010x}")]
+    NoBranch(u64),
+    /// The instruction set can not be decoded.
+    #[error("Decoding of {0:?} instructions is not supported")]
+    UnsupportedInstructionSet(InstructionSet),
+}
+
+/// Follows the program flow described by [`TraceElement`]s through the program.
+///
+/// `code` returns the (up to) four bytes of the program at an address, or `None` if the address
+/// is not part of the program.
+pub struct ProgramFlow<F> {
+    code: F,
+    /// The address and instruction set of the next instruction, if known.
+    next: Option<(u64, InstructionSet)>,
+}
+
+impl<F: FnMut(u64) -> Option<[u8; 4]>> ProgramFlow<F> {
+    /// Creates a new program flow, which starts at the first address element.
+    pub fn new(code: F) -> Self {
+        Self { code, next: None }
+    }
+
+    /// Applies `element`, and calls `executed` with the address of every instruction it executed.
+    ///
+    /// After an error, the flow continues at the next address element.
+    pub fn process(
+        &mut self,
+        element: &TraceElement,
+        mut executed: impl FnMut(u64),
+    ) -> Result<(), FlowError> {
+        match *element {
+            TraceElement::TraceOn | TraceElement::Lost => self.next = None,
+            TraceElement::Address {
+                address,
+                instruction_set,
+            } => self.next = Some((address, instruction_set)),
+            TraceElement::Atom { taken } => {
+                let Some((start, instruction_set)) = self.next.take() else {
+                    return Ok(());
+                };
+
+                let mut address = start;
+                for _ in 0..MAX_INSTRUCTIONS {
+                    let (size, branch) = self.decode(address, instruction_set)?;
+                    executed(address);
+
+                    match branch {
+                        Branch::None => address += size,
+                        _ if !taken => {
+                            self.next = Some((address + size, instruction_set));
+                            return Ok(());
+                        }
+                        Branch::Direct {
+                            target,
+                            instruction_set,
+                        } => {
+                            self.next = Some((target, instruction_set));
+                            return Ok(());
+                        }
+                        // The target follows in an address element.
+                        Branch::Indirect => return Ok(()),
+                    }
+                }
+
+                return Err(FlowError::NoBranch(start));
+            }
+            TraceElement::Exception { return_address, .. } => {
+                let Some((mut address, instruction_set)) = self.next.take() else {
+                    return Ok(());
+                };
+
+                // The instructions up to the interrupted one were executed.
+                while address < return_address {
+                    let (size, branch) = self.decode(address, instruction_set)?;
+                    if branch != Branch::None {
+                        break;
+                    }
+                    executed(address);
+                    address += size;
+                }
+            }
+            TraceElement::ExceptionReturn
+            | TraceElement::Timestamp(_)
+            | TraceElement::CycleCount(_) => {}
+        }
+
+        Ok(())
+    }
+
+    fn decode(
+        &mut self,
+        address: u64,
+        instruction_set: InstructionSet,
+    ) -> Result<(u64, Branch), FlowError> {
+        let code = (self.code)(address).ok_or(FlowError::NoCode(address))?;
+        match instruction_set {
+            InstructionSet::Thumb2 => Ok(decode_thumb(address, code)),
+            InstructionSet::A32 => Ok((4, decode_a32(address, code))),
+            InstructionSet::A64 => Ok((4, decode_a64(address, code))),
+            instruction_set => Err(FlowError::UnsupportedInstructionSet(instruction_set)),
+        }
+    }
+}
+
+/// Returns the size of the Thumb instruction at the start of `code`.
+pub fn thumb_instruction_size(code: &[u8]) -> u64 {
+    match code {
+        // The first halfword of 32-bit instructions starts with 0b11101, 0b11110 or 0b11111.
+        [_, high, ..] if high >> 3 >= 0b11101 => 4,
+        _ => 2,
+    }
+}
+
+/// Sign extends the lowest `bits` bits of `value`.
+fn sign_extend(value: u32, bits: u32) -> i64 {
+    let shift = 32 - bits;
+    i64::from(((value << shift) as i32) >> shift)
+}
+
+/// Decodes the size and the branch of the Thumb instruction at `address`, see the ARMv7-M
+/// architecture reference manual A5.
+fn decode_thumb(address: u64, code: [u8; 4]) -> (u64, Branch) {
+    let first = u16::from_le_bytes([code[0], code[1]]);
+    let second = u16::from_le_bytes([code[2], code[3]]);
+    let direct = |offset: i64| Branch::Direct {
+        target: address.wrapping_add(4).wrapping_add_signed(offset),
+        instruction_set: InstructionSet::Thumb2,
+    };
+
+    if thumb_instruction_size(&code) == 2 {
+        let branch = if first & 0xF000 == 0xD000 && (first >> 8) & 0xF < 0xE {
+            // B<c> T1, excluding UDF and SVC
+            direct(sign_extend(u32::from(first & 0xFF), 8) << 1)
+        } else if first & 0xF800 == 0xE000 {
+            // B T2
+            direct(sign_extend(u32::from(first & 0x7FF), 11) << 1)
+        } else if first & 0xF500 == 0xB100 {
+            // CB{N}Z
+            direct(i64::from((first >> 9) & 1) << 6 | i64::from((first >> 3) & 0x1F) << 1)
+        } else if first & 0xFF00 == 0x4700 || first & 0xFF00 == 0xBD00 {
+            // BX and BLX with a register, POP with the PC
+            Branch::Indirect
+        } else if first & 0xFF87 == 0x4687 || first & 0xFF87 == 0x4487 {
+            // MOV and ADD with the PC as destination
+            Branch::Indirect
+        } else {
+            Branch::None
+        };
+        return (2, branch);
+    }
+
+    let branch = if first & 0xF800 == 0xF000 && second & 0x8000 != 0 {
+        let s = u32::from(first >> 10) & 1;
+        let j1 = u32::from(second >> 13) & 1;
+        let j2 = u32::from(second >> 11) & 1;
+        let imm11 = u32::from(second & 0x7FF);
+
+        match second & 0xD000 {
+            // B<c> T3, the other conditions encode miscellaneous instructions.
+            0x8000 if (first >> 6) & 0xF < 0xE => {
+                let imm6 = u32::from(first & 0x3F);
+                direct(sign_extend(
+                    s << 20 | j2 << 19 | j1 << 18 | imm6 << 12 | imm11 << 1,
+                    21,
+                ))
+            }
+            // SUBS PC, LR, #imm8
+            0x8000 if first == 0xF3DE && second & 0xFF00 == 0x8F00 => Branch::Indirect,
+            0x8000 => Branch::None,
+            // B T4, BL and BLX with an immediate
+            kind => {
+                let i1 = !(j1 ^ s) & 1;
+                let i2 = !(j2 ^ s) & 1;
+                let imm10 = u32::from(first & 0x3FF);
+                let offset =
+                    sign_extend(s << 24 | i1 << 23 | i2 << 22 | imm10 << 12 | imm11 << 1, 25);
+                if kind == 0xC000 {
+                    Branch::Direct {
+                        target: (address.wrapping_add(4) & !3).wrapping_add_signed(offset),
+                        instruction_set: InstructionSet::A32,
+                    }
+                } else {
+                    direct(offset)
+                }
+            }
+        }
+    } else if (first & 0xFFF0 == 0xF8D0 || first & 0xFFF0 == 0xF850) && second >> 12 == 0xF {
+        // LDR with the PC as destination
+        Branch::Indirect
+    } else if (first & 0xFFD0 == 0xE890 || first & 0xFFD0 == 0xE910) && second & 0x8000 != 0 {
+        // LDM and POP with the PC
+        Branch::Indirect
+    } else if first & 0xFFF0 == 0xE8D0 && second & 0xFFE0 == 0xF000 {
+        // TBB and TBH
+        Branch::Indirect
+    } else {
+        Branch::None
+    };
+
+    (4, branch)
+}
+
+/// Decodes the branch of the A32 instruction at `address`, see the ARMv7-A/R architecture
+/// reference manual A5.
+fn decode_a32(address: u64, code: [u8; 4]) -> Branch {
+    let instruction = u32::from_le_bytes(code);
+    let offset = sign_extend(instruction & 0x00FF_FFFF, 24) << 2;
+    let target = address.wrapping_add(8).wrapping_add_signed(offset);
+    let condition = instruction >> 28;
+
+    if instruction & 0xFE00_0000 == 0xFA00_0000 {
+        // BLX with an immediate, which switches to Thumb
+        Branch::Direct {
+            target: target | u64::from((instruction >> 23) & 2),
+            instruction_set: InstructionSet::Thumb2,
+        }
+    } else if condition == 0xF {
+        // The other unconditional instructions don't branch, except for RFE.
+        if instruction & 0xFE50_0000 == 0xF810_0000 {
+            Branch::Indirect
+        } else {
+            Branch::None
+        }
+    } else if instruction & 0x0E00_0000 == 0x0A00_0000 {
+        // B and BL
+        Branch::Direct {
+            target,
+            instruction_set: InstructionSet::A32,
+        }
+    } else if instruction & 0x0FFF_FFD0 == 0x012F_FF10 {
+        // BX and BLX with a register
+        Branch::Indirect
+    } else if instruction & 0x0C50_F000 == 0x0410_F000 {
+        // LDR with the PC as destination
+        Branch::Indirect
+    } else if instruction & 0x0E10_8000 == 0x0810_8000 {
+        // LDM and POP with the PC
+        Branch::Indirect
+    } else if instruction & 0x0C00_F000 == 0x0000_F000
+        && !(8..=11).contains(&((instruction >> 21) & 0xF))
+        && (instruction & 0x0200_0000 != 0 || instruction & 0x90 != 0x90)
+    {
+        // Data processing with the PC as destination, excluding the compare and miscellaneous
+        // instructions as well as multiplies and extra loads and stores
+        Branch::Indirect
+    } else {
+        Branch::None
+    }
+}
+
+/// Decodes the branch of the A64 instruction at `address`, see the ARMv8-A architecture
+/// reference manual C4.1.
+fn decode_a64(address: u64, code: [u8; 4]) -> Branch {
+    let instruction = u32::from_le_bytes(code);
+    let direct = |offset: i64| Branch::Direct {
+        target: address.wrapping_add_signed(offset << 2),
+        instruction_set: InstructionSet::A64,
+    };
+
+    if instruction & 0x7C00_0000 == 0x1400_0000 {
+        // B and BL
+        direct(sign_extend(instruction & 0x03FF_FFFF, 26))
+    } else if instruction & 0xFF00_0010 == 0x5400_0000 || instruction & 0x7E00_0000 == 0x3400_0000 {
+        // B.cond and CB{N}Z
+        direct(sign_extend((instruction >> 5) & 0x7FFFF, 19))
+    } else if instruction & 0x7E00_0000 == 0x3600_0000 {
+        // TB{N}Z
+        direct(sign_extend((instruction >> 5) & 0x3FFF, 14))
+    } else if instruction & 0xFE00_0000 == 0xD600_0000 {
+        // BR, BLR, RET, ERET
+        Branch::Indirect
+    } else {
+        Branch::None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn thumb(address: u64, halfwords: &[u16]) -> (u64, Branch) {
+        let mut code = [0; 4];
+        for (index, halfword) in halfwords.iter().enumerate() {
+            code[index * 2..][..2].copy_from_slice(&halfword.to_le_bytes());
+        }
+        decode_thumb(address, code)
+    }
+
+    fn to(target: u64) -> Branch {
+        Branch::Direct {
+            target,
+            instruction_set: InstructionSet::Thumb2,
+        }
+    }
+
+    #[test]
+    fn thumb_branches() {
+        // b.n to itself
+        assert_eq!(thumb(0x100, &[0xE7FE]), (2, to(0x100)));
+        // beq.n with an offset of 8
+        assert_eq!(thumb(0x100, &[0xD004]), (2, to(0x10C)));
+        // cbz r0 with an offset of 4
+        assert_eq!(thumb(0x100, &[0xB110]), (2, to(0x108)));
+        // bl with an offset of 0x1000
+        assert_eq!(thumb(0x100, &[0xF001, 0xF800]), (4, to(0x1104)));
+        // bl with an offset of -8
+        assert_eq!(thumb(0x100, &[0xF7FF, 0xFFFC]), (4, to(0xFC)));
+        // bx lr, pop {r4, pc}, ldr.w pc, [sp], #4
+        assert_eq!(thumb(0x100, &[0x4770]), (2, Branch::Indirect));
+        assert_eq!(thumb(0x100, &[0xBD10]), (2, Branch::Indirect));
+        assert_eq!(thumb(0x100, &[0xF85D, 0xFB04]), (4, Branch::Indirect));
+        // movs r0, #1 and a 32-bit add.w
+        assert_eq!(thumb(0x100, &[0x2001]), (2, Branch::None));
+        assert_eq!(thumb(0x100, &[0xEB00, 0x0001]), (4, Branch::None));
+    }
+
+    #[test]
+    fn thumb_instruction_sizes() {
+        // movs r0, #1
+        assert_eq!(thumb_instruction_size(&[0x01, 0x20]), 2);
+        // bl with an offset
+        assert_eq!(thumb_instruction_size(&[0x00, 0xF0, 0x02, 0xF8]), 4);
+        // b.n
+        assert_eq!(thumb_instruction_size(&[0xFE, 0xE7]), 2);
+    }
+
+    #[test]
+    fn a32_branches() {
+        let a32 = |instruction: u32| decode_a32(0x1000, instruction.to_le_bytes());
+        let to = |target, instruction_set| Branch::Direct {
+            target,
+            instruction_set,
+        };
+
+        // b to itself, bl #8, bne #16
+        assert_eq!(a32(0xEAFF_FFFE), to(0x1000, InstructionSet::A32));
+        assert_eq!(a32(0xEB00_0000), to(0x1008, InstructionSet::A32));
+        assert_eq!(a32(0x1A00_0002), to(0x1010, InstructionSet::A32));
+        // blx to Thumb code, with and without the halfword offset
+        assert_eq!(a32(0xFA00_0000), to(0x1008, InstructionSet::Thumb2));
+        assert_eq!(a32(0xFB00_0000), to(0x100A, InstructionSet::Thumb2));
+        // bx lr, ldr pc, [sp], #4, pop {r4, pc}, mov pc, lr
+        assert_eq!(a32(0xE12F_FF1E), Branch::Indirect);
+        assert_eq!(a32(0xE49D_F004), Branch::Indirect);
+        assert_eq!(a32(0xE8BD_8010), Branch::Indirect);
+        assert_eq!(a32(0xE1A0_F00E), Branch::Indirect);
+        // mov r0, #1, cmp r0, #0, nop, push {r4, lr}
+        assert_eq!(a32(0xE3A0_0001), Branch::None);
+        assert_eq!(a32(0xE350_0000), Branch::None);
+        assert_eq!(a32(0xE320_F000), Branch::None);
+        assert_eq!(a32(0xE92D_4010), Branch::None);
+    }
+
+    #[test]
+    fn a64_branches() {
+        let a64 = |instruction: u32| decode_a64(0x1000, instruction.to_le_bytes());
+        let to = |target| Branch::Direct {
+            target,
+            instruction_set: InstructionSet::A64,
+        };
+
+        // b #-4, bl #8, b.ne #16, cbz x0, #-8
+        assert_eq!(a64(0x17FF_FFFF), to(0xFFC));
+        assert_eq!(a64(0x9400_0002), to(0x1008));
+        assert_eq!(a64(0x5400_0081), to(0x1010));
+        assert_eq!(a64(0xB4FF_FFC0), to(0xFF8));
+        // ret, nop
+        assert_eq!(a64(0xD65F_03C0), Branch::Indirect);
+        assert_eq!(a64(0xD503_201F), Branch::None);
+    }
+
+    #[test]
+    fn atoms_follow_the_program() {
+        let program = HashMap::from([
+            // movs r0, #1
+            (0x100, 0x2001),
+            // cbz r0, 0x10a
+            (0x102, 0xB110),
+            // b.n 0x100
+            (0x104, 0xE7FC),
+            // bx lr
+            (0x106, 0x4770),
+        ]);
+        let mut flow = ProgramFlow::new(|address| {
+            let halfword: u16 = *program.get(&address)?;
+            let [low, high] = halfword.to_le_bytes();
+            Some([low, high, 0, 0])
+        });
+
+        let mut executed = Vec::new();
+        let elements = [
+            TraceElement::Address {
+                address: 0x100,
+                instruction_set: InstructionSet::Thumb2,
+            },
+            // cbz not taken, b.n taken, cbz not taken
+            TraceElement::Atom { taken: false },
+            TraceElement::Atom { taken: true },
+            TraceElement::Atom { taken: false },
+        ];
+        for element in &elements {
+            flow.process(element, |address| executed.push(address))
+                .unwrap();
+        }
+
+        assert_eq!(executed, [0x100, 0x102, 0x104, 0x100, 0x102]);
+    }
+}
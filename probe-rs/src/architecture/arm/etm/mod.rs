@@ -0,0 +1,53 @@
+//! Decoding of ETM instruction trace.
+//!
+//! The packets of a trace unit are decoded into [`TraceElement`]s, which only describe the
+//! decisions of the program flow: whether branches were taken, and where indirect branches and
+//! exceptions went to. The [`ProgramFlow`] combines them with the program to reconstruct the
+//! executed instructions.
+//!
+//! Only the ETMv4 protocol is decoded, for Thumb, A32 and A64 code. The PTM (Program Flow Trace)
+//! and ETMv3 protocols of older cores such as the Cortex-A9 use different packets, and
+//! `probe-rs trace etm` refuses to trace with them.
+
+mod flow;
+mod v4;
+
+use crate::InstructionSet;
+
+pub use flow::{thumb_instruction_size, Branch, FlowError, ProgramFlow};
+pub use v4::EtmV4Decoder;
+
+/// An element of the instruction trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceElement {
+    /// Tracing started, or restarted after a gap. The program flow before is unrelated.
+    TraceOn,
+    /// Execution continues at `address`, e.g. after an indirect branch or an exception.
+    Address {
+        /// The address of the next executed instruction.
+        address: u64,
+        /// The instruction set of the next executed instruction.
+        instruction_set: InstructionSet,
+    },
+    /// The next branch instruction was taken, or not.
+    Atom {
+        /// Whether the branch was taken.
+        taken: bool,
+    },
+    /// An exception was taken. The instructions before `return_address` were executed, and the
+    /// handler address follows in an [`TraceElement::Address`].
+    Exception {
+        /// The exception number.
+        number: u16,
+        /// The address execution returns to after the exception.
+        return_address: u64,
+    },
+    /// An exception handler returned.
+    ExceptionReturn,
+    /// The value of the global timestamp.
+    Timestamp(u64),
+    /// The number of cycles since the last cycle count.
+    CycleCount(u32),
+    /// Trace was lost, e.g. due to an overflow or an undecodable packet.
+    Lost,
+}
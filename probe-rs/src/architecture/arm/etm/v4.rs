@@ -0,0 +1,475 @@
+//! Decoder of ETMv4 instruction trace packets, see ARM IHI 0064 chapter 6.
+
+use super::TraceElement;
+use crate::InstructionSet;
+
+/// The trailing byte of an a-sync packet, which follows at least 11 zero bytes.
+const A_SYNC_END: u8 = 0x80;
+const A_SYNC_ZEROS: usize = 11;
+
+/// The atom patterns of the format 4 atom packets, with the oldest atom in bit 0.
+const FORMAT_4_ATOMS: [u32; 4] = [0b1110, 0b0000, 0b1010, 0b0101];
+
+/// A packet could not be decoded.
+enum PacketError {
+    /// The data ended within the packet.
+    Truncated,
+    /// The packet is invalid or not supported by the decoder.
+    Unsupported(u8),
+}
+
+/// A cursor over the bytes of a packet.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, PacketError> {
+        let byte = *self.data.get(self.position).ok_or(PacketError::Truncated)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Reads a field of up to `max_bytes` bytes, whose bytes have a continuation bit.
+    fn continued(&mut self, max_bytes: usize) -> Result<u64, PacketError> {
+        let mut value = 0;
+        for index in 0..max_bytes {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7F) << (7 * index);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// An entry of the address history, which compressed addresses refer to.
+#[derive(Debug, Clone, Copy, Default)]
+struct HistoryEntry {
+    address: u64,
+    /// The instruction set is T32, instead of A32 or A64.
+    is1: bool,
+}
+
+/// Decodes the ETMv4 instruction trace of one trace source into [`TraceElement`]s.
+///
+/// Data trace, Q elements and speculative trace, i.e. commit and cancel packets with a speculation
+/// depth above zero, are not supported. The decoder resynchronizes at the next a-sync packet if it
+/// encounters them.
+#[derive(Debug)]
+pub struct EtmV4Decoder {
+    /// Cycle count packets don't contain a commit count, see TRCIDR0.COMMOPT.
+    commit_opt: bool,
+    synchronized: bool,
+    addresses: [HistoryEntry; 3],
+    /// The core is in AArch64 state, so that IS0 is A64 and not A32.
+    sixty_four_bit: bool,
+    cycle_count_threshold: u32,
+    /// The exception of the last exception packet, whose return address is the next address.
+    pending_exception: Option<u16>,
+}
+
+impl EtmV4Decoder {
+    /// Creates a decoder for a trace unit with the given TRCIDR0.COMMOPT, see
+    /// [`Etm::commit_opt`](crate::architecture::arm::component::Etm::commit_opt).
+    pub fn new(commit_opt: bool) -> Self {
+        Self {
+            commit_opt,
+            synchronized: false,
+            addresses: Default::default(),
+            sixty_four_bit: false,
+            cycle_count_threshold: 0,
+            pending_exception: None,
+        }
+    }
+
+    /// Decodes the packets in `data`, starting at the first a-sync packet.
+    pub fn decode(&mut self, data: &[u8]) -> Vec<TraceElement> {
+        let mut elements = Vec::new();
+        let mut position = 0;
+
+        while position < data.len() {
+            if !self.synchronized {
+                let Some(end) = find_a_sync(&data[position..]) else {
+                    break;
+                };
+                position += end;
+                self.synchronized = true;
+                continue;
+            }
+
+            let mut reader = Reader { data, position };
+            match self.decode_packet(&mut reader, &mut elements) {
+                Ok(()) => position = reader.position,
+                Err(PacketError::Truncated) => break,
+                Err(PacketError::Unsupported(header)) => {
+                    tracing::warn!(
+                        "Unsupported ETMv4 packet with header {header:#04x}, resynchronizing"
+                    );
+                    elements.push(TraceElement::Lost);
+                    self.synchronized = false;
+                    position += 1;
+                }
+            }
+        }
+
+        elements
+    }
+
+    fn decode_packet(
+        &mut self,
+        reader: &mut Reader<'_>,
+        elements: &mut Vec<TraceElement>,
+    ) -> Result<(), PacketError> {
+        let header = reader.byte()?;
+
+        match header {
+            0x00 => match reader.byte()? {
+                // A-sync, which only needs to be skipped here.
+                0x00 => loop {
+                    match reader.byte()? {
+                        0x00 => continue,
+                        A_SYNC_END => break,
+                        _ => return Err(PacketError::Unsupported(header)),
+                    }
+                },
+                // Discard and overflow.
+                0x03 | 0x05 => {
+                    self.pending_exception = None;
+                    elements.push(TraceElement::Lost);
+                }
+                _ => return Err(PacketError::Unsupported(header)),
+            },
+            // Trace info
+            0x01 => {
+                let sections = reader.continued(1)?;
+                if sections & 0b0001 != 0 {
+                    reader.continued(2)?;
+                }
+                if sections & 0b0010 != 0 {
+                    reader.continued(4)?;
+                }
+                if sections & 0b0100 != 0 {
+                    reader.continued(4)?;
+                }
+                if sections & 0b1000 != 0 {
+                    self.cycle_count_threshold = reader.continued(2)? as u32;
+                }
+                self.addresses = Default::default();
+            }
+            // Timestamp, with a cycle count if bit 0 is set.
+            0x02 | 0x03 => {
+                let mut timestamp = 0;
+                for index in 0..9 {
+                    let byte = reader.byte()?;
+                    if index == 8 {
+                        timestamp |= u64::from(byte) << 56;
+                        break;
+                    }
+                    timestamp |= u64::from(byte & 0x7F) << (7 * index);
+                    if byte & 0x80 == 0 {
+                        break;
+                    }
+                }
+                elements.push(TraceElement::Timestamp(timestamp));
+                if header & 1 != 0 {
+                    let cycles = reader.continued(3)? as u32;
+                    elements.push(TraceElement::CycleCount(cycles));
+                }
+            }
+            0x04 => {
+                self.pending_exception = None;
+                elements.push(TraceElement::TraceOn);
+            }
+            // Function return, only used by ARMv8-M.
+            0x05 => {}
+            0x06 => {
+                let info = reader.byte()?;
+                let mut number = u16::from(info >> 1) & 0x1F;
+                if info & 0x80 != 0 {
+                    number |= (u16::from(reader.byte()?) & 0x1F) << 5;
+                }
+                self.pending_exception = Some(number);
+            }
+            0x07 => elements.push(TraceElement::ExceptionReturn),
+            // Cycle count format 2
+            0x0C | 0x0D => {
+                let counts = reader.byte()?;
+                self.cycle_count(u32::from(counts >> 4), elements);
+            }
+            // Cycle count format 1, without a count if bit 0 is set.
+            0x0E | 0x0F => {
+                if !self.commit_opt {
+                    reader.continued(4)?;
+                }
+                if header & 1 == 0 {
+                    let cycles = reader.continued(3)? as u32;
+                    self.cycle_count(cycles, elements);
+                }
+            }
+            // Cycle count format 3
+            0x10..=0x1F => self.cycle_count(u32::from(header & 0b11), elements),
+            // Commit
+            0x2D => {
+                reader.continued(4)?;
+            }
+            // Ignore and events
+            0x70..=0x7F => {}
+            // Context, unchanged
+            0x80 => {}
+            0x81 => self.context(reader)?,
+            // Address with context, the IS bit in bit 0 for 32 bit and inverted for 64 bit.
+            0x82 | 0x83 => {
+                let address = self.long_address_32(reader, header & 1 != 0)?;
+                self.context(reader)?;
+                self.address(address, header & 1 != 0, elements);
+            }
+            0x85 | 0x86 => {
+                let address = self.long_address_64(reader, header & 1 == 0)?;
+                self.context(reader)?;
+                self.address(address, header & 1 == 0, elements);
+            }
+            // Exact match address
+            0x90..=0x92 => {
+                let entry = self.addresses[usize::from(header & 0b11)];
+                self.address(entry.address, entry.is1, elements);
+            }
+            // Short address
+            0x95 | 0x96 => {
+                let is1 = header == 0x96;
+                let shift = if is1 { 1 } else { 2 };
+                let first = reader.byte()?;
+                let mut value = u64::from(first & 0x7F) << shift;
+                let mut bits = 7 + shift;
+                if first & 0x80 != 0 {
+                    value |= u64::from(reader.byte()?) << (7 + shift);
+                    bits += 8;
+                }
+                let mask = (1 << bits) - 1;
+                let address = (self.addresses[0].address & !mask) | value;
+                self.address(address, is1, elements);
+            }
+            0x9A | 0x9B => {
+                let address = self.long_address_32(reader, header & 1 != 0)?;
+                self.address(address, header & 1 != 0, elements);
+            }
+            0x9D | 0x9E => {
+                let address = self.long_address_64(reader, header & 1 == 0)?;
+                self.address(address, header & 1 == 0, elements);
+            }
+            // Atom format 1
+            0xF6 | 0xF7 => atoms(u32::from(header & 1), 1, elements),
+            // Atom format 2
+            0xD8..=0xDB => atoms(u32::from(header & 0b11), 2, elements),
+            // Atom format 3
+            0xF8..=0xFF => atoms(u32::from(header & 0b111), 3, elements),
+            // Atom format 4
+            0xDC..=0xDF => atoms(FORMAT_4_ATOMS[usize::from(header & 0b11)], 4, elements),
+            // Atom format 5
+            0xD5..=0xD7 | 0xF5 => {
+                let pattern = match ((header & 0x20) >> 3) | (header & 0b11) {
+                    0b101 => 0b11110,
+                    0b001 => 0b00000,
+                    0b010 => 0b01010,
+                    _ => 0b10101,
+                };
+                atoms(pattern, 5, elements)
+            }
+            // Atom format 6, a run of E atoms followed by an E or N atom.
+            0xC0..=0xD4 | 0xE0..=0xF4 => {
+                let count = u32::from(header & 0x1F) + 3;
+                let mut pattern = (1 << count) - 1;
+                if header & 0x20 == 0 {
+                    pattern |= 1 << count;
+                }
+                atoms(pattern, count + 1, elements)
+            }
+            _ => return Err(PacketError::Unsupported(header)),
+        }
+
+        Ok(())
+    }
+
+    fn context(&mut self, reader: &mut Reader<'_>) -> Result<(), PacketError> {
+        let info = reader.byte()?;
+        self.sixty_four_bit = info & 0x10 != 0;
+        // VMID
+        if info & 0x40 != 0 {
+            reader.byte()?;
+        }
+        // Context ID
+        if info & 0x80 != 0 {
+            for _ in 0..4 {
+                reader.byte()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn long_address_32(&mut self, reader: &mut Reader<'_>, is1: bool) -> Result<u64, PacketError> {
+        let mut value = if is1 {
+            u64::from(reader.byte()? & 0x7F) << 1 | u64::from(reader.byte()?) << 8
+        } else {
+            u64::from(reader.byte()? & 0x7F) << 2 | u64::from(reader.byte()? & 0x7F) << 9
+        };
+        value |= u64::from(reader.byte()?) << 16;
+        value |= u64::from(reader.byte()?) << 24;
+        Ok((self.addresses[0].address & !0xFFFF_FFFF) | value)
+    }
+
+    fn long_address_64(&mut self, reader: &mut Reader<'_>, is1: bool) -> Result<u64, PacketError> {
+        let low = self.long_address_32(reader, is1)? & 0xFFFF_FFFF;
+        let mut high = 0;
+        for index in 0..4 {
+            high |= u64::from(reader.byte()?) << (32 + 8 * index);
+        }
+        Ok(high | low)
+    }
+
+    fn address(&mut self, address: u64, is1: bool, elements: &mut Vec<TraceElement>) {
+        self.addresses.rotate_right(1);
+        self.addresses[0] = HistoryEntry { address, is1 };
+
+        let element = match self.pending_exception.take() {
+            Some(number) => TraceElement::Exception {
+                number,
+                return_address: address,
+            },
+            None => TraceElement::Address {
+                address,
+                instruction_set: if is1 {
+                    InstructionSet::Thumb2
+                } else if self.sixty_four_bit {
+                    InstructionSet::A64
+                } else {
+                    InstructionSet::A32
+                },
+            },
+        };
+        elements.push(element);
+    }
+
+    fn cycle_count(&self, cycles: u32, elements: &mut Vec<TraceElement>) {
+        elements.push(TraceElement::CycleCount(
+            cycles + self.cycle_count_threshold,
+        ));
+    }
+}
+
+/// Pushes `count` atoms, the oldest one in bit 0 of `pattern` and a set bit for a taken branch.
+fn atoms(pattern: u32, count: u32, elements: &mut Vec<TraceElement>) {
+    elements.extend((0..count).map(|index| TraceElement::Atom {
+        taken: pattern & (1 << index) != 0,
+    }));
+}
+
+/// Returns the offset after the first a-sync packet in `data`.
+fn find_a_sync(data: &[u8]) -> Option<usize> {
+    let mut zeros = 0;
+    for (offset, &byte) in data.iter().enumerate() {
+        match byte {
+            0x00 => zeros += 1,
+            A_SYNC_END if zeros >= A_SYNC_ZEROS => return Some(offset + 1),
+            _ => zeros = 0,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const A_SYNC: [u8; 12] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80];
+
+    fn decode(packets: &[u8]) -> Vec<TraceElement> {
+        let mut data = vec![0xAA, 0x55];
+        data.extend_from_slice(&A_SYNC);
+        data.extend_from_slice(packets);
+        EtmV4Decoder::new(true).decode(&data)
+    }
+
+    fn atom(taken: bool) -> TraceElement {
+        TraceElement::Atom { taken }
+    }
+
+    #[test]
+    fn data_before_a_sync_is_skipped() {
+        assert_eq!(decode(&[0x04]), [TraceElement::TraceOn]);
+    }
+
+    #[test]
+    fn thumb_addresses() {
+        let elements = decode(&[
+            // Trace info without sections
+            0x01, 0x00, // Long address 0x0800_1234, IS1
+            0x9B, 0x1A, 0x12, 0x00, 0x08,
+            // Short address with a single byte, replacing bits 7:1
+            0x96, 0x10, // Exact match of the previous address
+            0x91,
+        ]);
+
+        let thumb = |address| TraceElement::Address {
+            address,
+            instruction_set: InstructionSet::Thumb2,
+        };
+        assert_eq!(
+            elements,
+            [thumb(0x0800_1234), thumb(0x0800_1220), thumb(0x0800_1234)]
+        );
+    }
+
+    #[test]
+    fn atom_formats() {
+        // Format 1 E, format 2 N E, format 3 E E N, format 4 N E N E
+        let elements = decode(&[0xF7, 0xDA, 0xFB, 0xDE]);
+
+        assert_eq!(
+            elements,
+            [true, false, true, true, true, false, false, true, false, true].map(atom)
+        );
+    }
+
+    #[test]
+    fn atom_format_6() {
+        // Three E atoms and a final N atom.
+        assert_eq!(decode(&[0xE0]), [true, true, true, false].map(atom));
+        // Four E atoms.
+        assert_eq!(decode(&[0xC0]), [true, true, true, true].map(atom));
+    }
+
+    #[test]
+    fn exception_with_return_address() {
+        let elements = decode(&[
+            // Exception 3, HardFault
+            0x06, 0x06, // Long address 0x0000_0100, IS1
+            0x9B, 0x00, 0x01, 0x00, 0x00, // Handler address
+            0x9B, 0x40, 0x02, 0x00, 0x00,
+        ]);
+
+        assert_eq!(
+            elements,
+            [
+                TraceElement::Exception {
+                    number: 3,
+                    return_address: 0x100
+                },
+                TraceElement::Address {
+                    address: 0x280,
+                    instruction_set: InstructionSet::Thumb2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_packets_resynchronize() {
+        let mut packets = vec![0x40, 0xF7];
+        packets.extend_from_slice(&A_SYNC);
+        packets.push(0xF6);
+
+        assert_eq!(decode(&packets), [TraceElement::Lost, atom(false)]);
+    }
+}
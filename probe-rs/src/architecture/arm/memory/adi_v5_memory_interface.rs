@@ -18,6 +18,17 @@ pub trait ArmProbe: SwdSequence {
 
     fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError>;
 
+    /// Reads `data.len()` 32 bit words from the same `address`, e.g. to sample a register.
+    ///
+    /// The default implementation reads the words one by one.
+    fn read_32_repeated(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        for value in data {
+            *value = self.read_word_32(address)?;
+        }
+
+        Ok(())
+    }
+
     /// Reads a 64 bit word from `address`.
     fn read_word_64(&mut self, address: u64) -> Result<u64, ArmError> {
         let mut buff = [0];
@@ -235,6 +246,14 @@ where
     ///
     /// Currently, only AMBA AHB Access is supported.
     fn write_csw_register(&mut self, data_size: DataSize) -> Result<(), ArmError> {
+        self.write_csw_register_with_increment(data_size, AddressIncrement::Single)
+    }
+
+    fn write_csw_register_with_increment(
+        &mut self,
+        data_size: DataSize,
+        address_increment: AddressIncrement,
+    ) -> Result<(), ArmError> {
         // The CSW Register is set for an AMBA AHB Acccess, according to
         // the ARM Debug Interface Architecture Specification.
         //
@@ -258,7 +277,7 @@ where
             HNONSEC: !self.ap_information.supports_hnonsec as u8,
             PROT: 0b10,
             CACHE: 0b1011,
-            AddrInc: AddressIncrement::Single,
+            AddrInc: address_increment,
             SIZE: data_size,
             ..Default::default()
         };
@@ -448,6 +467,25 @@ where
         Ok(())
     }
 
+    /// Read `data.len()` 32 bit words from the same `address`.
+    ///
+    /// The address is not incremented, so all words are read in a single batch of transfers.
+    /// The address where the read should be performed at has to be a multiple of 4.
+    /// Returns `ArmError::MemoryNotAligned` if this does not hold true.
+    pub fn read_32_repeated(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if (address % 4) != 0 {
+            return Err(ArmError::alignment_error(address, 4));
+        }
+
+        self.write_csw_register_with_increment(DataSize::U32, AddressIncrement::Off)?;
+        self.write_tar_register(address)?;
+        self.read_drw(data)
+    }
+
     /// Read a block of 16 bit words at `address`.
     ///
     /// The number of words read is `data.len()`.
@@ -811,6 +849,10 @@ where
         self.read_64(address, data)
     }
 
+    fn read_32_repeated(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
+        self.read_32_repeated(address, data)
+    }
+
     fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
         self.write_8(address, data)
     }
@@ -1005,6 +1047,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_32_repeated() {
+        let mut mock = MockMemoryAp::with_pattern_and_size(256);
+        mock.memory[..DATA8.len()].copy_from_slice(DATA8);
+        let mut mi = ADIMemoryInterface::new_mock(&mut mock);
+
+        let mut data = [0u32; 3];
+        mi.read_32_repeated(4, &mut data).unwrap();
+        assert_eq!(data, [DATA32[1]; 3]);
+
+        // Following block reads increment the address again.
+        let mut data = [0u32; 2];
+        mi.read_32(0, &mut data).unwrap();
+        assert_eq!(data, DATA32[..2]);
+    }
+
     #[test]
     fn read_32_big_chunk() {
         let mut mock = MockMemoryAp::with_pattern_and_size(4096);
@@ -5,6 +5,7 @@ pub(crate) mod communication_interface;
 pub mod component;
 pub(crate) mod core;
 pub mod dp;
+pub mod etm;
 pub mod memory;
 pub mod sequences;
 pub mod swo;
@@ -8,7 +8,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use probe_rs_target::CoreType;
+use probe_rs_target::{CoreType, OptionBytesRegion};
 
 use crate::{
     architecture::arm::ArmProbeInterface,
@@ -52,6 +52,18 @@ impl ArmDebugSequenceError {
     }
 }
 
+/// The readout protection state of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionState {
+    /// The memory of the device can be read and written by the debugger.
+    Unprotected,
+    /// Debug access to the memory of the device is restricted.
+    /// The protection can be removed, which usually erases the device.
+    Protected,
+    /// Debug access to the device is restricted, and the protection can not be removed.
+    PermanentlyProtected,
+}
+
 /// The default sequences that is used for ARM chips that do not specify a specific sequence.
 #[derive(Debug)]
 pub struct DefaultArmSequence(pub(crate) ());
@@ -783,6 +795,72 @@ pub trait ArmDebugSequence: Send + Sync + Debug {
         Ok(())
     }
 
+    /// Query the readout protection state of the device.
+    ///
+    /// This is called after the debug port has been set up, but before [`ArmDebugSequence::debug_device_unlock`]
+    /// and before debugging of the cores is enabled. It must therefore not access the cores, and should
+    /// only use access ports which are available while the device is protected.
+    ///
+    /// This is not based on a sequence from the Open-CMSIS-Pack standard.
+    fn protection_state(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _default_ap: MemoryAp,
+    ) -> Result<ProtectionState, ArmError> {
+        Err(
+            DebugProbeError::NotImplemented("Readout protection is not available on this device")
+                .into(),
+        )
+    }
+
+    /// Enable the readout protection of the device.
+    ///
+    /// If `permanent` is set, the strongest protection level of the device is enabled,
+    /// which can not be removed anymore. Depending on the device, the protection only
+    /// takes effect after the next reset.
+    ///
+    /// This is not based on a sequence from the Open-CMSIS-Pack standard.
+    ///
+    /// # Errors
+    /// Requires the `device_lock` permission, and for a permanent protection additionally
+    /// the `irreversible_writes` permission. Some devices reset to apply the protection,
+    /// in which case `ArmError::ReAttachRequired` is returned.
+    fn lock(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _default_ap: MemoryAp,
+        _permanent: bool,
+        _permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
+        Err(
+            DebugProbeError::NotImplemented("Readout protection is not available on this device")
+                .into(),
+        )
+    }
+
+    /// Remove the readout protection of the device.
+    ///
+    /// Removing the protection erases the device on all supported families. Like
+    /// [`ArmDebugSequence::protection_state`], this is called before the device is unlocked
+    /// by [`ArmDebugSequence::debug_device_unlock`] and before debugging of the cores is enabled.
+    ///
+    /// This is not based on a sequence from the Open-CMSIS-Pack standard.
+    ///
+    /// # Errors
+    /// Requires the `erase_all` permission. Some devices reset after the protection has been removed,
+    /// in which case `ArmError::ReAttachRequired` is returned.
+    fn unlock(
+        &self,
+        _interface: &mut dyn ArmProbeInterface,
+        _default_ap: MemoryAp,
+        _permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
+        Err(
+            DebugProbeError::NotImplemented("Readout protection is not available on this device")
+                .into(),
+        )
+    }
+
     /// Executed before step or run command to support recovery from a lost target connection, e.g. after a low power mode.
     /// This is based on the `RecoverSupportStart` function from the [ARM SVD Debug Description].
     ///
@@ -908,6 +986,11 @@ pub trait ArmDebugSequence: Send + Sync + Debug {
     fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
         None
     }
+
+    /// Return the Option Bytes Sequence implementation if it exists
+    fn option_bytes_sequence(&self) -> Option<Arc<dyn OptionBytesSequence>> {
+        None
+    }
 }
 
 /// Chip-Erase Handling via the Device's Debug Interface
@@ -929,6 +1012,44 @@ pub trait DebugEraseSequence: Send + Sync {
     }
 }
 
+/// Option Bytes, UICR, OTP and Fuse Handling via the Device's Debug Interface
+pub trait OptionBytesSequence: Send + Sync {
+    /// Read the current contents of an option bytes region into `data`.
+    ///
+    /// `data` always has the size of the whole region. The default implementation
+    /// reads the region through the memory interface, which works for all devices
+    /// where the option bytes are memory mapped.
+    fn read_option_bytes(
+        &self,
+        memory: &mut dyn ArmProbe,
+        region: &OptionBytesRegion,
+        data: &mut [u8],
+    ) -> Result<(), ArmError> {
+        let mut words = vec![0u32; data.len() / 4];
+        memory.read_32(region.range.start, &mut words)?;
+
+        for (chunk, word) in data.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Program an option bytes region with the contents of `data`.
+    ///
+    /// `data` always has the size of the whole region.
+    ///
+    /// # Errors
+    /// Some devices reset after the new option bytes have been loaded, in which case
+    /// `ArmError::ReAttachRequired` is returned to signal that a re-connect is needed.
+    fn write_option_bytes(
+        &self,
+        memory: &mut dyn ArmProbe,
+        region: &OptionBytesRegion,
+        data: &[u8],
+    ) -> Result<(), ArmError>;
+}
+
 /// Perform a SWD line reset (SWDIO high for 50 clock cycles)
 fn swd_line_reset(interface: &mut dyn DapProbe) -> Result<(), ArmError> {
     tracing::debug!("Performing SWD line reset");
@@ -11,9 +11,12 @@ pub mod gdb;
 pub mod info;
 pub mod itm;
 pub mod list;
+pub mod option_bytes;
 pub mod profile;
+pub mod protect;
 pub mod read;
 pub mod reset;
+pub mod rtt_decode;
 pub mod run;
 pub mod trace;
 pub mod write;
@@ -27,6 +27,7 @@ use crate::util::common_options::{
 };
 use crate::util::flash::{build_loader, run_flash_download};
 use crate::util::logging::setup_logging;
+use crate::util::patch::PatchOptions;
 use crate::util::{build_artifact, common_options::CargoOptions, logging, rtt::DataFormat};
 use crate::FormatOptions;
 
@@ -186,11 +187,13 @@ fn main_try(mut args: Vec<OsString>, offset: UtcOffset) -> Result<()> {
         chip,
         chip_description_path: None,
         protocol: Some(config.probe.protocol),
-        probe_selector: selector,
+        probe_selector: selector.into_iter().collect(),
         speed: config.probe.speed,
         connect_under_reset: config.general.connect_under_reset,
         dry_run: false,
         allow_erase_all: config.flashing.enabled || config.gdb.enabled,
+        allow_irreversible_writes: false,
+        allow_device_lock: false,
     };
 
     let (mut session, probe_options) = match probe_options.simple_attach(&lister) {
@@ -525,6 +528,7 @@ fn flash(
         restore_unwritten: config.flashing.restore_unwritten_bytes,
         flash_layout_output_path: None,
         verify: false,
+        patch_options: PatchOptions::default(),
     };
     let format_options = FormatOptions::default();
     let loader = build_loader(session, path, format_options)?;
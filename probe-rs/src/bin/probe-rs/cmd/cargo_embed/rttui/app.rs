@@ -16,6 +16,9 @@ use ratatui::{
 use std::io::Write;
 use std::{fmt::write, path::PathBuf, sync::mpsc::TryRecvError};
 
+use crate::util::rtt::forward::RttForwarder;
+use crate::util::rtt::pipe::RttPipe;
+use crate::util::rtt::tcp::RttTcpServer;
 use crate::util::rtt::DataFormat;
 
 use super::{
@@ -48,10 +51,15 @@ impl<'defmt> App<'defmt> {
         let mut tabs = Vec::new();
         if !config.rtt.channels.is_empty() {
             for channel in &config.rtt.channels {
-                let data = match channel.format {
-                    DataFormat::String => ChannelData::new_string(config.rtt.show_timestamps),
-                    DataFormat::BinaryLE => ChannelData::new_binary(),
-                    DataFormat::Defmt => {
+                let down_channel = channel.down.and_then(|down| rtt.down_channels.take(down));
+                let forward_output = down_channel.is_some();
+                let data = match (&channel.plot, channel.format) {
+                    (Some(plot), _) => ChannelData::new_plot(plot.clone()),
+                    (None, DataFormat::String) => {
+                        ChannelData::new_string(config.rtt.show_timestamps)
+                    }
+                    (None, DataFormat::BinaryLE) => ChannelData::new_binary(),
+                    (None, DataFormat::Defmt) => {
                         let defmt_information = defmt_state.ok_or_else(|| {
                             anyhow!("Defmt information required for defmt channel {:?}", channel)
                         })?;
@@ -61,12 +69,25 @@ impl<'defmt> App<'defmt> {
                     }
                 };
 
+                let mut forwarder = RttForwarder::default();
+                if let Some(address) = channel.server {
+                    forwarder.add(RttTcpServer::bind(address).with_context(|| {
+                        format!("Failed to start the RTT server for channel {:?}", channel)
+                    })?);
+                }
+                if let Some(command) = channel.pipe.as_deref() {
+                    forwarder.add(RttPipe::spawn(command, forward_output).with_context(|| {
+                        format!("Failed to start the pipe for channel {:?}", channel)
+                    })?);
+                }
+
                 tabs.push(ChannelState::new(
                     channel.up.and_then(|up| rtt.up_channels.take(up)),
-                    channel.down.and_then(|down| rtt.down_channels.take(down)),
+                    down_channel,
                     channel.name.clone(),
                     data,
                     channel.socket,
+                    forwarder,
                 ))
             }
         } else {
@@ -79,6 +100,7 @@ impl<'defmt> App<'defmt> {
                     None,
                     ChannelData::new_string(config.rtt.show_timestamps),
                     None,
+                    RttForwarder::default(),
                 ));
             }
 
@@ -89,6 +111,7 @@ impl<'defmt> App<'defmt> {
                     None,
                     ChannelData::new_string(config.rtt.show_timestamps),
                     None,
+                    RttForwarder::default(),
                 ));
             }
         }
@@ -175,6 +198,10 @@ impl<'defmt> App<'defmt> {
                     ChannelData::Defmt { messages, .. } => {
                         messages_wrapped.extend_from_slice(messages);
                     }
+                    ChannelData::Plot { plot } => {
+                        plot.render(f, chunks[1]);
+                        return;
+                    }
                 };
 
                 let message_num = messages_wrapped.len();
@@ -236,6 +263,13 @@ impl<'defmt> App<'defmt> {
                             );
                             continue;
                         }
+                        ChannelData::Plot { .. } => {
+                            eprintln!(
+                                "Not saving tab {} as saving plots is currently unsupported.",
+                                i + 1
+                            );
+                            continue;
+                        }
                         ChannelData::String { data, .. } => {
                             let extension = "txt";
                             let name = format!("{}_channel{}.{}", self.logname, i, extension);
@@ -310,6 +344,9 @@ impl<'defmt> App<'defmt> {
             KeyCode::Char('l') if event.modifiers.contains(KeyModifiers::CONTROL) => {
                 self.current_tab_mut().clear();
             }
+            KeyCode::Char('p') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.current_tab_mut().toggle_pause();
+            }
             KeyCode::F(n) => {
                 let n = n as usize - 1;
                 if n < self.tabs.len() {
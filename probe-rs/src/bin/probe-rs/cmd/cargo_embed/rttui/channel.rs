@@ -7,8 +7,10 @@ use probe_rs::Core;
 use time::UtcOffset;
 use time::{macros::format_description, OffsetDateTime};
 
+use crate::cmd::cargo_embed::rttui::plot::{PlotConfig, PlotData};
 use crate::cmd::cargo_embed::rttui::tcp::TcpPublisher;
 use crate::cmd::cargo_embed::DefmtInformation;
+use crate::util::rtt::forward::RttForwarder;
 use crate::util::rtt::{DataFormat, RttBuffer};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -19,6 +21,9 @@ pub struct ChannelConfig {
     pub up_mode: Option<ChannelMode>,
     pub format: DataFormat,
     pub socket: Option<SocketAddr>,
+    pub server: Option<SocketAddr>,
+    pub pipe: Option<String>,
+    pub plot: Option<PlotConfig>,
 }
 
 pub enum ChannelData<'defmt> {
@@ -35,6 +40,9 @@ pub enum ChannelData<'defmt> {
         decoder: Box<dyn StreamDecoder + 'defmt>,
         information: &'defmt DefmtInformation,
     },
+    Plot {
+        plot: PlotData,
+    },
 }
 
 impl std::fmt::Debug for ChannelData<'_> {
@@ -55,6 +63,7 @@ impl std::fmt::Debug for ChannelData<'_> {
                 .debug_struct("Defmt")
                 .field("messages", messages)
                 .finish_non_exhaustive(),
+            Self::Plot { plot } => f.debug_struct("Plot").field("plot", plot).finish(),
         }
     }
 }
@@ -83,6 +92,12 @@ impl<'defmt> ChannelData<'defmt> {
         Self::Binary { data: Vec::new() }
     }
 
+    pub fn new_plot(config: PlotConfig) -> Self {
+        Self::Plot {
+            plot: PlotData::new(config),
+        }
+    }
+
     fn clear(&mut self) {
         match self {
             Self::String {
@@ -95,6 +110,7 @@ impl<'defmt> ChannelData<'defmt> {
             }
             Self::Binary { data, .. } => data.clear(),
             Self::Defmt { messages, .. } => messages.clear(),
+            Self::Plot { plot } => plot.clear(),
         }
     }
 }
@@ -109,6 +125,7 @@ pub struct ChannelState<'defmt> {
     scroll_offset: usize,
     rtt_buffer: RttBuffer,
     tcp_socket: Option<TcpPublisher>,
+    forwarder: RttForwarder,
 }
 
 impl<'defmt> ChannelState<'defmt> {
@@ -118,6 +135,7 @@ impl<'defmt> ChannelState<'defmt> {
         name: Option<String>,
         data: ChannelData<'defmt>,
         tcp_socket: Option<SocketAddr>,
+        forwarder: RttForwarder,
     ) -> Self {
         let name = name
             .or_else(|| up_channel.as_ref().and_then(|up| up.name().map(Into::into)))
@@ -139,6 +157,7 @@ impl<'defmt> ChannelState<'defmt> {
             rtt_buffer: RttBuffer::new(1024),
             data,
             tcp_socket,
+            forwarder,
         }
     }
 
@@ -159,15 +178,30 @@ impl<'defmt> ChannelState<'defmt> {
     }
 
     pub fn scroll_up(&mut self) {
+        if let ChannelData::Plot { plot } = &mut self.data {
+            plot.scroll_back();
+            return;
+        }
         self.scroll_offset += 1;
     }
 
     pub fn scroll_down(&mut self) {
+        if let ChannelData::Plot { plot } = &mut self.data {
+            plot.scroll_forward();
+            return;
+        }
         if self.scroll_offset > 0 {
             self.scroll_offset -= 1;
         }
     }
 
+    /// Pauses or resumes a plot channel. Does nothing for other channels.
+    pub fn toggle_pause(&mut self) {
+        if let ChannelData::Plot { plot } = &mut self.data {
+            plot.toggle_pause();
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -189,6 +223,12 @@ impl<'defmt> ChannelState<'defmt> {
     /// # Errors
     /// This function can return a [`time::Error`] if getting the local time or formatting a timestamp fails.
     pub fn poll_rtt(&mut self, core: &mut Core, offset: UtcOffset) -> Result<(), time::Error> {
+        if let Some(channel) = self.down_channel.as_mut() {
+            if let Err(err) = self.forwarder.write_to(core, channel) {
+                tracing::error!("\nError writing to RTT: {}", err);
+            }
+        }
+
         // TODO: Proper error handling.
         let count = if let Some(channel) = self.up_channel.as_mut() {
             match channel.read(core, self.rtt_buffer.0.as_mut()) {
@@ -206,6 +246,8 @@ impl<'defmt> ChannelState<'defmt> {
             return Ok(());
         }
 
+        self.forwarder.send(&self.rtt_buffer.0[..count]);
+
         match &mut self.data {
             ChannelData::String {
                 data: messages,
@@ -253,6 +295,7 @@ impl<'defmt> ChannelState<'defmt> {
             ChannelData::Binary { data, .. } => {
                 data.extend_from_slice(&self.rtt_buffer.0[..count]);
             }
+            ChannelData::Plot { plot } => plot.push(&self.rtt_buffer.0[..count]),
             // defmt output is later formatted into strings in [App::render].
             ChannelData::Defmt {
                 ref mut messages,
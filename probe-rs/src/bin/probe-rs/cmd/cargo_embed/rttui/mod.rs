@@ -1,4 +1,5 @@
 pub mod app;
 pub mod channel;
 pub mod event;
+pub mod plot;
 pub mod tcp;
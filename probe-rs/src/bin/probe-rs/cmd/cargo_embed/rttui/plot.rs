@@ -0,0 +1,300 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    symbols::Marker,
+    text::Span,
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType},
+    Frame,
+};
+use serde::{Deserialize, Serialize};
+
+/// The number of samples kept per series. Older samples are discarded.
+const MAX_SAMPLES: usize = 10_000;
+
+/// The number of samples shown at once.
+const VISIBLE_SAMPLES: usize = 500;
+
+/// The number of samples moved by a single scroll step.
+const SCROLL_STEP: usize = VISIBLE_SAMPLES / 4;
+
+const SERIES_COLORS: [Color; 6] = [
+    Color::Yellow,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Red,
+    Color::Blue,
+];
+
+/// The type of the values of a plot channel, which are sent in little endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlotElement {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+}
+
+impl PlotElement {
+    fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::U8 => bytes[0].into(),
+            Self::I8 => (bytes[0] as i8).into(),
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]).into(),
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]).into(),
+            Self::U32 => u32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            Self::I32 => i32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            Self::F32 => f32::from_le_bytes(bytes.try_into().unwrap()).into(),
+            Self::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+/// The layout of the samples of a plot channel.
+///
+/// Every sample consists of `count` values of type `element`, one per series.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlotConfig {
+    pub element: PlotElement,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Names of the series, shown in the legend.
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// The samples of a plot channel, and the part of them which is shown.
+#[derive(Debug)]
+pub struct PlotData {
+    config: PlotConfig,
+    /// Received bytes which don't form a complete sample yet.
+    pending: Vec<u8>,
+    /// The points of every series, with the index of the sample as x value.
+    series: Vec<Vec<(f64, f64)>>,
+    /// The number of samples received in total.
+    sample_count: usize,
+    /// The number of samples received when the plot was paused.
+    paused_at: Option<usize>,
+    /// How many samples the view is scrolled back from the newest shown sample.
+    scroll_back: usize,
+}
+
+impl PlotData {
+    pub fn new(config: PlotConfig) -> Self {
+        let count = config.count.max(1);
+        Self {
+            config,
+            pending: Vec::new(),
+            series: vec![Vec::new(); count],
+            sample_count: 0,
+            paused_at: None,
+            scroll_back: 0,
+        }
+    }
+
+    /// Splits `bytes` into samples, and adds them to the series.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+
+        let element_size = self.config.element.size();
+        let sample_size = element_size * self.series.len();
+
+        let mut samples = self.pending.chunks_exact(sample_size);
+        for sample in &mut samples {
+            let x = self.sample_count as f64;
+            for (series, value) in self.series.iter_mut().zip(sample.chunks(element_size)) {
+                series.push((x, self.config.element.decode(value)));
+            }
+            self.sample_count += 1;
+        }
+        let remainder = samples.remainder().len();
+        self.pending.drain(..self.pending.len() - remainder);
+
+        for series in &mut self.series {
+            // Drop old samples in batches, to avoid moving the samples on every push.
+            if series.len() > MAX_SAMPLES + MAX_SAMPLES / 10 {
+                series.drain(..series.len() - MAX_SAMPLES);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        for series in &mut self.series {
+            series.clear();
+        }
+        self.paused_at = self.paused_at.map(|_| self.sample_count);
+        self.scroll_back = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Freezes the view while samples keep being received, or resumes following the newest
+    /// samples.
+    pub fn toggle_pause(&mut self) {
+        self.paused_at = match self.paused_at {
+            Some(_) => None,
+            None => Some(self.sample_count),
+        };
+        self.scroll_back = 0;
+    }
+
+    /// Scrolls towards older samples, which pauses the plot.
+    pub fn scroll_back(&mut self) {
+        let end = *self.paused_at.get_or_insert(self.sample_count);
+        let oldest = self.series[0].first().map_or(end, |(x, _)| *x as usize);
+        let max_scroll = end.saturating_sub(oldest + VISIBLE_SAMPLES);
+        self.scroll_back = (self.scroll_back + SCROLL_STEP).min(max_scroll);
+    }
+
+    /// Scrolls towards newer samples.
+    pub fn scroll_forward(&mut self) {
+        self.scroll_back = self.scroll_back.saturating_sub(SCROLL_STEP);
+    }
+
+    /// Returns the range of sample indices which is shown.
+    fn visible_range(&self) -> (usize, usize) {
+        let end = self.paused_at.unwrap_or(self.sample_count) - self.scroll_back;
+        (end.saturating_sub(VISIBLE_SAMPLES), end)
+    }
+
+    /// Returns the visible points of every series.
+    fn visible_points(&self) -> Vec<&[(f64, f64)]> {
+        let (start, end) = self.visible_range();
+        self.series
+            .iter()
+            .map(|series| {
+                let first = series.partition_point(|(x, _)| (*x as usize) < start);
+                let last = series.partition_point(|(x, _)| (*x as usize) < end);
+                &series[first..last]
+            })
+            .collect()
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let points = self.visible_points();
+        let (start, end) = self.visible_range();
+
+        let (min, max) = points
+            .iter()
+            .flat_map(|points| points.iter().map(|(_, y)| *y))
+            .filter(|y| y.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| {
+                (min.min(y), max.max(y))
+            });
+        let (min, max) = if min > max {
+            (-1.0, 1.0)
+        } else if min == max {
+            (min - 1.0, max + 1.0)
+        } else {
+            let margin = (max - min) * 0.05;
+            (min - margin, max + margin)
+        };
+
+        let datasets = points
+            .into_iter()
+            .enumerate()
+            .map(|(i, points)| {
+                let name = self
+                    .config
+                    .labels
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| format!("{i}"));
+                Dataset::default()
+                    .name(name)
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::default().fg(SERIES_COLORS[i % SERIES_COLORS.len()]))
+                    .data(points)
+            })
+            .collect::<Vec<_>>();
+
+        let title = if self.is_paused() {
+            "Paused (Ctrl+P to resume, PageUp/PageDown to scroll)"
+        } else {
+            "Ctrl+P to pause"
+        };
+
+        let chart = Chart::new(datasets)
+            .block(Block::default().borders(Borders::NONE).title(title))
+            .x_axis(
+                Axis::default()
+                    .bounds([start as f64, end.max(start + 1) as f64])
+                    .labels(vec![
+                        Span::raw(start.to_string()),
+                        Span::raw(end.to_string()),
+                    ]),
+            )
+            .y_axis(Axis::default().bounds([min, max]).labels(vec![
+                Span::raw(format!("{min:.2}")),
+                Span::raw(format!("{:.2}", (min + max) / 2.0)),
+                Span::raw(format!("{max:.2}")),
+            ]));
+
+        f.render_widget(chart, area);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn samples_split_across_reads_are_decoded() {
+        let mut plot = PlotData::new(PlotConfig {
+            element: PlotElement::I16,
+            count: 2,
+            labels: Vec::new(),
+        });
+
+        plot.push(&[0x01, 0x00, 0xff]);
+        assert_eq!(plot.sample_count, 0);
+
+        plot.push(&[0xff, 0x02, 0x00, 0xfe]);
+        assert_eq!(plot.series[0], [(0.0, 1.0)]);
+        assert_eq!(plot.series[1], [(0.0, -1.0)]);
+        assert_eq!(plot.pending, [0x02, 0x00, 0xfe]);
+    }
+
+    #[test]
+    fn paused_view_stays_on_the_same_samples() {
+        let mut plot = PlotData::new(PlotConfig {
+            element: PlotElement::U8,
+            count: 1,
+            labels: Vec::new(),
+        });
+        plot.push(&[0; 1000]);
+
+        plot.toggle_pause();
+        plot.push(&[0; 100]);
+        assert_eq!(plot.visible_range(), (500, 1000));
+
+        plot.scroll_back();
+        assert_eq!(plot.visible_range(), (375, 875));
+
+        plot.toggle_pause();
+        assert_eq!(plot.visible_range(), (600, 1100));
+    }
+}
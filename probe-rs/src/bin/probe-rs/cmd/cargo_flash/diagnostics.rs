@@ -77,6 +77,18 @@ pub(crate) fn render_diagnostics(error: OperationError) {
                 "You can select a probe with the `--probe` argument. See `--help` for how to use it.".into()
             ],
         ),
+        OperationError::MultipleProbesSelected { .. } => (
+            error.to_string(),
+            vec![
+                "Only `probe-rs download` can flash several devices at once.".into()
+            ],
+        ),
+        OperationError::FailedToPatch { source, .. } => (
+            error.to_string(),
+            vec![
+                source.to_string(),
+            ],
+        ),
         OperationError::FlashingFailed { source, target, target_spec, .. } => generate_flash_error_hints(source, target, target_spec),
         OperationError::ChipDescriptionNotFound{ .. } => (
             error.to_string(),
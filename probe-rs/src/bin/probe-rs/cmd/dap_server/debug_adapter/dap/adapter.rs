@@ -295,6 +295,29 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
         }
     }
 
+    /// Writes to an RTT down channel, see [`RttWriteArguments`].
+    pub(crate) fn rtt_write(
+        &mut self,
+        target_core: &mut CoreHandle,
+        request: &Request,
+    ) -> Result<()> {
+        let arguments: RttWriteArguments = get_arguments(self, request)?;
+
+        let result = match target_core.core_data.rtt_connection.as_mut() {
+            Some(rtt_connection) => rtt_connection.write(&mut target_core.core, &arguments),
+            None => Err(DebuggerError::UserMessage(
+                "RTT is not active for this core.".to_string(),
+            )),
+        };
+
+        match result {
+            Ok(bytes_written) => {
+                self.send_response(request, Ok(Some(RttWriteResponseBody { bytes_written })))
+            }
+            Err(error) => self.send_response::<()>(request, Err(&error)),
+        }
+    }
+
     /// Evaluates the given expression in the context of the top most stack frame.
     /// The expression has access to any variables and arguments that are in scope.
     pub(crate) fn evaluate(
@@ -321,15 +344,16 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
             if context == "clipboard" {
                 response_body.result = arguments.expression;
             } else if context == "repl" {
-                // While the target is running, we only allow a 'break' command.
+                // While the target is running, we only allow commands which don't need a halted core.
                 // Override clippy, because the recommendation would change the logic.
                 #[allow(clippy::nonminimal_bool)]
                 if !target_core.core.core_halted()?
                     && !(arguments.expression.starts_with("break")
-                        || arguments.expression.starts_with("quit"))
+                        || arguments.expression.starts_with("quit")
+                        || arguments.expression.starts_with("rtt"))
                 {
                     response_body.result =
-                        "The target is running. Only the 'break' or 'quit' commands are allowed."
+                        "The target is running. Only the 'break', 'rtt' or 'quit' commands are allowed."
                             .to_string();
                 } else {
                     // The target is halted, so we can allow any repl command.
@@ -1804,6 +1828,26 @@ impl<P: ProtocolAdapter> DebugAdapter<P> {
             .is_ok()
     }
 
+    /// Send a line written to an ITM stimulus port as `output` event to the MS DAP Client.
+    pub fn itm_output(&mut self, line: String) -> bool {
+        let event_body = match serde_json::to_value(OutputEventBody {
+            output: format!("{line}\n"),
+            category: Some("stdout".to_owned()),
+            variables_reference: None,
+            source: None,
+            line: None,
+            column: None,
+            data: None,
+            group: None,
+        }) {
+            Ok(event_body) => event_body,
+            Err(_) => {
+                return false;
+            }
+        };
+        self.send_event("output", Some(event_body)).is_ok()
+    }
+
     fn new_progress_id(&mut self) -> ProgressId {
         let id = self.progress_id;
 
@@ -76,6 +76,47 @@ pub struct RttWindowOpenedArguments {
     pub window_is_open: bool,
 }
 
+/// Arguments for the custom `rttWrite` request, which writes to an RTT down channel.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RttWriteArguments {
+    /// The RTT down channel number.
+    pub channel_number: usize,
+    /// The data to write, interpreted according to `encoding`.
+    pub data: String,
+    #[serde(default)]
+    pub encoding: RttWriteEncoding,
+    /// Appended to the data, e.g. to submit a command to a shell on the target.
+    #[serde(default)]
+    pub line_ending: RttLineEnding,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RttWriteEncoding {
+    /// The data is written as UTF-8.
+    #[default]
+    Text,
+    /// The data is a sequence of hex bytes, e.g. `01 02 ff` or `0x0102ff`.
+    Hex,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RttLineEnding {
+    #[default]
+    None,
+    Lf,
+    CrLf,
+}
+
+/// Response to the `rttWrite` request.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RttWriteResponseBody {
+    pub bytes_written: usize,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RttChannelEventBody {
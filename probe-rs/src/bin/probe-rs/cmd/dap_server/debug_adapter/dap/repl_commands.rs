@@ -2,6 +2,7 @@ use super::{
     core_status::DapStatus,
     dap_types::{
         BreakpointEventBody, EvaluateArguments, InstructionBreakpoint, MemoryAddress, Response,
+        RttLineEnding, RttWriteArguments, RttWriteEncoding,
     },
     repl_commands_helpers::*,
     repl_types::*,
@@ -406,6 +407,83 @@ pub(crate) static REPL_COMMANDS: &[ReplCommand<ReplHandler>] = &[
             memory_read(input_address, gdb_nuf, target_core)
         },
     },
+    ReplCommand {
+        command: "rtt",
+        help_text: "Interact with the RTT channels of the target.",
+        sub_commands: Some(&[ReplCommand {
+            command: "write",
+            help_text: "Write to an RTT down channel. Text is followed by a newline, unless another line ending is specified.",
+            sub_commands: None,
+            args: Some(&[
+                ReplCommandArgs::Optional("/f (f=format[n(ewline)|r(CRLF)|t(ext only)|x(hex bytes)])"),
+                ReplCommandArgs::Required("channel"),
+                ReplCommandArgs::Required("data"),
+            ]),
+            handler: |target_core, command_arguments, _| {
+                let mut arguments = RttWriteArguments {
+                    line_ending: RttLineEnding::Lf,
+                    ..Default::default()
+                };
+
+                let mut remainder = command_arguments;
+                if let Some(format_arguments) = remainder.strip_prefix('/') {
+                    let (format, rest) = format_arguments
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((format_arguments, ""));
+                    match format {
+                        "n" => {}
+                        "r" => arguments.line_ending = RttLineEnding::CrLf,
+                        "t" => arguments.line_ending = RttLineEnding::None,
+                        "x" => {
+                            arguments.encoding = RttWriteEncoding::Hex;
+                            arguments.line_ending = RttLineEnding::None;
+                        }
+                        other => {
+                            return Err(DebuggerError::UserMessage(format!(
+                                "Invalid format specifier: {other}"
+                            )))
+                        }
+                    }
+                    remainder = rest.trim_start();
+                }
+
+                let (channel, data) = remainder
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((remainder, ""));
+                arguments.channel_number = channel.parse().map_err(|_| {
+                    DebuggerError::UserMessage(format!(
+                        "Invalid channel number {channel:?}. See the `help` command for more information."
+                    ))
+                })?;
+                // Keep the spaces in the text, only the separator after the channel is removed.
+                arguments.data = data.to_string();
+
+                let Some(rtt_connection) = target_core.core_data.rtt_connection.as_mut() else {
+                    return Err(DebuggerError::UserMessage(
+                        "RTT is not active for this core.".to_string(),
+                    ));
+                };
+                let bytes_written = rtt_connection.write(&mut target_core.core, &arguments)?;
+
+                Ok(Response {
+                    command: "rtt".to_string(),
+                    success: true,
+                    message: Some(format!(
+                        "Wrote {bytes_written} bytes to RTT channel {}.",
+                        arguments.channel_number
+                    )),
+                    type_: "response".to_string(),
+                    request_seq: 0,
+                    seq: 0,
+                    body: None,
+                })
+            },
+        }]),
+        args: None,
+        handler: |_, _, _| {
+            Err(DebuggerError::UserMessage("Please provide one of the required subcommands. See the `help` command for more information.".to_string()))
+        },
+    },
     ReplCommand {
         command: "dump",
         help_text: "Create a core dump at a target location. Specify memory ranges to dump, or leave blank to dump in-scope memory regions.",
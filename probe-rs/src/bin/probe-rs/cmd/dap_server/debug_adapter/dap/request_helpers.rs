@@ -4,15 +4,13 @@ use crate::cmd::dap_server::{
     server::{core_data::CoreHandle, session_data::BreakpointType},
     DebuggerError,
 };
+use crate::util::disassemble::capstone;
 use anyhow::{anyhow, Result};
-use capstone::{
-    arch::arm::ArchMode as armArchMode, arch::arm64::ArchMode as aarch64ArchMode,
-    arch::riscv::ArchMode as riscvArchMode, prelude::*, Endian,
-};
+use capstone::prelude::*;
 use num_traits::Zero;
 use probe_rs::{
     debug::{ColumnType, ObjectRef, SourceLocation},
-    CoreType, InstructionSet, MemoryInterface,
+    InstructionSet, MemoryInterface,
 };
 use std::{fmt::Write, time::Duration};
 
@@ -228,44 +226,7 @@ pub(crate) fn disassemble_target_memory(
 }
 
 pub(crate) fn get_capstone(target_core: &mut CoreHandle) -> Result<Capstone, DebuggerError> {
-    let mut cs = match target_core.core.instruction_set()? {
-        InstructionSet::Thumb2 => {
-            let mut capstone_builder = Capstone::new()
-                .arm()
-                .mode(armArchMode::Thumb)
-                .endian(Endian::Little);
-            if matches!(target_core.core.core_type(), CoreType::Armv8m) {
-                capstone_builder = capstone_builder
-                    .extra_mode(std::iter::once(capstone::arch::arm::ArchExtraMode::V8));
-            }
-            capstone_builder.build()
-        }
-        InstructionSet::A32 => Capstone::new()
-            .arm()
-            .mode(armArchMode::Arm)
-            .endian(Endian::Little)
-            .build(),
-        InstructionSet::A64 => Capstone::new()
-            .arm64()
-            .mode(aarch64ArchMode::Arm)
-            .endian(Endian::Little)
-            .build(),
-        InstructionSet::RV32 => Capstone::new()
-            .riscv()
-            .mode(riscvArchMode::RiscV32)
-            .endian(Endian::Little)
-            .build(),
-        InstructionSet::RV32C => Capstone::new()
-            .riscv()
-            .mode(riscvArchMode::RiscV32)
-            .endian(Endian::Little)
-            .extra_mode(std::iter::once(
-                capstone::arch::riscv::ArchExtraMode::RiscVC,
-            ))
-            .build(),
-        InstructionSet::Xtensa => return Err(DebuggerError::Unimplemented),
-    }
-    .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
+    let mut cs = capstone(&mut target_core.core)?.ok_or(DebuggerError::Unimplemented)?;
     let _ = cs.set_skipdata(true);
     Ok(cs)
 }
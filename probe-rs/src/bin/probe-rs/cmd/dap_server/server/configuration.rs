@@ -1,5 +1,5 @@
 use crate::util::common_options::ProbeOptions;
-use crate::util::rtt;
+use crate::util::{itm, rtt};
 use crate::{cmd::dap_server::DebuggerError, FormatOptions};
 use anyhow::{anyhow, Result};
 use probe_rs::probe::{DebugProbeSelector, WireProtocol};
@@ -142,11 +142,13 @@ impl SessionConfig {
             chip: self.chip.clone(),
             chip_description_path: self.chip_description_path.clone(),
             protocol: self.wire_protocol,
-            probe_selector: self.probe_selector.clone(),
+            probe_selector: self.probe_selector.iter().cloned().collect(),
             speed: self.speed,
             connect_under_reset: self.connect_under_reset,
             dry_run: false,
             allow_erase_all: self.allow_erase_all,
+            allow_irreversible_writes: false,
+            allow_device_lock: false,
         }
     }
 }
@@ -217,6 +219,9 @@ pub struct CoreConfig {
 
     #[serde(flatten)]
     pub(crate) rtt_config: rtt::RttConfig,
+
+    #[serde(flatten)]
+    pub(crate) itm_config: itm::ItmConfig,
 }
 
 fn default_console_log() -> Option<ConsoleLog> {
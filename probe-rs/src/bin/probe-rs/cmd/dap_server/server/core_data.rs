@@ -186,26 +186,26 @@ impl<'p> CoreHandle<'p> {
         let mut debugger_rtt_channels: Vec<debug_rtt::DebuggerRttChannel> = vec![];
 
         // Attach to RTT by using the RTT control block address from the ELF file. Do not scan the memory for the control block.
-        let Ok(target_rtt) =
+        let Ok(mut target_rtt) =
             try_attach_rtt(&mut self.core, program_binary, rtt_config, timestamp_offset)
         else {
             tracing::warn!("Failed to initalize RTT. Will try again on the next request... ");
             return Ok(());
         };
 
-        for any_channel in target_rtt.active_channels.iter() {
-            if let Some(up_channel) = &any_channel.up_channel {
+        for any_channel in target_rtt.active_channels.iter_mut() {
+            if let Some(channel_number) = any_channel.number() {
                 if any_channel.data_format == DataFormat::Defmt {
                     // For defmt, we set the channel to be blocking when full.
-                    up_channel.set_mode(&mut self.core, ChannelMode::BlockIfFull)?;
+                    any_channel.set_mode(&mut self.core, ChannelMode::BlockIfFull)?;
                 }
                 debugger_rtt_channels.push(debug_rtt::DebuggerRttChannel {
-                    channel_number: up_channel.number(),
+                    channel_number,
                     // This value will eventually be set to true by a VSCode client request "rttWindowOpened"
                     has_client_window: false,
                 });
                 debug_adapter.rtt_window(
-                    up_channel.number(),
+                    channel_number,
                     any_channel.channel_name.clone(),
                     any_channel.data_format,
                 );
@@ -444,7 +444,7 @@ fn try_attach_rtt(
     let header_address = RttActiveTarget::get_rtt_symbol(&mut open_file)
         .ok_or_else(|| anyhow!("No RTT control block found in ELF file"))?;
 
-    let scan_region = ScanRegion::Exact(header_address as u32);
+    let scan_region = ScanRegion::Exact(header_address);
 
     let memory_regions = core.memory_regions().cloned().collect::<Vec<_>>();
 
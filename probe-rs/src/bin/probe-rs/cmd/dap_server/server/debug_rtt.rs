@@ -1,9 +1,19 @@
 use crate::cmd::dap_server::{
-    debug_adapter::{dap::adapter::*, protocol::ProtocolAdapter},
+    debug_adapter::{
+        dap::{
+            adapter::*,
+            dap_types::{RttLineEnding, RttWriteArguments, RttWriteEncoding},
+        },
+        protocol::ProtocolAdapter,
+    },
     DebuggerError,
 };
 use crate::util::rtt;
 use probe_rs::Core;
+use std::time::{Duration, Instant};
+
+/// How long to wait for the target to read a down channel, when the data doesn't fit at once.
+const DOWN_CHANNEL_WRITE_TIMEOUT: Duration = Duration::from_millis(500);
 
 /// Manage the active RTT target for a specific SessionData, as well as provide methods to reliably move RTT from target, through the debug_adapter, to the client.
 pub struct RttConnection {
@@ -21,6 +31,10 @@ impl RttConnection {
         debug_adapter: &mut DebugAdapter<P>,
         target_core: &mut Core,
     ) -> bool {
+        if self.target_rtt.reattach_if_reset(target_core) {
+            debug_adapter.log_to_console(rtt::REATTACHED_MARKER);
+        }
+
         let mut at_least_one_channel_had_data = false;
         for debugger_rtt_channel in self.debugger_rtt_channels.iter_mut() {
             at_least_one_channel_had_data |=
@@ -28,6 +42,71 @@ impl RttConnection {
         }
         at_least_one_channel_had_data
     }
+
+    /// Writes the data of a `rttWrite` request to a down channel, and returns the number of bytes
+    /// written.
+    ///
+    /// Waits for the target to read the channel if the data doesn't fit at once, and fails if not
+    /// all data could be written.
+    pub fn write(
+        &mut self,
+        target_core: &mut Core,
+        arguments: &RttWriteArguments,
+    ) -> Result<usize, DebuggerError> {
+        let mut bytes = match arguments.encoding {
+            RttWriteEncoding::Text => arguments.data.as_bytes().to_vec(),
+            RttWriteEncoding::Hex => parse_hex(&arguments.data)?,
+        };
+        match arguments.line_ending {
+            RttLineEnding::None => {}
+            RttLineEnding::Lf => bytes.push(b'\n'),
+            RttLineEnding::CrLf => bytes.extend_from_slice(b"\r\n"),
+        }
+
+        let start = Instant::now();
+        let mut written = 0;
+        while written < bytes.len() {
+            written += self.target_rtt.write_down_channel(
+                target_core,
+                arguments.channel_number,
+                &bytes[written..],
+            )?;
+
+            if written < bytes.len() {
+                if start.elapsed() > DOWN_CHANNEL_WRITE_TIMEOUT {
+                    return Err(DebuggerError::UserMessage(format!(
+                        "Only {written} of {} bytes were written to RTT channel {}, because the target didn't read the channel in time.",
+                        bytes.len(),
+                        arguments.channel_number
+                    )));
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+/// Parses hex bytes, optionally prefixed with `0x` and separated by whitespace or commas.
+fn parse_hex(data: &str) -> Result<Vec<u8>, DebuggerError> {
+    let invalid = || DebuggerError::UserMessage(format!("Invalid hex data: {data:?}"));
+
+    let mut bytes = Vec::new();
+    for word in data.split(|c: char| c.is_whitespace() || c == ',') {
+        let digits = word
+            .strip_prefix("0x")
+            .or_else(|| word.strip_prefix("0X"))
+            .unwrap_or(word);
+        if digits.len() % 2 != 0 {
+            return Err(invalid());
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(pair, 16).map_err(|_| invalid())?);
+        }
+    }
+    Ok(bytes)
 }
 
 pub(crate) struct DebuggerRttChannel {
@@ -83,3 +162,19 @@ impl DebuggerRttChannel {
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod test {
+    use super::parse_hex;
+
+    #[test]
+    fn hex_data_is_parsed() {
+        assert_eq!(
+            parse_hex("01 0x02,ff  0A0b").unwrap(),
+            [0x01, 0x02, 0xff, 0x0a, 0x0b]
+        );
+        assert!(parse_hex("123").is_err());
+        assert!(parse_hex("0xzz").is_err());
+    }
+}
@@ -19,7 +19,7 @@ use crate::{
         peripherals::svd_variables::SvdCache,
         DebuggerError,
     },
-    util::flash::build_loader,
+    util::{flash::build_loader, itm::ItmStimulus},
 };
 use anyhow::{anyhow, Context};
 use probe_rs::{
@@ -224,6 +224,7 @@ impl Debugger {
                         debug_session = DebugSessionStatus::Terminate;
                         result
                     }
+                    "rttWrite" => debug_adapter.rtt_write(&mut target_core, &request),
                     "next" => debug_adapter.next(&mut target_core, &request),
                     "stepIn" => debug_adapter.step_in(&mut target_core, &request),
                     "stepOut" => debug_adapter.step_out(&mut target_core, &request),
@@ -472,6 +473,19 @@ impl Debugger {
             ))
         })?;
 
+        if target_core_config.itm_config.enabled {
+            match ItmStimulus::start(
+                &mut session_data.session,
+                target_core_config.core_index,
+                &target_core_config.itm_config,
+            ) {
+                Ok(itm_stimulus) => session_data.itm_stimulus = Some(itm_stimulus),
+                Err(error) => {
+                    debug_adapter.show_error_message(&DebuggerError::Other(error))?;
+                }
+            }
+        }
+
         // First, attach to the core
         let mut target_core = session_data
             .attach_core(target_core_config.core_index)
@@ -556,6 +570,19 @@ impl Debugger {
             ))
         })?;
 
+        if target_core_config.itm_config.enabled {
+            match ItmStimulus::start(
+                &mut session_data.session,
+                target_core_config.core_index,
+                &target_core_config.itm_config,
+            ) {
+                Ok(itm_stimulus) => session_data.itm_stimulus = Some(itm_stimulus),
+                Err(error) => {
+                    debug_adapter.show_error_message(&DebuggerError::Other(error))?;
+                }
+            }
+        }
+
         // First, attach to the core
         let mut target_core = session_data
             .attach_core(target_core_config.core_index)
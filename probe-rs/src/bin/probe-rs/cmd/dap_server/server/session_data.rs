@@ -10,7 +10,7 @@ use crate::{
         },
         DebuggerError,
     },
-    util::common_options::OperationError,
+    util::{common_options::OperationError, itm::ItmStimulus},
 };
 use anyhow::{anyhow, Result};
 use probe_rs::{
@@ -59,6 +59,9 @@ pub(crate) struct SessionData {
     /// [SessionData] will manage one [CoreData] per target core, that is also present in [SessionConfig::core_configs]
     pub(crate) core_data: Vec<CoreData>,
 
+    /// The output of the ITM stimulus ports, if enabled in [CoreConfig::itm_config].
+    pub(crate) itm_stimulus: Option<ItmStimulus>,
+
     /// Offset used for RTC timestamps
     ///
     /// Getting the offset can fail, so it's better to store it.
@@ -148,6 +151,7 @@ impl SessionData {
         Ok(SessionData {
             session: target_session,
             core_data: core_data_vec,
+            itm_stimulus: None,
             timestamp_offset,
         })
     }
@@ -219,6 +223,26 @@ impl SessionData {
 
         let cores_halted_previously = debug_adapter.all_cores_halted;
 
+        // The ITM data of all cores is read from the same trace sink.
+        if let Some(itm_stimulus) = &mut self.itm_stimulus {
+            match itm_stimulus.poll(&mut self.session) {
+                Ok(lines) => {
+                    if !lines.is_empty() {
+                        suggest_delay_required = false;
+                    }
+                    for line in lines {
+                        debug_adapter.itm_output(line);
+                    }
+                }
+                Err(error) => {
+                    debug_adapter
+                        .show_error_message(&DebuggerError::Other(error))
+                        .ok();
+                    self.itm_stimulus = None;
+                }
+            }
+        }
+
         // Always set `all_cores_halted` to true, until one core is found to be running.
         debug_adapter.all_cores_halted = true;
         for core_config in session_config.core_configs.iter() {
@@ -3,10 +3,6 @@ use std::path::PathBuf;
 use std::time::Duration;
 
 use anyhow::anyhow;
-use capstone::{
-    arch::arm::ArchMode as armArchMode, arch::arm64::ArchMode as aarch64ArchMode,
-    arch::riscv::ArchMode as riscvArchMode, prelude::*, Endian,
-};
 use num_traits::Num;
 use parse_int::parse;
 use probe_rs::architecture::arm::ap::AccessPortError;
@@ -18,11 +14,12 @@ use probe_rs::probe::DebugProbeError;
 use probe_rs::CoreDumpError;
 use probe_rs::{
     debug::{debug_info::DebugInfo, registers::DebugRegisters, stack_frame::StackFrame},
-    Core, CoreType, InstructionSet, MemoryInterface, RegisterValue,
+    Core, CoreType, MemoryInterface, RegisterValue,
 };
 use rustyline::DefaultEditor;
 
-use crate::{util::common_options::ProbeOptions, CoreOptions};
+use crate::util::{common_options::ProbeOptions, disassemble::capstone};
+use crate::CoreOptions;
 
 #[derive(clap::Parser)]
 pub struct Cmd {
@@ -164,44 +161,8 @@ impl DebugCli {
 
                 cli_data.core.read(cpu_info.pc, &mut code)?;
 
-                let cs = match cli_data.core.instruction_set()? {
-                    InstructionSet::Thumb2 => Capstone::new()
-                        .arm()
-                        .mode(armArchMode::Thumb)
-                        .endian(Endian::Little)
-                        .build(),
-                    InstructionSet::A32 => {
-                        // We need to inspect the CPSR to determine what mode this is opearting in
-                        Capstone::new()
-                            .arm()
-                            .mode(armArchMode::Arm)
-                            .endian(Endian::Little)
-                            .build()
-                    }
-                    InstructionSet::A64 => {
-                        // We need to inspect the CPSR to determine what mode this is opearting in
-                        Capstone::new()
-                            .arm64()
-                            .mode(aarch64ArchMode::Arm)
-                            .endian(Endian::Little)
-                            .build()
-                    }
-                    InstructionSet::RV32 => Capstone::new()
-                        .riscv()
-                        .mode(riscvArchMode::RiscV32)
-                        .endian(Endian::Little)
-                        .build(),
-                    InstructionSet::RV32C => Capstone::new()
-                        .riscv()
-                        .mode(riscvArchMode::RiscV32)
-                        .endian(Endian::Little)
-                        .extra_mode(std::iter::once(
-                            capstone::arch::riscv::ArchExtraMode::RiscVC,
-                        ))
-                        .build(),
-                    InstructionSet::Xtensa => Err(capstone::Error::UnsupportedArch),
-                }
-                .map_err(|err| anyhow!("Error creating capstone: {:?}", err))?;
+                let cs = capstone(&mut cli_data.core)?
+                    .ok_or_else(|| anyhow!("Disassembly is not supported for this core"))?;
 
                 // Attempt to dissassemble
                 match cs.disasm_all(&code, cpu_info.pc) {
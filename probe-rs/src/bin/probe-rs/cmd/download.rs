@@ -1,11 +1,18 @@
-use std::path::Path;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use colored::Colorize;
 use probe_rs::probe::list::Lister;
+use probe_rs::Session;
+use serde::Serialize;
 
 use crate::util::common_options::BinaryDownloadOptions;
 use crate::util::common_options::ProbeOptions;
 use crate::util::flash::build_loader;
 use crate::util::flash::run_flash_download;
+use crate::util::flash::run_parallel_flash_download;
+use crate::util::logging;
 use crate::FormatOptions;
 
 #[derive(clap::Parser)]
@@ -20,6 +27,12 @@ pub struct Cmd {
     #[clap(long)]
     chip_erase: bool,
 
+    /// Write a JSON report with the result for each device to this file.
+    ///
+    /// Only used when several probes are selected with `--probe`.
+    #[clap(long, value_name = "path")]
+    report: Option<PathBuf>,
+
     #[clap(flatten)]
     download_options: BinaryDownloadOptions,
 
@@ -27,8 +40,51 @@ pub struct Cmd {
     format_options: FormatOptions,
 }
 
+/// Returned when flashing several devices, and only some of them failed.
+///
+/// This results in a different exit code than a complete failure.
+#[derive(Debug, thiserror::Error)]
+#[error("{failed} of {total} devices failed to flash.")]
+pub struct PartialFlashFailure {
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// The result of flashing several devices, written with `--report`.
+#[derive(Serialize)]
+struct FlashReport {
+    path: String,
+    passed: usize,
+    failed: usize,
+    duration_ms: u128,
+    devices: Vec<DeviceReport>,
+}
+
+#[derive(Serialize)]
+struct DeviceReport {
+    probe: String,
+    chip: Option<String>,
+    success: bool,
+    error: Option<String>,
+}
+
+impl DeviceReport {
+    fn failed(probe: String, session: Option<&Session>, error: &anyhow::Error) -> Self {
+        Self {
+            probe,
+            chip: session.map(|session| session.target().name.clone()),
+            success: false,
+            error: Some(format!("{error:#}")),
+        }
+    }
+}
+
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        if self.probe_options.probe_selector.len() > 1 {
+            return self.run_parallel(lister);
+        }
+
         let (mut session, probe_options) = self.probe_options.simple_attach(lister)?;
 
         let loader = build_loader(&mut session, &self.path, self.format_options)?;
@@ -43,4 +99,140 @@ impl Cmd {
 
         Ok(())
     }
+
+    /// Flash the same image to all selected probes at once.
+    fn run_parallel(self, lister: &Lister) -> anyhow::Result<()> {
+        let instant = Instant::now();
+        let probe_options = self.probe_options.load()?;
+        let target = probe_options.get_target_selector()?;
+
+        let mut devices = Vec::new();
+        let mut sessions = Vec::new();
+        let mut names = Vec::new();
+        for selector in probe_options.probe_selectors() {
+            let session = probe_options
+                .attach_probe_by_selector(lister, selector)
+                .and_then(|probe| probe_options.attach_session(probe, target.clone()));
+
+            match session {
+                Ok(session) => {
+                    names.push(selector.to_string());
+                    sessions.push(session);
+                }
+                Err(error) => devices.push(DeviceReport::failed(
+                    selector.to_string(),
+                    None,
+                    &anyhow::Error::from(error),
+                )),
+            }
+        }
+
+        // All devices are flashed with the same image, so it is only loaded once,
+        // and then patched with the data of each device.
+        let loader = sessions
+            .first_mut()
+            .map(|session| build_loader(session, &self.path, self.format_options));
+
+        match loader {
+            Some(Ok(loader)) => {
+                // Devices whose data can't be patched into the image are not flashed.
+                let mut flash_sessions = Vec::new();
+                let mut flash_names = Vec::new();
+                let mut loaders = Vec::new();
+                for (session, name) in sessions.into_iter().zip(names) {
+                    let mut loader = loader.clone();
+                    match self.download_options.patch_options.apply(&mut loader) {
+                        Ok(()) => {
+                            flash_sessions.push(session);
+                            flash_names.push(name);
+                            loaders.push(loader);
+                        }
+                        Err(error) => devices.push(DeviceReport::failed(
+                            name,
+                            Some(&session),
+                            &anyhow::Error::from(error),
+                        )),
+                    }
+                }
+
+                let results = run_parallel_flash_download(
+                    &mut flash_sessions,
+                    &loaders,
+                    &flash_names,
+                    Path::new(&self.path),
+                    &self.download_options,
+                    &probe_options,
+                    self.chip_erase,
+                );
+
+                for ((result, session), name) in
+                    results.into_iter().zip(&flash_sessions).zip(flash_names)
+                {
+                    devices.push(match result {
+                        Ok(()) => DeviceReport {
+                            probe: name,
+                            chip: Some(session.target().name.clone()),
+                            success: true,
+                            error: None,
+                        },
+                        Err(error) => {
+                            DeviceReport::failed(name, Some(session), &anyhow::Error::from(error))
+                        }
+                    });
+                }
+            }
+            Some(Err(error)) => {
+                for (session, name) in sessions.iter().zip(names) {
+                    devices.push(DeviceReport::failed(name, Some(session), &error));
+                }
+            }
+            None => {}
+        }
+
+        let failed = devices.iter().filter(|device| !device.success).count();
+        for device in &devices {
+            match &device.error {
+                None => logging::eprintln(format!(
+                    "      {} {}",
+                    "Passed".green().bold(),
+                    device.probe
+                )),
+                Some(error) => logging::eprintln(format!(
+                    "      {} {}: {error}",
+                    "Failed".red().bold(),
+                    device.probe
+                )),
+            }
+        }
+
+        let report = FlashReport {
+            path: self.path,
+            passed: devices.len() - failed,
+            failed,
+            duration_ms: instant.elapsed().as_millis(),
+            devices,
+        };
+
+        if let Some(path) = &self.report {
+            let file = File::create(path)?;
+            serde_json::to_writer_pretty(file, &report)?;
+        }
+
+        logging::eprintln(format!(
+            "    {} {} of {} devices in {}s",
+            "Finished".green().bold(),
+            report.passed,
+            report.devices.len(),
+            report.duration_ms as f32 / 1000.0,
+        ));
+
+        let total = report.devices.len();
+        if failed == total {
+            anyhow::bail!("All {total} devices failed to flash.");
+        } else if failed > 0 {
+            return Err(PartialFlashFailure { failed, total }.into());
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,151 @@
+use anyhow::Context;
+use probe_rs::{
+    flashing::{read_option_bytes, write_option_bytes, OptionBytes},
+    probe::list::Lister,
+};
+
+use crate::util::{common_options::ProbeOptions, parse_u32};
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+/// Read and modify option bytes, UICR, OTP and fuse regions of the target
+enum Subcommand {
+    /// Read and decode the option bytes of the target
+    #[clap(name = "get")]
+    Get {
+        #[clap(flatten)]
+        probe_options: ProbeOptions,
+
+        /// The name of the region to read. All regions are read if omitted.
+        region: Option<String>,
+    },
+    /// Modify fields of an option bytes region
+    ///
+    /// e.g. probe-rs option-bytes set --chip STM32L476RGTx OPTR RDP=Level1 nBOOT1=0
+    #[clap(name = "set", verbatim_doc_comment)]
+    Set {
+        #[clap(flatten)]
+        probe_options: ProbeOptions,
+
+        /// The name of the region to modify.
+        region: String,
+
+        /// The fields to modify, as FIELD=VALUE.
+        /// The value is either a named value from the target description, or an integer
+        /// in decimal (16), hexadecimal (0x10) or octal (0o20) format.
+        #[clap(value_parser = parse_field_assignment, required = true)]
+        fields: Vec<(String, String)>,
+    },
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        match self.subcommand {
+            Subcommand::Get {
+                probe_options,
+                region,
+            } => {
+                let (mut session, _probe_options) = probe_options.simple_attach(lister)?;
+
+                let regions = match region {
+                    Some(region) => vec![region],
+                    None => session
+                        .target()
+                        .option_bytes
+                        .iter()
+                        .map(|region| region.name.clone())
+                        .collect(),
+                };
+
+                if regions.is_empty() {
+                    println!(
+                        "The target {} has no option bytes regions.",
+                        session.target().name
+                    );
+                    return Ok(());
+                }
+
+                for region in regions {
+                    let option_bytes = read_option_bytes(&mut session, &region)?;
+                    print_option_bytes(&option_bytes);
+                }
+
+                Ok(())
+            }
+            Subcommand::Set {
+                probe_options,
+                region,
+                fields,
+            } => {
+                let (mut session, probe_options) = probe_options.simple_attach(lister)?;
+
+                let mut option_bytes = read_option_bytes(&mut session, &region)?;
+                for (field, value) in &fields {
+                    match parse_u32(value) {
+                        Ok(raw) => option_bytes.set(field, raw)?,
+                        Err(_) => option_bytes.set_named(field, value)?,
+                    }
+                }
+
+                write_option_bytes(&mut session, &option_bytes, &probe_options.permissions())
+                    .with_context(|| format!("Failed to write option bytes region {region}"))?;
+
+                let option_bytes = read_option_bytes(&mut session, &region)?;
+                print_option_bytes(&option_bytes);
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn print_option_bytes(option_bytes: &OptionBytes) {
+    let region = option_bytes.region();
+
+    println!(
+        "{} ({:?}): {:#010x}..{:#010x}",
+        region.name, region.kind, region.range.start, region.range.end
+    );
+
+    for (index, word) in option_bytes.data().chunks_exact(4).enumerate() {
+        let word = u32::from_le_bytes(word.try_into().unwrap());
+        println!(
+            "    {:#010x}: {word:08x}",
+            region.range.start + 4 * index as u64
+        );
+    }
+
+    for state in option_bytes.fields() {
+        match state.named_value {
+            Some(named) => println!(
+                "    {:<20} {:#x} ({})",
+                state.field.name, state.value, named.name
+            ),
+            None => println!("    {:<20} {:#x}", state.field.name, state.value),
+        }
+    }
+}
+
+fn parse_field_assignment(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((field, value)) if !field.is_empty() && !value.is_empty() => {
+            Ok((field.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("'{input}' is not in the format FIELD=VALUE")),
+    }
+}
+
+#[test]
+fn field_assignment() {
+    assert_eq!(
+        parse_field_assignment("RDP=Level1"),
+        Ok(("RDP".to_string(), "Level1".to_string()))
+    );
+    assert!(parse_field_assignment("RDP").is_err());
+    assert!(parse_field_assignment("=0x1").is_err());
+}
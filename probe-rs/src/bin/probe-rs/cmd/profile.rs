@@ -1,5 +1,11 @@
+mod callstack;
+mod report;
+
 use std::collections::HashMap;
-use std::path::Path;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use itm::TracePacket;
@@ -10,17 +16,23 @@ use probe_rs::{
         DpAddress, SwoConfig,
     },
     probe::list::Lister,
+    Core, Error,
 };
-use time::Instant;
+use std::time::Instant;
 
 use addr2line::{
     gimli::{EndianRcSlice, RunTimeEndian},
-    object::{read::File as ObjectFile, Object},
+    object::{read::File as ObjectFile, Object, ObjectSection, ObjectSymbol, SymbolKind},
     Context as ObjectContext, LookupResult,
 };
 
+use crate::util::coverage::CoverageOptions;
+use crate::util::disassemble::capstone;
 use crate::util::flash::{build_loader, run_flash_download};
-use tracing::info;
+use callstack::{sample_call_stack, CallStacks};
+use probe_rs::debug::DebugInfo;
+use report::{Location, ProfileReport, ReportFormat};
+use tracing::{info, warn};
 
 #[derive(clap::Parser)]
 pub struct ProfileCmd {
@@ -41,17 +53,56 @@ pub struct ProfileCmd {
     /// Limit the number of entries to output
     #[clap(long, default_value_t = 25)]
     limit: usize,
+    /// How the report is written. The `callstack` method writes the hottest call stacks instead
+    /// of the text report.
+    #[clap(long, value_enum, default_value_t)]
+    report_format: ReportFormat,
+    /// Write the report to this file instead of stdout
+    #[clap(long)]
+    report_output: Option<PathBuf>,
+    /// Print the sampled source lines and instructions of the most sampled functions
+    #[clap(long)]
+    annotate: bool,
+    #[clap(flatten)]
+    coverage: CoverageOptions,
     /// Profile Method
     #[clap(subcommand)]
     method: ProfileMethod,
 }
 
-#[derive(clap::Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(clap::Subcommand, Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ProfileMethod {
     /// Naive, Halt -> Read PC -> Resume profiler
     #[clap(name = "naive")]
     Naive,
+    /// Read the PC sample register of the core without halting it, e.g. DWT_PCSR on Cortex-M or
+    /// EDPCSR on ARMv8-A (ARM only). Falls back to the naive method if the core has none.
+    #[clap(name = "pcsr")]
+    Pcsr {
+        /// The number of samples read in a single batch.
+        #[clap(long, default_value_t = 256)]
+        batch_size: usize,
+    },
+    /// Halt -> Unwind the stack -> Resume profiler, to find the hot call paths
+    #[clap(name = "callstack")]
+    CallStack {
+        /// The number of samples per second.
+        #[clap(long, default_value_t = 100)]
+        rate: u32,
+        /// The maximum number of frames per stack. Outer frames beyond are dropped.
+        #[clap(long, default_value_t = 64)]
+        max_depth: usize,
+        /// Write the stacks in the folded format of flamegraph.pl and inferno to this file.
+        #[clap(long)]
+        folded: Option<PathBuf>,
+        /// Write the stacks in the file format of speedscope to this file.
+        #[clap(long)]
+        speedscope: Option<PathBuf>,
+        /// Write the stacks as an uncompressed pprof profile to this file.
+        #[clap(long)]
+        pprof: Option<PathBuf>,
+    },
     /// Use the Itm port to profile the chip (ARM only)
     #[clap(name = "itm")]
     Itm {
@@ -91,8 +142,11 @@ impl ProfileCmd {
 
         let start = Instant::now();
         let mut reads = 0;
-        let mut samples: HashMap<u32, u64> = HashMap::with_capacity(256 * (self.duration as usize));
+        let mut samples: HashMap<u64, u64> = HashMap::with_capacity(256 * (self.duration as usize));
         let duration = Duration::from_secs(self.duration);
+        // Set by the `callstack` method, which shows the hottest call stacks instead of the
+        // text report.
+        let mut call_stacks = None;
         info!("Profiling...");
 
         match self.method {
@@ -100,19 +154,90 @@ impl ProfileCmd {
                 let mut core = session.core(self.core)?;
                 info!("Attached to Core {}", self.core);
                 core.reset()?;
-                let pc_reg = core.program_counter();
 
+                reads = sample_naive(&mut core, &mut samples, start, duration)?;
+            }
+            ProfileMethod::Pcsr { batch_size } => {
+                let mut core = session.core(self.core)?;
+                info!("Attached to Core {}", self.core);
+                core.reset()?;
+
+                let mut batch = vec![0; batch_size.max(1)];
                 loop {
-                    core.halt(std::time::Duration::from_millis(10))?;
-                    let pc: u32 = core.read_core_reg(pc_reg)?;
-                    *samples.entry(pc).or_insert(1) += 1;
-                    reads += 1;
-                    core.run()?;
+                    let count = match core.read_pc_samples(&mut batch) {
+                        Ok(count) => count,
+                        Err(Error::NotImplemented(_)) => {
+                            warn!(
+                                "Core {} does not support PC sampling, falling back to the naive method",
+                                self.core
+                            );
+                            reads += sample_naive(&mut core, &mut samples, start, duration)?;
+                            break;
+                        }
+                        Err(error) => return Err(error.into()),
+                    };
+
+                    for &pc in &batch[..count] {
+                        *samples.entry(pc).or_insert(0) += 1;
+                    }
+                    reads += count as u64;
+
                     if Instant::now() - start > duration {
                         break;
                     }
                 }
             }
+            ProfileMethod::CallStack {
+                rate,
+                max_depth,
+                folded,
+                speedscope,
+                pprof,
+            } => {
+                let debug_info = DebugInfo::from_file(&self.run.path)?;
+                let mut core = session.core(self.core)?;
+                info!("Attached to Core {}", self.core);
+                core.reset()?;
+
+                let period = Duration::from_secs(1) / rate.max(1);
+                let mut stacks = CallStacks::default();
+                let mut failed = 0;
+                loop {
+                    let sampled_at = Instant::now();
+                    match sample_call_stack(&mut core, &debug_info, max_depth) {
+                        Ok((pc, stack)) => {
+                            *samples.entry(pc).or_insert(0) += 1;
+                            reads += 1;
+                            stacks.add(stack);
+                        }
+                        Err(error) => {
+                            tracing::debug!("Failed to unwind the stack: {error}");
+                            failed += 1;
+                        }
+                    }
+
+                    if Instant::now() - start > duration {
+                        break;
+                    }
+                    std::thread::sleep(period.saturating_sub(sampled_at.elapsed()));
+                }
+
+                if failed > 0 {
+                    warn!("Failed to unwind {failed} samples");
+                }
+
+                if let Some(path) = folded {
+                    stacks.write_folded(BufWriter::new(File::create(path)?))?;
+                }
+                if let Some(path) = speedscope {
+                    stacks.write_speedscope(BufWriter::new(File::create(path)?), &self.run.path)?;
+                }
+                if let Some(path) = pprof {
+                    stacks.write_pprof(File::create(path)?, period.as_nanos() as u64)?;
+                }
+
+                call_stacks = Some(stacks);
+            }
             ProfileMethod::Itm { clk, baud } => {
                 let sink = TraceSink::Swo(SwoConfig::new(clk).set_baud(baud));
                 session.setup_tracing(self.core, sink)?;
@@ -132,7 +257,7 @@ impl ProfileCmd {
 
                 for packet in iter {
                     if let TracePacket::PCSample { pc: Some(pc) } = packet? {
-                        *samples.entry(pc).or_insert(1) += 1;
+                        *samples.entry(pc.into()).or_insert(0) += 1;
                         reads += 1;
                     }
                     if Instant::now() - start > duration {
@@ -142,33 +267,123 @@ impl ProfileCmd {
             }
         }
 
-        let mut v = Vec::from_iter(samples);
-        // sort by frequency
-        v.sort_by(|&(_, a), &(_, b)| b.cmp(&a));
-
-        println!("Samples {}", reads);
+        let debug_info = DebugInfo::from_file(&self.run.path)?;
+        if self.coverage.coverage.is_some() {
+            self.coverage.export(
+                &debug_info,
+                samples.iter().map(|(&address, &count)| (address, count)),
+                Path::new(&self.run.path),
+            )?;
+        }
 
-        for (address, count) in v.into_iter().take(self.limit) {
+        let report = ProfileReport::new(&samples, reads, |address| {
             let name = symbols
-                .get_name(address as u64)
+                .get_name(address)
                 .unwrap_or(format!("UNKNOWN - {:08X}", address));
-            let (file, num) = symbols
-                .get_location(address as u64)
-                .unwrap_or(("UNKNOWN".to_owned(), 0));
-            if self.line_info {
-                println!("{}:{}", file, num);
+            let location = debug_info
+                .get_source_location(address)
+                .and_then(|location| {
+                    Some(Location {
+                        line: location.line?,
+                        file: location
+                            .combined_typed_path()?
+                            .to_string_lossy()
+                            .into_owned(),
+                    })
+                });
+            (name, location)
+        });
+
+        if self.annotate {
+            let mut core = session.core(self.core)?;
+            let disassembler = capstone(&mut core)?;
+            let functions = symbols.functions();
+            let line_table = debug_info.line_table();
+            report.write_annotated(
+                std::io::stdout().lock(),
+                self.limit,
+                |address| {
+                    // The source lines with code of the function containing `address`.
+                    let Some((_, function)) =
+                        functions.iter().find(|(_, range)| range.contains(&address))
+                    else {
+                        return Vec::new();
+                    };
+                    line_table
+                        .iter()
+                        .filter(|(range, _)| function.contains(&range.start))
+                        .filter_map(|(_, location)| {
+                            Some(Location {
+                                line: location.line?,
+                                file: location
+                                    .combined_typed_path()?
+                                    .to_string_lossy()
+                                    .into_owned(),
+                            })
+                        })
+                        .collect()
+                },
+                |address| {
+                    let code = symbols.code(address)?;
+                    let instructions =
+                        disassembler.as_ref()?.disasm_count(code, address, 1).ok()?;
+                    let instruction = instructions.iter().next()?;
+                    Some(format!(
+                        "{} {}",
+                        instruction.mnemonic().unwrap_or_default(),
+                        instruction.op_str().unwrap_or_default()
+                    ))
+                },
+            )?;
+        }
+
+        let mut writer: Box<dyn Write> = match &self.report_output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout().lock()),
+        };
+        match call_stacks {
+            Some(stacks) if self.report_format == ReportFormat::Text => {
+                stacks.write_hottest(&mut writer, self.limit)?
             }
-            println!(
-                "{:>50} - {:.01}%",
-                name,
-                (count as f64 / reads as f64) * 100.0
-            );
+            _ => report.write(
+                self.report_format,
+                &mut writer,
+                &self.run.path,
+                self.limit,
+                self.line_info,
+            )?,
         }
+        writer.flush()?;
 
         Ok(())
     }
 }
 
+/// Samples the PC by halting the core, reading the PC and resuming the core, until `duration` has
+/// passed since `start`. Returns the number of samples.
+fn sample_naive(
+    core: &mut Core,
+    samples: &mut HashMap<u64, u64>,
+    start: Instant,
+    duration: Duration,
+) -> anyhow::Result<u64> {
+    let pc_reg = core.program_counter();
+    let mut reads = 0;
+
+    loop {
+        core.halt(std::time::Duration::from_millis(10))?;
+        let pc: u64 = core.read_core_reg(pc_reg)?;
+        *samples.entry(pc).or_insert(0) += 1;
+        reads += 1;
+        core.run()?;
+        if Instant::now() - start > duration {
+            break;
+        }
+    }
+
+    Ok(reads)
+}
+
 // Wrapper around addr2line that allows to look up function names
 pub(crate) struct Symbols<'sym> {
     file: ObjectFile<'sym, &'sym [u8]>,
@@ -213,18 +428,41 @@ impl<'sym> Symbols<'sym> {
             })
     }
 
-    /// Returns the file name and line number of the function at the given address, if one can be.
-    pub fn get_location(&self, addr: u64) -> Option<(String, u32)> {
-        // Find the location which `addr` is in. If we can dedetermine a file name and
-        // line number for this function we will return them both in a tuple.
-        self.ctx.find_location(addr).ok()?.map(|location| {
-            let file = location.file.map(|f| f.to_string());
-            let line = location.line;
-
-            match (file, line) {
-                (Some(file), Some(line)) => Some((file, line)),
-                _ => None,
+    /// Returns the bytes of the program starting at the given address, if it is part of a section.
+    pub fn code(&self, addr: u64) -> Option<&'sym [u8]> {
+        self.file.sections().find_map(|section| {
+            let offset = addr.checked_sub(section.address())?;
+            if offset >= section.size() {
+                return None;
             }
-        })?
+            section.data().ok()?.get(offset as usize..)
+        })
+    }
+
+    /// Returns the data of the section with the given name, if the program has one.
+    pub fn section(&self, name: &str) -> Option<&'sym [u8]> {
+        self.file
+            .section_by_name(name)
+            .and_then(|section| section.data().ok())
+    }
+
+    /// Returns the name and address range of every function in the program, sorted by address.
+    pub fn functions(&self) -> Vec<(String, Range<u64>)> {
+        let mut functions = self
+            .file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.size() > 0)
+            .filter_map(|symbol| {
+                // The lowest bit of Thumb function addresses is set.
+                let start = symbol.address() & !1;
+                let name = self
+                    .get_name(start)
+                    .or_else(|| symbol.name().ok().map(str::to_owned))?;
+                Some((name, start..start + symbol.size()))
+            })
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|(_, range)| range.start);
+        functions.dedup_by_key(|(_, range)| range.start);
+        functions
     }
 }
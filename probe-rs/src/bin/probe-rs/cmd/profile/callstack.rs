@@ -0,0 +1,401 @@
+//! Sampling of call stacks, and their export for flame graph tools.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use probe_rs::debug::{DebugInfo, DebugRegisters};
+use probe_rs::{exception_handler_for_core, Core};
+use serde::Serialize;
+
+/// A function of a sampled call stack.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub name: String,
+    /// The source file of the function, if known.
+    pub file: Option<String>,
+}
+
+/// Halts the core, unwinds its stack and resumes it.
+///
+/// Returns the program counter, and the innermost `max_depth` frames of the stack, ordered from
+/// the outermost to the innermost frame.
+pub fn sample_call_stack(
+    core: &mut Core,
+    debug_info: &DebugInfo,
+    max_depth: usize,
+) -> anyhow::Result<(u64, Vec<Frame>)> {
+    core.halt(std::time::Duration::from_millis(10))?;
+
+    let pc_reg = core.program_counter();
+    let pc = core.read_core_reg::<u64>(pc_reg);
+    let initial_registers = DebugRegisters::from_core(core);
+    let exception_interface = exception_handler_for_core(core.core_type());
+    let instruction_set = core.instruction_set().ok();
+    let stack_frames = debug_info.unwind(
+        core,
+        initial_registers,
+        exception_interface.as_ref(),
+        instruction_set,
+    );
+
+    // Resume before reporting an error, so that the target keeps running.
+    core.run()?;
+
+    let pc = pc?;
+    let mut frames = stack_frames?
+        .into_iter()
+        .take(max_depth)
+        .map(|frame| Frame {
+            file: frame
+                .source_location
+                .as_ref()
+                .and_then(|location| location.combined_typed_path())
+                .map(|path| path.to_string_lossy().into_owned()),
+            name: frame.function_name,
+        })
+        .collect::<Vec<_>>();
+    frames.reverse();
+
+    Ok((pc, frames))
+}
+
+/// The number of samples of every call stack.
+#[derive(Debug, Default)]
+pub struct CallStacks {
+    frames: Vec<Frame>,
+    frame_ids: HashMap<Frame, usize>,
+    /// The frame ids of every stack, from the outermost to the innermost frame.
+    stacks: HashMap<Vec<usize>, u64>,
+    samples: u64,
+}
+
+impl CallStacks {
+    /// Adds a sample of `stack`, ordered from the outermost to the innermost frame.
+    pub fn add(&mut self, stack: Vec<Frame>) {
+        let ids = stack
+            .into_iter()
+            .map(|frame| match self.frame_ids.get(&frame) {
+                Some(&id) => id,
+                None => {
+                    let id = self.frames.len();
+                    self.frame_ids.insert(frame.clone(), id);
+                    self.frames.push(frame);
+                    id
+                }
+            })
+            .collect();
+
+        *self.stacks.entry(ids).or_default() += 1;
+        self.samples += 1;
+    }
+
+    /// Returns the `limit` most sampled stacks, as `outer;inner` strings with their counts.
+    pub fn hottest(&self, limit: usize) -> Vec<(String, u64)> {
+        let mut stacks = self.sorted_stacks();
+        stacks.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        stacks
+            .into_iter()
+            .take(limit)
+            .map(|(stack, count)| {
+                let names = stack
+                    .iter()
+                    .map(|&id| self.frames[id].name.as_str())
+                    .collect::<Vec<_>>();
+                (names.join(";"), count)
+            })
+            .collect()
+    }
+
+    /// Writes the number of samples and the `limit` most sampled stacks, as human readable text.
+    pub fn write_hottest(&self, mut writer: impl Write, limit: usize) -> io::Result<()> {
+        writeln!(writer, "Samples {}", self.samples)?;
+        for (stack, count) in self.hottest(limit) {
+            writeln!(
+                writer,
+                "{:.01}% {stack}",
+                (count as f64 / self.samples.max(1) as f64) * 100.0
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the stacks sorted by their frames, for a stable output.
+    fn sorted_stacks(&self) -> Vec<(&[usize], u64)> {
+        let mut stacks = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| (stack.as_slice(), *count))
+            .collect::<Vec<_>>();
+        stacks.sort_by_cached_key(|(stack, _)| {
+            stack
+                .iter()
+                .map(|&id| self.frames[id].name.as_str())
+                .collect::<Vec<_>>()
+        });
+        stacks
+    }
+
+    /// Writes the stacks in the folded format of `flamegraph.pl` and `inferno`, one
+    /// `outer;inner count` line per stack.
+    pub fn write_folded(&self, mut writer: impl Write) -> io::Result<()> {
+        for (stack, count) in self.sorted_stacks() {
+            let names = stack
+                .iter()
+                // `;` separates the frames, so it must not appear in names.
+                .map(|&id| self.frames[id].name.replace(';', ":"))
+                .collect::<Vec<_>>();
+            writeln!(writer, "{} {count}", names.join(";"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the stacks as a sampled profile in the file format of speedscope.
+    pub fn write_speedscope(&self, writer: impl Write, name: &str) -> io::Result<()> {
+        let (samples, weights) = self.sorted_stacks().into_iter().unzip();
+
+        let file = SpeedscopeFile {
+            schema: "https://www.speedscope.app/file-format-schema.json",
+            shared: SpeedscopeShared {
+                frames: self
+                    .frames
+                    .iter()
+                    .map(|frame| SpeedscopeFrame {
+                        name: &frame.name,
+                        file: frame.file.as_deref(),
+                    })
+                    .collect(),
+            },
+            profiles: vec![SpeedscopeProfile {
+                kind: "sampled",
+                name,
+                unit: "none",
+                start_value: 0,
+                end_value: self.samples,
+                samples,
+                weights,
+            }],
+            name,
+            exporter: concat!("probe-rs ", env!("CARGO_PKG_VERSION")),
+        };
+
+        serde_json::to_writer(writer, &file)?;
+        Ok(())
+    }
+
+    /// Writes the stacks as an uncompressed pprof profile, with one sample every `period_nanos`.
+    pub fn write_pprof(&self, mut writer: impl Write, period_nanos: u64) -> io::Result<()> {
+        let mut strings = StringTable::default();
+        let mut profile = Vec::new();
+
+        let samples = strings.get("samples");
+        let count = strings.get("count");
+        let cpu = strings.get("cpu");
+        let nanoseconds = strings.get("nanoseconds");
+
+        // sample_type
+        let mut value_type = Vec::new();
+        write_int_field(&mut value_type, 1, samples);
+        write_int_field(&mut value_type, 2, count);
+        write_bytes_field(&mut profile, 1, &value_type);
+
+        for (stack, count) in self.sorted_stacks() {
+            let mut sample = Vec::new();
+            // The location ids, from the innermost to the outermost frame. A location is created
+            // for every frame, with the frame id + 1 as id.
+            let mut location_ids = Vec::new();
+            for &id in stack.iter().rev() {
+                write_varint(&mut location_ids, id as u64 + 1);
+            }
+            write_bytes_field(&mut sample, 1, &location_ids);
+            let mut values = Vec::new();
+            write_varint(&mut values, count);
+            write_bytes_field(&mut sample, 2, &values);
+            write_bytes_field(&mut profile, 2, &sample);
+        }
+
+        for (id, frame) in self.frames.iter().enumerate() {
+            let id = id as u64 + 1;
+
+            let mut line = Vec::new();
+            write_int_field(&mut line, 1, id);
+            let mut location = Vec::new();
+            write_int_field(&mut location, 1, id);
+            write_bytes_field(&mut location, 4, &line);
+            write_bytes_field(&mut profile, 4, &location);
+
+            let name = strings.get(&frame.name);
+            let file = strings.get(frame.file.as_deref().unwrap_or_default());
+            let mut function = Vec::new();
+            write_int_field(&mut function, 1, id);
+            write_int_field(&mut function, 2, name);
+            write_int_field(&mut function, 3, name);
+            write_int_field(&mut function, 4, file);
+            write_bytes_field(&mut profile, 5, &function);
+        }
+
+        // period_type and period
+        let mut period_type = Vec::new();
+        write_int_field(&mut period_type, 1, cpu);
+        write_int_field(&mut period_type, 2, nanoseconds);
+
+        // The string table has to come after all strings were added.
+        for string in &strings.strings {
+            write_bytes_field(&mut profile, 6, string.as_bytes());
+        }
+        write_bytes_field(&mut profile, 11, &period_type);
+        write_int_field(&mut profile, 12, period_nanos);
+
+        writer.write_all(&profile)
+    }
+}
+
+#[derive(Serialize)]
+struct SpeedscopeFile<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    shared: SpeedscopeShared<'a>,
+    profiles: Vec<SpeedscopeProfile<'a>>,
+    name: &'a str,
+    exporter: &'static str,
+}
+
+#[derive(Serialize)]
+struct SpeedscopeShared<'a> {
+    frames: Vec<SpeedscopeFrame<'a>>,
+}
+
+#[derive(Serialize)]
+struct SpeedscopeFrame<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpeedscopeProfile<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: &'a str,
+    unit: &'static str,
+    start_value: u64,
+    end_value: u64,
+    samples: Vec<&'a [usize]>,
+    weights: Vec<u64>,
+}
+
+/// The string table of a pprof profile, whose first entry is always the empty string.
+struct StringTable {
+    strings: Vec<String>,
+    indices: HashMap<String, u64>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self {
+            strings: vec![String::new()],
+            indices: HashMap::from([(String::new(), 0)]),
+        }
+    }
+}
+
+impl StringTable {
+    /// Returns the index of `string`, and adds it if necessary.
+    fn get(&mut self, string: &str) -> u64 {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+
+        let index = self.strings.len() as u64;
+        self.strings.push(string.to_owned());
+        self.indices.insert(string.to_owned(), index);
+        index
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Writes a protobuf field of wire type varint.
+fn write_int_field(buffer: &mut Vec<u8>, field: u32, value: u64) {
+    write_varint(buffer, u64::from(field) << 3);
+    write_varint(buffer, value);
+}
+
+/// Writes a protobuf field of wire type length-delimited.
+fn write_bytes_field(buffer: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_varint(buffer, u64::from(field) << 3 | 2);
+    write_varint(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(name: &str) -> Frame {
+        Frame {
+            name: name.to_owned(),
+            file: None,
+        }
+    }
+
+    fn call_stacks() -> CallStacks {
+        let mut stacks = CallStacks::default();
+        stacks.add(vec![frame("main"), frame("work")]);
+        stacks.add(vec![frame("main"), frame("idle")]);
+        stacks.add(vec![frame("main"), frame("work")]);
+        stacks
+    }
+
+    #[test]
+    fn folded_stacks_are_aggregated() {
+        let mut output = Vec::new();
+        call_stacks().write_folded(&mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "main;idle 1\nmain;work 2\n"
+        );
+    }
+
+    #[test]
+    fn speedscope_profile_references_shared_frames() {
+        let mut output = Vec::new();
+        call_stacks()
+            .write_speedscope(&mut output, "firmware")
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json["shared"]["frames"],
+            serde_json::json!([{"name": "main"}, {"name": "work"}, {"name": "idle"}])
+        );
+        assert_eq!(
+            json["profiles"][0]["samples"],
+            serde_json::json!([[0, 2], [0, 1]])
+        );
+        assert_eq!(json["profiles"][0]["weights"], serde_json::json!([1, 2]));
+        assert_eq!(json["profiles"][0]["endValue"], 3);
+    }
+
+    #[test]
+    fn protobuf_fields_are_encoded() {
+        let mut buffer = Vec::new();
+        write_int_field(&mut buffer, 12, 300);
+        write_bytes_field(&mut buffer, 6, b"main");
+
+        assert_eq!(
+            buffer,
+            [0x60, 0xac, 0x02, 0x32, 0x04, b'm', b'a', b'i', b'n']
+        );
+    }
+}
@@ -0,0 +1,515 @@
+//! Symbol-level profile reports, and their export for other tools.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+/// How the profile report is written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// The most sampled addresses, as human readable text.
+    #[default]
+    Text,
+    /// The samples of every symbol and source line, as JSON.
+    Json,
+    /// The samples of every symbol, as CSV.
+    Csv,
+    /// The samples of every source line in the callgrind format, e.g. for KCachegrind.
+    Callgrind,
+}
+
+/// The number of source lines shown before and after the lines of an annotated symbol.
+const CONTEXT_LINES: u64 = 2;
+
+/// The source location of a sampled address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    pub line: u64,
+}
+
+/// The samples of every symbol, ordered from the most to the least sampled symbol.
+#[derive(Debug, Serialize)]
+pub struct ProfileReport {
+    samples: u64,
+    symbols: Vec<SymbolProfile>,
+}
+
+#[derive(Debug, Serialize)]
+struct SymbolProfile {
+    name: String,
+    samples: u64,
+    percent: f64,
+    /// The sampled source lines of the symbol, in the order of their first address.
+    lines: Vec<LineProfile>,
+}
+
+#[derive(Debug, Serialize)]
+struct LineProfile {
+    file: Option<String>,
+    line: Option<u64>,
+    samples: u64,
+    percent: f64,
+    /// The sampled addresses of the line, with their number of samples.
+    #[serde(skip)]
+    addresses: Vec<(u64, u64)>,
+}
+
+impl ProfileReport {
+    /// Aggregates the samples per address into symbols and source lines.
+    ///
+    /// `total` is the total number of samples, and `resolve` returns the symbol name and source
+    /// location of an address.
+    pub fn new(
+        samples: &HashMap<u64, u64>,
+        total: u64,
+        mut resolve: impl FnMut(u64) -> (String, Option<Location>),
+    ) -> Self {
+        let percent = |count: u64| (count as f64 / total.max(1) as f64) * 100.0;
+
+        let mut symbols = HashMap::<String, BTreeMap<u64, (Option<Location>, u64)>>::new();
+        for (&address, &count) in samples {
+            let (name, location) = resolve(address);
+            symbols
+                .entry(name)
+                .or_default()
+                .insert(address, (location, count));
+        }
+
+        let mut symbols = symbols
+            .into_iter()
+            .map(|(name, addresses)| {
+                let mut lines: Vec<LineProfile> = Vec::new();
+                for (address, (location, count)) in addresses {
+                    let (file, line) = match location {
+                        Some(location) => (Some(location.file), Some(location.line)),
+                        None => (None, None),
+                    };
+
+                    match lines
+                        .iter_mut()
+                        .find(|entry| entry.file == file && entry.line == line)
+                    {
+                        Some(entry) => {
+                            entry.samples += count;
+                            entry.addresses.push((address, count));
+                        }
+                        None => lines.push(LineProfile {
+                            file,
+                            line,
+                            samples: count,
+                            percent: 0.0,
+                            addresses: vec![(address, count)],
+                        }),
+                    }
+                }
+
+                for line in &mut lines {
+                    line.percent = percent(line.samples);
+                }
+
+                let samples = lines.iter().map(|line| line.samples).sum();
+                SymbolProfile {
+                    name,
+                    samples,
+                    percent: percent(samples),
+                    lines,
+                }
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by(|a, b| b.samples.cmp(&a.samples).then(a.name.cmp(&b.name)));
+
+        Self {
+            samples: total,
+            symbols,
+        }
+    }
+
+    /// Writes the report in `format`, with `command` as the profiled command.
+    ///
+    /// The text report contains the `limit` most sampled addresses, and their source location
+    /// if `line_info` is set. The other formats contain every symbol.
+    pub fn write(
+        &self,
+        format: ReportFormat,
+        writer: impl Write,
+        command: &str,
+        limit: usize,
+        line_info: bool,
+    ) -> io::Result<()> {
+        match format {
+            ReportFormat::Text => self.write_text(writer, limit, line_info),
+            ReportFormat::Json => self.write_json(writer),
+            ReportFormat::Csv => self.write_csv(writer),
+            ReportFormat::Callgrind => self.write_callgrind(writer, command),
+        }
+    }
+
+    fn write_text(&self, mut writer: impl Write, limit: usize, line_info: bool) -> io::Result<()> {
+        let mut addresses = self
+            .symbols
+            .iter()
+            .flat_map(|symbol| {
+                symbol.lines.iter().flat_map(move |line| {
+                    line.addresses
+                        .iter()
+                        .map(move |&(address, count)| (address, count, symbol, line))
+                })
+            })
+            .collect::<Vec<_>>();
+        // Sort by frequency.
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        writeln!(writer, "Samples {}", self.samples)?;
+        for (_, count, symbol, line) in addresses.into_iter().take(limit) {
+            if line_info {
+                writeln!(
+                    writer,
+                    "{}:{}",
+                    line.file.as_deref().unwrap_or("UNKNOWN"),
+                    line.line.unwrap_or(0)
+                )?;
+            }
+            writeln!(writer, "{:>50} - {:.01}%", symbol.name, self.percent(count))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)
+    }
+
+    fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "symbol,samples,percent")?;
+        for symbol in &self.symbols {
+            writeln!(
+                writer,
+                "{},{},{:.02}",
+                csv_field(&symbol.name),
+                symbol.samples,
+                symbol.percent
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the samples in the callgrind format, with `command` as the profiled command.
+    fn write_callgrind(&self, mut writer: impl Write, command: &str) -> io::Result<()> {
+        writeln!(writer, "# callgrind format")?;
+        writeln!(writer, "version: 1")?;
+        writeln!(writer, "creator: probe-rs {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(writer, "cmd: {command}")?;
+        writeln!(writer, "positions: line")?;
+        writeln!(writer, "events: Samples")?;
+        writeln!(writer, "summary: {}", self.samples)?;
+
+        for symbol in &self.symbols {
+            let file = |line: &LineProfile| line.file.as_deref().unwrap_or("???").to_owned();
+
+            writeln!(writer)?;
+            let mut current_file = symbol.lines.first().map(file).unwrap_or_default();
+            writeln!(writer, "fl={current_file}")?;
+            writeln!(writer, "fn={}", symbol.name)?;
+            for line in &symbol.lines {
+                // Inlined code is attributed to the file it was inlined from.
+                if file(line) != current_file {
+                    current_file = file(line);
+                    writeln!(writer, "fi={current_file}")?;
+                }
+                writeln!(writer, "{} {}", line.line.unwrap_or(0), line.samples)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the source of the `limit` most sampled symbols with the samples of every line,
+    /// each sampled line followed by its sampled instructions as returned by `disassemble`.
+    ///
+    /// `code_lines` returns the source lines with code of the function at an address, e.g. from
+    /// the line table. These lines and the sampled lines are shown with [`CONTEXT_LINES`]
+    /// surrounding lines.
+    pub fn write_annotated(
+        &self,
+        mut writer: impl Write,
+        limit: usize,
+        mut code_lines: impl FnMut(u64) -> Vec<Location>,
+        mut disassemble: impl FnMut(u64) -> Option<String>,
+    ) -> io::Result<()> {
+        let mut sources = HashMap::<String, Option<Vec<String>>>::new();
+
+        for symbol in self.symbols.iter().take(limit) {
+            writeln!(writer, "{} - {:.01}%", symbol.name, symbol.percent)?;
+
+            // The lines are ordered by their first address, so this is the start of the symbol.
+            let start = symbol
+                .lines
+                .first()
+                .and_then(|line| line.addresses.first())
+                .map(|&(address, _)| address);
+            let sampled_lines = symbol.lines.iter().filter_map(|line| {
+                Some(Location {
+                    file: line.file.clone()?,
+                    line: line.line?,
+                })
+            });
+
+            // The lines to show of every file, in the order of the sampled lines.
+            let mut files: Vec<(String, BTreeSet<u64>)> = Vec::new();
+            for location in sampled_lines.chain(start.map(&mut code_lines).unwrap_or_default()) {
+                match files.iter_mut().find(|(file, _)| *file == location.file) {
+                    Some((_, lines)) => {
+                        lines.insert(location.line);
+                    }
+                    None => files.push((location.file, BTreeSet::from([location.line]))),
+                }
+            }
+
+            for (file, lines) in files {
+                writeln!(writer, "{file}")?;
+                let sampled_line = |number: u64| {
+                    symbol.lines.iter().find(|line| {
+                        line.file.as_deref() == Some(file.as_str()) && line.line == Some(number)
+                    })
+                };
+
+                let source = sources.entry(file.clone()).or_insert_with(|| {
+                    std::fs::read_to_string(&file)
+                        .ok()
+                        .map(|source| source.lines().map(str::to_owned).collect())
+                });
+                let Some(source) = source else {
+                    // Without the source, only the sampled lines can be shown.
+                    for line in lines.into_iter().filter_map(sampled_line) {
+                        writeln!(
+                            writer,
+                            "{:>6.01}%  {:>5}",
+                            line.percent,
+                            line.line.unwrap_or(0)
+                        )?;
+                        self.write_instructions(&mut writer, line, &mut disassemble)?;
+                    }
+                    continue;
+                };
+
+                // The first line which wasn't shown yet.
+                let mut next = 1;
+                for number in lines {
+                    let first = number.saturating_sub(CONTEXT_LINES).max(next);
+                    let last = (number + CONTEXT_LINES).min(source.len() as u64);
+                    if first > next && next > 1 {
+                        writeln!(writer, "{:>7}  {:>5}", "", "...")?;
+                    }
+
+                    for number in first..=last {
+                        let text = source[number as usize - 1].trim_end();
+                        match sampled_line(number) {
+                            Some(line) => {
+                                writeln!(writer, "{:>6.01}%  {number:>5}  {text}", line.percent)?;
+                                self.write_instructions(&mut writer, line, &mut disassemble)?;
+                            }
+                            None => writeln!(writer, "{:>7}  {number:>5}  {text}", "")?,
+                        }
+                    }
+                    next = next.max(last + 1);
+                }
+            }
+
+            for line in symbol.lines.iter().filter(|line| line.line.is_none()) {
+                writeln!(writer, "{:>6.01}%  <unknown location>", line.percent)?;
+                self.write_instructions(&mut writer, line, &mut disassemble)?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the sampled instructions of `line`, as returned by `disassemble`.
+    fn write_instructions(
+        &self,
+        mut writer: impl Write,
+        line: &LineProfile,
+        disassemble: &mut impl FnMut(u64) -> Option<String>,
+    ) -> io::Result<()> {
+        for &(address, count) in &line.addresses {
+            let instruction = disassemble(address).unwrap_or_default();
+            writeln!(
+                writer,
+                "{:>6.01}%         {address:#010x}  {instruction}",
+                self.percent(count)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the share of `count` in all samples, in percent.
+    fn percent(&self, count: u64) -> f64 {
+        (count as f64 / self.samples.max(1) as f64) * 100.0
+    }
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report() -> ProfileReport {
+        let samples = HashMap::from([(0x100, 2), (0x102, 1), (0x104, 3), (0x200, 4)]);
+        ProfileReport::new(&samples, 10, |address| match address {
+            0x100..=0x1ff => (
+                "main".to_owned(),
+                Some(Location {
+                    file: "src/main.rs".to_owned(),
+                    line: if address < 0x104 { 10 } else { 11 },
+                }),
+            ),
+            _ => ("<u8 as Debug>::fmt, inlined".to_owned(), None),
+        })
+    }
+
+    #[test]
+    fn samples_are_aggregated_per_symbol_and_line() {
+        let mut output = Vec::new();
+        report()
+            .write(ReportFormat::Json, &mut output, "", 0, false)
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["samples"], 10);
+        assert_eq!(json["symbols"][0]["name"], "main");
+        assert_eq!(json["symbols"][0]["samples"], 6);
+        assert_eq!(
+            json["symbols"][0]["lines"],
+            serde_json::json!([
+                {"file": "src/main.rs", "line": 10, "samples": 3, "percent": 30.0},
+                {"file": "src/main.rs", "line": 11, "samples": 3, "percent": 30.0},
+            ])
+        );
+        assert_eq!(json["symbols"][1]["samples"], 4);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted() {
+        let mut output = Vec::new();
+        report()
+            .write(ReportFormat::Csv, &mut output, "", 0, false)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "symbol,samples,percent\nmain,6,60.00\n\"<u8 as Debug>::fmt, inlined\",4,40.00\n"
+        );
+    }
+
+    #[test]
+    fn callgrind_costs_are_per_line() {
+        let mut output = Vec::new();
+        report()
+            .write(
+                ReportFormat::Callgrind,
+                &mut output,
+                "firmware.elf",
+                0,
+                false,
+            )
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("cmd: firmware.elf\n"));
+        assert!(output.contains("\nfl=src/main.rs\nfn=main\n10 3\n11 3\n"));
+        assert!(output.contains("\nfl=???\nfn=<u8 as Debug>::fmt, inlined\n0 4\n"));
+    }
+
+    #[test]
+    fn text_shows_the_most_sampled_addresses() {
+        let mut output = Vec::new();
+        report()
+            .write(ReportFormat::Text, &mut output, "", 2, true)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Samples 10\nUNKNOWN:0\n{:>50} - 40.0%\nsrc/main.rs:11\n{:>50} - 30.0%\n",
+                "<u8 as Debug>::fmt, inlined", "main"
+            )
+        );
+    }
+
+    #[test]
+    fn annotated_lines_are_shown_with_context() {
+        let path =
+            std::env::temp_dir().join(format!("probe-rs-annotate-{}.rs", std::process::id()));
+        let source = (1..=20)
+            .map(|number| format!("line {number}\n"))
+            .collect::<String>();
+        std::fs::write(&path, source).unwrap();
+
+        let file = path.to_string_lossy().into_owned();
+        let samples = HashMap::from([(0x100, 3), (0x102, 1)]);
+        let report = ProfileReport::new(&samples, 4, |address| {
+            let line = if address == 0x100 { 4 } else { 15 };
+            (
+                "main".to_owned(),
+                Some(Location {
+                    file: file.clone(),
+                    line,
+                }),
+            )
+        });
+
+        let mut output = Vec::new();
+        report
+            .write_annotated(
+                &mut output,
+                1,
+                |_| {
+                    vec![Location {
+                        file: file.clone(),
+                        line: 5,
+                    }]
+                },
+                |address| Some(format!("nop @ {address:#x}")),
+            )
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = [
+            "main - 100.0%".to_owned(),
+            file.clone(),
+            "             2  line 2".to_owned(),
+            "             3  line 3".to_owned(),
+            "  75.0%      4  line 4".to_owned(),
+            "  75.0%         0x00000100  nop @ 0x100".to_owned(),
+            "             5  line 5".to_owned(),
+            "             6  line 6".to_owned(),
+            "             7  line 7".to_owned(),
+            "           ...".to_owned(),
+            "            13  line 13".to_owned(),
+            "            14  line 14".to_owned(),
+            "  25.0%     15  line 15".to_owned(),
+            "  25.0%         0x00000102  nop @ 0x102".to_owned(),
+            "            16  line 16".to_owned(),
+            "            17  line 17".to_owned(),
+            String::new(),
+        ];
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            expected.join("\n") + "\n"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use probe_rs::{architecture::arm::sequences::ProtectionState, probe::list::Lister};
+
+use crate::util::common_options::ProbeOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+#[derive(clap::Subcommand)]
+/// Query and change the readout protection of the target
+enum Subcommand {
+    /// Show the readout protection state of the target
+    #[clap(name = "status")]
+    Status {
+        #[clap(flatten)]
+        probe_options: ProbeOptions,
+    },
+    /// Enable the readout protection of the target
+    ///
+    /// Requires --allow-device-lock. Depending on the target, the protection
+    /// only becomes active after the next reset.
+    #[clap(name = "enable", verbatim_doc_comment)]
+    Enable {
+        #[clap(flatten)]
+        probe_options: ProbeOptions,
+
+        /// Enable the strongest protection level of the target, which can not be removed anymore.
+        /// Requires --allow-irreversible-writes in addition.
+        #[clap(long)]
+        permanent: bool,
+    },
+    /// Remove the readout protection of the target
+    ///
+    /// This erases the target, and requires --allow-erase-all.
+    #[clap(name = "disable", verbatim_doc_comment)]
+    Disable {
+        #[clap(flatten)]
+        probe_options: ProbeOptions,
+    },
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        match self.subcommand {
+            // The state is queried and the protection removed before attaching to the target,
+            // since a protected target can usually not be attached to.
+            Subcommand::Status { probe_options } => {
+                let probe_options = probe_options.load()?;
+                let target = probe_options.get_target_selector()?;
+                let probe = probe_options.attach_probe(lister)?;
+
+                print_protection_state(probe.protection_state(target)?);
+            }
+            Subcommand::Enable {
+                probe_options,
+                permanent,
+            } => {
+                let probe_options = probe_options.load()?;
+                let target = probe_options.get_target_selector()?;
+                let probe = probe_options.attach_probe(lister)?;
+
+                print_protection_state(probe.lock_device(
+                    target,
+                    permanent,
+                    &probe_options.permissions(),
+                )?);
+            }
+            Subcommand::Disable { probe_options } => {
+                let probe_options = probe_options.load()?;
+                let target = probe_options.get_target_selector()?;
+                let probe = probe_options.attach_probe(lister)?;
+
+                print_protection_state(probe.unlock_device(target, &probe_options.permissions())?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn print_protection_state(state: ProtectionState) {
+    match state {
+        ProtectionState::Unprotected => println!("Readout protection: disabled"),
+        ProtectionState::Protected => println!("Readout protection: enabled"),
+        ProtectionState::PermanentlyProtected => {
+            println!("Readout protection: permanently enabled")
+        }
+    }
+}
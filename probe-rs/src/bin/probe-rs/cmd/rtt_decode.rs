@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use time::UtcOffset;
+
+use crate::util::rtt::capture::{CaptureReader, CaptureRecord};
+use crate::util::rtt::structured::{LogFilter, OutputFormat};
+use crate::util::rtt::{DataFormat, DefmtState, RttActiveChannel, RttChannelConfig};
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// The capture file written by `probe-rs run --rtt-capture`
+    capture: PathBuf,
+
+    /// The ELF file of the program which produced the capture. Required to decode defmt channels.
+    #[clap(long)]
+    elf: Option<PathBuf>,
+
+    /// Prefix every line of string channels with the time at which it was captured
+    #[clap(long)]
+    show_timestamps: bool,
+
+    /// Suppress filename and line number information from defmt channels
+    #[clap(long)]
+    no_location: bool,
+
+    /// The format of defmt messages, e.g. "{t} {L} {s}" for the timestamp, level and message.
+    /// See the defmt-decoder documentation for all placeholders
+    #[clap(long)]
+    log_format: Option<String>,
+
+    /// How the messages of string and defmt channels are written
+    #[clap(long, value_enum, default_value_t)]
+    output_format: OutputFormat,
+
+    /// Only show messages which pass this filter, e.g. `warn,my_app::net=debug`
+    #[clap(long, value_name = "FILTER")]
+    log_filter: Option<LogFilter>,
+}
+
+impl Cmd {
+    pub fn run(self, timestamp_offset: UtcOffset) -> anyhow::Result<()> {
+        let mut reader = CaptureReader::open(&self.capture).with_context(|| {
+            format!("Failed to open the RTT capture {}", self.capture.display())
+        })?;
+
+        let mut channels = HashMap::new();
+        let mut defmt_state = None;
+        let mut stdout = std::io::stdout().lock();
+
+        while let Some(record) = reader
+            .next_record()
+            .with_context(|| format!("Failed to read the RTT capture {}", self.capture.display()))?
+        {
+            match record {
+                CaptureRecord::Channel {
+                    number,
+                    name,
+                    data_format,
+                } => {
+                    if data_format == DataFormat::Defmt && defmt_state.is_none() {
+                        defmt_state = Some(self.load_defmt_state(&name)?);
+                    }
+
+                    channels.insert(
+                        number,
+                        self.channel(number, Some(name), data_format, timestamp_offset),
+                    );
+                }
+                CaptureRecord::Data {
+                    timestamp,
+                    channel,
+                    bytes,
+                } => {
+                    // Data without a preceding description of the channel is shown as string.
+                    let channel = channels.entry(channel).or_insert_with(|| {
+                        self.channel(channel, None, DataFormat::String, timestamp_offset)
+                    });
+
+                    let data = channel.format_data(&bytes, timestamp, defmt_state.as_ref())?;
+                    stdout.write_all(data.as_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn channel(
+        &self,
+        number: usize,
+        name: Option<String>,
+        data_format: DataFormat,
+        timestamp_offset: UtcOffset,
+    ) -> RttActiveChannel {
+        let mut channel = RttActiveChannel::new(
+            None,
+            None,
+            Some(RttChannelConfig {
+                channel_number: Some(number),
+                channel_name: name,
+                data_format,
+                show_timestamps: self.show_timestamps,
+                show_location: !self.no_location,
+                ..Default::default()
+            }),
+            timestamp_offset,
+        );
+        channel.set_output_format(self.output_format);
+        channel.set_log_filter(self.log_filter.clone());
+        channel
+    }
+
+    fn load_defmt_state(&self, channel_name: &str) -> anyhow::Result<DefmtState> {
+        let elf_path = self.elf.as_ref().ok_or_else(|| {
+            anyhow!("The capture contains the defmt channel '{channel_name}', which requires --elf to decode")
+        })?;
+        let elf = std::fs::read(elf_path)
+            .with_context(|| format!("Failed to read the ELF file {}", elf_path.display()))?;
+
+        DefmtState::try_from_bytes(&elf, !self.no_location, self.log_format.as_deref())?
+            .ok_or_else(|| anyhow!("The ELF file {} contains no defmt data", elf_path.display()))
+    }
+}
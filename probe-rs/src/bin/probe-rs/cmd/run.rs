@@ -1,16 +1,16 @@
 use std::io::Write;
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use probe_rs::debug::{DebugInfo, DebugRegisters};
 use probe_rs::rtt::ScanRegion;
 use probe_rs::{
     exception_handler_for_core, probe::list::Lister, BreakpointCause, Core, CoreInterface, Error,
-    HaltReason, SemihostingCommand, VectorCatchCondition,
+    HaltReason, SemihostingCommand, Session, VectorCatchCondition,
 };
 use probe_rs_target::MemoryRegion;
 use signal_hook::consts::signal;
@@ -18,6 +18,11 @@ use time::UtcOffset;
 
 use crate::util::common_options::{BinaryDownloadOptions, ProbeOptions};
 use crate::util::flash::{build_loader, run_flash_download};
+use crate::util::itm::{ItmConfig, ItmStimulus};
+use crate::util::rtt::capture::CaptureWriter;
+use crate::util::rtt::pipe::RttPipeConfig;
+use crate::util::rtt::structured::{LogFilter, OutputFormat};
+use crate::util::rtt::tcp::RttTcpServerConfig;
 use crate::util::rtt::{self, RttActiveTarget, RttConfig};
 use crate::FormatOptions;
 
@@ -62,6 +67,41 @@ pub struct Cmd {
     /// Scan the memory to find the RTT control block
     #[clap(long)]
     pub(crate) rtt_scan_memory: bool,
+
+    /// Listen for TCP clients of an RTT channel, e.g. `0=127.0.0.1:19021`.
+    ///
+    /// All clients receive the data of the up channel, and anything they send is written to the
+    /// down channel with the same number. Can be used once for each channel.
+    #[clap(long, value_name = "CHANNEL=ADDRESS")]
+    pub(crate) rtt_tcp: Vec<RttTcpServerConfig>,
+
+    /// Pipe an RTT channel through a shell command, e.g. `1=tee rtt.bin | decoder`.
+    ///
+    /// The data of the up channel is written to the stdin of the command. Its stdout is written
+    /// to the down channel with the same number if there is one, and shown otherwise. Can be used
+    /// once for each channel.
+    #[clap(long, value_name = "CHANNEL=COMMAND")]
+    pub(crate) rtt_pipe: Vec<RttPipeConfig>,
+
+    /// Write the raw data of all RTT up channels with host timestamps to this file.
+    ///
+    /// The capture can be decoded later with `probe-rs rtt-decode`.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) rtt_capture: Option<PathBuf>,
+
+    /// How the messages of string and defmt RTT channels are written.
+    #[clap(long, value_enum, default_value_t)]
+    pub(crate) rtt_output_format: OutputFormat,
+
+    /// Only show RTT messages which pass this filter, e.g. `warn,my_app::net=debug`.
+    ///
+    /// The syntax is the one of `RUST_LOG`. The level of string channels is taken from the first
+    /// word of each line, e.g. `INFO` or `[warn]`. Lines without a level are always shown.
+    #[clap(long, value_name = "FILTER")]
+    pub(crate) rtt_log_filter: Option<LogFilter>,
+
+    #[clap(flatten)]
+    pub(crate) itm_config: ItmConfig,
 }
 
 impl Cmd {
@@ -95,6 +135,12 @@ impl Cmd {
             true => session.target().rtt_scan_regions.clone(),
             false => Vec::new(),
         };
+        let itm = if self.itm_config.enabled {
+            Some(ItmStimulus::start(&mut session, 0, &self.itm_config)?)
+        } else {
+            None
+        };
+
         let mut core = session.core(0)?;
 
         if self.catch_hardfault || self.catch_reset {
@@ -113,9 +159,11 @@ impl Cmd {
             }
         }
         core.run()?;
+        drop(core);
 
         run_loop(
-            &mut core,
+            &mut session,
+            itm,
             &memory_map,
             &rtt_scan_regions,
             path,
@@ -123,19 +171,25 @@ impl Cmd {
             self.always_print_stacktrace,
             self.no_location,
             self.log_format.as_deref(),
+            &self.rtt_tcp,
+            &self.rtt_pipe,
+            self.rtt_capture.as_deref(),
+            self.rtt_output_format,
+            self.rtt_log_filter.as_ref(),
         )?;
 
         Ok(())
     }
 }
 
-/// Print all RTT messages and a stacktrace when the core stops due to an
+/// Print all RTT and ITM messages and a stacktrace when the core stops due to an
 /// exception or when ctrl + c is pressed.
 ///
 /// Returns `Ok(())` if the core gracefully halted, or an error.
 #[allow(clippy::too_many_arguments)]
 fn run_loop(
-    core: &mut Core<'_>,
+    session: &mut Session,
+    mut itm: Option<ItmStimulus>,
     memory_map: &[MemoryRegion],
     rtt_scan_regions: &[Range<u64>],
     path: &Path,
@@ -143,6 +197,11 @@ fn run_loop(
     always_print_stacktrace: bool,
     no_location: bool,
     log_format: Option<&str>,
+    rtt_tcp: &[RttTcpServerConfig],
+    rtt_pipe: &[RttPipeConfig],
+    rtt_capture: Option<&Path>,
+    rtt_output_format: OutputFormat,
+    rtt_log_filter: Option<&LogFilter>,
 ) -> Result<(), anyhow::Error> {
     let mut rtt_config = rtt::RttConfig::default();
     rtt_config.channels.push(rtt::RttChannelConfig {
@@ -150,9 +209,15 @@ fn run_loop(
         show_location: !no_location,
         ..Default::default()
     });
+    for server in rtt_tcp {
+        rtt_config.channel_mut(server.channel).tcp_server = Some(server.address);
+    }
+    for pipe in rtt_pipe {
+        rtt_config.channel_mut(pipe.channel).pipe = Some(pipe.command.clone());
+    }
 
     let mut rtta = attach_to_rtt(
-        core,
+        &mut session.core(0)?,
         memory_map,
         rtt_scan_regions,
         path,
@@ -161,12 +226,31 @@ fn run_loop(
         log_format,
     );
 
+    if let Some(rtta) = rtta.as_mut() {
+        for channel in &mut rtta.active_channels {
+            channel.set_output_format(rtt_output_format);
+            channel.set_log_filter(rtt_log_filter.cloned());
+        }
+    }
+
+    if let (Some(rtta), Some(capture_path)) = (rtta.as_mut(), rtt_capture) {
+        let capture = CaptureWriter::create(capture_path).with_context(|| {
+            format!(
+                "Failed to create the RTT capture file {}",
+                capture_path.display()
+            )
+        })?;
+        rtta.start_capture(capture)?;
+    }
+
     let exit = Arc::new(AtomicBool::new(false));
     let sig_id = signal_hook::flag::register(signal::SIGINT, exit.clone())?;
 
     let mut stdout = std::io::stdout();
     let mut halt_reason = None;
     while !exit.load(Ordering::Relaxed) && halt_reason.is_none() {
+        let mut core = session.core(0)?;
+
         // check for halt first, poll rtt after.
         // this is important so we do one last poll after halt, so we flush all messages
         // the core printed before halting, such as a panic message.
@@ -186,20 +270,33 @@ fn run_loop(
             }
         }
 
-        let had_rtt_data = poll_rtt(&mut rtta, core, &mut stdout)?;
+        poll_rtt(&mut rtta, &mut core, &mut stdout)?;
+        drop(core);
 
-        // Poll RTT with a frequency of 10 Hz if we do not receive any new data.
-        // Once we receive new data, we bump the frequency to 1kHz.
+        if let Some(itm) = itm.as_mut() {
+            for line in itm.poll(session)? {
+                writeln!(stdout, "{line}")?;
+            }
+        }
+
+        // Poll RTT with a frequency of 1 kHz while we receive new data, backing off
+        // to 10 Hz while the target is idle.
         //
         // If the polling frequency is too high, the USB connection to the probe
         // can become unstable. Hence we only pull as little as necessary.
-        if had_rtt_data {
-            std::thread::sleep(Duration::from_millis(1));
-        } else {
-            std::thread::sleep(Duration::from_millis(100));
+        let delay = rtta
+            .as_ref()
+            .map_or(Duration::from_millis(100), |rtta| rtta.poll_delay());
+        std::thread::sleep(delay);
+    }
+
+    if let Some(itm) = itm {
+        for line in itm.finish() {
+            writeln!(stdout, "{line}")?;
         }
     }
 
+    let mut core = session.core(0)?;
     let result = match halt_reason {
         None => {
             // manually halted with Control+C. Stop the core.
@@ -220,7 +317,7 @@ fn run_loop(
     };
 
     if always_print_stacktrace || result.is_err() {
-        print_stacktrace(core, path)?;
+        print_stacktrace(&mut core, path)?;
     }
 
     signal_hook::low_level::unregister(sig_id);
@@ -293,17 +390,16 @@ fn poll_rtt(
     rtta: &mut Option<rtt::RttActiveTarget>,
     core: &mut Core<'_>,
     stdout: &mut std::io::Stdout,
-) -> Result<bool, anyhow::Error> {
-    let mut had_data = false;
+) -> Result<(), anyhow::Error> {
     if let Some(rtta) = rtta {
+        if rtta.reattach_if_reset(core) {
+            writeln!(stdout, "\n{}", rtt::REATTACHED_MARKER)?;
+        }
         for (_ch, data) in rtta.poll_rtt_fallible(core)? {
-            if !data.is_empty() {
-                had_data = true;
-            }
             stdout.write_all(data.as_bytes())?;
         }
     };
-    Ok(had_data)
+    Ok(())
 }
 
 /// Attach to the RTT buffers.
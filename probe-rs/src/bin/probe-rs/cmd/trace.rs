@@ -1,17 +1,34 @@
+mod data;
+mod decode;
+mod etm;
+mod exceptions;
+mod mtb;
+
+use std::collections::{HashMap, VecDeque};
 use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Arc;
 use std::thread::sleep;
 use std::time::Duration;
 use std::time::Instant;
 
+use probe_rs::debug::DebugInfo;
 use probe_rs::probe::list::Lister;
-use probe_rs::MemoryInterface;
+use probe_rs::{MemoryInterface, Session};
 use scroll::{Pwrite, LE};
+use signal_hook::consts::signal;
 
+use crate::cmd::profile::Symbols;
 use crate::util::{common_options::ProbeOptions, parse_u64};
 use crate::CoreOptions;
 
 #[derive(clap::Parser)]
+#[clap(subcommand_negates_reqs = true)]
 pub struct Cmd {
+    #[clap(subcommand)]
+    method: Option<TraceMethod>,
+
     #[clap(flatten)]
     shared: CoreOptions,
 
@@ -19,12 +36,49 @@ pub struct Cmd {
     common: ProbeOptions,
 
     /// The address of the memory to dump from the target.
-    #[clap(value_parser = parse_u64)]
-    loc: u64,
+    #[clap(value_parser = parse_u64, required = true)]
+    loc: Option<u64>,
+}
+
+#[derive(clap::Subcommand)]
+enum TraceMethod {
+    /// Record the program flow with the Micro Trace Buffer of a Cortex-M0+, and print the last
+    /// executed source lines once the core halts, e.g. on a HardFault.
+    #[clap(name = "mtb")]
+    Mtb(mtb::Cmd),
+    /// Record the program flow with the ETMv4 of the core into the trace memory, and print the
+    /// last executed source lines and the instruction coverage of every function.
+    #[clap(name = "etm")]
+    Etm(etm::Cmd),
+    /// Record the values written to up to four static variables with the DWT data trace over
+    /// SWO, and write them as a timeline to the terminal, a CSV or a VCD file.
+    ///
+    /// Unlike tracing a memory location, which polls the target, no writes are missed.
+    #[clap(name = "data")]
+    Data(data::Cmd),
+    /// Record the entries and exits of the exception handlers with the DWT exception trace over
+    /// SWO, and print the count and durations of every exception.
+    #[clap(name = "exceptions")]
+    Exceptions(exceptions::Cmd),
+    /// Split trace data captured from the trace port of the TPIU into its sources, and show the
+    /// data written to the ITM stimulus ports.
+    #[clap(name = "decode")]
+    Decode(decode::Cmd),
 }
 
 impl Cmd {
     pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let loc = match self.method {
+            Some(TraceMethod::Mtb(cmd)) => return cmd.run(lister),
+            Some(TraceMethod::Etm(cmd)) => return cmd.run(lister),
+            Some(TraceMethod::Data(cmd)) => return cmd.run(lister),
+            Some(TraceMethod::Exceptions(cmd)) => return cmd.run(lister),
+            Some(TraceMethod::Decode(cmd)) => return cmd.run(),
+            None => self
+                .loc
+                .expect("the location is required without a subcommand"),
+        };
+
         let mut xs = vec![];
         let mut ys = vec![];
 
@@ -40,7 +94,7 @@ impl Cmd {
             let instant = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
 
             // Read data.
-            let value: u32 = core.read_word_32(self.loc)?;
+            let value: u32 = core.read_word_32(loc)?;
 
             xs.push(instant);
             ys.push(value);
@@ -63,3 +117,119 @@ impl Cmd {
         }
     }
 }
+
+/// Sends the trace data of the session to a decoder, and passes its results to `handle`, until
+/// `duration` in seconds has passed, or until Ctrl+C is pressed without a duration.
+fn stream_trace<T>(
+    session: &mut Session,
+    duration: Option<f64>,
+    data: Sender<Vec<u8>>,
+    results: Receiver<T>,
+    handle: impl FnMut(T) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let exit = Arc::new(AtomicBool::new(false));
+    let sig_id = signal_hook::flag::register(signal::SIGINT, exit.clone())?;
+
+    match duration {
+        Some(duration) => eprintln!("Tracing for {duration} s..."),
+        None => eprintln!("Tracing until Ctrl+C is pressed..."),
+    }
+    let result = read_trace(session, duration, &exit, data, results, handle);
+
+    signal_hook::low_level::unregister(sig_id);
+    signal_hook::flag::register_conditional_default(signal::SIGINT, exit)?;
+
+    result
+}
+
+fn read_trace<T>(
+    session: &mut Session,
+    duration: Option<f64>,
+    exit: &AtomicBool,
+    data: Sender<Vec<u8>>,
+    results: Receiver<T>,
+    mut handle: impl FnMut(T) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    while !exit.load(Ordering::Relaxed)
+        && duration.map_or(true, |duration| start.elapsed().as_secs_f64() < duration)
+    {
+        let trace = session.read_trace_data()?;
+        if !trace.is_empty() {
+            // An error means that the decoder stopped, and there are no more results.
+            let _ = data.send(trace);
+        }
+        while let Ok(result) = results.try_recv() {
+            handle(result)?;
+        }
+        sleep(Duration::from_millis(10));
+    }
+
+    // Decode the remaining data.
+    drop(data);
+    for result in results.iter() {
+        handle(result)?;
+    }
+    Ok(())
+}
+
+/// The last executed source lines with their function, from the oldest to the newest one.
+///
+/// Consecutive instructions of the same line are listed once.
+struct ExecutionHistory<'a> {
+    symbols: &'a Symbols<'a>,
+    debug_info: &'a DebugInfo,
+    limit: usize,
+    lines: VecDeque<(String, String)>,
+    locations: HashMap<u64, String>,
+}
+
+impl<'a> ExecutionHistory<'a> {
+    fn new(symbols: &'a Symbols<'a>, debug_info: &'a DebugInfo, limit: usize) -> Self {
+        Self {
+            symbols,
+            debug_info,
+            limit,
+            lines: VecDeque::with_capacity(limit + 1),
+            locations: HashMap::new(),
+        }
+    }
+
+    /// Adds the executed instruction at `address`.
+    fn push(&mut self, address: u64) {
+        let debug_info = self.debug_info;
+        let location = self
+            .locations
+            .entry(address)
+            .or_insert_with(|| {
+                debug_info
+                    .get_source_location(address)
+                    .and_then(|location| {
+                        Some(format!(
+                            "{}:{}",
+                            location.combined_typed_path()?.to_string_lossy(),
+                            location.line?
+                        ))
+                    })
+                    .unwrap_or_else(|| format!("{address:#010x}"))
+            })
+            .clone();
+
+        if self.lines.back().map(|(last, _)| last) != Some(&location) {
+            let function = self
+                .symbols
+                .get_name(address)
+                .unwrap_or_else(|| "<unknown>".to_owned());
+            self.lines.push_back((location, function));
+            if self.lines.len() > self.limit {
+                self.lines.pop_front();
+            }
+        }
+    }
+
+    fn print(&self) {
+        for (location, function) in &self.lines {
+            println!("  {location} ({function})");
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! Timeline of firmware variables, recorded with the DWT data trace over SWO.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use itm::TracePacket;
+use probe_rs::architecture::arm::{component::TraceSink, SwoConfig};
+use probe_rs::debug::DebugInfo;
+use probe_rs::probe::list::Lister;
+
+use super::stream_trace;
+use crate::util::common_options::ProbeOptions;
+use crate::util::itm::{spawn_decoder, timestamp_duration, ChannelReader};
+use crate::CoreOptions;
+
+/// The number of DWT comparators of most cores, which limits the number of traced variables.
+const MAX_VARIABLES: usize = 4;
+
+/// The width of the bars of the terminal chart.
+const CHART_WIDTH: usize = 40;
+
+/// How the samples of the variables are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+enum DataFormat {
+    /// One line per sample, with a bar scaled to the values of the variable seen so far.
+    #[default]
+    Chart,
+    /// The columns `time_s,name,value`.
+    Csv,
+    /// Value Change Dump, e.g. for GTKWave or PulseView.
+    Vcd,
+}
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The ELF file of the firmware running on the target.
+    path: PathBuf,
+
+    /// The name of a static variable to trace, e.g. `COUNTER` or `app::state::MODE`. Can be given
+    /// up to four times.
+    #[clap(long = "variable", value_name = "NAME", required = true)]
+    variables: Vec<String>,
+
+    /// The frequency of the clock feeding the TPIU/SWO, usually the core clock, in Hz.
+    #[clap(long, value_name = "HZ")]
+    clock: u32,
+
+    /// The baud rate of the SWO output.
+    #[clap(long, default_value_t = 1_000_000)]
+    baud: u32,
+
+    /// How long to trace for, in seconds. Without it, tracing stops on Ctrl+C.
+    #[clap(long)]
+    duration: Option<f64>,
+
+    /// How the samples are written.
+    #[clap(long, value_enum, default_value_t)]
+    format: DataFormat,
+
+    /// Write the samples to this file instead of stdout.
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+/// A traced static variable. It is traced by the DWT comparator with its index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Variable {
+    name: String,
+    address: u64,
+    size: u64,
+}
+
+impl Variable {
+    fn bits(&self) -> u32 {
+        self.size as u32 * 8
+    }
+}
+
+/// A value written to the variable at an index in the list of variables.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Sample {
+    time: Duration,
+    variable: usize,
+    value: u32,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        if self.variables.len() > MAX_VARIABLES {
+            bail!("At most {MAX_VARIABLES} variables can be traced at once");
+        }
+
+        let debug_info = DebugInfo::from_file(&self.path)?;
+        let variables = self
+            .variables
+            .iter()
+            .map(|name| resolve_variable(&debug_info, name))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+        session.setup_tracing(
+            self.shared.core,
+            TraceSink::Swo(SwoConfig::new(self.clock).set_baud(self.baud)),
+        )?;
+        for (unit, variable) in variables.iter().enumerate() {
+            session.add_swv_data_trace(unit, variable.address as u32)?;
+        }
+
+        let clock_frequency = self.clock;
+        let (data, samples) =
+            spawn_decoder(move |reader, samples| decode(reader, samples, clock_frequency));
+
+        let writer: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(io::stdout()),
+        };
+        let mut timeline = Timeline::new(self.format, writer, variables.clone());
+        timeline.start()?;
+
+        let result = stream_trace(&mut session, self.duration, data, samples, |sample| {
+            Ok(timeline.sample(sample)?)
+        })
+        .and_then(|()| timeline.flush());
+
+        for unit in 0..variables.len() {
+            session.remove_swv_data_trace(unit)?;
+        }
+
+        result
+    }
+}
+
+/// Looks up the address and size of the static variable `name`.
+fn resolve_variable(debug_info: &DebugInfo, name: &str) -> anyhow::Result<Variable> {
+    let (address, size) = debug_info
+        .static_variable(name)
+        .ok_or_else(|| anyhow!("The static variable {name} was not found in the debug info"))?;
+    let size = size.unwrap_or(4);
+    if !matches!(size, 1 | 2 | 4) {
+        bail!("The variable {name} has {size} bytes, only 1, 2 or 4 bytes can be traced");
+    }
+    if address > u64::from(u32::MAX) {
+        bail!("The variable {name} is at {address:#x}, outside of the 32-bit address space");
+    }
+
+    Ok(Variable {
+        name: name.to_owned(),
+        address,
+        size,
+    })
+}
+
+/// Decodes the trace data from `reader`, and sends the timestamped data trace values to `samples`
+/// until the data ends. The comparator of a value is the index of its variable.
+fn decode(reader: ChannelReader, samples: Sender<Sample>, clock_frequency: u32) {
+    let decoder = itm::Decoder::new(reader, itm::DecoderOptions { ignore_eof: false });
+    let timestamps = decoder.timestamps(itm::TimestampsConfiguration {
+        clock_frequency,
+        lts_prescaler: itm::LocalTimestampOptions::Enabled,
+        expect_malformed: true,
+    });
+
+    for packets in timestamps {
+        let packets = match packets {
+            Ok(packets) => packets,
+            Err(error) => {
+                tracing::warn!("Failed to read ITM data: {error:?}");
+                return;
+            }
+        };
+
+        let time = timestamp_duration(&packets.timestamp);
+        for packet in packets.packets {
+            match packet {
+                TracePacket::DataTraceValue {
+                    comparator, value, ..
+                } => {
+                    let mut bytes = [0; 4];
+                    let length = value.len().min(4);
+                    bytes[..length].copy_from_slice(&value[..length]);
+                    let sample = Sample {
+                        time,
+                        variable: usize::from(comparator),
+                        value: u32::from_le_bytes(bytes),
+                    };
+                    if samples.send(sample).is_err() {
+                        return;
+                    }
+                }
+                TracePacket::Overflow => tracing::warn!("ITM overflow, data was lost"),
+                _ => {}
+            }
+        }
+        for error in packets.malformed_packets {
+            tracing::debug!("Malformed ITM packet: {error}");
+        }
+    }
+}
+
+/// Writes the samples of the traced variables in a [`DataFormat`].
+struct Timeline<W: Write> {
+    format: DataFormat,
+    writer: W,
+    variables: Vec<Variable>,
+    /// The smallest and largest value of every variable so far, to scale the chart.
+    ranges: Vec<Option<(u32, u32)>>,
+    /// The time of the last VCD timestamp.
+    last_time: Option<Duration>,
+}
+
+impl<W: Write> Timeline<W> {
+    fn new(format: DataFormat, writer: W, variables: Vec<Variable>) -> Self {
+        Self {
+            format,
+            writer,
+            ranges: vec![None; variables.len()],
+            variables,
+            last_time: None,
+        }
+    }
+
+    /// Writes the header of the format.
+    fn start(&mut self) -> io::Result<()> {
+        match self.format {
+            DataFormat::Chart => Ok(()),
+            DataFormat::Csv => writeln!(self.writer, "time_s,name,value"),
+            DataFormat::Vcd => {
+                writeln!(
+                    self.writer,
+                    "$version probe-rs {} $end",
+                    env!("CARGO_PKG_VERSION")
+                )?;
+                writeln!(self.writer, "$timescale 1 ns $end")?;
+                writeln!(self.writer, "$scope module target $end")?;
+                for (index, variable) in self.variables.iter().enumerate() {
+                    writeln!(
+                        self.writer,
+                        "$var wire {} {} {} $end",
+                        variable.bits(),
+                        vcd_id(index),
+                        variable.name.replace("::", ".")
+                    )?;
+                }
+                writeln!(self.writer, "$upscope $end")?;
+                writeln!(self.writer, "$enddefinitions $end")
+            }
+        }
+    }
+
+    /// Writes a sample. Samples of comparators without a variable are ignored.
+    fn sample(&mut self, sample: Sample) -> io::Result<()> {
+        let Some(variable) = self.variables.get(sample.variable) else {
+            return Ok(());
+        };
+        let value = match variable.bits() {
+            32 => sample.value,
+            bits => sample.value & ((1 << bits) - 1),
+        };
+
+        match self.format {
+            DataFormat::Chart => {
+                let (min, max) = match self.ranges[sample.variable] {
+                    Some((min, max)) => (min.min(value), max.max(value)),
+                    None => (value, value),
+                };
+                self.ranges[sample.variable] = Some((min, max));
+
+                let width = if max == min {
+                    CHART_WIDTH
+                } else {
+                    ((u64::from(value - min) * CHART_WIDTH as u64) / u64::from(max - min)) as usize
+                };
+                writeln!(
+                    self.writer,
+                    "{:>12.6} s  {:<24} {:>10}  {}",
+                    sample.time.as_secs_f64(),
+                    variable.name,
+                    value,
+                    "#".repeat(width)
+                )?;
+                // Keep the chart live.
+                self.writer.flush()
+            }
+            DataFormat::Csv => writeln!(
+                self.writer,
+                "{:.9},{},{}",
+                sample.time.as_secs_f64(),
+                variable.name,
+                value
+            ),
+            DataFormat::Vcd => {
+                if self.last_time != Some(sample.time) {
+                    writeln!(self.writer, "#{}", sample.time.as_nanos())?;
+                    self.last_time = Some(sample.time);
+                }
+                writeln!(
+                    self.writer,
+                    "b{value:0width$b} {}",
+                    vcd_id(sample.variable),
+                    width = variable.bits() as usize
+                )
+            }
+        }
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// The identifier code of a variable in a VCD file, one printable character from `!` on.
+fn vcd_id(index: usize) -> char {
+    char::from(b'!' + index as u8)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn variables() -> Vec<Variable> {
+        vec![
+            Variable {
+                name: "app::COUNTER".to_owned(),
+                address: 0x2000_0000,
+                size: 4,
+            },
+            Variable {
+                name: "STATE".to_owned(),
+                address: 0x2000_0004,
+                size: 1,
+            },
+        ]
+    }
+
+    fn write(format: DataFormat, samples: &[Sample]) -> String {
+        let mut output = Vec::new();
+        let mut timeline = Timeline::new(format, &mut output, variables());
+        timeline.start().unwrap();
+        for sample in samples {
+            timeline.sample(*sample).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    fn sample(micros: u64, variable: usize, value: u32) -> Sample {
+        Sample {
+            time: Duration::from_micros(micros),
+            variable,
+            value,
+        }
+    }
+
+    #[test]
+    fn csv_rows() {
+        assert_eq!(
+            write(
+                DataFormat::Csv,
+                &[sample(1, 0, 7), sample(2, 1, 0x1FF), sample(2, 2, 1)]
+            ),
+            "time_s,name,value\n0.000001000,app::COUNTER,7\n0.000002000,STATE,255\n"
+        );
+    }
+
+    #[test]
+    fn vcd_changes_share_timestamps() {
+        let output = write(
+            DataFormat::Vcd,
+            &[sample(1, 0, 5), sample(1, 1, 3), sample(3, 1, 0)],
+        );
+
+        assert!(output.contains("$var wire 32 ! app.COUNTER $end\n$var wire 8 \" STATE $end\n"));
+        assert!(output.ends_with(
+            "$enddefinitions $end\n\
+             #1000\nb00000000000000000000000000000101 !\nb00000011 \"\n\
+             #3000\nb00000000 \"\n"
+        ));
+    }
+
+    #[test]
+    fn chart_is_scaled_to_the_values_so_far() {
+        let output = write(
+            DataFormat::Chart,
+            &[sample(0, 1, 10), sample(0, 1, 20), sample(0, 1, 15)],
+        );
+        let bars = output
+            .lines()
+            .map(|line| line.chars().filter(|c| *c == '#').count())
+            .collect::<Vec<_>>();
+
+        assert_eq!(bars, [CHART_WIDTH, CHART_WIDTH, CHART_WIDTH / 2]);
+    }
+}
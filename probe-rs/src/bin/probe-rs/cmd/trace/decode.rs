@@ -0,0 +1,72 @@
+//! Offline decoding of trace data captured outside of probe-rs.
+
+use std::path::PathBuf;
+
+use anyhow::bail;
+use probe_rs::architecture::arm::component::{TpiuDeformatter, DEFAULT_ETM_TRACE_ID, ITM_TRACE_ID};
+
+use crate::util::itm::{decode_stimulus, ItmPortName};
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    /// A raw capture of the parallel trace port of the TPIU, e.g. from a logic analyzer, with the
+    /// formatter in continuous mode.
+    #[clap(long, value_name = "PATH")]
+    tpiu: PathBuf,
+
+    /// The trace bus ID of the ITM, whose stimulus port data is shown.
+    #[clap(long, default_value_t = ITM_TRACE_ID)]
+    itm_id: u8,
+
+    /// Show the lines of a stimulus port with a name, e.g. `1=net`. Lines of the other ports are
+    /// shown with their port number, except for port 0.
+    #[clap(long = "itm-port-name", value_name = "PORT=NAME")]
+    port_names: Vec<ItmPortName>,
+
+    /// Write the data of every trace source to `source-<ID>.bin` in this directory, e.g. to decode
+    /// the ETM trace with other tools.
+    #[clap(long, value_name = "DIR")]
+    split: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub fn run(self) -> anyhow::Result<()> {
+        let capture = std::fs::read(&self.tpiu)?;
+
+        let mut deformatter = TpiuDeformatter::new();
+        let mut sources = deformatter.push(&capture);
+        if sources.is_empty() && !deformatter.is_synchronized() {
+            bail!(
+                "No synchronization packet was found in {}, is the formatter in continuous mode?",
+                self.tpiu.display()
+            );
+        }
+
+        for (id, data) in &sources {
+            let source = match *id {
+                id if id == self.itm_id => "ITM",
+                DEFAULT_ETM_TRACE_ID => "ETM",
+                _ => "unknown source",
+            };
+            eprintln!("Trace ID {id:#04x} ({source}): {} bytes", data.len());
+        }
+
+        if let Some(directory) = &self.split {
+            std::fs::create_dir_all(directory)?;
+            for (id, data) in &sources {
+                std::fs::write(directory.join(format!("source-{id}.bin")), data)?;
+            }
+        }
+
+        match sources.remove(&self.itm_id) {
+            Some(itm) => {
+                for line in decode_stimulus(itm, &self.port_names) {
+                    println!("{line}");
+                }
+            }
+            None => eprintln!("The capture contains no ITM data"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! Instruction trace with the ETMv4 of the core, captured in the trace memory.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use probe_rs::architecture::arm::component::{
+    find_component, Etm, EtmConfig, TraceSink, DEFAULT_ETM_TRACE_ID,
+};
+use probe_rs::architecture::arm::etm::{
+    thumb_instruction_size, EtmV4Decoder, ProgramFlow, TraceElement,
+};
+use probe_rs::architecture::arm::{memory::PeripheralType, DpAddress};
+use probe_rs::debug::DebugInfo;
+use probe_rs::probe::list::Lister;
+use probe_rs::InstructionSet;
+
+use super::ExecutionHistory;
+use crate::cmd::profile::Symbols;
+use crate::util::{common_options::ProbeOptions, coverage::CoverageOptions, parse_u64};
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The ELF file of the firmware running on the target.
+    path: PathBuf,
+
+    /// How long to trace for, in seconds. Tracing stops earlier if the core halts.
+    #[clap(long, default_value_t = 1.0)]
+    duration: f64,
+
+    /// The number of executed source lines to print.
+    #[clap(long, default_value_t = 20)]
+    lines: usize,
+
+    /// The trace bus ID of the ETM, which must differ from the ones of the other trace sources.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..0x70), default_value_t = DEFAULT_ETM_TRACE_ID)]
+    trace_id: u8,
+
+    /// Trace the target of every taken branch, not only of indirect ones.
+    #[clap(long)]
+    branch_broadcast: bool,
+
+    /// Trace the number of cycles between instructions.
+    #[clap(long)]
+    cycle_accurate: bool,
+
+    /// Only trace the instructions in the address range START..END. Can be given multiple times.
+    #[clap(long = "range", value_parser = parse_range)]
+    ranges: Vec<Range<u64>>,
+
+    #[clap(flatten)]
+    coverage: CoverageOptions,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let instruction_set = {
+            let mut core = session.core(self.shared.core)?;
+            core.reset_and_halt(Duration::from_millis(100))?;
+            core.instruction_set()?
+        };
+        session.setup_tracing(self.shared.core, TraceSink::TraceMemory)?;
+
+        let components = session.get_arm_components(DpAddress::Default)?;
+        let component = find_component(&components, PeripheralType::Etm)?;
+
+        let commit_opt = {
+            let mut etm = Etm::new(session.get_arm_interface()?, component);
+            let (major, minor) = etm.version()?;
+            if major != 4 {
+                // PTM and ETMv3 trace units use a different packet protocol.
+                bail!(
+                    "The trace unit of the core implements architecture version {major}.{minor}. \
+                     Only ETMv4 traces can be decoded, PTM and ETMv3 traces are not supported"
+                );
+            }
+            tracing::info!("Found ETMv{major}.{minor}");
+
+            etm.configure(&EtmConfig {
+                trace_id: self.trace_id,
+                branch_broadcast: self.branch_broadcast,
+                cycle_accurate: self.cycle_accurate,
+                address_ranges: self.ranges.clone(),
+            })?;
+            etm.enable()?;
+            etm.commit_opt()?
+        };
+
+        session.core(self.shared.core)?.run()?;
+
+        eprintln!("Tracing for {} s or until the core halts...", self.duration);
+        let start = Instant::now();
+        let mut trace = Vec::new();
+        while start.elapsed().as_secs_f64() < self.duration
+            && !session.core(self.shared.core)?.core_halted()?
+        {
+            trace.extend(session.read_trace_source(self.trace_id)?);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        Etm::new(session.get_arm_interface()?, component).disable()?;
+        trace.extend(session.read_trace_source(self.trace_id)?);
+        session
+            .core(self.shared.core)?
+            .halt(Duration::from_millis(100))?;
+
+        let bytes = std::fs::read(&self.path)?;
+        let symbols = Symbols::try_from(&bytes)?;
+        let debug_info = DebugInfo::from_file(&self.path)?;
+
+        let elements = EtmV4Decoder::new(commit_opt).decode(&trace);
+        let lost = elements
+            .iter()
+            .filter(|element| **element == TraceElement::Lost)
+            .count();
+        if lost > 0 {
+            tracing::warn!("Trace was lost {lost} times, the program flow has gaps");
+        }
+
+        let mut flow = ProgramFlow::new(|address| {
+            let code = symbols.code(address)?;
+            let mut bytes = [0; 4];
+            let length = code.len().min(4);
+            bytes[..length].copy_from_slice(&code[..length]);
+            Some(bytes)
+        });
+        let mut history = ExecutionHistory::new(&symbols, &debug_info, self.lines);
+        let mut executed = HashMap::new();
+        for element in &elements {
+            let result = flow.process(element, |address| {
+                history.push(address);
+                *executed.entry(address).or_insert(0) += 1;
+            });
+            if let Err(error) = result {
+                tracing::warn!("{error}");
+            }
+        }
+
+        println!(
+            "Captured {} bytes of trace, last executed source lines:",
+            trace.len()
+        );
+        history.print();
+
+        println!("Instruction coverage:");
+        for (name, range) in symbols.functions() {
+            let (covered, total) = coverage(&symbols, range, instruction_set, &executed);
+            if covered > 0 {
+                println!(
+                    "  {:5.1}% {covered:>6}/{total:<6} {name}",
+                    covered as f64 * 100.0 / total as f64
+                );
+            }
+        }
+
+        self.coverage.export(&debug_info, executed, &self.path)
+    }
+}
+
+/// Returns the number of executed and of all instructions in `range`.
+fn coverage(
+    symbols: &Symbols,
+    range: Range<u64>,
+    instruction_set: InstructionSet,
+    executed: &HashMap<u64, u64>,
+) -> (usize, usize) {
+    let (mut covered, mut total) = (0, 0);
+
+    let mut address = range.start;
+    while address < range.end {
+        let Some(code) = symbols.code(address) else {
+            break;
+        };
+        total += 1;
+        if executed.contains_key(&address) {
+            covered += 1;
+        }
+        address += match instruction_set {
+            InstructionSet::Thumb2 => thumb_instruction_size(code),
+            _ => 4,
+        };
+    }
+
+    (covered, total)
+}
+
+fn parse_range(input: &str) -> anyhow::Result<Range<u64>> {
+    let (start, end) = input
+        .split_once("..")
+        .ok_or_else(|| anyhow!("Expected a range in the form START..END"))?;
+    Ok(parse_u64(start)?..parse_u64(end)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ranges_are_parsed() {
+        assert_eq!(parse_range("0x100..0x200").unwrap(), 0x100..0x200);
+        assert_eq!(parse_range("16..32").unwrap(), 16..32);
+        assert!(parse_range("0x100").is_err());
+    }
+}
@@ -0,0 +1,518 @@
+//! Timeline and statistics of the exceptions and interrupts of the core, recorded with the DWT
+//! exception trace over SWO.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use itm::cortex_m::{Exception, VectActive};
+use itm::{ExceptionAction, TracePacket};
+use probe_rs::architecture::arm::{component::TraceSink, SwoConfig};
+use probe_rs::probe::list::Lister;
+use probe_rs::{exception_handler_for_core, ExceptionInterface};
+use serde::Serialize;
+
+use super::stream_trace;
+use crate::cmd::profile::Symbols;
+use crate::util::common_options::ProbeOptions;
+use crate::util::itm::{spawn_decoder, timestamp_duration, ChannelReader};
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The ELF file of the firmware running on the target, to name the exceptions after their
+    /// handlers in the vector table.
+    path: Option<PathBuf>,
+
+    /// The frequency of the clock feeding the TPIU/SWO, usually the core clock, in Hz.
+    #[clap(long, value_name = "HZ")]
+    clock: u32,
+
+    /// The baud rate of the SWO output.
+    #[clap(long, default_value_t = 1_000_000)]
+    baud: u32,
+
+    /// How long to trace for, in seconds. Without it, tracing stops on Ctrl+C.
+    #[clap(long)]
+    duration: Option<f64>,
+
+    /// Write the timeline of the exception handlers to this file, in the Chrome trace event
+    /// format, e.g. for Perfetto or `chrome://tracing`.
+    #[clap(long)]
+    timeline: Option<PathBuf>,
+}
+
+/// An exception trace event, with the exception number.
+#[derive(Debug, Clone, PartialEq)]
+struct Event {
+    time: Duration,
+    number: u16,
+    action: ExceptionAction,
+}
+
+/// A completed run of an exception handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Span {
+    number: u16,
+    start: Duration,
+    duration: Duration,
+    /// The number of active handlers, including this one.
+    depth: usize,
+}
+
+/// The statistics of the runs of an exception handler. Durations include the time spent in the
+/// handlers preempting it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct ExceptionStats {
+    count: u64,
+    total: Duration,
+    min: Option<Duration>,
+    max: Duration,
+    /// The number of times the exception preempted another handler.
+    preempted: u64,
+    /// The largest number of active handlers while the exception was active, including itself.
+    max_depth: usize,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let core_type = session.core(self.shared.core)?.core_type();
+        let names = ExceptionNames {
+            handler: exception_handler_for_core(core_type),
+            vector_table: match &self.path {
+                Some(path) => vector_table_names(&std::fs::read(path)?)?,
+                None => Vec::new(),
+            },
+        };
+
+        session.setup_tracing(
+            self.shared.core,
+            TraceSink::Swo(SwoConfig::new(self.clock).set_baud(self.baud)),
+        )?;
+
+        let clock_frequency = self.clock;
+        let (data, events) =
+            spawn_decoder(move |reader, events| decode(reader, events, clock_frequency));
+
+        let mut recorder = Recorder::default();
+        stream_trace(&mut session, self.duration, data, events, |event| {
+            recorder.push(&event);
+            Ok(())
+        })?;
+
+        recorder.write_stats(io::stdout().lock(), &names)?;
+
+        if let Some(path) = &self.timeline {
+            let mut writer = BufWriter::new(File::create(path)?);
+            recorder.write_chrome_trace(&mut writer, &names)?;
+            writer.flush()?;
+            println!(
+                "Written {} handler runs to {}",
+                recorder.spans.len(),
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Names exceptions after the architecture, and after their handler in the vector table.
+struct ExceptionNames {
+    handler: Box<dyn ExceptionInterface>,
+    /// The name of the handler of every exception number, if known.
+    vector_table: Vec<Option<String>>,
+}
+
+impl ExceptionNames {
+    fn name(&self, number: u16) -> String {
+        let name = self
+            .handler
+            .exception_name(u32::from(number))
+            .unwrap_or_else(|| format!("Exception {number}"));
+        match self
+            .vector_table
+            .get(usize::from(number))
+            .cloned()
+            .flatten()
+        {
+            Some(handler) if handler != name => format!("{name} ({handler})"),
+            _ => name,
+        }
+    }
+}
+
+/// Returns the names of the handlers in the vector table of the program, indexed by exception
+/// number.
+fn vector_table_names(elf: &[u8]) -> anyhow::Result<Vec<Option<String>>> {
+    let symbols = Symbols::try_from(elf)?;
+    let Some(vector_table) = symbols.section(".vector_table") else {
+        tracing::warn!("The program has no .vector_table section, handlers can't be named");
+        return Ok(Vec::new());
+    };
+
+    Ok(vector_table
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(number, entry)| {
+            // The first entry is the initial stack pointer.
+            let address = u32::from_le_bytes(entry.try_into().unwrap()) & !1;
+            if number == 0 || address == 0 {
+                return None;
+            }
+            symbols.get_name(u64::from(address))
+        })
+        .collect())
+}
+
+/// Returns the exception number of `exception`.
+fn exception_number(exception: VectActive) -> u16 {
+    match exception {
+        VectActive::ThreadMode => 0,
+        VectActive::Exception(exception) => match exception {
+            Exception::NonMaskableInt => 2,
+            Exception::HardFault => 3,
+            Exception::MemoryManagement => 4,
+            Exception::BusFault => 5,
+            Exception::UsageFault => 6,
+            Exception::SecureFault => 7,
+            Exception::SVCall => 11,
+            Exception::DebugMonitor => 12,
+            Exception::PendSV => 14,
+            Exception::SysTick => 15,
+        },
+        VectActive::Interrupt { irqn } => irqn + 16,
+    }
+}
+
+/// Decodes the trace data from `reader`, and sends the timestamped exception trace events to
+/// `events` until the data ends.
+fn decode(reader: ChannelReader, events: Sender<Event>, clock_frequency: u32) {
+    let decoder = itm::Decoder::new(reader, itm::DecoderOptions { ignore_eof: false });
+    let timestamps = decoder.timestamps(itm::TimestampsConfiguration {
+        clock_frequency,
+        lts_prescaler: itm::LocalTimestampOptions::Enabled,
+        expect_malformed: true,
+    });
+
+    for packets in timestamps {
+        let packets = match packets {
+            Ok(packets) => packets,
+            Err(error) => {
+                tracing::warn!("Failed to read ITM data: {error:?}");
+                return;
+            }
+        };
+
+        let time = timestamp_duration(&packets.timestamp);
+        for packet in packets.packets {
+            match packet {
+                TracePacket::ExceptionTrace { exception, action } => {
+                    let event = Event {
+                        time,
+                        number: exception_number(exception),
+                        action,
+                    };
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                }
+                TracePacket::Overflow => tracing::warn!("ITM overflow, events were lost"),
+                _ => {}
+            }
+        }
+        for error in packets.malformed_packets {
+            tracing::debug!("Malformed ITM packet: {error}");
+        }
+    }
+}
+
+/// Follows the active exception handlers through the trace events.
+#[derive(Debug, Default)]
+struct Recorder {
+    /// The exception number and start time of every active handler, from the oldest one.
+    active: Vec<(u16, Duration)>,
+    spans: Vec<Span>,
+    stats: BTreeMap<u16, ExceptionStats>,
+}
+
+impl Recorder {
+    fn push(&mut self, event: &Event) {
+        match event.action {
+            ExceptionAction::Entered => {
+                if event.number == 0 {
+                    return;
+                }
+                self.active.push((event.number, event.time));
+
+                let depth = self.active.len();
+                let stats = self.stats.entry(event.number).or_default();
+                stats.max_depth = stats.max_depth.max(depth);
+                if depth > 1 {
+                    stats.preempted += 1;
+                }
+                for (number, _) in &self.active[..depth - 1] {
+                    let stats = self.stats.entry(*number).or_default();
+                    stats.max_depth = stats.max_depth.max(depth);
+                }
+            }
+            ExceptionAction::Exited => {
+                // Handlers above the exited one are only left if their events were lost.
+                let Some(index) = self
+                    .active
+                    .iter()
+                    .rposition(|(number, _)| *number == event.number)
+                else {
+                    return;
+                };
+                let (number, start) = self.active[index];
+                self.active.truncate(index);
+
+                let duration = event.time.saturating_sub(start);
+                let stats = self.stats.entry(number).or_default();
+                stats.count += 1;
+                stats.total += duration;
+                stats.min = Some(stats.min.map_or(duration, |min| min.min(duration)));
+                stats.max = stats.max.max(duration);
+
+                self.spans.push(Span {
+                    number,
+                    start,
+                    duration,
+                    depth: index + 1,
+                });
+            }
+            ExceptionAction::Returned => {
+                // All handlers above the one returned to have exited.
+                match self
+                    .active
+                    .iter()
+                    .rposition(|(number, _)| *number == event.number)
+                {
+                    Some(index) => self.active.truncate(index + 1),
+                    None if event.number == 0 => self.active.clear(),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Writes the statistics of every exception as a table.
+    fn write_stats(&self, mut writer: impl Write, names: &ExceptionNames) -> io::Result<()> {
+        if self.stats.is_empty() {
+            return writeln!(writer, "No exceptions were traced");
+        }
+
+        writeln!(
+            writer,
+            "{:<40} {:>8} {:>12} {:>12} {:>12} {:>10} {:>6}",
+            "Exception", "Count", "Min (µs)", "Avg (µs)", "Max (µs)", "Preempted", "Depth"
+        )?;
+        for (number, stats) in &self.stats {
+            let average = match u32::try_from(stats.count) {
+                Ok(count) if count > 0 => stats.total / count,
+                _ => Duration::ZERO,
+            };
+            writeln!(
+                writer,
+                "{:<40} {:>8} {:>12.3} {:>12.3} {:>12.3} {:>10} {:>6}",
+                names.name(*number),
+                stats.count,
+                micros(stats.min.unwrap_or_default()),
+                micros(average),
+                micros(stats.max),
+                stats.preempted,
+                stats.max_depth
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the runs of the handlers as complete events in the Chrome trace event format.
+    fn write_chrome_trace(&self, writer: impl Write, names: &ExceptionNames) -> io::Result<()> {
+        let mut names_by_number = BTreeMap::new();
+        let trace = ChromeTrace {
+            trace_events: self
+                .spans
+                .iter()
+                .map(|span| ChromeEvent {
+                    name: names_by_number
+                        .entry(span.number)
+                        .or_insert_with(|| names.name(span.number))
+                        .clone(),
+                    cat: "exception",
+                    ph: "X",
+                    ts: micros(span.start),
+                    dur: micros(span.duration),
+                    pid: 0,
+                    tid: 0,
+                    args: ChromeArgs {
+                        number: span.number,
+                        depth: span.depth,
+                    },
+                })
+                .collect(),
+            display_time_unit: "ns",
+        };
+
+        serde_json::to_writer(writer, &trace)?;
+        Ok(())
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChromeTrace {
+    trace_events: Vec<ChromeEvent>,
+    display_time_unit: &'static str,
+}
+
+#[derive(Serialize)]
+struct ChromeEvent {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    dur: f64,
+    pid: u32,
+    tid: u32,
+    args: ChromeArgs,
+}
+
+#[derive(Serialize)]
+struct ChromeArgs {
+    number: u16,
+    depth: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names() -> ExceptionNames {
+        ExceptionNames {
+            handler: exception_handler_for_core(probe_rs::CoreType::Armv7m),
+            vector_table: vec![None, None, None, Some("HardFault".to_owned())]
+                .into_iter()
+                .chain(std::iter::repeat(None).take(12))
+                .chain([Some("TIMER0".to_owned())])
+                .collect(),
+        }
+    }
+
+    fn record(events: &[(u64, u16, ExceptionAction)]) -> Recorder {
+        let mut recorder = Recorder::default();
+        for (micros, number, action) in events {
+            recorder.push(&Event {
+                time: Duration::from_micros(*micros),
+                number: *number,
+                action: action.clone(),
+            });
+        }
+        recorder
+    }
+
+    #[test]
+    fn nested_handlers() {
+        use ExceptionAction::*;
+
+        // TIMER0 is preempted by SysTick, then runs once more on its own.
+        let recorder = record(&[
+            (10, 16, Entered),
+            (12, 15, Entered),
+            (15, 15, Exited),
+            (15, 16, Returned),
+            (20, 16, Exited),
+            (20, 0, Returned),
+            (30, 16, Entered),
+            (32, 16, Exited),
+            (32, 0, Returned),
+        ]);
+
+        assert!(recorder.active.is_empty());
+        assert_eq!(
+            recorder.stats[&16],
+            ExceptionStats {
+                count: 2,
+                total: Duration::from_micros(12),
+                min: Some(Duration::from_micros(2)),
+                max: Duration::from_micros(10),
+                preempted: 0,
+                max_depth: 2,
+            }
+        );
+        assert_eq!(recorder.stats[&15].preempted, 1);
+        assert_eq!(
+            recorder.spans[0],
+            Span {
+                number: 15,
+                start: Duration::from_micros(12),
+                duration: Duration::from_micros(3),
+                depth: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn lost_exits_are_dropped() {
+        use ExceptionAction::*;
+
+        let recorder = record(&[
+            (0, 15, Exited),
+            (1, 16, Entered),
+            (2, 15, Entered),
+            (5, 0, Returned),
+        ]);
+
+        assert!(recorder.active.is_empty());
+        assert!(recorder.spans.is_empty());
+    }
+
+    #[test]
+    fn names_from_vector_table() {
+        let names = names();
+
+        assert_eq!(names.name(3), "HardFault");
+        assert_eq!(names.name(15), "SysTick");
+        assert_eq!(names.name(16), "ExternalInterrupt(0) (TIMER0)");
+    }
+
+    #[test]
+    fn chrome_trace_events() {
+        use ExceptionAction::*;
+
+        let recorder = record(&[(10, 16, Entered), (12, 16, Exited)]);
+        let mut output = Vec::new();
+        recorder.write_chrome_trace(&mut output, &names()).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(
+            json["traceEvents"],
+            serde_json::json!([{
+                "name": "ExternalInterrupt(0) (TIMER0)",
+                "cat": "exception",
+                "ph": "X",
+                "ts": 10.0,
+                "dur": 2.0,
+                "pid": 0,
+                "tid": 0,
+                "args": {"number": 16, "depth": 1},
+            }])
+        );
+    }
+}
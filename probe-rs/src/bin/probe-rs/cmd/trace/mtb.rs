@@ -0,0 +1,202 @@
+//! Instruction trace with the Micro Trace Buffer of Cortex-M0+ cores.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use probe_rs::architecture::arm::component::{executed_ranges, find_component, Mtb};
+use probe_rs::architecture::arm::{etm::thumb_instruction_size, memory::PeripheralType, DpAddress};
+use probe_rs::config::MemoryRegion;
+use probe_rs::debug::DebugInfo;
+use probe_rs::probe::list::Lister;
+use probe_rs::{Core, VectorCatchCondition};
+use signal_hook::consts::signal;
+
+use super::ExecutionHistory;
+use crate::cmd::profile::Symbols;
+use crate::util::{common_options::ProbeOptions, coverage::CoverageOptions, parse_u32};
+use crate::CoreOptions;
+
+#[derive(clap::Parser)]
+pub struct Cmd {
+    #[clap(flatten)]
+    shared: CoreOptions,
+
+    #[clap(flatten)]
+    common: ProbeOptions,
+
+    /// The ELF file of the firmware running on the target.
+    path: PathBuf,
+
+    /// The offset of the trace buffer from the start of the SRAM used by the MTB. The firmware
+    /// must not use this part of the SRAM. Defaults to the end of the SRAM.
+    #[clap(long, value_parser = parse_u32)]
+    buffer_offset: Option<u32>,
+
+    /// The size of the trace buffer in bytes, rounded down to a power of two.
+    #[clap(long, value_parser = parse_u32, default_value = "1024")]
+    buffer_size: u32,
+
+    /// How long to wait for the core to halt, in seconds. The core is halted afterwards, as
+    /// well as when Ctrl+C is pressed.
+    #[clap(long, default_value_t = 60.0)]
+    timeout: f64,
+
+    /// The number of executed source lines to print.
+    #[clap(long, default_value_t = 20)]
+    lines: usize,
+
+    #[clap(flatten)]
+    coverage: CoverageOptions,
+}
+
+impl Cmd {
+    pub fn run(self, lister: &Lister) -> anyhow::Result<()> {
+        let (mut session, _probe_options) = self.common.simple_attach(lister)?;
+
+        let components = session.get_arm_components(DpAddress::Default)?;
+        let component = find_component(&components, PeripheralType::Mtb)?;
+
+        {
+            let mut core = session.core(self.shared.core)?;
+            core.reset_and_halt(Duration::from_millis(100))?;
+            core.enable_vector_catch(VectorCatchCondition::HardFault)?;
+        }
+
+        let base = Mtb::new(session.get_arm_interface()?, component).base()?;
+        let sram_size = session
+            .target()
+            .memory_map
+            .iter()
+            .find_map(|region| match region {
+                MemoryRegion::Ram(ram) if ram.range.contains(&u64::from(base)) => {
+                    Some(ram.range.end - u64::from(base))
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "The SRAM of the MTB at {base:#010x} is not part of the target's memory map"
+                )
+            })?;
+        let buffer_offset = buffer_offset(sram_size, self.buffer_offset, self.buffer_size)?;
+        tracing::info!(
+            "Placing the trace buffer at {:#010x}",
+            u64::from(base) + u64::from(buffer_offset)
+        );
+
+        {
+            let mut mtb = Mtb::new(session.get_arm_interface()?, component);
+            mtb.configure(buffer_offset, self.buffer_size)?;
+            mtb.set_watermark(None, false)?;
+            mtb.start()?;
+        }
+
+        let pc = {
+            let mut core = session.core(self.shared.core)?;
+            core.run()?;
+
+            eprintln!("Tracing until the core halts, e.g. on a HardFault, or Ctrl+C is pressed...");
+            let exit = Arc::new(AtomicBool::new(false));
+            let sig_id = signal_hook::flag::register(signal::SIGINT, exit.clone())?;
+
+            let result = wait_for_halt(&mut core, self.timeout, &exit);
+
+            signal_hook::low_level::unregister(sig_id);
+            signal_hook::flag::register_conditional_default(signal::SIGINT, exit)?;
+            result?;
+
+            let pc: u64 = core.read_core_reg(core.program_counter())?;
+            pc as u32
+        };
+
+        let packets = {
+            let mut mtb = Mtb::new(session.get_arm_interface()?, component);
+            mtb.stop()?;
+            mtb.read_packets()?
+        };
+
+        let bytes = std::fs::read(&self.path)?;
+        let symbols = Symbols::try_from(&bytes)?;
+        let debug_info = DebugInfo::from_file(&self.path)?;
+
+        let mut history = ExecutionHistory::new(&symbols, &debug_info, self.lines);
+        let mut executed = HashMap::new();
+        for range in executed_ranges(&packets, Some(pc)) {
+            let mut address = *range.start() as u64;
+            while address <= *range.end() as u64 {
+                let Some(code) = symbols.code(address) else {
+                    break;
+                };
+                history.push(address);
+                *executed.entry(address).or_insert(0) += 1;
+                address += thumb_instruction_size(code);
+            }
+        }
+
+        println!("Core halted at {pc:#010x}, last executed source lines:");
+        history.print();
+
+        self.coverage.export(&debug_info, executed, &self.path)
+    }
+}
+
+/// Waits until the core halts, and halts it after `timeout` seconds or when `exit` is set.
+fn wait_for_halt(core: &mut Core, timeout: f64, exit: &AtomicBool) -> anyhow::Result<()> {
+    let start = Instant::now();
+    while !core.core_halted()? {
+        if exit.load(Ordering::Relaxed) || start.elapsed().as_secs_f64() >= timeout {
+            eprintln!("Halting the core...");
+            core.halt(Duration::from_millis(100))?;
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Ok(())
+}
+
+/// Returns the offset of the trace buffer in the SRAM of `sram_size` bytes, which is `offset` or
+/// the end of the SRAM, and checks that the buffer fits.
+fn buffer_offset(sram_size: u64, offset: Option<u32>, size: u32) -> anyhow::Result<u32> {
+    // The MTB rounds the size down to a power of two, of at least 16 bytes.
+    let size = 1 << size.max(16).ilog2();
+    let offset = match offset {
+        Some(offset) => offset,
+        None => {
+            let end = u32::try_from(sram_size).unwrap_or(u32::MAX);
+            // The offset has to be aligned to the size.
+            end.checked_sub(size)
+                .map_or(0, |offset| offset & !(size - 1))
+        }
+    };
+
+    if u64::from(offset) + u64::from(size) > sram_size {
+        bail!(
+            "The trace buffer of {size} bytes at offset {offset:#x} doesn't fit into the \
+             {sram_size} bytes of SRAM of the MTB"
+        );
+    }
+    Ok(offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffer_is_placed_at_the_end_of_the_sram() {
+        assert_eq!(buffer_offset(0x8000, None, 1024).unwrap(), 0x7C00);
+        // The size is rounded down to a power of two, and the buffer aligned to it.
+        assert_eq!(buffer_offset(0x7F00, None, 1500).unwrap(), 0x7800);
+        assert_eq!(buffer_offset(0x8000, Some(0x100), 256).unwrap(), 0x100);
+    }
+
+    #[test]
+    fn buffer_has_to_fit_into_the_sram() {
+        assert!(buffer_offset(0x8000, Some(0x7F00), 1024).is_err());
+        assert!(buffer_offset(0x200, None, 1024).is_err());
+    }
+}
@@ -60,16 +60,23 @@ enum Subcommand {
     Gdb(cmd::gdb::Cmd),
     /// Basic command line debugger
     Debug(cmd::debug::Cmd),
-    /// Download memory to attached target
+    /// Download memory to attached target, or to several targets at once
     Download(cmd::download::Cmd),
     /// Erase all nonvolatile memory of attached target
     Erase(cmd::erase::Cmd),
+    /// Read and modify option bytes, UICR, OTP and fuses of attached target
+    OptionBytes(cmd::option_bytes::Cmd),
+    /// Query and change the readout protection of attached target
+    Protect(cmd::protect::Cmd),
     /// Flash and run an ELF program
     #[clap(name = "run")]
     Run(cmd::run::Cmd),
     /// Attach to rtt logging
     #[clap(name = "attach")]
     Attach(cmd::attach::Cmd),
+    /// Decode an RTT capture written by `probe-rs run --rtt-capture`
+    #[clap(name = "rtt-decode")]
+    RttDecode(cmd::rtt_decode::Cmd),
     /// Trace a memory location on the target
     #[clap(name = "trace")]
     Trace(cmd::trace::Cmd),
@@ -287,7 +294,7 @@ fn main() -> Result<()> {
 
     let _logger_guard = setup_logging(log_path.as_deref(), None);
 
-    match matches.subcommand {
+    let result = match matches.subcommand {
         Subcommand::DapServer { .. } => unreachable!(), // handled above.
         Subcommand::List(cmd) => cmd.run(&lister),
         Subcommand::Info(cmd) => cmd.run(&lister),
@@ -297,7 +304,10 @@ fn main() -> Result<()> {
         Subcommand::Download(cmd) => cmd.run(&lister),
         Subcommand::Run(cmd) => cmd.run(&lister, true, utc_offset),
         Subcommand::Attach(cmd) => cmd.run(&lister, utc_offset),
+        Subcommand::RttDecode(cmd) => cmd.run(utc_offset),
         Subcommand::Erase(cmd) => cmd.run(&lister),
+        Subcommand::OptionBytes(cmd) => cmd.run(&lister),
+        Subcommand::Protect(cmd) => cmd.run(&lister),
         Subcommand::Trace(cmd) => cmd.run(&lister),
         Subcommand::Itm(cmd) => cmd.run(&lister),
         Subcommand::Chip(cmd) => cmd.run(),
@@ -305,5 +315,19 @@ fn main() -> Result<()> {
         Subcommand::Profile(cmd) => cmd.run(&lister),
         Subcommand::Read(cmd) => cmd.run(&lister),
         Subcommand::Write(cmd) => cmd.run(&lister),
+    };
+
+    // Flashing several devices exits with a distinct code if only some of them failed.
+    if let Err(error) = &result {
+        if error
+            .downcast_ref::<cmd::download::PartialFlashFailure>()
+            .is_some()
+        {
+            eprintln!("Error: {error:?}");
+            drop(_logger_guard);
+            std::process::exit(2);
+        }
     }
+
+    result
 }
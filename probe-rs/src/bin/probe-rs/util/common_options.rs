@@ -35,6 +35,7 @@ use super::ArtifactError;
 
 use std::{fs::File, path::Path, path::PathBuf};
 
+use crate::util::patch::{PatchError, PatchOptions};
 use crate::util::{logging::LevelFilter, parse_u64};
 use probe_rs::{
     config::{RegistryError, TargetSelector},
@@ -98,6 +99,8 @@ pub struct BinaryDownloadOptions {
     /// After flashing, read back all the flashed data to verify it has been written correctly.
     #[arg(long)]
     pub verify: bool,
+    #[clap(flatten)]
+    pub patch_options: PatchOptions,
 }
 
 /// Supported bit-widths for read/write commands (not every device may support each width).
@@ -143,12 +146,14 @@ pub struct ProbeOptions {
     ///
     /// Use '--probe VID:PID' or '--probe VID:PID:Serial' if you have more than one
     /// probe with the same VID:PID.",
+    ///
+    /// Commands which support multiple probes, like `download`, accept this flag several times.
     #[arg(
         long = "probe",
         env = "PROBE_RS_PROBE",
         help_heading = "PROBE CONFIGURATION"
     )]
-    pub probe_selector: Option<DebugProbeSelector>,
+    pub probe_selector: Vec<DebugProbeSelector>,
     /// The protocol speed in kHz.
     #[arg(long, env = "PROBE_RS_SPEED", help_heading = "PROBE CONFIGURATION")]
     pub speed: Option<u32>,
@@ -162,6 +167,13 @@ pub struct ProbeOptions {
     /// firmware, to be erased even when it has read-only protection.
     #[arg(long, env = "PROBE_RS_ALLOW_ERASE_ALL")]
     pub allow_erase_all: bool,
+    /// Use this flag to allow writes which can not be undone, such as programming
+    /// OTP memory, fuses or permanent readout protection.
+    #[arg(long, env = "PROBE_RS_ALLOW_IRREVERSIBLE_WRITES")]
+    pub allow_irreversible_writes: bool,
+    /// Use this flag to allow the readout protection of the chip to be enabled.
+    #[arg(long, env = "PROBE_RS_ALLOW_DEVICE_LOCK")]
+    pub allow_device_lock: bool,
 }
 
 impl ProbeOptions {
@@ -242,14 +254,13 @@ impl LoadedProbeOptions {
 
     /// Attaches to specified probe and configures it.
     pub fn attach_probe(&self, lister: &Lister) -> Result<Probe, OperationError> {
-        let mut probe = if self.0.dry_run {
+        let probe = if self.0.dry_run {
             Probe::from_specific_probe(Box::new(FakeProbe::with_mocked_core()))
         } else {
             // If we got a probe selector as an argument, open the probe
             // matching the selector if possible.
-            let probe = match &self.0.probe_selector {
-                Some(selector) => lister.open(selector),
-                None => {
+            let probe = match self.0.probe_selector.as_slice() {
+                [] => {
                     // Only automatically select a probe if there is
                     // only a single probe detected.
                     let list = lister.list_all();
@@ -263,11 +274,44 @@ impl LoadedProbeOptions {
 
                     lister.open(info)
                 }
+                [selector] => lister.open(selector),
+                selectors => {
+                    return Err(OperationError::MultipleProbesSelected {
+                        count: selectors.len(),
+                    })
+                }
             };
 
             probe.map_err(OperationError::FailedToOpenProbe)?
         };
 
+        self.configure_probe(probe)
+    }
+
+    /// The probes selected with `--probe`.
+    pub fn probe_selectors(&self) -> &[DebugProbeSelector] {
+        &self.0.probe_selector
+    }
+
+    /// Attaches to the probe matching `selector` and configures it.
+    pub fn attach_probe_by_selector(
+        &self,
+        lister: &Lister,
+        selector: &DebugProbeSelector,
+    ) -> Result<Probe, OperationError> {
+        let probe = if self.0.dry_run {
+            Probe::from_specific_probe(Box::new(FakeProbe::with_mocked_core()))
+        } else {
+            lister
+                .open(selector)
+                .map_err(OperationError::FailedToOpenProbe)?
+        };
+
+        self.configure_probe(probe)
+    }
+
+    /// Selects the protocol and speed given in [ProbeOptions].
+    fn configure_probe(&self, mut probe: Probe) -> Result<Probe, OperationError> {
         if let Some(protocol) = self.0.protocol {
             // Select protocol and speed
             probe.select_protocol(protocol).map_err(|error| {
@@ -312,10 +356,7 @@ impl LoadedProbeOptions {
         probe: Probe,
         target: TargetSelector,
     ) -> Result<Session, OperationError> {
-        let mut permissions = Permissions::new();
-        if self.0.allow_erase_all {
-            permissions = permissions.allow_erase_all();
-        }
+        let permissions = self.permissions();
 
         let session = if self.0.connect_under_reset {
             probe.attach_under_reset(target, permissions)
@@ -330,6 +371,22 @@ impl LoadedProbeOptions {
         Ok(session)
     }
 
+    /// The permissions granted by the user through [ProbeOptions].
+    pub fn permissions(&self) -> Permissions {
+        let mut permissions = Permissions::new();
+        if self.0.allow_erase_all {
+            permissions = permissions.allow_erase_all();
+        }
+        if self.0.allow_irreversible_writes {
+            permissions = permissions.allow_irreversible_writes();
+        }
+        if self.0.allow_device_lock {
+            permissions = permissions.allow_device_lock();
+        }
+
+        permissions
+    }
+
     pub(crate) fn protocol(&self) -> Option<WireProtocol> {
         self.0.protocol
     }
@@ -484,6 +541,14 @@ pub enum OperationError {
     FailedToOpenProbe(#[source] DebugProbeError),
     #[error("{} probes were found: {}", .list.len(), print_list(.list))]
     MultipleProbesFound { list: Vec<DebugProbeInfo> },
+    #[error("{count} probes were selected, but this command only supports a single probe.")]
+    MultipleProbesSelected { count: usize },
+    #[error("Failed to patch the device specific data into '{path}'.")]
+    FailedToPatch {
+        #[source]
+        source: PatchError,
+        path: PathBuf,
+    },
     #[error("The flashing procedure failed for '{path}'.")]
     FlashingFailed {
         #[source]
@@ -0,0 +1,290 @@
+//! Source line coverage of executed addresses, and its export for coverage tools.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use probe_rs::debug::DebugInfo;
+
+/// The file format of the coverage report.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CoverageFormat {
+    /// The tracefile format of lcov and genhtml.
+    #[default]
+    Lcov,
+    /// Cobertura XML, e.g. for CI systems.
+    Cobertura,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct CoverageOptions {
+    /// Write the source line coverage of the executed instructions to this file.
+    #[clap(long)]
+    pub coverage: Option<PathBuf>,
+    /// The file format of the coverage report.
+    #[clap(long, value_enum, default_value_t)]
+    pub coverage_format: CoverageFormat,
+}
+
+impl CoverageOptions {
+    /// Writes the coverage of the executed addresses, with the number of executions or samples
+    /// of each, to the configured file. Does nothing if no file is configured.
+    pub fn export(
+        &self,
+        debug_info: &DebugInfo,
+        executed: impl IntoIterator<Item = (u64, u64)>,
+        program: &Path,
+    ) -> anyhow::Result<()> {
+        let Some(path) = &self.coverage else {
+            return Ok(());
+        };
+
+        let mut coverage = Coverage::new(debug_info);
+        for (address, count) in executed {
+            coverage.add(address, count);
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        match self.coverage_format {
+            CoverageFormat::Lcov => coverage.write_lcov(&mut writer, program)?,
+            CoverageFormat::Cobertura => coverage.write_cobertura(&mut writer)?,
+        }
+        writer.flush()?;
+
+        let (covered, total) = coverage.summary();
+        println!(
+            "Covered {covered} of {total} source lines, written to {}",
+            path.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// The number of executions of every source line with code, per file.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// The file, as an index into `files`, and the line of every address range.
+    table: Vec<(Range<u64>, usize, u64)>,
+    files: Vec<(String, BTreeMap<u64, u64>)>,
+}
+
+impl Coverage {
+    /// Creates a coverage with no executed lines, for all lines in the line table of the program.
+    pub fn new(debug_info: &DebugInfo) -> Self {
+        let mut coverage = Self::default();
+        let mut indices = BTreeMap::new();
+
+        for (range, location) in debug_info.line_table() {
+            let (Some(path), Some(line)) = (location.combined_typed_path(), location.line) else {
+                continue;
+            };
+            // Line 0 marks compiler generated code.
+            if line == 0 {
+                continue;
+            }
+
+            let path = path.to_string_lossy().into_owned();
+            let index = *indices.entry(path.clone()).or_insert_with(|| {
+                coverage.files.push((path, BTreeMap::new()));
+                coverage.files.len() - 1
+            });
+            coverage.files[index].1.insert(line, 0);
+            coverage.table.push((range, index, line));
+        }
+
+        coverage
+    }
+
+    /// Adds `count` executions of the instruction at `address`.
+    pub fn add(&mut self, address: u64, count: u64) {
+        let end = self
+            .table
+            .partition_point(|(range, _, _)| range.start <= address);
+        let Some((range, file, line)) = end.checked_sub(1).map(|index| &self.table[index]) else {
+            return;
+        };
+        if range.contains(&address) {
+            *self.files[*file].1.entry(*line).or_default() += count;
+        }
+    }
+
+    /// Returns the number of executed source lines, and of all source lines with code.
+    pub fn summary(&self) -> (usize, usize) {
+        self.files
+            .iter()
+            .fold((0, 0), |(covered, total), (_, lines)| {
+                (covered + Self::covered(lines), total + lines.len())
+            })
+    }
+
+    fn covered(lines: &BTreeMap<u64, u64>) -> usize {
+        lines.values().filter(|count| **count > 0).count()
+    }
+
+    fn sorted_files(&self) -> Vec<&(String, BTreeMap<u64, u64>)> {
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    }
+
+    /// Writes the coverage as an lcov tracefile, with the name of the `program` as test name.
+    pub fn write_lcov(&self, mut writer: impl Write, program: &Path) -> io::Result<()> {
+        let name = program
+            .file_stem()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        // Test names may only contain letters, digits and underscores.
+        let name = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+
+        for (file, lines) in self.sorted_files() {
+            writeln!(writer, "TN:{name}")?;
+            writeln!(writer, "SF:{file}")?;
+            for (line, count) in lines {
+                writeln!(writer, "DA:{line},{count}")?;
+            }
+            writeln!(writer, "LF:{}", lines.len())?;
+            writeln!(writer, "LH:{}", Self::covered(lines))?;
+            writeln!(writer, "end_of_record")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the coverage as Cobertura XML, with one class per source file.
+    pub fn write_cobertura(&self, mut writer: impl Write) -> io::Result<()> {
+        let (covered, total) = self.summary();
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        writeln!(writer, r#"<?xml version="1.0" ?>"#)?;
+        writeln!(
+            writer,
+            r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#
+        )?;
+        writeln!(
+            writer,
+            r#"<coverage line-rate="{}" branch-rate="0" lines-covered="{covered}" lines-valid="{total}" branches-covered="0" branches-valid="0" complexity="0" version="{}" timestamp="{timestamp}">"#,
+            rate(covered, total),
+            env!("CARGO_PKG_VERSION"),
+        )?;
+        writeln!(writer, "  <sources/>")?;
+        writeln!(writer, "  <packages>")?;
+        writeln!(
+            writer,
+            r#"    <package name="firmware" line-rate="{}" branch-rate="0" complexity="0">"#,
+            rate(covered, total)
+        )?;
+        writeln!(writer, "      <classes>")?;
+        for (file, lines) in self.sorted_files() {
+            let name = Path::new(file)
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            writeln!(
+                writer,
+                r#"        <class name="{}" filename="{}" line-rate="{}" branch-rate="0" complexity="0">"#,
+                escape_xml(&name),
+                escape_xml(file),
+                rate(Self::covered(lines), lines.len())
+            )?;
+            writeln!(writer, "          <methods/>")?;
+            writeln!(writer, "          <lines>")?;
+            for (line, count) in lines {
+                writeln!(
+                    writer,
+                    r#"            <line number="{line}" hits="{count}" branch="false"/>"#
+                )?;
+            }
+            writeln!(writer, "          </lines>")?;
+            writeln!(writer, "        </class>")?;
+        }
+        writeln!(writer, "      </classes>")?;
+        writeln!(writer, "    </package>")?;
+        writeln!(writer, "  </packages>")?;
+        writeln!(writer, "</coverage>")
+    }
+}
+
+fn rate(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        covered as f64 / total as f64
+    }
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn coverage() -> Coverage {
+        Coverage {
+            table: vec![
+                (0x100..0x104, 0, 1),
+                (0x104..0x108, 0, 2),
+                (0x108..0x10C, 1, 7),
+            ],
+            files: vec![
+                ("/src/main.rs".to_owned(), BTreeMap::from([(1, 0), (2, 0)])),
+                ("/src/a&b.rs".to_owned(), BTreeMap::from([(7, 0)])),
+            ],
+        }
+    }
+
+    #[test]
+    fn addresses_are_mapped_to_lines() {
+        let mut coverage = coverage();
+        coverage.add(0x100, 2);
+        coverage.add(0x102, 1);
+        coverage.add(0x200, 1);
+        coverage.add(0x0FF, 1);
+
+        assert_eq!(coverage.files[0].1, BTreeMap::from([(1, 3), (2, 0)]));
+        assert_eq!(coverage.summary(), (1, 3));
+    }
+
+    #[test]
+    fn lcov_tracefile() {
+        let mut coverage = coverage();
+        coverage.add(0x104, 1);
+
+        let mut output = Vec::new();
+        coverage
+            .write_lcov(&mut output, Path::new("target/my-app.elf"))
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "TN:my_app\nSF:/src/a&b.rs\nDA:7,0\nLF:1\nLH:0\nend_of_record\n\
+             TN:my_app\nSF:/src/main.rs\nDA:1,0\nDA:2,1\nLF:2\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn cobertura_escapes_file_names() {
+        let mut output = Vec::new();
+        coverage().write_cobertura(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(r#"filename="/src/a&amp;b.rs""#));
+        assert!(output.contains(r#"<line number="2" hits="0" branch="false"/>"#));
+        assert!(output.contains(r#"lines-covered="0" lines-valid="3""#));
+    }
+}
@@ -0,0 +1,56 @@
+//! Disassembly of the code running on the target.
+
+use capstone::{
+    arch::arm::{ArchExtraMode, ArchMode as armArchMode},
+    arch::arm64::ArchMode as aarch64ArchMode,
+    arch::riscv::ArchMode as riscvArchMode,
+    prelude::*,
+    Endian,
+};
+use probe_rs::{Core, CoreType, InstructionSet};
+
+/// Returns a disassembler for the current instruction set of `core`, or `None` if the instruction
+/// set isn't supported by Capstone.
+pub fn capstone(core: &mut Core) -> anyhow::Result<Option<Capstone>> {
+    let capstone = match core.instruction_set()? {
+        InstructionSet::Thumb2 => {
+            let mut builder = Capstone::new()
+                .arm()
+                .mode(armArchMode::Thumb)
+                .endian(Endian::Little);
+            if core.core_type() == CoreType::Armv8m {
+                builder = builder.extra_mode(std::iter::once(ArchExtraMode::V8));
+            }
+            builder.build()
+        }
+        // The mode of A32 and A64 cores is not inspected, the disassembler always uses the
+        // ARM instruction set.
+        InstructionSet::A32 => Capstone::new()
+            .arm()
+            .mode(armArchMode::Arm)
+            .endian(Endian::Little)
+            .build(),
+        InstructionSet::A64 => Capstone::new()
+            .arm64()
+            .mode(aarch64ArchMode::Arm)
+            .endian(Endian::Little)
+            .build(),
+        InstructionSet::RV32 => Capstone::new()
+            .riscv()
+            .mode(riscvArchMode::RiscV32)
+            .endian(Endian::Little)
+            .build(),
+        InstructionSet::RV32C => Capstone::new()
+            .riscv()
+            .mode(riscvArchMode::RiscV32)
+            .endian(Endian::Little)
+            .extra_mode(std::iter::once(
+                capstone::arch::riscv::ArchExtraMode::RiscVC,
+            ))
+            .build(),
+        InstructionSet::Xtensa => return Ok(None),
+    }
+    .map_err(|err| anyhow::anyhow!("Error creating capstone: {:?}", err))?;
+
+    Ok(Some(capstone))
+}
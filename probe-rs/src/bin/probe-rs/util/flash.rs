@@ -3,6 +3,7 @@ use crate::FormatOptions;
 use super::common_options::{BinaryDownloadOptions, LoadedProbeOptions, OperationError};
 use super::logging;
 
+use std::cell::Cell;
 use std::fs::File;
 use std::time::Duration;
 use std::{path::Path, time::Instant};
@@ -32,18 +33,21 @@ pub fn run_flash_download(
     path: &Path,
     download_options: &BinaryDownloadOptions,
     probe_options: &LoadedProbeOptions,
-    loader: FlashLoader,
+    mut loader: FlashLoader,
     do_chip_erase: bool,
 ) -> Result<(), OperationError> {
     // Start timer.
     let instant = Instant::now();
 
-    let mut options = DownloadOptions::default();
-    options.keep_unwritten_bytes = download_options.restore_unwritten;
-    options.dry_run = probe_options.dry_run();
-    options.do_chip_erase = do_chip_erase;
-    options.disable_double_buffering = download_options.disable_double_buffering;
-    options.verify = download_options.verify;
+    download_options
+        .patch_options
+        .apply(&mut loader)
+        .map_err(|source| OperationError::FailedToPatch {
+            source,
+            path: path.to_path_buf(),
+        })?;
+
+    let mut options = build_download_options(download_options, probe_options, do_chip_erase);
 
     if !download_options.disable_progressbars {
         // Create progress bars.
@@ -167,6 +171,118 @@ pub fn run_flash_download(
     Ok(())
 }
 
+fn build_download_options(
+    download_options: &BinaryDownloadOptions,
+    probe_options: &LoadedProbeOptions,
+    do_chip_erase: bool,
+) -> DownloadOptions {
+    let mut options = DownloadOptions::default();
+    options.keep_unwritten_bytes = download_options.restore_unwritten;
+    options.dry_run = probe_options.dry_run();
+    options.do_chip_erase = do_chip_erase;
+    options.disable_double_buffering = download_options.disable_double_buffering;
+    options.verify = download_options.verify;
+
+    options
+}
+
+/// Performs the flash download to several targets at once, with one progress bar per device.
+///
+/// Each session is programmed with the loader with the same index, so that each device can
+/// receive its own patched data. `names` are used to label the progress bars of the sessions.
+/// Returns the result for each session, in the same order as `sessions`.
+pub fn run_parallel_flash_download(
+    sessions: &mut [Session],
+    loaders: &[FlashLoader],
+    names: &[String],
+    path: &Path,
+    download_options: &BinaryDownloadOptions,
+    probe_options: &LoadedProbeOptions,
+    do_chip_erase: bool,
+) -> Vec<Result<(), OperationError>> {
+    let multi_progress = MultiProgress::new();
+    let progress_bars = if download_options.disable_progressbars {
+        vec![]
+    } else {
+        logging::set_progress_bar(multi_progress.clone());
+
+        let style = ProgressStyle::default_bar()
+                    .tick_chars("⠁⠁⠉⠙⠚⠒⠂⠂⠒⠲⠴⠤⠄⠄⠤⠠⠠⠤⠦⠖⠒⠐⠐⠒⠓⠋⠉⠈⠈✔")
+                    .progress_chars("--")
+                    .template("{prefix:.bold} {msg:.green.bold} {spinner} [{elapsed_precise}] [{wide_bar}] {bytes:>8}/{total_bytes:>8} @ {bytes_per_sec:>10}").expect("Error in progress bar creation. This is a bug, please report it.");
+
+        names
+            .iter()
+            .map(|name| {
+                let bar = multi_progress.add(ProgressBar::new(0));
+                bar.set_style(style.clone());
+                bar.set_prefix(name.clone());
+                bar.set_message("  Waiting");
+                bar
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let device_options = |index: usize| {
+        let mut options = build_download_options(download_options, probe_options, do_chip_erase);
+
+        if let Some(bar) = progress_bars.get(index).cloned() {
+            let start = move |bar: &ProgressBar, message: &'static str, length: u64| {
+                init_progress_bar(bar);
+                bar.set_message(message);
+                bar.set_position(0);
+                bar.set_length(length);
+            };
+
+            // The sizes of the fill and erase phases are known once the layout is initialized.
+            let lengths = Cell::new((0, 0));
+            options.progress = Some(FlashProgress::new(move |event| match event {
+                ProgressEvent::Initialized { flash_layout } => {
+                    let total_fill_size: u64 = flash_layout.fills().iter().map(|s| s.size()).sum();
+                    let total_sector_size: u64 =
+                        flash_layout.sectors().iter().map(|s| s.size()).sum();
+                    lengths.set((total_fill_size, total_sector_size));
+                }
+                ProgressEvent::StartedFilling => start(&bar, "Reading flash", lengths.get().0),
+                ProgressEvent::StartedErasing => start(&bar, "      Erasing", lengths.get().1),
+                ProgressEvent::StartedProgramming { length } => {
+                    start(&bar, "  Programming", length)
+                }
+                ProgressEvent::PageFilled { size, .. }
+                | ProgressEvent::SectorErased { size, .. } => bar.inc(size),
+                ProgressEvent::PageProgrammed { size, .. } => bar.inc(size as u64),
+                ProgressEvent::FailedFilling
+                | ProgressEvent::FailedErasing
+                | ProgressEvent::FailedProgramming => bar.abandon_with_message("       Failed"),
+                ProgressEvent::FinishedProgramming => bar.finish_with_message("         Done"),
+                ProgressEvent::FinishedFilling
+                | ProgressEvent::FinishedErasing
+                | ProgressEvent::DiagnosticMessage { .. } => (),
+            }));
+        }
+
+        options
+    };
+
+    let results = FlashLoader::commit_parallel(loaders, sessions, device_options);
+
+    // If we don't do this, the progress bars disappear.
+    logging::clear_progress_bar();
+
+    results
+        .into_iter()
+        .zip(sessions.iter())
+        .map(|(result, session)| {
+            result.map_err(|error| OperationError::FlashingFailed {
+                source: error,
+                target: Box::new(session.target().clone()),
+                target_spec: probe_options.chip(),
+                path: path.to_path_buf(),
+            })
+        })
+        .collect()
+}
+
 /// Builds a new flash loader for the given target and path. This
 /// will check the path for validity and check what pages have to be
 /// flashed etc.
@@ -0,0 +1,388 @@
+//! Output of the data written to the ITM stimulus ports, e.g. by `ITM_SendChar`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use itm::TracePacket;
+use probe_rs::architecture::arm::{component::TraceSink, SwoConfig};
+use probe_rs::Session;
+use serde::{Deserialize, Serialize};
+
+/// The baud rate of the SWO output, if none is configured.
+const DEFAULT_BAUD: u32 = 1_000_000;
+
+/// Where the ITM data is sent to, and read from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ItmSink {
+    /// The SWO pin, read by the probe.
+    #[default]
+    Swo,
+    /// The trace memory (ETB/ETF) of the target, read through the debug port.
+    Memory,
+}
+
+/// The name of a stimulus port, which is shown in front of its lines.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItmPortName {
+    pub port: u8,
+    pub name: String,
+}
+
+impl FromStr for ItmPortName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (port, name) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected a port name in the form PORT=NAME"))?;
+        let port = port.trim().parse()?;
+        if port > 31 {
+            return Err(anyhow!("The stimulus port {port} doesn't exist"));
+        }
+        Ok(Self {
+            port,
+            name: name.to_owned(),
+        })
+    }
+}
+
+/// The configuration of the ITM stimulus port output, via command line options or via a DAP
+/// Client configuration (`launch.json`).
+#[derive(clap::Args, Debug, Clone, Serialize, Deserialize)]
+pub struct ItmConfig {
+    /// Show the data written to the ITM stimulus ports.
+    #[clap(long = "itm")]
+    #[serde(default, rename = "itmEnabled")]
+    pub enabled: bool,
+
+    /// Where the ITM data is sent to, and read from.
+    #[clap(long = "itm-sink", value_enum, default_value_t)]
+    #[serde(default, rename = "itmSink")]
+    pub sink: ItmSink,
+
+    /// The frequency of the clock feeding the TPIU/SWO, usually the core clock, in Hz. Required
+    /// for the SWO sink.
+    #[clap(long = "itm-clock", value_name = "HZ")]
+    #[serde(default, rename = "itmClockFrequency")]
+    pub clock_frequency: Option<u32>,
+
+    /// The baud rate of the SWO output.
+    #[clap(long = "itm-baud", default_value_t = DEFAULT_BAUD)]
+    #[serde(default = "default_baud", rename = "itmBaud")]
+    pub baud: u32,
+
+    /// Show the lines of a stimulus port with a name, e.g. `1=net`. Lines of the other ports are
+    /// shown with their port number, except for port 0.
+    #[clap(long = "itm-port-name", value_name = "PORT=NAME")]
+    #[serde(default, rename = "itmPortNames")]
+    pub port_names: Vec<ItmPortName>,
+}
+
+impl Default for ItmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sink: ItmSink::default(),
+            clock_frequency: None,
+            baud: DEFAULT_BAUD,
+            port_names: Vec::new(),
+        }
+    }
+}
+
+fn default_baud() -> u32 {
+    DEFAULT_BAUD
+}
+
+/// Reads the ITM data of the target, and splits the data of the stimulus ports into lines.
+pub struct ItmStimulus {
+    /// Sends the raw trace data to the decoder thread. Dropping it stops the thread.
+    data: Option<Sender<Vec<u8>>>,
+    packets: Receiver<(u8, Vec<u8>)>,
+    lines: StimulusLines,
+}
+
+impl ItmStimulus {
+    /// Configures the tracing of the core with `core_index` for `config`, and starts decoding.
+    pub fn start(
+        session: &mut Session,
+        core_index: usize,
+        config: &ItmConfig,
+    ) -> anyhow::Result<Self> {
+        let sink = match config.sink {
+            ItmSink::Swo => {
+                let clock_frequency = config.clock_frequency.ok_or_else(|| {
+                    anyhow!("The clock frequency of the TPIU/SWO is required for SWO output")
+                })?;
+                TraceSink::Swo(SwoConfig::new(clock_frequency).set_baud(config.baud))
+            }
+            ItmSink::Memory => TraceSink::TraceMemory,
+        };
+        session.setup_tracing(core_index, sink)?;
+
+        let (data, packets) = spawn_decoder(decode);
+
+        Ok(Self {
+            data: Some(data),
+            packets,
+            lines: StimulusLines::new(&config.port_names),
+        })
+    }
+
+    /// Reads the available trace data, and returns the completed lines of all stimulus ports.
+    pub fn poll(&mut self, session: &mut Session) -> anyhow::Result<Vec<String>> {
+        let data = session.read_trace_data()?;
+        if !data.is_empty() {
+            if let Some(sender) = &self.data {
+                // An error means that the decoder stopped, and there are no more packets.
+                let _ = sender.send(data);
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Ok((port, payload)) = self.packets.try_recv() {
+            lines.extend(self.lines.push(port, &payload));
+        }
+        Ok(lines)
+    }
+
+    /// Stops decoding, and returns the remaining data of all stimulus ports, including
+    /// incomplete lines.
+    pub fn finish(mut self) -> Vec<String> {
+        self.data = None;
+
+        let mut lines = Vec::new();
+        for (port, payload) in self.packets.iter() {
+            lines.extend(self.lines.push(port, &payload));
+        }
+        lines.extend(self.lines.flush());
+        lines
+    }
+}
+
+/// Decodes captured ITM trace data, and returns the data of all stimulus ports as lines, named
+/// after `port_names`.
+pub fn decode_stimulus(data: Vec<u8>, port_names: &[ItmPortName]) -> Vec<String> {
+    let (sender, packets) = spawn_decoder(decode);
+    // An error means that the decoder stopped, and there are no more packets.
+    let _ = sender.send(data);
+    drop(sender);
+
+    let mut lines = StimulusLines::new(port_names);
+    let mut output = Vec::new();
+    for (port, payload) in packets.iter() {
+        output.extend(lines.push(port, &payload));
+    }
+    output.extend(lines.flush());
+    output
+}
+
+/// Runs `decode` in a thread, which reads the trace data sent to the returned sender, and sends
+/// its results to the returned receiver.
+///
+/// The ITM decoder reads from a blocking reader, so that packets split across reads of the trace
+/// data are continued. The data ends when the sender is dropped.
+pub fn spawn_decoder<T: Send + 'static>(
+    decode: impl FnOnce(ChannelReader, Sender<T>) + Send + 'static,
+) -> (Sender<Vec<u8>>, Receiver<T>) {
+    let (data, receiver) = mpsc::channel();
+    let (sender, results) = mpsc::channel();
+    std::thread::spawn(move || decode(ChannelReader::new(receiver), sender));
+    (data, results)
+}
+
+/// Returns the time of `timestamp` since the start of the trace. For timestamps which are only
+/// known to be in a range, this is the end of the range.
+pub fn timestamp_duration(timestamp: &itm::Timestamp) -> Duration {
+    match *timestamp {
+        itm::Timestamp::Sync(time) | itm::Timestamp::AssocEventDelay(time) => time,
+        itm::Timestamp::UnknownDelay { curr, .. }
+        | itm::Timestamp::UnknownAssocEventDelay { curr, .. } => curr,
+    }
+}
+
+/// Decodes the trace data from `reader`, and sends the data of the stimulus ports to `packets`
+/// until the data ends.
+fn decode(reader: ChannelReader, packets: Sender<(u8, Vec<u8>)>) {
+    let decoder = itm::Decoder::new(reader, itm::DecoderOptions { ignore_eof: false });
+    for packet in decoder.singles() {
+        match packet {
+            Ok(TracePacket::Instrumentation { port, payload }) => {
+                if packets.send((port, payload)).is_err() {
+                    return;
+                }
+            }
+            Ok(TracePacket::Overflow) => tracing::warn!("ITM overflow, data was lost"),
+            Ok(_) => {}
+            Err(itm::DecoderError::MalformedPacket(error)) => {
+                tracing::debug!("Malformed ITM packet: {error}")
+            }
+            Err(itm::DecoderError::Io(error)) => {
+                tracing::warn!("Failed to read ITM data: {error}");
+                return;
+            }
+        }
+    }
+}
+
+/// Blocks on a channel of trace data, so that the ITM decoder can continue packets which are
+/// split across reads. The data ends when the channel is closed.
+pub struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            buffer: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.buffer.len() {
+            match self.receiver.recv() {
+                Ok(data) => {
+                    self.buffer = data;
+                    self.position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let length = buf.len().min(self.buffer.len() - self.position);
+        buf[..length].copy_from_slice(&self.buffer[self.position..][..length]);
+        self.position += length;
+        Ok(length)
+    }
+}
+
+/// Splits the data of every stimulus port into lines, so that the output of different ports
+/// isn't interleaved within a line. Invalid UTF-8 is replaced.
+#[derive(Debug, Default)]
+struct StimulusLines {
+    names: HashMap<u8, String>,
+    buffers: BTreeMap<u8, Vec<u8>>,
+}
+
+impl StimulusLines {
+    fn new(port_names: &[ItmPortName]) -> Self {
+        Self {
+            names: port_names
+                .iter()
+                .map(|port_name| (port_name.port, port_name.name.clone()))
+                .collect(),
+            buffers: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the `payload` written to `port`, and returns the lines it completed.
+    fn push(&mut self, port: u8, payload: &[u8]) -> Vec<String> {
+        let buffer = self.buffers.entry(port).or_default();
+        buffer.extend_from_slice(payload);
+
+        let mut lines = Vec::new();
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line = buffer.drain(..=end).collect::<Vec<_>>();
+            lines.push(line);
+        }
+
+        lines
+            .into_iter()
+            .map(|line| self.format(port, &line))
+            .collect()
+    }
+
+    /// Returns the incomplete lines of all ports.
+    fn flush(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.buffers)
+            .into_iter()
+            .filter(|(_, buffer)| !buffer.is_empty())
+            .map(|(port, buffer)| self.format(port, &buffer))
+            .collect()
+    }
+
+    fn format(&self, port: u8, line: &[u8]) -> String {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        match self.names.get(&port) {
+            Some(name) => format!("{name}: {line}"),
+            None if port == 0 => line.to_owned(),
+            None => format!("ITM{port}: {line}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines_are_buffered_per_port() {
+        let mut lines = StimulusLines::new(&["1=net".parse().unwrap()]);
+
+        assert!(lines.push(0, b"hel").is_empty());
+        assert_eq!(lines.push(1, b"up\r\nlink"), ["net: up"]);
+        assert_eq!(lines.push(0, b"lo\nwor"), ["hello"]);
+        assert_eq!(lines.push(2, b"x\n"), ["ITM2: x"]);
+        assert_eq!(lines.flush(), ["wor", "net: link"]);
+    }
+
+    #[test]
+    fn utf8_split_across_packets() {
+        let mut lines = StimulusLines::default();
+
+        let text = "grüße\n".as_bytes();
+        assert!(lines.push(0, &text[..3]).is_empty());
+        assert_eq!(lines.push(0, &text[3..]), ["grüße"]);
+    }
+
+    #[test]
+    fn port_names_are_parsed() {
+        assert_eq!(
+            "3=log".parse::<ItmPortName>().unwrap(),
+            ItmPortName {
+                port: 3,
+                name: "log".to_owned()
+            }
+        );
+        assert!("32=log".parse::<ItmPortName>().is_err());
+        assert!("log".parse::<ItmPortName>().is_err());
+    }
+
+    #[test]
+    fn packets_are_decoded_across_reads() {
+        let (data, packets) = spawn_decoder(decode);
+
+        // A 32-bit write of "abcd" to port 1, split in the middle of the payload, and a byte to
+        // port 0.
+        data.send(vec![0x0B, b'a', b'b']).unwrap();
+        data.send(vec![b'c', b'd', 0x01, b'!']).unwrap();
+        drop(data);
+
+        assert_eq!(
+            packets.iter().collect::<Vec<_>>(),
+            [(1, b"abcd".to_vec()), (0, b"!".to_vec())]
+        );
+    }
+
+    #[test]
+    fn captured_data_is_decoded() {
+        let data = vec![0x01, b'o', 0x01, b'k', 0x01, b'\n', 0x11, b'x'];
+
+        assert_eq!(
+            decode_stimulus(data, &["2=log".parse().unwrap()]),
+            ["ok", "log: x"]
+        );
+    }
+}
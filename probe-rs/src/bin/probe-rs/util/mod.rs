@@ -1,6 +1,10 @@
 pub mod common_options;
+pub mod coverage;
+pub mod disassemble;
 pub mod flash;
+pub mod itm;
 pub mod logging;
+pub mod patch;
 pub mod rtt;
 
 use anyhow::Result;
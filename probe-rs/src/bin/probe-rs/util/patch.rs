@@ -0,0 +1,402 @@
+//! Patching of device specific data, like serial numbers or calibration data, into a loaded image.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use probe_rs::flashing::{FlashError, FlashLoader};
+
+use super::parse_u64;
+
+/// The width of values patched at an absolute address.
+const ADDRESS_VALUE_WIDTH: u64 = 4;
+
+/// Options to patch device specific data into the image before flashing.
+#[derive(Debug, Default, clap::Parser)]
+pub struct PatchOptions {
+    /// Patch an integer value into the image, e.g. `SERIAL=0x1234`.
+    ///
+    /// The value is written in little endian, with the size of the symbol. Use `LABEL@ADDRESS=VALUE`
+    /// to write a 32-bit value at an absolute address instead. Use `SYMBOL=counter:NAME` to write
+    /// the next value of an auto-incrementing counter, stored in the `--patch-counters` file.
+    ///
+    /// Symbols are looked up by their name in the ELF file, so Rust statics need `#[no_mangle]`.
+    #[arg(long, value_name = "SYMBOL=VALUE", help_heading = "PATCHING")]
+    pub patch_symbol: Vec<ValuePatch>,
+
+    /// Patch the contents of a file into the image, e.g. `CALIB@0x0807F000=calib.bin`.
+    ///
+    /// Either a symbol or a `LABEL@ADDRESS` pair can be used as the location.
+    #[arg(long, value_name = "SYMBOL=PATH", help_heading = "PATCHING")]
+    pub patch_file: Vec<FilePatch>,
+
+    /// The file storing the next value of each counter.
+    ///
+    /// A counter is incremented each time it is used, even if flashing fails afterwards,
+    /// so that a value is never used twice.
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = "probe-rs-counters.json",
+        help_heading = "PATCHING"
+    )]
+    pub patch_counters: PathBuf,
+}
+
+/// Where data is patched into the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchLocation {
+    /// At the location of a symbol of the ELF file.
+    Symbol(String),
+    /// At an absolute address, with a label for diagnostics.
+    Address { label: String, address: u64 },
+}
+
+impl FromStr for PatchLocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('@') {
+            Some((label, address)) => Ok(PatchLocation::Address {
+                label: label.to_string(),
+                address: parse_u64(address)
+                    .map_err(|error| format!("Invalid address '{address}': {error}"))?,
+            }),
+            None if s.is_empty() => Err("The location must not be empty".to_string()),
+            None => Ok(PatchLocation::Symbol(s.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for PatchLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchLocation::Symbol(name) => write!(f, "{name}"),
+            PatchLocation::Address { label, address } => write!(f, "{label}@{address:#010x}"),
+        }
+    }
+}
+
+/// The value of a [`ValuePatch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchValue {
+    /// A fixed value.
+    Integer(u64),
+    /// The next value of the named counter.
+    Counter(String),
+}
+
+/// An integer value patched into the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValuePatch {
+    pub location: PatchLocation,
+    pub value: PatchValue,
+}
+
+impl FromStr for ValuePatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, value) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected 'SYMBOL=VALUE', got '{s}'"))?;
+
+        let value = match value.strip_prefix("counter:") {
+            Some(counter) => PatchValue::Counter(counter.to_string()),
+            None => PatchValue::Integer(
+                parse_u64(value).map_err(|error| format!("Invalid value '{value}': {error}"))?,
+            ),
+        };
+
+        Ok(Self {
+            location: location.parse()?,
+            value,
+        })
+    }
+}
+
+/// The contents of a file patched into the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub location: PatchLocation,
+    pub path: PathBuf,
+}
+
+impl FromStr for FilePatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (location, path) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected 'SYMBOL=PATH', got '{s}'"))?;
+
+        Ok(Self {
+            location: location.parse()?,
+            path: path.into(),
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PatchError {
+    #[error("Failed to patch '{location}'.")]
+    Flash {
+        #[source]
+        source: FlashError,
+        location: String,
+    },
+    #[error("The symbol '{location}' has a size of 0 bytes. Use LABEL@ADDRESS to patch a value at its address instead.")]
+    ZeroSizeSymbol { location: String },
+    #[error("The value {value:#x} does not fit into the {width} bytes of '{location}'.")]
+    ValueTooLarge {
+        value: u64,
+        width: u64,
+        location: String,
+    },
+    #[error("Failed to read the patch file '{path}'.")]
+    ReadFile {
+        #[source]
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[error("Failed to access the counters file '{path}'.")]
+    Counters {
+        #[source]
+        source: anyhow::Error,
+        path: PathBuf,
+    },
+}
+
+impl PatchOptions {
+    /// Applies all patches to `loader`.
+    ///
+    /// Each counter which is used is incremented in the counters file.
+    pub fn apply(&self, loader: &mut FlashLoader) -> Result<(), PatchError> {
+        if self.patch_symbol.is_empty() && self.patch_file.is_empty() {
+            return Ok(());
+        }
+
+        let uses_counters = self
+            .patch_symbol
+            .iter()
+            .any(|patch| matches!(patch.value, PatchValue::Counter(_)));
+        let mut counters = if uses_counters {
+            Counters::load(&self.patch_counters)?
+        } else {
+            Counters::default()
+        };
+
+        for patch in &self.patch_symbol {
+            let value = match &patch.value {
+                PatchValue::Integer(value) => *value,
+                PatchValue::Counter(name) => counters.next(name),
+            };
+
+            let width = match &patch.location {
+                PatchLocation::Symbol(name) => loader
+                    .symbol_range(name)
+                    .map(|range| range.end - range.start)
+                    .ok_or_else(|| PatchError::Flash {
+                        source: FlashError::SymbolNotFound(name.clone()),
+                        location: patch.location.to_string(),
+                    })?,
+                PatchLocation::Address { .. } => ADDRESS_VALUE_WIDTH,
+            };
+            if width == 0 {
+                return Err(PatchError::ZeroSizeSymbol {
+                    location: patch.location.to_string(),
+                });
+            }
+
+            let data = encode_value(value, width).ok_or_else(|| PatchError::ValueTooLarge {
+                value,
+                width,
+                location: patch.location.to_string(),
+            })?;
+
+            tracing::info!("Patching {} with {:#x}", patch.location, value);
+            patch_location(loader, &patch.location, &data)?;
+        }
+
+        for patch in &self.patch_file {
+            let data = std::fs::read(&patch.path).map_err(|source| PatchError::ReadFile {
+                source,
+                path: patch.path.clone(),
+            })?;
+
+            tracing::info!(
+                "Patching {} with {} bytes from {}",
+                patch.location,
+                data.len(),
+                patch.path.display()
+            );
+            patch_location(loader, &patch.location, &data)?;
+        }
+
+        // Only store the counters once all patches are applied successfully.
+        if uses_counters {
+            counters.store(&self.patch_counters)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn patch_location(
+    loader: &mut FlashLoader,
+    location: &PatchLocation,
+    data: &[u8],
+) -> Result<(), PatchError> {
+    match location {
+        PatchLocation::Symbol(name) => loader.patch_symbol(name, data),
+        PatchLocation::Address { address, .. } => loader.patch_data(*address, data),
+    }
+    .map_err(|source| PatchError::Flash {
+        source,
+        location: location.to_string(),
+    })
+}
+
+/// Encodes `value` in little endian with `width` bytes.
+///
+/// Returns `None` if the value does not fit.
+fn encode_value(value: u64, width: u64) -> Option<Vec<u8>> {
+    if width < 8 && value >> (width * 8) != 0 {
+        return None;
+    }
+
+    let mut data = value.to_le_bytes().to_vec();
+    data.resize(width as usize, 0);
+    Some(data)
+}
+
+/// The next value of each counter, stored in a JSON file.
+#[derive(Default)]
+struct Counters(BTreeMap<String, u64>);
+
+impl Counters {
+    fn load(path: &Path) -> Result<Self, PatchError> {
+        let counters = match File::open(path) {
+            Ok(file) => serde_json::from_reader(file).map_err(|error| PatchError::Counters {
+                source: error.into(),
+                path: path.to_path_buf(),
+            })?,
+            // Counters start at zero, if the file does not exist yet.
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => {
+                return Err(PatchError::Counters {
+                    source: error.into(),
+                    path: path.to_path_buf(),
+                })
+            }
+        };
+
+        Ok(Self(counters))
+    }
+
+    /// Returns the next value of the counter, and increments it.
+    fn next(&mut self, name: &str) -> u64 {
+        let counter = self.0.entry(name.to_string()).or_insert(0);
+        let value = *counter;
+        *counter += 1;
+
+        value
+    }
+
+    fn store(&self, path: &Path) -> Result<(), PatchError> {
+        let contents =
+            serde_json::to_string_pretty(&self.0).map_err(|error| PatchError::Counters {
+                source: error.into(),
+                path: path.to_path_buf(),
+            })?;
+
+        std::fs::write(path, contents).map_err(|error| PatchError::Counters {
+            source: error.into(),
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_value_patch() {
+        assert_eq!(
+            "SERIAL=0x1234".parse::<ValuePatch>(),
+            Ok(ValuePatch {
+                location: PatchLocation::Symbol("SERIAL".to_string()),
+                value: PatchValue::Integer(0x1234),
+            })
+        );
+
+        assert_eq!(
+            "SERIAL@0x0807F000=counter:serial".parse::<ValuePatch>(),
+            Ok(ValuePatch {
+                location: PatchLocation::Address {
+                    label: "SERIAL".to_string(),
+                    address: 0x0807_F000,
+                },
+                value: PatchValue::Counter("serial".to_string()),
+            })
+        );
+
+        assert!("SERIAL".parse::<ValuePatch>().is_err());
+        assert!("SERIAL=abc".parse::<ValuePatch>().is_err());
+    }
+
+    #[test]
+    fn parse_file_patch() {
+        assert_eq!(
+            "CALIB@0x0807F000=calib.bin".parse::<FilePatch>(),
+            Ok(FilePatch {
+                location: PatchLocation::Address {
+                    label: "CALIB".to_string(),
+                    address: 0x0807_F000,
+                },
+                path: "calib.bin".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn encode_values() {
+        assert_eq!(encode_value(0x1234, 2), Some(vec![0x34, 0x12]));
+        assert_eq!(encode_value(0x1234, 1), None);
+        assert_eq!(
+            encode_value(0x1234, 12),
+            Some(vec![0x34, 0x12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        );
+        assert_eq!(encode_value(0x1234, 0), None);
+    }
+
+    #[test]
+    fn counters_are_not_stored_if_a_patch_fails() {
+        use probe_rs::config::{MemoryRegion, RamRegion, TargetDescriptionSource};
+
+        let counters =
+            std::env::temp_dir().join(format!("probe-rs-counters-{}.json", std::process::id()));
+        let options = PatchOptions {
+            patch_symbol: vec!["SERIAL@0x20000000=counter:serial".parse().unwrap()],
+            patch_file: vec!["CALIB@0x20000004=does-not-exist.bin".parse().unwrap()],
+            patch_counters: counters.clone(),
+        };
+        let mut loader = FlashLoader::new(
+            vec![MemoryRegion::Ram(RamRegion {
+                name: None,
+                range: 0x2000_0000..0x2000_1000,
+                is_boot_memory: false,
+                cores: vec![],
+            })],
+            TargetDescriptionSource::BuiltIn,
+        );
+
+        assert!(matches!(
+            options.apply(&mut loader),
+            Err(PatchError::ReadFile { .. })
+        ));
+        assert!(!counters.exists());
+    }
+}
@@ -1,12 +1,14 @@
 use anyhow::{anyhow, Result};
 use defmt_decoder::DecodeError;
 pub use probe_rs::rtt::ChannelMode;
-use probe_rs::rtt::{DownChannel, Rtt, ScanRegion, UpChannel};
+use probe_rs::rtt::{DownChannel, Rtt, RttPoller, ScanRegion, UpChannel};
 use probe_rs::Core;
 use probe_rs_target::MemoryRegion;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
 use std::{
     fmt,
     fmt::Write,
@@ -14,9 +16,28 @@ use std::{
     io::{Read, Seek},
     path::Path,
     str::FromStr,
+    time::{Duration, Instant},
 };
 use time::{OffsetDateTime, UtcOffset};
 
+pub mod capture;
+pub mod forward;
+pub mod pipe;
+pub mod structured;
+pub mod tcp;
+
+use capture::CaptureWriter;
+use forward::RttForwarder;
+use pipe::RttPipe;
+use structured::{JsonLine, LogFilter, LogLevel, OutputFormat};
+use tcp::RttTcpServer;
+
+/// Shown in the output after re-attaching to RTT, see [`RttActiveTarget::reattach_if_reset`].
+pub const REATTACHED_MARKER: &str = "--- target reset, RTT re-attached ---";
+
+/// How often [`RttActiveTarget::reattach_if_reset`] checks the control block.
+const CONTROL_BLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
 /// Try to find the RTT control block in the ELF file and attach to it.
 ///
 /// This function can return `Ok(None)` to indicate that RTT is not available on the target.
@@ -35,7 +56,7 @@ pub fn attach_to_rtt(
 
     if let Ok(mut file) = File::open(elf_file) {
         if let Some(address) = RttActiveTarget::get_rtt_symbol(&mut file) {
-            exact_rtt_region = ScanRegion::Exact(address as u32);
+            exact_rtt_region = ScanRegion::Exact(address);
             rtt_region = &exact_rtt_region;
         }
     }
@@ -104,6 +125,28 @@ pub struct RttConfig {
     pub channels: Vec<RttChannelConfig>,
 }
 
+impl RttConfig {
+    /// Returns the configuration of the channel `number`, which is added if there is none yet.
+    pub fn channel_mut(&mut self, number: usize) -> &mut RttChannelConfig {
+        let index = match self
+            .channels
+            .iter()
+            .position(|channel| channel.channel_number == Some(number))
+        {
+            Some(index) => index,
+            None => {
+                self.channels.push(RttChannelConfig {
+                    channel_number: Some(number),
+                    ..Default::default()
+                });
+                self.channels.len() - 1
+            }
+        };
+
+        &mut self.channels[index]
+    }
+}
+
 /// The User specified configuration for each active RTT Channel. The configuration is passed via a
 /// DAP Client configuration (`launch.json`). If no configuration is specified, the defaults will be
 /// `Dataformat::String` and `show_timestamps=false`.
@@ -122,6 +165,15 @@ pub struct RttChannelConfig {
     #[serde(default = "default_include_location")]
     // Control the inclusion of source location information for DataFormat::Defmt.
     pub show_location: bool,
+    #[structopt(skip)]
+    #[serde(default)]
+    // Listen for TCP clients on this address, which exchange data with the channel.
+    pub tcp_server: Option<SocketAddr>,
+    #[structopt(skip)]
+    #[serde(default)]
+    // Spawn this command with the data of the up channel on its stdin, and its stdout written to
+    // the down channel.
+    pub pipe: Option<String>,
 }
 
 /// This is the primary interface through which RTT channel data is read and written. Every actual
@@ -130,6 +182,11 @@ pub struct RttChannelConfig {
 pub struct RttActiveChannel {
     pub up_channel: Option<UpChannel>,
     pub down_channel: Option<DownChannel>,
+    /// Numbers of the channels, kept while they are detached after the target re-initialized RTT.
+    up_number: Option<usize>,
+    down_number: Option<usize>,
+    /// The mode set with [`RttActiveChannel::set_mode`], which is set again after re-attaching.
+    mode: Option<ChannelMode>,
     pub channel_name: String,
     pub data_format: DataFormat,
     /// Data that will be written to the down_channel (host to target)
@@ -137,6 +194,13 @@ pub struct RttActiveChannel {
     rtt_buffer: RttBuffer,
     show_timestamps: bool,
     show_location: bool,
+    /// Exchanges the data of the channel with its TCP server and the process it is piped through.
+    forwarder: RttForwarder,
+    /// The number shown in structured output, also when the channel is not attached.
+    output_number: usize,
+    output_format: OutputFormat,
+    /// Hides messages of string and defmt channels which don't pass the filter.
+    log_filter: Option<LogFilter>,
 
     /// UTC offset used for creating timestamps
     ///
@@ -194,7 +258,7 @@ impl RttActiveChannel {
                     .as_ref()
                     .and_then(|down| down.name().map(Into::into))
             })
-            .or_else(|| full_config.clone().channel_name)
+            .or_else(|| full_config.channel_name.clone())
             .unwrap_or_else(|| {
                 format!(
                     "Unnamed {:?} RTT channel - {}",
@@ -202,33 +266,109 @@ impl RttActiveChannel {
                     full_config.channel_number.unwrap_or(0)
                 )
             });
+        let up_number = up_channel.as_ref().map(|up| up.number());
         Self {
+            up_number,
+            down_number: down_channel.as_ref().map(|down| down.number()),
             up_channel,
             down_channel,
+            mode: None,
             channel_name: name,
             data_format,
             _input_data: String::new(),
             rtt_buffer: RttBuffer::new(buffer_size),
             show_timestamps: full_config.show_timestamps,
             show_location,
+            forwarder: RttForwarder::default(),
+            output_number: up_number.or(full_config.channel_number).unwrap_or(0),
+            output_format: OutputFormat::Text,
+            log_filter: None,
             timestamp_offset,
         }
     }
 
     /// Returns the number of the `UpChannel`.
     pub fn number(&self) -> Option<usize> {
-        self.up_channel.as_ref().map(|uc| uc.number())
+        self.up_number
+    }
+
+    /// Sets the mode of the `UpChannel`, and remembers it to set it again after re-attaching.
+    pub fn set_mode(
+        &mut self,
+        core: &mut Core,
+        mode: ChannelMode,
+    ) -> Result<(), probe_rs::rtt::Error> {
+        self.mode = Some(mode);
+        if let Some(channel) = self.up_channel.as_ref() {
+            channel.set_mode(core, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Sets how the messages of string and defmt channels are written.
+    pub fn set_output_format(&mut self, output_format: OutputFormat) {
+        self.output_format = output_format;
+    }
+
+    /// Sets the filter for the messages of string and defmt channels, `None` to show all.
+    pub fn set_log_filter(&mut self, log_filter: Option<LogFilter>) {
+        self.log_filter = log_filter;
+    }
+
+    /// Replaces the channels with the ones of the same numbers in `rtt`, after the target
+    /// re-initialized RTT.
+    fn reattach(&mut self, core: &mut Core, rtt: &mut Rtt) {
+        self.up_channel = self
+            .up_number
+            .and_then(|number| rtt.up_channels.take(number));
+        self.down_channel = self
+            .down_number
+            .and_then(|number| rtt.down_channels.take(number));
+
+        if let Some(up_channel) = self.up_channel.as_ref() {
+            self.rtt_buffer = RttBuffer::new(up_channel.buffer_size());
+        }
+        self.restore_mode(core);
+    }
+
+    /// Returns whether the target initialized the channels again, e.g. after a watchdog reset,
+    /// see [`UpChannel::was_reinitialized`].
+    fn was_reinitialized(&self, core: &mut Core) -> bool {
+        let up = self
+            .up_channel
+            .as_ref()
+            .map(|channel| channel.was_reinitialized(core));
+        let down = self
+            .down_channel
+            .as_ref()
+            .map(|channel| channel.was_reinitialized(core));
+
+        matches!(up, Some(Ok(true))) || matches!(down, Some(Ok(true)))
+    }
+
+    /// Sets the mode set with [`RttActiveChannel::set_mode`] again.
+    fn restore_mode(&self, core: &mut Core) {
+        if let (Some(up_channel), Some(mode)) = (self.up_channel.as_ref(), self.mode) {
+            if let Err(err) = up_channel.set_mode(core, mode) {
+                tracing::error!("\nError setting the mode of RTT channel: {}", err);
+            }
+        }
     }
 
     /// Polls the RTT target for new data on the channel represented by `self`.
     /// Processes all the new data into the channel internal buffer and returns the number of bytes that was read.
     pub fn poll_rtt(&mut self, core: &mut Core) -> Option<usize> {
+        self.poll_forwarder(core);
+
         if let Some(channel) = self.up_channel.as_mut() {
             // Retry loop, in case the probe is temporarily unavailable, e.g. user pressed the `reset` button.
             for _loop_count in 0..10 {
                 match channel.read(core, self.rtt_buffer.0.as_mut()) {
                     Ok(0) => return None,
-                    Ok(count) => return Some(count),
+                    Ok(count) => {
+                        self.forwarder.send(&self.rtt_buffer.0[..count]);
+                        return Some(count);
+                    }
                     Err(probe_rs::rtt::Error::Probe(_)) => {
                         std::thread::sleep(std::time::Duration::from_millis(50));
                     }
@@ -242,6 +382,16 @@ impl RttActiveChannel {
         None
     }
 
+    /// Writes the data received by the TCP server and the process the channel is piped through,
+    /// if any, to the down channel.
+    fn poll_forwarder(&mut self, core: &mut Core) {
+        if let Some(channel) = self.down_channel.as_mut() {
+            if let Err(err) = self.forwarder.write_to(core, channel) {
+                tracing::error!("\nError writing to RTT: {}", err);
+            }
+        }
+    }
+
     /// Retrieves available data from the channel and if available, returns `Some(channel_number:String, formatted_data:String)`.
     /// If no data is available, or we encounter a recoverable error, it returns `None` value fore `formatted_data`.
     /// Non-recoverable errors are propagated to the caller.
@@ -254,24 +404,35 @@ impl RttActiveChannel {
             .map(|bytes_read| {
                 Ok((
                     self.number().unwrap_or(0).to_string(), // If the Channel doesn't have a number, then send the output to channel 0
-                    {
-                        let mut formatted_data = String::new();
-                        match self.data_format {
-                            DataFormat::String => self.get_string(bytes_read, &mut formatted_data),
-                            DataFormat::BinaryLE => {
-                                self.get_binary_le(bytes_read, &mut formatted_data)
-                            }
-                            DataFormat::Defmt => {
-                                self.get_defmt(bytes_read, &mut formatted_data, defmt_state)?
-                            }
-                        };
-                        formatted_data
-                    },
+                    self.format_data(
+                        &self.rtt_buffer.0[..bytes_read],
+                        OffsetDateTime::now_utc(),
+                        defmt_state,
+                    )?,
                 ))
             })
             .transpose()
     }
 
+    /// Formats `bytes` according to the data format of the channel. `timestamp` is the time at
+    /// which the data was received, and is shown if timestamps are enabled for the channel.
+    pub fn format_data(
+        &self,
+        bytes: &[u8],
+        timestamp: OffsetDateTime,
+        defmt_state: Option<&DefmtState>,
+    ) -> Result<String> {
+        let mut formatted_data = String::new();
+        match self.data_format {
+            DataFormat::String => self.get_string(bytes, timestamp, &mut formatted_data),
+            DataFormat::BinaryLE => self.get_binary_le(bytes, &mut formatted_data),
+            DataFormat::Defmt => {
+                self.get_defmt(bytes, timestamp, &mut formatted_data, defmt_state)?
+            }
+        };
+        Ok(formatted_data)
+    }
+
     pub fn _push_rtt(&mut self, core: &mut Core) {
         if let Some(down_channel) = self.down_channel.as_mut() {
             self._input_data += "\n";
@@ -282,14 +443,28 @@ impl RttActiveChannel {
         }
     }
 
-    fn get_string(&self, bytes_read: usize, formatted_data: &mut String) {
-        let incoming = String::from_utf8_lossy(&self.rtt_buffer.0[..bytes_read]).to_string();
+    fn get_string(&self, bytes: &[u8], timestamp: OffsetDateTime, formatted_data: &mut String) {
+        let incoming = String::from_utf8_lossy(bytes).to_string();
         for line in incoming.split_terminator('\n') {
+            let level = LogLevel::detect(line);
+            if !self.allows(level, None) {
+                continue;
+            }
+
+            if self.output_format == OutputFormat::JsonLines {
+                let json = JsonLine {
+                    level,
+                    ..JsonLine::new(timestamp, self.output_number, line.trim_end_matches('\r'))
+                };
+                writeln!(formatted_data, "{json}").expect("Writing to String cannot fail");
+                continue;
+            }
+
             if self.show_timestamps {
                 write!(
                     formatted_data,
                     "{} :",
-                    OffsetDateTime::now_utc().to_offset(self.timestamp_offset)
+                    timestamp.to_offset(self.timestamp_offset)
                 )
                 .expect("Writing to String cannot fail");
             }
@@ -297,8 +472,15 @@ impl RttActiveChannel {
         }
     }
 
-    fn get_binary_le(&self, bytes_read: usize, formatted_data: &mut String) {
-        for element in &self.rtt_buffer.0[..bytes_read] {
+    fn allows(&self, level: Option<LogLevel>, module: Option<&str>) -> bool {
+        match self.log_filter.as_ref() {
+            Some(filter) => filter.allows(level, module),
+            None => true,
+        }
+    }
+
+    fn get_binary_le(&self, bytes: &[u8], formatted_data: &mut String) {
+        for element in bytes {
             // Width of 4 allows 0xFF to be printed.
             write!(formatted_data, "{element:#04x}").expect("Writing to String cannot fail");
         }
@@ -306,7 +488,8 @@ impl RttActiveChannel {
 
     fn get_defmt(
         &self,
-        bytes_read: usize,
+        bytes: &[u8],
+        timestamp: OffsetDateTime,
         formatted_data: &mut String,
         defmt_state: Option<&DefmtState>,
     ) -> anyhow::Result<()> {
@@ -315,13 +498,21 @@ impl RttActiveChannel {
                 table,
                 locs,
                 formatter,
+                formats,
             }) => {
                 let mut stream_decoder = table.new_stream_decoder();
-                stream_decoder.received(&self.rtt_buffer.0[..bytes_read]);
+                stream_decoder.received(bytes);
                 loop {
                     match stream_decoder.decode() {
                         Ok(frame) => {
                             let loc = locs.as_ref().and_then(|locs| locs.get(&frame.index()));
+                            let level = frame
+                                .level()
+                                .and_then(|level| level.as_str().parse::<LogLevel>().ok());
+                            if !self.allows(level, loc.map(|loc| loc.module.as_str())) {
+                                continue;
+                            }
+
                             let (file, line, module) = if let Some(loc) = loc {
                                 let relpath = loc
                                     .file
@@ -342,6 +533,29 @@ impl RttActiveChannel {
                                     None,
                                 )
                             };
+
+                            if self.output_format == OutputFormat::JsonLines {
+                                let message = frame.display_message().to_string();
+                                let json = JsonLine {
+                                    target_timestamp: frame
+                                        .display_timestamp()
+                                        .map(|timestamp| timestamp.to_string()),
+                                    level,
+                                    module_path: module,
+                                    // Without a location, `file` only holds an error message.
+                                    file: loc.and(file.as_deref()),
+                                    line,
+                                    defmt_index: Some(frame.index()),
+                                    raw_args: formats.get(&frame.index()).and_then(|format| {
+                                        structured::split_defmt_args(format, &message)
+                                    }),
+                                    ..JsonLine::new(timestamp, self.output_number, &message)
+                                };
+                                writeln!(formatted_data, "{json}")
+                                    .expect("Writing to String cannot fail");
+                                continue;
+                            }
+
                             let s = formatter.format_frame(frame, file.as_deref(), line, module);
                             writeln!(formatted_data, "{s}").expect("Writing to String cannot fail");
                             continue;
@@ -376,12 +590,25 @@ impl RttActiveChannel {
 pub struct RttActiveTarget {
     pub active_channels: Vec<RttActiveChannel>,
     pub defmt_state: Option<DefmtState>,
+    /// Receives the raw data of all up channels, see [`RttActiveTarget::start_capture`].
+    capture: Option<CaptureWriter<BufWriter<File>>>,
+    /// Reads the up channels of all active channels at once.
+    poller: RttPoller,
+    /// The control block, after all channels were moved into `active_channels`. Used to detect
+    /// when the target re-initialized RTT, see [`RttActiveTarget::reattach_if_reset`].
+    control_block: Rtt,
+    /// When the control block was checked last, or `None` to check it on the next call.
+    last_control_block_check: Option<Instant>,
+    /// Set when the control block became invalid, until re-attaching succeeds.
+    detached: bool,
 }
 
 pub struct DefmtState {
     table: defmt_decoder::Table,
     locs: Option<defmt_decoder::Locations>,
     formatter: defmt_decoder::log::format::Formatter,
+    /// The format strings of the log messages by their index, see [`read_defmt_formats`].
+    formats: HashMap<u64, String>,
 }
 
 impl fmt::Debug for DefmtState {
@@ -390,33 +617,155 @@ impl fmt::Debug for DefmtState {
     }
 }
 
+impl DefmtState {
+    /// Loads the defmt table and locations from the contents of an ELF file.
+    ///
+    /// Returns `Ok(None)` if the ELF file doesn't contain a defmt table.
+    pub fn try_from_bytes(
+        elf: &[u8],
+        show_location: bool,
+        log_format: Option<&str>,
+    ) -> Result<Option<Self>> {
+        let Some(table) = defmt_decoder::Table::parse(elf)? else {
+            tracing::warn!("No `Table` definition in DWARF info; compile your program with `debug = 2` to enable location info.");
+            return Ok(None);
+        };
+
+        let has_timestamp = table.has_timestamp();
+
+        // Format options:
+        // 1. Custom format
+        // 2. Default with timestamp with location
+        // 3. Default with timestamp without location
+        // 4. Default without timestamp with location
+        // 5. Default without timestamp without location
+        let format = log_format.unwrap_or(match (show_location, has_timestamp) {
+            (true, true) => "{t} {L} {s}\n└─ {m} @ {F}:{l}",
+            (true, false) => "{L} {s}\n└─ {m} @ {F}:{l}",
+            (false, true) => "{t} {L} {s}",
+            (false, false) => "{L} {s}",
+        });
+        let format = defmt_decoder::log::format::FormatterConfig::custom(format);
+        let formatter = defmt_decoder::log::format::Formatter::new(format);
+
+        let locs = {
+            let locs = table.get_locations(elf)?;
+
+            if !table.is_empty() && locs.is_empty() {
+                tracing::warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info.");
+                None
+            } else if table.indices().all(|idx| locs.contains_key(&(idx as u64))) {
+                Some(locs)
+            } else {
+                tracing::warn!("Location info is incomplete; it will be omitted from the output.");
+                None
+            }
+        };
+
+        Ok(Some(Self {
+            table,
+            locs,
+            formatter,
+            formats: read_defmt_formats(elf),
+        }))
+    }
+}
+
+/// Reads the format strings of the defmt log messages from the symbols in the `.defmt` section of
+/// an ELF file. The address of a symbol is the index of its message.
+///
+/// `defmt_decoder` doesn't expose the arguments of decoded frames, so these are used to split the
+/// formatted messages into their arguments, see [`structured::split_defmt_args`].
+fn read_defmt_formats(elf: &[u8]) -> HashMap<u64, String> {
+    /// The JSON encoded name of a defmt symbol.
+    #[derive(Deserialize)]
+    struct DefmtSymbol {
+        data: String,
+    }
+
+    let Ok(binary) = goblin::elf::Elf::parse(elf) else {
+        return HashMap::new();
+    };
+    let Some(defmt_section) = binary
+        .section_headers
+        .iter()
+        .position(|section| binary.shdr_strtab.get_at(section.sh_name) == Some(".defmt"))
+    else {
+        return HashMap::new();
+    };
+
+    binary
+        .syms
+        .iter()
+        .filter(|sym| sym.st_shndx == defmt_section)
+        .filter_map(|sym| {
+            let name = binary.strtab.get_at(sym.st_name)?;
+            let symbol = serde_json::from_str::<DefmtSymbol>(name).ok()?;
+            Some((sym.st_value, symbol.data))
+        })
+        .collect()
+}
+
 impl RttActiveTarget {
     /// RttActiveTarget collects references to all the `RttActiveChannel`s, for latter polling/pushing of data.
     pub fn new(
-        rtt: probe_rs::rtt::Rtt,
+        mut rtt: probe_rs::rtt::Rtt,
         elf_file: &Path,
         rtt_config: &RttConfig,
         timestamp_offset: UtcOffset,
         log_format: Option<&str>,
     ) -> Result<Self> {
         let mut active_channels = Vec::new();
+        let up_channels = std::mem::take(&mut rtt.up_channels);
         // For each channel configured in the RTT Control Block (`Rtt`), check if there are additional user configuration in a `RttChannelConfig`. If not, apply defaults.
-        for channel in rtt.up_channels.into_iter() {
+        for channel in up_channels.into_iter() {
             let number = channel.number();
             let channel_config = rtt_config
                 .channels
                 .clone()
                 .into_iter()
                 .find(|channel| channel.channel_number == Some(number));
-            active_channels.push(RttActiveChannel::new(
+
+            // A TCP server or pipe exchanges data in both directions, so the down channel with the
+            // same number belongs to it.
+            let tcp_server = channel_config.as_ref().and_then(|config| config.tcp_server);
+            let pipe = channel_config
+                .as_ref()
+                .and_then(|config| config.pipe.clone());
+            let down_channel = (tcp_server.is_some() || pipe.is_some())
+                .then(|| rtt.down_channels.take(number))
+                .flatten();
+
+            let forward_output = down_channel.is_some();
+            let mut active_channel = RttActiveChannel::new(
                 Some(channel),
-                None,
+                down_channel,
                 channel_config,
                 timestamp_offset,
-            ));
+            );
+            if let Some(address) = tcp_server {
+                active_channel
+                    .forwarder
+                    .add(RttTcpServer::bind(address).map_err(|err| {
+                        anyhow!("Failed to start the RTT server on {}: {}", address, err)
+                    })?);
+            }
+            if let Some(command) = pipe {
+                active_channel
+                    .forwarder
+                    .add(RttPipe::spawn(&command, forward_output).map_err(|err| {
+                        anyhow!(
+                            "Failed to start '{}' for RTT channel {}: {}",
+                            command,
+                            number,
+                            err
+                        )
+                    })?);
+            }
+            active_channels.push(active_channel);
         }
 
-        for channel in rtt.down_channels.into_iter() {
+        for channel in std::mem::take(&mut rtt.down_channels).into_iter() {
             let number = channel.number();
             let channel_config = rtt_config
                 .channels
@@ -454,48 +803,7 @@ impl RttActiveTarget {
                 .expect("`active_channels` is not empty")
                 .show_location;
 
-            if let Some(table) = defmt_decoder::Table::parse(&elf)? {
-                let has_timestamp = table.has_timestamp();
-
-                // Format options:
-                // 1. Custom format
-                // 2. Default with timestamp with location
-                // 3. Default with timestamp without location
-                // 4. Default without timestamp with location
-                // 5. Default without timestamp without location
-                let format = log_format.unwrap_or(match (show_location, has_timestamp) {
-                    (true, true) => "{t} {L} {s}\n└─ {m} @ {F}:{l}",
-                    (true, false) => "{L} {s}\n└─ {m} @ {F}:{l}",
-                    (false, true) => "{t} {L} {s}",
-                    (false, false) => "{L} {s}",
-                });
-                let format = defmt_decoder::log::format::FormatterConfig::custom(format);
-                let formatter = defmt_decoder::log::format::Formatter::new(format);
-
-                let locs = {
-                    let locs = table.get_locations(&elf)?;
-
-                    if !table.is_empty() && locs.is_empty() {
-                        tracing::warn!("Insufficient DWARF info; compile your program with `debug = 2` to enable location info.");
-                        None
-                    } else if table.indices().all(|idx| locs.contains_key(&(idx as u64))) {
-                        Some(locs)
-                    } else {
-                        tracing::warn!(
-                            "Location info is incomplete; it will be omitted from the output."
-                        );
-                        None
-                    }
-                };
-                Some(DefmtState {
-                    table,
-                    locs,
-                    formatter,
-                })
-            } else {
-                tracing::warn!("No `Table` definition in DWARF info; compile your program with `debug = 2` to enable location info.");
-                None
-            }
+            DefmtState::try_from_bytes(&elf, show_location, log_format)?
         } else {
             None
         };
@@ -503,9 +811,48 @@ impl RttActiveTarget {
         Ok(Self {
             active_channels,
             defmt_state,
+            capture: None,
+            poller: RttPoller::new(),
+            control_block: rtt,
+            last_control_block_check: Some(Instant::now()),
+            detached: false,
         })
     }
 
+    /// Starts writing the raw data of all up channels to `capture`, so that it can be decoded
+    /// later with `probe-rs rtt-decode`.
+    ///
+    /// Only data read by [`RttActiveTarget::poll_rtt_fallible`] is captured.
+    pub fn start_capture(&mut self, mut capture: CaptureWriter<BufWriter<File>>) -> Result<()> {
+        for channel in &self.active_channels {
+            if let Some(number) = channel.number() {
+                capture.write_channel(number, &channel.channel_name, channel.data_format)?;
+            }
+        }
+        capture.flush()?;
+
+        self.capture = Some(capture);
+        Ok(())
+    }
+
+    /// Writes `bytes` to the down channel `number`, and returns how many of them fit into the
+    /// channel.
+    pub fn write_down_channel(
+        &mut self,
+        core: &mut Core,
+        number: usize,
+        bytes: &[u8],
+    ) -> Result<usize> {
+        let channel = self
+            .active_channels
+            .iter_mut()
+            .filter_map(|channel| channel.down_channel.as_mut())
+            .find(|channel| channel.number() == number)
+            .ok_or_else(|| anyhow!("There is no RTT down channel {}", number))?;
+
+        Ok(channel.write(core, bytes)?)
+    }
+
     pub fn get_rtt_symbol<T: Read + Seek>(file: &mut T) -> Option<u64> {
         let mut buffer = Vec::new();
         if file.read_to_end(&mut buffer).is_ok() {
@@ -532,16 +879,131 @@ impl RttActiveTarget {
         &mut self,
         core: &mut Core,
     ) -> Result<HashMap<String, String>, anyhow::Error> {
+        for channel in self.active_channels.iter_mut() {
+            channel.poll_forwarder(core);
+        }
+
+        let up_channels = self
+            .active_channels
+            .iter()
+            .filter_map(|channel| channel.up_channel.as_ref())
+            .collect::<Vec<_>>();
+        let mut received = Vec::new();
+        // Retry loop, in case the probe is temporarily unavailable, e.g. user pressed the `reset` button.
+        for _loop_count in 0..10 {
+            let result = self.poller.poll(core, &up_channels, |channel, bytes| {
+                received.push((channel.number(), bytes.to_vec()))
+            });
+            match result {
+                Ok(_) => break,
+                Err(probe_rs::rtt::Error::Probe(_)) => {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
+                Err(probe_rs::rtt::Error::ControlBlockCorrupted(err)) => {
+                    // Probably the target re-initialized RTT, check it on the next poll.
+                    tracing::debug!("RTT control block corrupted: {}", err);
+                    self.last_control_block_check = None;
+                    break;
+                }
+                Err(err) => {
+                    tracing::error!("\nError reading from RTT: {}", err);
+                    break;
+                }
+            }
+        }
+
+        let timestamp = OffsetDateTime::now_utc();
         let defmt_state = self.defmt_state.as_ref();
         let mut data = HashMap::new();
-        for channel in self.active_channels.iter_mut() {
-            if let Some((channel, formatted_data)) = channel.get_rtt_data(core, defmt_state)? {
-                data.insert(channel, formatted_data);
+        for (number, bytes) in received {
+            let Some(channel) = self
+                .active_channels
+                .iter_mut()
+                .find(|channel| channel.number() == Some(number))
+            else {
+                continue;
+            };
+
+            channel.forwarder.send(&bytes);
+            if let Some(capture) = self.capture.as_mut() {
+                capture.write_data(timestamp, number, &bytes)?;
             }
+
+            let formatted_data = channel.format_data(&bytes, timestamp, defmt_state)?;
+            data.insert(number.to_string(), formatted_data);
+        }
+
+        // Flush once per poll, so that the capture is complete even if we are killed.
+        if let Some(capture) = self.capture.as_mut() {
+            capture.flush()?;
         }
         Ok(data)
     }
 
+    /// Checks periodically whether the target re-initialized RTT, e.g. after a reset, and attaches
+    /// to the new control block if it did, setting the channel modes again. Returns `true` when it
+    /// re-attached, so that the caller can show [`REATTACHED_MARKER`].
+    ///
+    /// While the control block is invalid, the channels don't access the target memory.
+    pub fn reattach_if_reset(&mut self, core: &mut Core) -> bool {
+        if self
+            .last_control_block_check
+            .is_some_and(|last_check| last_check.elapsed() < CONTROL_BLOCK_CHECK_INTERVAL)
+        {
+            return false;
+        }
+        self.last_control_block_check = Some(Instant::now());
+
+        if !self.detached {
+            let reinitialized = match self.control_block.control_block_is_valid(core) {
+                // A control block initialized again with the same layout is only detected by
+                // the fields of the channels which only the host changes.
+                Ok(true) => self
+                    .active_channels
+                    .iter()
+                    .any(|channel| channel.was_reinitialized(core)),
+                Ok(false) => true,
+                Err(err) => {
+                    // E.g. the core is held in reset, try again later.
+                    tracing::debug!("Failed to check the RTT control block: {}", err);
+                    return false;
+                }
+            };
+            if !reinitialized {
+                return false;
+            }
+
+            tracing::info!("The target re-initialized RTT, re-attaching");
+            for channel in self.active_channels.iter_mut() {
+                channel.up_channel = None;
+                channel.down_channel = None;
+            }
+            self.detached = true;
+        }
+
+        let memory_map = core.memory_regions().cloned().collect::<Vec<_>>();
+        match self.control_block.reattach(core, &memory_map) {
+            Ok(mut rtt) => {
+                for channel in self.active_channels.iter_mut() {
+                    channel.reattach(core, &mut rtt);
+                }
+                self.control_block = rtt;
+                self.detached = false;
+                true
+            }
+            Err(err) => {
+                tracing::debug!("Failed to re-attach to RTT: {}", err);
+                false
+            }
+        }
+    }
+
+    /// Returns how long to wait before calling [`RttActiveTarget::poll_rtt_fallible`] again. The
+    /// delay grows while the target doesn't send any data.
+    pub fn poll_delay(&self) -> Duration {
+        self.poller.next_poll_delay()
+    }
+
     // pub fn push_rtt(&mut self) {
     //     self.tabs[self.current_tab].push_rtt();
     // }
@@ -0,0 +1,292 @@
+//! A file format for raw RTT data, so that it can be decoded later with `probe-rs rtt-decode`.
+//!
+//! A capture starts with the magic bytes `RTTCAP` and a version byte, followed by records. Every
+//! record starts with a kind byte, and all integers are little endian:
+//!
+//! - Channel (`0`): `u32` channel number, `u8` data format, `u16` name length, UTF-8 name.
+//! - Data (`1`): `i64` host timestamp in nanoseconds since the UNIX epoch, `u32` channel number,
+//!   `u32` length, raw bytes as read from the up channel.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use time::OffsetDateTime;
+
+use super::DataFormat;
+
+const MAGIC: &[u8; 6] = b"RTTCAP";
+const VERSION: u8 = 1;
+
+const RECORD_CHANNEL: u8 = 0;
+const RECORD_DATA: u8 = 1;
+
+/// A single record of a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureRecord {
+    /// Describes an up channel. Written once per channel, before any of its data.
+    Channel {
+        number: usize,
+        name: String,
+        data_format: DataFormat,
+    },
+    /// A chunk of data read from an up channel.
+    Data {
+        timestamp: OffsetDateTime,
+        channel: usize,
+        bytes: Vec<u8>,
+    },
+}
+
+/// Writes RTT data into a capture.
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    /// Creates the capture file at `path`, replacing an existing file.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Starts a capture by writing the header to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(Self { writer })
+    }
+
+    /// Records the name and format of an up channel.
+    pub fn write_channel(
+        &mut self,
+        number: usize,
+        name: &str,
+        data_format: DataFormat,
+    ) -> io::Result<()> {
+        let format = match data_format {
+            DataFormat::String => 0u8,
+            DataFormat::BinaryLE => 1,
+            DataFormat::Defmt => 2,
+        };
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Channel name is too long"))?;
+
+        self.writer.write_all(&[RECORD_CHANNEL])?;
+        self.writer
+            .write_all(&channel_number(number)?.to_le_bytes())?;
+        self.writer.write_all(&[format])?;
+        self.writer.write_all(&name_len.to_le_bytes())?;
+        self.writer.write_all(name.as_bytes())
+    }
+
+    /// Records data read from an up channel at `timestamp`.
+    pub fn write_data(
+        &mut self,
+        timestamp: OffsetDateTime,
+        channel: usize,
+        bytes: &[u8],
+    ) -> io::Result<()> {
+        let timestamp = i64::try_from(timestamp.unix_timestamp_nanos())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Timestamp is out of range"))?;
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Data chunk is too long"))?;
+
+        self.writer.write_all(&[RECORD_DATA])?;
+        self.writer.write_all(&timestamp.to_le_bytes())?;
+        self.writer
+            .write_all(&channel_number(channel)?.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(bytes)
+    }
+
+    /// Flushes all records to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+fn channel_number(number: usize) -> io::Result<u32> {
+    u32::try_from(number)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Channel number is out of range"))
+}
+
+/// Reads the records of a capture.
+#[derive(Debug)]
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Reads and checks the header of the capture.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; MAGIC.len() + 1];
+        reader
+            .read_exact(&mut header)
+            .map_err(|_| invalid_data("Not an RTT capture"))?;
+
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("Not an RTT capture"));
+        }
+        if header[MAGIC.len()] != VERSION {
+            return Err(invalid_data(format!(
+                "Unsupported RTT capture version {}",
+                header[MAGIC.len()]
+            )));
+        }
+
+        Ok(Self { reader })
+    }
+
+    /// Reads the next record, returns `None` at the end of the capture.
+    ///
+    /// A capture which ends in the middle of a record, e.g. because the capturing process was
+    /// killed, results in an [`ErrorKind::UnexpectedEof`] error.
+    pub fn next_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        let mut kind = [0];
+        if self.reader.read(&mut kind)? == 0 {
+            return Ok(None);
+        }
+
+        let record = match kind[0] {
+            RECORD_CHANNEL => {
+                let number = self.read_u32()? as usize;
+                let data_format = match self.read_array::<1>()?[0] {
+                    0 => DataFormat::String,
+                    1 => DataFormat::BinaryLE,
+                    2 => DataFormat::Defmt,
+                    other => return Err(invalid_data(format!("Unknown data format {other}"))),
+                };
+                let name_len = u16::from_le_bytes(self.read_array()?) as usize;
+                let name = String::from_utf8(self.read_vec(name_len)?)
+                    .map_err(|_| invalid_data("Channel name is not valid UTF-8"))?;
+
+                CaptureRecord::Channel {
+                    number,
+                    name,
+                    data_format,
+                }
+            }
+            RECORD_DATA => {
+                let timestamp = i64::from_le_bytes(self.read_array()?);
+                let timestamp = OffsetDateTime::from_unix_timestamp_nanos(timestamp.into())
+                    .map_err(|error| invalid_data(error.to_string()))?;
+                let channel = self.read_u32()? as usize;
+                let len = self.read_u32()? as usize;
+
+                CaptureRecord::Data {
+                    timestamp,
+                    channel,
+                    bytes: self.read_vec(len)?,
+                }
+            }
+            other => return Err(invalid_data(format!("Unknown record kind {other}"))),
+        };
+
+        Ok(Some(record))
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0; N];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        self.read_array().map(u32::from_le_bytes)
+    }
+
+    fn read_vec(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0; len];
+        self.reader.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn records_are_read_back() {
+        let timestamp = datetime!(2024-01-02 03:04:05.123456789 UTC);
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write_channel(0, "defmt", DataFormat::Defmt).unwrap();
+        writer.write_channel(1, "", DataFormat::BinaryLE).unwrap();
+        writer.write_data(timestamp, 0, b"\x01\x02\x03").unwrap();
+        writer.write_data(timestamp, 1, b"").unwrap();
+
+        let mut reader = CaptureReader::new(writer.writer.as_slice()).unwrap();
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(CaptureRecord::Channel {
+                number: 0,
+                name: "defmt".to_string(),
+                data_format: DataFormat::Defmt,
+            })
+        );
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(CaptureRecord::Channel {
+                number: 1,
+                name: String::new(),
+                data_format: DataFormat::BinaryLE,
+            })
+        );
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(CaptureRecord::Data {
+                timestamp,
+                channel: 0,
+                bytes: vec![1, 2, 3],
+            })
+        );
+        assert_eq!(
+            reader.next_record().unwrap(),
+            Some(CaptureRecord::Data {
+                timestamp,
+                channel: 1,
+                bytes: vec![],
+            })
+        );
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_capture_is_an_error() {
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer
+            .write_data(OffsetDateTime::UNIX_EPOCH, 0, b"hello")
+            .unwrap();
+
+        let data = &writer.writer[..writer.writer.len() - 1];
+        let mut reader = CaptureReader::new(data).unwrap();
+        assert_eq!(
+            reader.next_record().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        assert!(CaptureReader::new(&b"\x7fELF\x01\x01\x01"[..]).is_err());
+        assert!(CaptureReader::new(&b"RTTCAP\x02"[..]).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Exchanges the data of RTT channels with endpoints on the host, e.g. TCP clients or processes.
+
+use std::fmt;
+
+use probe_rs::rtt::DownChannel;
+use probe_rs::Core;
+
+/// The maximum amount of data buffered for an endpoint which doesn't keep up with the target.
+///
+/// Endpoints exceeding this are disconnected, so that a stalled endpoint can't stall the others.
+pub const MAX_BACKLOG: usize = 1024 * 1024;
+
+/// The maximum amount of data received from the endpoints which is buffered until it fits into
+/// the down channel.
+///
+/// No more data is received from the endpoints while the buffer is full, so that they are slowed
+/// down by flow control instead.
+pub const MAX_PENDING: usize = 64 * 1024;
+
+/// An endpoint on the host for an RTT channel, see [`RttForwarder`].
+pub trait RttEndpoint: fmt::Debug + Send {
+    /// Sends data of the up channel to the endpoint.
+    fn send(&mut self, bytes: &[u8]);
+
+    /// Appends the data the endpoint produced for the down channel to `buffer`, until it holds
+    /// about [`MAX_PENDING`] bytes.
+    fn receive(&mut self, buffer: &mut Vec<u8>);
+}
+
+/// Forwards the data of an up channel to all endpoints of the channel, and the data produced by
+/// the endpoints to the down channel with the same number.
+#[derive(Debug, Default)]
+pub struct RttForwarder {
+    endpoints: Vec<Box<dyn RttEndpoint>>,
+    /// Data received from the endpoints which didn't fit into the down channel yet.
+    pending: Vec<u8>,
+}
+
+impl RttForwarder {
+    /// Adds an endpoint, which receives all data of the up channel sent from now on.
+    pub fn add(&mut self, endpoint: impl RttEndpoint + 'static) {
+        self.endpoints.push(Box::new(endpoint));
+    }
+
+    /// Sends data of the up channel to all endpoints.
+    pub fn send(&mut self, bytes: &[u8]) {
+        for endpoint in self.endpoints.iter_mut() {
+            endpoint.send(bytes);
+        }
+    }
+
+    /// Receives the data of all endpoints, and returns all data which wasn't written to the target
+    /// yet.
+    ///
+    /// Once [`MAX_PENDING`] bytes are waiting for the target, no more data is received.
+    pub fn receive(&mut self) -> &[u8] {
+        for endpoint in self.endpoints.iter_mut() {
+            if self.pending.len() >= MAX_PENDING {
+                break;
+            }
+            endpoint.receive(&mut self.pending);
+        }
+
+        &self.pending
+    }
+
+    /// Marks the first `count` bytes returned by [`Self::receive`] as written to the target.
+    pub fn consume(&mut self, count: usize) {
+        self.pending.drain(..count.min(self.pending.len()));
+    }
+
+    /// Writes the data received from the endpoints to `channel`, returns the number of bytes
+    /// written.
+    ///
+    /// Data which doesn't fit into the channel is kept, and written on the next call.
+    pub fn write_to(
+        &mut self,
+        core: &mut Core,
+        channel: &mut DownChannel,
+    ) -> Result<usize, probe_rs::rtt::Error> {
+        if self.receive().is_empty() {
+            return Ok(0);
+        }
+
+        let count = channel.write(core, &self.pending)?;
+        self.consume(count);
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Echoes the data of the up channel.
+    #[derive(Debug, Default)]
+    struct Echo(Vec<u8>);
+
+    impl RttEndpoint for Echo {
+        fn send(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+
+        fn receive(&mut self, buffer: &mut Vec<u8>) {
+            buffer.append(&mut self.0);
+        }
+    }
+
+    #[test]
+    fn data_of_all_endpoints_is_kept_until_consumed() {
+        let mut forwarder = RttForwarder::default();
+        forwarder.add(Echo::default());
+        forwarder.add(Echo::default());
+
+        forwarder.send(b"ab");
+        assert_eq!(forwarder.receive(), b"abab");
+
+        forwarder.consume(3);
+        forwarder.send(b"c");
+        assert_eq!(forwarder.receive(), b"bcc");
+    }
+
+    #[test]
+    fn no_data_is_received_while_the_pending_data_is_full() {
+        let mut forwarder = RttForwarder::default();
+        forwarder.add(Echo::default());
+
+        forwarder.send(&vec![1; MAX_PENDING]);
+        assert_eq!(forwarder.receive().len(), MAX_PENDING);
+
+        forwarder.send(b"a");
+        assert_eq!(forwarder.receive().len(), MAX_PENDING);
+
+        forwarder.consume(MAX_PENDING);
+        assert_eq!(forwarder.receive(), b"a");
+    }
+}
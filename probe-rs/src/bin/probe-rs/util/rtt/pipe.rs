@@ -0,0 +1,284 @@
+//! Pipes an RTT channel through an external process, e.g. a protocol decoder.
+
+use std::io::{self, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::forward::{RttEndpoint, MAX_BACKLOG, MAX_PENDING};
+
+/// How long the process may take to exit after its stdin was closed, before it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The size of the chunks read from the stdout of the process.
+const CHUNK_SIZE: usize = 1024;
+
+/// An external process for an RTT channel.
+///
+/// All data of the up channel is written to the stdin of the process, and its stdout is written
+/// to the down channel with the same number, if there is one. The stderr of the process is
+/// inherited.
+#[derive(Debug)]
+pub struct RttPipe {
+    command: String,
+    child: Child,
+    /// Sends data to the thread writing to stdin. Dropped to close stdin.
+    stdin: Option<SyncSender<Vec<u8>>>,
+    /// Data which couldn't be passed to the thread writing to stdin yet.
+    backlog: Vec<u8>,
+    /// Receives data from the thread reading stdout, if stdout is forwarded.
+    stdout: Option<Receiver<Vec<u8>>>,
+}
+
+impl RttPipe {
+    /// Spawns `command` with the shell of the platform, so that it can be a pipeline.
+    ///
+    /// If `forward_output` is set, the stdout of the process is forwarded to the down channel,
+    /// otherwise it is inherited, e.g. to show the output of a decoder.
+    pub fn spawn(command: &str, forward_output: bool) -> io::Result<Self> {
+        let stdout = if forward_output {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        };
+        let mut child = shell_command(command)
+            .stdin(Stdio::piped())
+            .stdout(stdout)
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let mut child_stdin = child.stdin.take().expect("stdin is piped");
+        // Only one write is queued, the rest of the data is kept in the backlog.
+        let (stdin, stdin_receiver) = mpsc::sync_channel::<Vec<u8>>(1);
+        thread::Builder::new()
+            .name("probe-rs-rtt-pipe-stdin".to_owned())
+            .spawn(move || {
+                for bytes in stdin_receiver {
+                    if child_stdin
+                        .write_all(&bytes)
+                        .and_then(|_| child_stdin.flush())
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })?;
+
+        let stdout = match child.stdout.take() {
+            Some(mut child_stdout) => {
+                // The thread stops reading while the channel is full, so that the process is
+                // slowed down by the pipe instead.
+                let (stdout_sender, stdout) = mpsc::sync_channel(MAX_PENDING / CHUNK_SIZE);
+                thread::Builder::new()
+                    .name("probe-rs-rtt-pipe-stdout".to_owned())
+                    .spawn(move || {
+                        let mut chunk = [0; CHUNK_SIZE];
+                        loop {
+                            match child_stdout.read(&mut chunk) {
+                                Ok(0) => break,
+                                Ok(count) => {
+                                    if stdout_sender.send(chunk[..count].to_vec()).is_err() {
+                                        break;
+                                    }
+                                }
+                                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                                Err(_) => break,
+                            }
+                        }
+                    })?;
+                Some(stdout)
+            }
+            None => None,
+        };
+
+        tracing::info!("Piping RTT channel through '{command}'");
+
+        Ok(Self {
+            command: command.to_owned(),
+            child,
+            stdin: Some(stdin),
+            backlog: Vec::new(),
+            stdout,
+        })
+    }
+
+    /// Passes the backlog to the thread writing to stdin, if it is ready for more data.
+    ///
+    /// The process is disconnected if the backlog exceeds [`MAX_BACKLOG`], or if it stopped
+    /// reading.
+    fn flush(&mut self) {
+        let Some(stdin) = self.stdin.as_ref() else {
+            return;
+        };
+        if self.backlog.is_empty() {
+            return;
+        }
+
+        match stdin.try_send(std::mem::take(&mut self.backlog)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(bytes)) => {
+                self.backlog = bytes;
+                if self.backlog.len() > MAX_BACKLOG {
+                    tracing::warn!(
+                        "'{}' doesn't keep up with the RTT data, closing its stdin",
+                        self.command
+                    );
+                    self.stdin = None;
+                    self.backlog = Vec::new();
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                tracing::warn!("'{}' stopped reading RTT data", self.command);
+                self.stdin = None;
+                self.backlog = Vec::new();
+            }
+        }
+    }
+
+    /// Passes the rest of the backlog to the process within `timeout`, and closes its stdin.
+    fn close_stdin(&mut self, timeout: Duration) {
+        let start = Instant::now();
+        while !self.backlog.is_empty() && start.elapsed() < timeout {
+            self.flush();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        self.stdin = None;
+    }
+}
+
+impl RttEndpoint for RttPipe {
+    /// Writes data of the up channel to the stdin of the process.
+    fn send(&mut self, bytes: &[u8]) {
+        if self.stdin.is_some() {
+            self.backlog.extend_from_slice(bytes);
+            self.flush();
+        }
+    }
+
+    /// Receives the output of the process, until `buffer` holds [`MAX_PENDING`] bytes.
+    fn receive(&mut self, buffer: &mut Vec<u8>) {
+        self.flush();
+
+        if let Some(stdout) = self.stdout.as_ref() {
+            while buffer.len() < MAX_PENDING {
+                let Ok(bytes) = stdout.try_recv() else {
+                    break;
+                };
+                buffer.extend_from_slice(&bytes);
+            }
+        }
+    }
+}
+
+impl Drop for RttPipe {
+    fn drop(&mut self) {
+        // Closing stdin lets the process finish its output and exit on its own.
+        self.close_stdin(EXIT_TIMEOUT);
+
+        let start = Instant::now();
+        while let Ok(None) = self.child.try_wait() {
+            if start.elapsed() > EXIT_TIMEOUT {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+/// The command to pipe an RTT channel through, given as `CHANNEL=COMMAND`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RttPipeConfig {
+    pub channel: usize,
+    pub command: String,
+}
+
+impl FromStr for RttPipeConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, command) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected 'CHANNEL=COMMAND', got '{s}'"))?;
+
+        if command.trim().is_empty() {
+            return Err(format!("Missing command for channel '{channel}'"));
+        }
+
+        Ok(Self {
+            channel: channel
+                .parse()
+                .map_err(|error| format!("Invalid channel number '{channel}': {error}"))?,
+            command: command.to_owned(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_pipe_config() {
+        assert_eq!(
+            "1=tee log.bin | decoder --format=cbor".parse::<RttPipeConfig>(),
+            Ok(RttPipeConfig {
+                channel: 1,
+                command: "tee log.bin | decoder --format=cbor".to_owned(),
+            })
+        );
+
+        assert!("decoder".parse::<RttPipeConfig>().is_err());
+        assert!("1=".parse::<RttPipeConfig>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn data_is_piped_through_the_process() {
+        let mut pipe = RttPipe::spawn("tr a-z A-Z", true).unwrap();
+        pipe.send(b"hello");
+        pipe.send(b" rtt");
+        // `tr` exits after stdin is closed, which flushes its output.
+        pipe.close_stdin(Duration::from_secs(5));
+
+        let mut received = Vec::new();
+        let start = Instant::now();
+        while received != b"HELLO RTT" {
+            assert!(start.elapsed() < Duration::from_secs(5), "Timeout");
+            thread::sleep(Duration::from_millis(1));
+            pipe.receive(&mut received);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process_which_does_not_read_is_disconnected() {
+        let mut pipe = RttPipe::spawn("sleep 5", false).unwrap();
+
+        let chunk = vec![0; 64 * 1024];
+        for _ in 0..(MAX_BACKLOG * 2) / chunk.len() {
+            pipe.send(&chunk);
+        }
+
+        assert!(pipe.stdin.is_none());
+        assert!(pipe.backlog.is_empty());
+    }
+}
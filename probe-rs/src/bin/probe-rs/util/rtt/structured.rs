@@ -0,0 +1,336 @@
+//! Machine readable output of RTT channels, and filtering of log messages on the host.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// How the decoded messages of string and defmt channels are written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text, formatted according to the channel configuration.
+    #[default]
+    Text,
+    /// One JSON object per message, see [`JsonLine`].
+    JsonLines,
+}
+
+/// The level of a log message, ordered from least to most severe.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Detects the level of a line of a string channel from its first word, e.g. `INFO`,
+    /// `[warn]` or `ERROR:`. Returns `None` if the line doesn't start with a level.
+    pub fn detect(line: &str) -> Option<Self> {
+        let word = line.split_whitespace().next()?;
+        let word = word.trim_end_matches(':');
+        let word = word
+            .strip_prefix('[')
+            .and_then(|word| word.strip_suffix(']'))
+            .unwrap_or(word);
+
+        word.parse().ok()
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_ascii_lowercase()[..] {
+            "trace" => Ok(Self::Trace),
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" | "warning" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("{s} is not a valid log level")),
+        }
+    }
+}
+
+/// Filters log messages by level and module path, with the syntax of `RUST_LOG`.
+///
+/// The filter is a comma separated list of directives. A directive is either a level, which
+/// applies to all modules, or `module=level`, which applies to the module and its submodules. The
+/// level `off` hides all messages. For example, `warn,my_app::net=debug` shows warnings and
+/// errors, and all messages of level debug or higher of `my_app::net`.
+///
+/// Messages without a level, e.g. lines of string channels which don't start with a level, are
+/// never hidden.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LogFilter {
+    /// The lowest level shown for modules without a directive, `None` to hide them.
+    default: Option<LogLevel>,
+    /// The lowest level shown per module, `None` to hide the module.
+    modules: Vec<(String, Option<LogLevel>)>,
+    directives: String,
+}
+
+impl LogFilter {
+    /// Returns if a message of `level` from `module` passes the filter.
+    pub fn allows(&self, level: Option<LogLevel>, module: Option<&str>) -> bool {
+        let Some(level) = level else {
+            return true;
+        };
+
+        let threshold = module
+            .and_then(|module| {
+                self.modules
+                    .iter()
+                    .filter(|(prefix, _)| is_same_or_submodule(module, prefix))
+                    .max_by_key(|(prefix, _)| prefix.len())
+            })
+            .map_or(self.default, |(_, threshold)| *threshold);
+
+        threshold.is_some_and(|threshold| level >= threshold)
+    }
+}
+
+fn is_same_or_submodule(module: &str, parent: &str) -> bool {
+    module
+        .strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+fn parse_threshold(level: &str) -> Result<Option<LogLevel>, String> {
+    if level.eq_ignore_ascii_case("off") {
+        Ok(None)
+    } else {
+        level.parse().map(Some)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut default = None;
+        let mut has_default = false;
+        let mut modules = Vec::new();
+
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    modules.push((module.trim().to_string(), parse_threshold(level.trim())?));
+                }
+                None => match parse_threshold(directive) {
+                    Ok(threshold) => {
+                        default = threshold;
+                        has_default = true;
+                    }
+                    // A module without a level shows all of its messages.
+                    Err(_) => modules.push((directive.to_string(), Some(LogLevel::Trace))),
+                },
+            }
+        }
+
+        // An empty filter shows everything, but like `RUST_LOG`, naming only modules hides all
+        // other modules.
+        if !has_default && modules.is_empty() {
+            default = Some(LogLevel::Trace);
+        }
+
+        Ok(Self {
+            default,
+            modules,
+            directives: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for LogFilter {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<LogFilter> for String {
+    fn from(filter: LogFilter) -> Self {
+        filter.directives
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.directives)
+    }
+}
+
+/// A message of a string or defmt channel, written as a single line of JSON.
+///
+/// All fields are always present, and `null` if unknown, so that every line has the same shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JsonLine<'a> {
+    /// When the host received the message, in RFC 3339 format.
+    pub timestamp: String,
+    /// The timestamp of the target, if the defmt channel has one.
+    pub target_timestamp: Option<String>,
+    /// The number of the up channel.
+    pub channel: usize,
+    pub level: Option<LogLevel>,
+    pub module_path: Option<&'a str>,
+    pub file: Option<&'a str>,
+    pub line: Option<u32>,
+    pub message: &'a str,
+    /// The index of the format string in the defmt table.
+    pub defmt_index: Option<u64>,
+    /// The arguments of the defmt message as formatted in `message`, see [`split_defmt_args`].
+    pub raw_args: Option<Vec<&'a str>>,
+}
+
+impl<'a> JsonLine<'a> {
+    /// Creates a line for `message`, which was received by the host at `timestamp`.
+    pub fn new(timestamp: OffsetDateTime, channel: usize, message: &'a str) -> Self {
+        Self {
+            timestamp: timestamp
+                .format(&Rfc3339)
+                .expect("Formatting a timestamp as RFC 3339 cannot fail"),
+            target_timestamp: None,
+            channel,
+            level: None,
+            module_path: None,
+            file: None,
+            line: None,
+            message,
+            defmt_index: None,
+            raw_args: None,
+        }
+    }
+}
+
+impl fmt::Display for JsonLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+/// Splits `message`, which was formatted from the defmt format string `format`, into the
+/// formatted arguments, in the order of the parameters in `format`.
+///
+/// Returns `None` if the message doesn't match the format string, or if two parameters are not
+/// separated by text, so that the arguments can't be told apart.
+pub fn split_defmt_args<'a>(format: &str, message: &'a str) -> Option<Vec<&'a str>> {
+    let literals = format_literals(format);
+
+    let mut rest = message.strip_prefix(literals[0].as_str())?;
+    let mut args = Vec::with_capacity(literals.len() - 1);
+    for (i, literal) in literals.iter().enumerate().skip(1) {
+        let end = if i == literals.len() - 1 {
+            rest.strip_suffix(literal.as_str())?.len()
+        } else if literal.is_empty() {
+            return None;
+        } else {
+            rest.find(literal.as_str())?
+        };
+
+        args.push(&rest[..end]);
+        rest = &rest[end + literal.len()..];
+    }
+
+    // Without parameters, the message has to consist of the literal only.
+    rest.is_empty().then_some(args)
+}
+
+/// Returns the text around the parameters of a defmt format string, i.e. one more literal than
+/// there are parameters. `{{` and `}}` are unescaped.
+fn format_literals(format: &str) -> Vec<String> {
+    let mut literals = vec![String::new()];
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' if chars.peek() == Some(&c) => {
+                chars.next();
+                literals.last_mut().unwrap().push(c);
+            }
+            '{' => {
+                // defmt parameters, e.g. `{=u8:x}`, don't contain braces.
+                chars.by_ref().find(|&c| c == '}');
+                literals.push(String::new());
+            }
+            c => literals.last_mut().unwrap().push(c),
+        }
+    }
+
+    literals
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn level_is_detected_from_first_word() {
+        assert_eq!(LogLevel::detect("INFO booted"), Some(LogLevel::Info));
+        assert_eq!(LogLevel::detect("[warn] low battery"), Some(LogLevel::Warn));
+        assert_eq!(LogLevel::detect("  ERROR: failed"), Some(LogLevel::Error));
+        assert_eq!(LogLevel::detect("information"), None);
+        assert_eq!(LogLevel::detect(""), None);
+    }
+
+    #[test]
+    fn most_specific_module_directive_applies() {
+        let filter: LogFilter = "warn,app::net=debug,app::net::dns=off".parse().unwrap();
+
+        assert!(filter.allows(Some(LogLevel::Warn), Some("app")));
+        assert!(!filter.allows(Some(LogLevel::Info), Some("app")));
+        assert!(!filter.allows(Some(LogLevel::Info), None));
+        assert!(filter.allows(Some(LogLevel::Debug), Some("app::net::tcp")));
+        assert!(!filter.allows(Some(LogLevel::Error), Some("app::net::dns")));
+        // `app::network` is not a submodule of `app::net`.
+        assert!(!filter.allows(Some(LogLevel::Debug), Some("app::network")));
+        assert!(filter.allows(None, Some("app::net::dns")));
+    }
+
+    #[test]
+    fn module_only_filter_hides_other_modules() {
+        let filter: LogFilter = "app".parse().unwrap();
+
+        assert!(filter.allows(Some(LogLevel::Trace), Some("app::net")));
+        assert!(!filter.allows(Some(LogLevel::Error), Some("hal")));
+        assert!("info=debug,app=loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test]
+    fn json_line_has_all_fields() {
+        let mut line = JsonLine::new(datetime!(2024-01-02 03:04:05 UTC), 1, "hello \"world\"");
+        line.level = Some(LogLevel::Info);
+        line.line = Some(42);
+
+        assert_eq!(
+            line.to_string(),
+            r#"{"timestamp":"2024-01-02T03:04:05Z","target_timestamp":null,"channel":1,"level":"info","module_path":null,"file":null,"line":42,"message":"hello \"world\"","defmt_index":null,"raw_args":null}"#
+        );
+    }
+
+    #[test]
+    fn defmt_args_are_split_at_the_format_text() {
+        assert_eq!(
+            split_defmt_args("x = {=u8:x}, name = {=str}!", "x = 0x2a, name = a, b!"),
+            Some(vec!["0x2a", "a, b"])
+        );
+        assert_eq!(
+            split_defmt_args("{{{}}} {}", "{1} [1, 2]"),
+            Some(vec!["1", "[1, 2]"])
+        );
+        assert_eq!(split_defmt_args("booted", "booted"), Some(vec![]));
+        assert_eq!(split_defmt_args("booted", "booted twice"), None);
+        assert_eq!(split_defmt_args("{}{}", "12"), None);
+        assert_eq!(split_defmt_args("x = {}", "y = 1"), None);
+    }
+}
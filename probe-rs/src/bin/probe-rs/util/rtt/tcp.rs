@@ -0,0 +1,254 @@
+//! A TCP server for a single RTT channel, similar to the RTT telnet server of the J-Link software.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+
+use super::forward::{RttEndpoint, MAX_BACKLOG, MAX_PENDING};
+
+/// A listening TCP server for an RTT channel.
+///
+/// All data of the up channel is sent to every connected client, and data received from any client
+/// is written to the down channel with the same number.
+#[derive(Debug)]
+pub struct RttTcpServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+}
+
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    /// Data which couldn't be sent to the client yet.
+    backlog: Vec<u8>,
+}
+
+impl Client {
+    /// Sends as much of the backlog as possible, returns `false` if the client disconnected.
+    fn flush(&mut self) -> bool {
+        while !self.backlog.is_empty() {
+            match self.stream.write(&self.backlog) {
+                Ok(0) => return false,
+                Ok(count) => {
+                    self.backlog.drain(..count);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+
+        self.backlog.len() <= MAX_BACKLOG
+    }
+
+    /// Reads available data into `buffer` until it holds [`MAX_PENDING`] bytes, returns `false`
+    /// if the client disconnected.
+    fn receive(&mut self, buffer: &mut Vec<u8>) -> bool {
+        let mut chunk = [0; 1024];
+        while buffer.len() < MAX_PENDING {
+            let space = chunk.len().min(MAX_PENDING - buffer.len());
+            match self.stream.read(&mut chunk[..space]) {
+                Ok(0) => return false,
+                Ok(count) => buffer.extend_from_slice(&chunk[..count]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl RttTcpServer {
+    /// Starts listening on `address`.
+    pub fn bind(address: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+
+        tracing::info!("Listening for RTT clients on {}", listener.local_addr()?);
+
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    /// Accepts all pending connections.
+    fn accept(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    if let Err(error) = stream.set_nonblocking(true) {
+                        tracing::warn!("Failed to configure RTT client {address}: {error}");
+                        continue;
+                    }
+                    // RTT data is often line based and small, so it is sent right away.
+                    let _ = stream.set_nodelay(true);
+
+                    tracing::info!("RTT client {address} connected");
+                    self.clients.push(Client {
+                        stream,
+                        address,
+                        backlog: Vec::new(),
+                    });
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    tracing::warn!("Failed to accept RTT client: {error}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl RttEndpoint for RttTcpServer {
+    /// Sends data of the up channel to all connected clients.
+    fn send(&mut self, bytes: &[u8]) {
+        self.accept();
+
+        self.clients.retain_mut(|client| {
+            client.backlog.extend_from_slice(bytes);
+            let connected = client.flush();
+            if !connected {
+                tracing::info!("RTT client {} disconnected", client.address);
+            }
+            connected
+        });
+    }
+
+    /// Receives data from all connected clients.
+    fn receive(&mut self, buffer: &mut Vec<u8>) {
+        self.accept();
+
+        self.clients.retain_mut(|client| {
+            // Also send anything left over from previous calls to `send`.
+            let connected = client.receive(buffer) && client.flush();
+            if !connected {
+                tracing::info!("RTT client {} disconnected", client.address);
+            }
+            connected
+        });
+    }
+}
+
+/// The address of the TCP server for an RTT channel, given as `CHANNEL=ADDRESS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RttTcpServerConfig {
+    pub channel: usize,
+    pub address: SocketAddr,
+}
+
+impl FromStr for RttTcpServerConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (channel, address) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Expected 'CHANNEL=ADDRESS', got '{s}'"))?;
+
+        Ok(Self {
+            channel: channel
+                .parse()
+                .map_err(|error| format!("Invalid channel number '{channel}': {error}"))?,
+            address: address
+                .parse()
+                .map_err(|error| format!("Invalid address '{address}': {error}"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Calls `f` until it returns true, or a timeout expires.
+    fn wait_for(mut f: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !f() {
+            assert!(start.elapsed() < Duration::from_secs(5), "Timeout");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn parse_server_config() {
+        assert_eq!(
+            "1=127.0.0.1:19021".parse::<RttTcpServerConfig>(),
+            Ok(RttTcpServerConfig {
+                channel: 1,
+                address: "127.0.0.1:19021".parse().unwrap(),
+            })
+        );
+
+        assert!("127.0.0.1:19021".parse::<RttTcpServerConfig>().is_err());
+        assert!("a=127.0.0.1:19021".parse::<RttTcpServerConfig>().is_err());
+    }
+
+    #[test]
+    fn data_is_exchanged_with_all_clients() {
+        let mut server = RttTcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.listener.local_addr().unwrap();
+
+        let mut first = TcpStream::connect(address).unwrap();
+        let mut second = TcpStream::connect(address).unwrap();
+        wait_for(|| {
+            server.accept();
+            server.clients.len() == 2
+        });
+
+        server.send(b"hello");
+        for client in [&mut first, &mut second] {
+            let mut buffer = [0; 5];
+            client.read_exact(&mut buffer).unwrap();
+            assert_eq!(&buffer, b"hello");
+        }
+
+        let mut received = Vec::new();
+        first.write_all(b"ab").unwrap();
+        wait_for(|| {
+            server.receive(&mut received);
+            received.len() == 2
+        });
+        second.write_all(b"c").unwrap();
+        wait_for(|| {
+            server.receive(&mut received);
+            received == b"abc"
+        });
+
+        drop(first);
+        wait_for(|| {
+            server.receive(&mut received);
+            server.clients.len() == 1
+        });
+    }
+
+    #[test]
+    fn received_data_is_limited() {
+        let mut server = RttTcpServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = server.listener.local_addr().unwrap();
+
+        // The data may not fit into the socket buffers, so it is written while the server reads.
+        let client = std::thread::spawn(move || {
+            let mut client = TcpStream::connect(address).unwrap();
+            client.write_all(&vec![1; MAX_PENDING + 10]).unwrap();
+            client
+        });
+
+        let mut received = Vec::new();
+        wait_for(|| {
+            server.receive(&mut received);
+            received.len() == MAX_PENDING
+        });
+        received.clear();
+        wait_for(|| {
+            server.receive(&mut received);
+            received.len() == 10
+        });
+        drop(client.join().unwrap());
+    }
+}
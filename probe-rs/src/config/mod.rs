@@ -29,8 +29,10 @@ mod target;
 
 pub use probe_rs_target::{
     Chip, ChipFamily, Core, CoreType, FlashProperties, GenericRegion, InstructionSet, MemoryRange,
-    MemoryRegion, NvmRegion, PageInfo, RamRegion, RawFlashAlgorithm, ScanChainElement,
-    SectorDescription, SectorInfo, TargetDescriptionSource,
+    MemoryRegion, NvmRegion, OptionBytesField, OptionBytesFieldValue, OptionBytesKind,
+    OptionBytesRegion, PageInfo, RamRegion, RawFlashAlgorithm, RegisterWrite, ScanChainElement,
+    SectorDescription, SectorInfo, SpiFlash, SpiFlashController, Stm32QuadspiOptions,
+    TargetDescriptionSource,
 };
 
 pub use registry::{
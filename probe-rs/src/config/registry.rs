@@ -114,6 +114,8 @@ fn add_generic_targets(vec: &mut Vec<ChipFamily>) {
                 rtt_scan_ranges: None,
                 jtag: None,
                 default_binary_format: Some(BinaryFormat::Raw),
+                option_bytes: vec![],
+                spi_flash: vec![],
             }],
             flash_algorithms: vec![],
             source: TargetDescriptionSource::Generic,
@@ -447,6 +449,7 @@ fn match_name_prefix(pattern: &str, name: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::SpiFlashController;
     use std::fs::File;
     type TestResult = Result<(), RegistryError>;
 
@@ -494,6 +497,36 @@ mod tests {
         assert!(registry.get_target_by_name("nrf51822_Xxaa").is_ok());
     }
 
+    #[test]
+    fn spi_flash_without_flash_algorithm() {
+        let registry = Registry::from_builtin_families();
+        let target = registry.get_target_by_name("RP2040_SFDP").unwrap();
+        assert!(target.flash_algorithms.is_empty());
+        assert_eq!(target.spi_flash[0].name, "QSPI");
+    }
+
+    #[test]
+    fn spi_flash_with_stm32_quadspi() {
+        let registry = Registry::from_builtin_families();
+        let target = registry.get_target_by_name("STM32L476VGTx_QSPI").unwrap();
+        assert_eq!(target.spi_flash[0].address_range, 0x9000_0000..0x9100_0000);
+        assert!(matches!(
+            target.spi_flash[0].controller,
+            SpiFlashController::Stm32Quadspi(_)
+        ));
+    }
+
+    #[test]
+    fn option_bytes_are_shared_between_variants() {
+        let registry = Registry::from_builtin_families();
+        // The option bytes of the STM32L4 variants refer to the ones of the first variant with
+        // the same layout.
+        let first = registry.get_target_by_name("STM32L476RGTx").unwrap();
+        let other = registry.get_target_by_name("STM32L486RGTx").unwrap();
+        assert_eq!(first.option_bytes, other.option_bytes);
+        assert_eq!(other.option_bytes[0].name, "OPTR");
+    }
+
     #[test]
     fn validate_generic_targets() {
         let mut families = vec![];
@@ -6,7 +6,9 @@ use crate::{
         arm::{
             ap::MemoryAp,
             memory::adi_v5_memory_interface::ArmProbe,
-            sequences::{ArmDebugSequence, ArmDebugSequenceError, DebugEraseSequence},
+            sequences::{
+                ArmDebugSequence, ArmDebugSequenceError, DebugEraseSequence, ProtectionState,
+            },
             ApAddress, ArmError, ArmProbeInterface, DpAddress,
         },
     },
@@ -186,6 +188,70 @@ impl DsuStatusB {
     pub const ADDRESS: u64 = 0x4100_2102;
 }
 
+bitfield! {
+    /// Device Service Unit Device Identification Register, DSU - DID
+    #[derive(Copy, Clone)]
+    pub struct DsuDid(u32);
+    impl Debug;
+
+    /// Processor
+    ///
+    /// `0x1` for the Cortex-M0+ based SAMD1x/D2x/DAx, `0x6` for the Cortex-M4 based SAMD5x/E5x.
+    pub processor, _: 31, 28;
+    /// Product Family
+    pub family, _: 27, 23;
+    /// Product Series
+    pub series, _: 21, 16;
+}
+
+impl DsuDid {
+    /// The DSU DID register address
+    pub const ADDRESS: u64 = 0x4100_2118;
+}
+
+/// The Non-Volatile Memory Controller, which is used to set the security bit.
+mod nvmctrl {
+    use crate::architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError};
+    use std::time::{Duration, Instant};
+
+    /// The base address of the NVMCTRL peripheral
+    const NVMCTRL: u64 = 0x4100_4000;
+
+    /// The command execution key, which has to be written together with each command.
+    const CMDEX: u16 = 0xA5 << 8;
+
+    /// Set the security bit, using the register layout of SAMD1x/D2x/DAx devices.
+    pub fn set_security_bit_d2x(memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        const CTRLA: u64 = 0x00;
+        const INTFLAG: u64 = 0x14;
+        const CMD_SSB: u16 = 0x45;
+
+        memory.write_word_16(NVMCTRL + CTRLA, CMDEX | CMD_SSB)?;
+        wait_ready(|| Ok(memory.read_word_8(NVMCTRL + INTFLAG)? & 1 != 0))
+    }
+
+    /// Set the security bit, using the register layout of SAMD5x/E5x devices.
+    pub fn set_security_bit_d5x(memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        const CTRLB: u64 = 0x04;
+        const STATUS: u64 = 0x12;
+        const CMD_SSB: u16 = 0x16;
+
+        memory.write_word_16(NVMCTRL + CTRLB, CMDEX | CMD_SSB)?;
+        wait_ready(|| Ok(memory.read_word_16(NVMCTRL + STATUS)? & 1 != 0))
+    }
+
+    fn wait_ready(mut ready: impl FnMut() -> Result<bool, ArmError>) -> Result<(), ArmError> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(100) {
+            if ready()? {
+                return Ok(());
+            }
+        }
+
+        Err(ArmError::Timeout)
+    }
+}
+
 /// A wrapper for different types that can perform SWD Commands (SWJ_Pins SWJ_Sequence)
 struct SwdSequenceShim<'a>(&'a mut dyn architecture::arm::communication_interface::DapProbe);
 
@@ -431,6 +497,81 @@ impl ArmDebugSequence for AtSAM {
         }
     }
 
+    fn protection_state(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+    ) -> Result<ProtectionState, ArmError> {
+        let mut memory = interface.memory_interface(default_ap)?;
+        let dsu_status_b = DsuStatusB::from(memory.read_word_8(DsuStatusB::ADDRESS)?);
+
+        if dsu_status_b.prot() {
+            Ok(ProtectionState::Protected)
+        } else {
+            Ok(ProtectionState::Unprotected)
+        }
+    }
+
+    /// `lock` for ATSAM devices
+    ///
+    /// Sets the security bit, which takes effect after the next reset.
+    fn lock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        permissions
+            .device_lock()
+            .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
+
+        if permanent {
+            return Err(ArmDebugSequenceError::custom(
+                "ATSAM devices do not support a permanent readout protection",
+            )
+            .into());
+        }
+
+        let mut memory = interface.memory_interface(default_ap)?;
+        let did = DsuDid(memory.read_word_32(DsuDid::ADDRESS)?);
+
+        match did.processor() {
+            0x1 => nvmctrl::set_security_bit_d2x(&mut *memory)?,
+            0x6 => nvmctrl::set_security_bit_d5x(&mut *memory)?,
+            processor => {
+                return Err(ArmDebugSequenceError::custom(format!(
+                    "Unknown ATSAM processor {processor:#x} in DSU DID register"
+                ))
+                .into())
+            }
+        }
+
+        tracing::info!("Security bit set, it will be active after the next reset");
+
+        Ok(())
+    }
+
+    /// `unlock` for ATSAM devices
+    ///
+    /// Performs a Chip-Erase if the device is protected, which also clears the security bit.
+    fn unlock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        let mut memory = interface.memory_interface(default_ap)?;
+        let dsu_status_b = DsuStatusB::from(memory.read_word_8(DsuStatusB::ADDRESS)?);
+
+        if !dsu_status_b.prot() {
+            tracing::info!("The device is not protected");
+            return Ok(());
+        }
+
+        self.erase_all(&mut *memory, permissions)
+    }
+
     fn debug_erase_sequence(&self) -> Option<Arc<dyn DebugEraseSequence>> {
         Some(Self::create())
     }
@@ -3,6 +3,7 @@
 // Common code for certain chip families
 mod esp;
 mod nrf;
+pub mod stm32;
 
 // RISC-V ESP32 devices
 pub mod esp32c2;
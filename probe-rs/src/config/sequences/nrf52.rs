@@ -2,11 +2,13 @@
 
 use std::sync::Arc;
 
+use probe_rs_target::OptionBytesRegion;
+
 use crate::architecture::arm::{
     ap::MemoryAp,
     component::TraceSink,
-    memory::CoresightComponent,
-    sequences::{ArmDebugSequence, ArmDebugSequenceError},
+    memory::{adi_v5_memory_interface::ArmProbe, CoresightComponent},
+    sequences::{ArmDebugSequence, ArmDebugSequenceError, OptionBytesSequence, ProtectionState},
     ApAddress, ArmError, ArmProbeInterface, DpAddress,
 };
 use crate::session::MissingPermissions;
@@ -28,6 +30,16 @@ const ERASEALL: u8 = 0x04;
 const ERASEALLSTATUS: u8 = 0x08;
 const APPROTECTSTATUS: u8 = 0x0C;
 
+/// The APPROTECT register in the UICR.
+const UICR_APPROTECT: u64 = 0x1000_1208;
+/// The value of the APPROTECT register which enables the access port protection.
+const APPROTECT_ENABLED: u32 = 0xFFFF_FF00;
+
+/// Returns true if the access port protection is enabled in the UICR.
+fn is_approtect_enabled(memory: &mut dyn ArmProbe) -> Result<bool, ArmError> {
+    Ok(memory.read_word_32(UICR_APPROTECT)? & 0xFF == APPROTECT_ENABLED & 0xFF)
+}
+
 /// Marker struct indicating initialization sequencing for nRF52 family parts.
 #[derive(Debug)]
 pub struct Nrf52 {}
@@ -46,8 +58,85 @@ impl Nrf52 {
         let status = iface.read_raw_ap_register(ctrl_ap, APPROTECTSTATUS)?;
         Ok(status != 0)
     }
+
+    /// Erase the whole device through the CTRL-AP, which also disables the access port protection.
+    fn erase_all(
+        &self,
+        iface: &mut dyn ArmProbeInterface,
+        ctrl_ap: ApAddress,
+        permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
+        permissions
+            .erase_all()
+            .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
+
+        // Reset
+        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
+        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+
+        // Start erase
+        iface.write_raw_ap_register(ctrl_ap, ERASEALL, 1)?;
+
+        // Wait for erase done
+        while iface.read_raw_ap_register(ctrl_ap, ERASEALLSTATUS)? != 0 {}
+
+        // Reset again
+        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
+        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+
+        if !self.is_core_unlocked(iface, ctrl_ap)? {
+            return Err(ArmDebugSequenceError::custom("Could not unlock core").into());
+        }
+
+        Ok(())
+    }
+}
+
+mod nvmc {
+    use crate::architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError};
+    use std::time::{Duration, Instant};
+
+    /// The base address of the NVMC peripheral
+    const NVMC: u64 = 0x4001_E000;
+
+    const READY: u64 = 0x400;
+    const CONFIG: u64 = 0x504;
+    const ERASEUICR: u64 = 0x514;
+
+    /// The write modes of the CONFIG register, described in
+    /// "nRF52840 Product Specification" section 4.3.9.
+    #[derive(Clone, Copy)]
+    pub enum Mode {
+        ReadOnly = 0,
+        Write = 1,
+        Erase = 2,
+    }
+
+    pub fn set_mode(memory: &mut dyn ArmProbe, mode: Mode) -> Result<(), ArmError> {
+        memory.write_word_32(NVMC + CONFIG, mode as u32)?;
+        wait_ready(memory)
+    }
+
+    pub fn wait_ready(memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(500) {
+            if memory.read_word_32(NVMC + READY)? & 1 != 0 {
+                return Ok(());
+            }
+        }
+
+        Err(ArmError::Timeout)
+    }
+
+    pub fn erase_uicr(memory: &mut dyn ArmProbe) -> Result<(), ArmError> {
+        memory.write_word_32(NVMC + ERASEUICR, 1)?;
+        wait_ready(memory)
+    }
 }
 
+/// The UICR page, which can only be erased as a whole.
+const UICR: std::ops::Range<u64> = 0x1000_1000..0x1000_2000;
+
 mod clock {
     use crate::architecture::arm::{memory::adi_v5_memory_interface::ArmProbe, ArmError};
     use bitfield::bitfield;
@@ -102,28 +191,99 @@ impl ArmDebugSequence for Nrf52 {
         }
 
         tracing::warn!("Core is locked. Erase procedure will be started to unlock it.");
+        self.erase_all(iface, ctrl_ap, permissions)?;
+
+        Err(ArmError::ReAttachRequired)
+    }
+
+    fn protection_state(
+        &self,
+        iface: &mut dyn ArmProbeInterface,
+        _default_ap: MemoryAp,
+    ) -> Result<ProtectionState, ArmError> {
+        let ctrl_ap = ApAddress {
+            ap: 1,
+            dp: DpAddress::Default,
+        };
+
+        if self.is_core_unlocked(iface, ctrl_ap)? {
+            Ok(ProtectionState::Unprotected)
+        } else {
+            Ok(ProtectionState::Protected)
+        }
+    }
+
+    /// `lock` for nRF52 devices
+    ///
+    /// Enables the access port protection in the UICR, which takes effect after the next reset.
+    fn lock(
+        &self,
+        iface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permanent: bool,
+        permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
         permissions
-            .erase_all()
+            .device_lock()
             .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
 
-        // Reset
-        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
-        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+        if permanent {
+            return Err(ArmDebugSequenceError::custom(
+                "nRF52 devices do not support a permanent readout protection",
+            )
+            .into());
+        }
 
-        // Start erase
-        iface.write_raw_ap_register(ctrl_ap, ERASEALL, 1)?;
+        let mut memory = iface.memory_interface(default_ap)?;
+        if is_approtect_enabled(&mut *memory)? {
+            tracing::info!("Access port protection is already enabled");
+            return Ok(());
+        }
 
-        // Wait for erase done
-        while iface.read_raw_ap_register(ctrl_ap, ERASEALLSTATUS)? != 0 {}
+        nvmc::set_mode(&mut *memory, nvmc::Mode::Write)?;
+        memory.write_word_32(UICR_APPROTECT, APPROTECT_ENABLED)?;
+        nvmc::wait_ready(&mut *memory)?;
+        nvmc::set_mode(&mut *memory, nvmc::Mode::ReadOnly)?;
 
-        // Reset again
-        iface.write_raw_ap_register(ctrl_ap, RESET, 1)?;
-        iface.write_raw_ap_register(ctrl_ap, RESET, 0)?;
+        tracing::info!("Access port protection enabled, it will be active after the next reset");
 
+        Ok(())
+    }
+
+    /// `unlock` for nRF52 devices
+    ///
+    /// Erases the whole device through the CTRL-AP, which also clears the UICR.
+    fn unlock(
+        &self,
+        iface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permissions: &crate::Permissions,
+    ) -> Result<(), ArmError> {
+        let ctrl_ap = ApAddress {
+            ap: 1,
+            dp: DpAddress::Default,
+        };
+
+        // A protected device can only be accessed through the CTRL-AP.
         if !self.is_core_unlocked(iface, ctrl_ap)? {
-            return Err(ArmDebugSequenceError::custom("Could not unlock core").into());
+            self.erase_all(iface, ctrl_ap, permissions)?;
+
+            return Err(ArmError::ReAttachRequired);
         }
 
+        // The protection may be enabled in the UICR, but not be active until the next reset.
+        let pending = {
+            let mut memory = iface.memory_interface(default_ap)?;
+            is_approtect_enabled(&mut *memory)?
+        };
+
+        if !pending {
+            tracing::info!("Access port protection is not enabled");
+            return Ok(());
+        }
+
+        self.erase_all(iface, ctrl_ap, permissions)?;
+
         Err(ArmError::ReAttachRequired)
     }
 
@@ -168,6 +328,77 @@ impl ArmDebugSequence for Nrf52 {
 
         Ok(())
     }
+
+    fn option_bytes_sequence(&self) -> Option<Arc<dyn OptionBytesSequence>> {
+        Some(Self::create())
+    }
+}
+
+impl OptionBytesSequence for Nrf52 {
+    /// `write_option_bytes` for nRF52 devices
+    ///
+    /// The UICR is written word by word through the NVMC. Since bits can only be changed
+    /// from 1 to 0, the UICR is erased first if required. All contents of the UICR outside
+    /// of `region` are preserved.
+    fn write_option_bytes(
+        &self,
+        memory: &mut dyn ArmProbe,
+        region: &OptionBytesRegion,
+        data: &[u8],
+    ) -> Result<(), ArmError> {
+        if region.range.start < UICR.start || region.range.end > UICR.end {
+            return Err(ArmDebugSequenceError::custom(format!(
+                "Option bytes region {} is not located in the UICR",
+                region.name
+            ))
+            .into());
+        }
+
+        let mut uicr = vec![0u32; ((UICR.end - UICR.start) / 4) as usize];
+        memory.read_32(UICR.start, &mut uicr)?;
+
+        let offset = ((region.range.start - UICR.start) / 4) as usize;
+        let new_words = data
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+
+        let mut needs_erase = false;
+        let mut changed = vec![false; uicr.len()];
+        for (index, new) in new_words.enumerate() {
+            let old = uicr[offset + index];
+            if old != new {
+                needs_erase |= !old & new != 0;
+                changed[offset + index] = true;
+                uicr[offset + index] = new;
+            }
+        }
+
+        if needs_erase {
+            tracing::info!("Erasing UICR to set bits from 0 to 1");
+            nvmc::set_mode(memory, nvmc::Mode::Erase)?;
+            nvmc::erase_uicr(memory)?;
+        }
+
+        nvmc::set_mode(memory, nvmc::Mode::Write)?;
+        for (index, word) in uicr.iter().enumerate() {
+            // After an erase all words have to be restored, otherwise only the changed ones.
+            let write = if needs_erase {
+                *word != 0xFFFF_FFFF
+            } else {
+                changed[index]
+            };
+
+            if write {
+                memory.write_word_32(UICR.start + 4 * index as u64, *word)?;
+                nvmc::wait_ready(memory)?;
+            }
+        }
+        nvmc::set_mode(memory, nvmc::Mode::ReadOnly)?;
+
+        tracing::info!("UICR programmed, the new values will be used after the next reset");
+
+        Ok(())
+    }
 }
 
 impl From<ComponentError> for ArmError {
@@ -0,0 +1,322 @@
+//! Common option bytes programming code for STM32 devices.
+
+use std::time::{Duration, Instant};
+
+use probe_rs_target::OptionBytesRegion;
+
+use crate::{
+    architecture::arm::{
+        memory::adi_v5_memory_interface::ArmProbe,
+        sequences::{ArmDebugSequenceError, OptionBytesSequence, ProtectionState},
+        ArmError,
+    },
+    session::MissingPermissions,
+    Permissions,
+};
+
+/// Key sequence to unlock the flash control register.
+const KEY: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+/// Key sequence to unlock the option bytes.
+const OPTKEY: [u32; 2] = [0x0819_2A3B, 0x4C5D_6E7F];
+
+/// The busy flag in the flash status register, which is at the same position for all families.
+const SR_BSY: u32 = 1 << 16;
+
+/// Readout protection level 0, no protection.
+const RDP_LEVEL_0: u8 = 0xAA;
+/// Readout protection level 1, reverting to level 0 mass erases the flash.
+const RDP_LEVEL_1: u8 = 0xBB;
+/// Readout protection level 2, debug access is disabled permanently.
+const RDP_LEVEL_2: u8 = 0xCC;
+
+/// Maximum time to program the option bytes, without a mass erase.
+const PROGRAM_TIMEOUT: Duration = Duration::from_secs(2);
+/// Maximum time for a readout protection regression, which mass erases the flash.
+const REGRESSION_TIMEOUT: Duration = Duration::from_secs(40);
+
+/// The flash interface used to program the option bytes of an STM32 device.
+///
+/// On all supported families the option bytes are loaded into registers of the flash
+/// interface, which are modified and then written back to the option bytes by setting
+/// the `OPTSTRT` bit. The region in the target description must therefore cover these
+/// registers, and not the option bytes area in flash.
+#[derive(Debug, Clone, Copy)]
+pub enum Stm32OptionBytes {
+    /// STM32F2, STM32F4 and STM32F7: the option bytes are mapped to `FLASH_OPTCR` and
+    /// `FLASH_OPTCR1`, and are reloaded on the next reset.
+    Optcr {
+        /// Base address of the flash interface registers.
+        flash: u64,
+    },
+    /// STM32G0, STM32G4, STM32L4, STM32WB and STM32WL: the option bytes are mapped to
+    /// `FLASH_OPTR` and the following registers, and are reloaded by setting `OBL_LAUNCH`,
+    /// which resets the device.
+    Optr {
+        /// Base address of the flash interface registers.
+        flash: u64,
+    },
+}
+
+impl Stm32OptionBytes {
+    /// Read the current readout protection state.
+    pub fn protection_state(&self, memory: &mut dyn ArmProbe) -> Result<ProtectionState, ArmError> {
+        let (address, shift) = self.rdp_location();
+        let rdp = (memory.read_word_32(address)? >> shift) as u8;
+
+        Ok(match rdp {
+            RDP_LEVEL_0 => ProtectionState::Unprotected,
+            RDP_LEVEL_2 => ProtectionState::PermanentlyProtected,
+            _ => ProtectionState::Protected,
+        })
+    }
+
+    /// Set the readout protection to level 1, or to level 2 if `permanent` is set.
+    pub fn lock(
+        &self,
+        memory: &mut dyn ArmProbe,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        permissions
+            .device_lock()
+            .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
+
+        let rdp = if permanent {
+            permissions
+                .irreversible_writes()
+                .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
+            tracing::warn!("Setting readout protection level 2, this can not be undone");
+            RDP_LEVEL_2
+        } else {
+            RDP_LEVEL_1
+        };
+
+        match self.protection_state(memory)? {
+            ProtectionState::PermanentlyProtected => {
+                tracing::info!("Readout protection level 2 is already active");
+                Ok(())
+            }
+            ProtectionState::Protected if !permanent => {
+                tracing::info!("Readout protection level 1 is already active");
+                Ok(())
+            }
+            _ => self.write_rdp(memory, rdp, PROGRAM_TIMEOUT),
+        }
+    }
+
+    /// Revert the readout protection to level 0, which mass erases the flash.
+    pub fn unlock(
+        &self,
+        memory: &mut dyn ArmProbe,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        match self.protection_state(memory)? {
+            ProtectionState::Unprotected => {
+                tracing::info!("Readout protection is not active");
+                Ok(())
+            }
+            ProtectionState::PermanentlyProtected => Err(ArmDebugSequenceError::custom(
+                "Readout protection level 2 is active, the device can not be unlocked",
+            )
+            .into()),
+            ProtectionState::Protected => {
+                permissions
+                    .erase_all()
+                    .map_err(|MissingPermissions(desc)| ArmError::MissingPermissions(desc))?;
+
+                tracing::warn!("Reverting readout protection to level 0, this erases the flash");
+                self.write_rdp(memory, RDP_LEVEL_0, REGRESSION_TIMEOUT)
+            }
+        }
+    }
+
+    /// The address of the register containing the RDP byte, and the position of the byte.
+    fn rdp_location(&self) -> (u64, u32) {
+        match *self {
+            Stm32OptionBytes::Optcr { flash } => (flash + 0x14, 8),
+            Stm32OptionBytes::Optr { flash } => (flash + 0x20, 0),
+        }
+    }
+
+    fn write_rdp(
+        &self,
+        memory: &mut dyn ArmProbe,
+        rdp: u8,
+        timeout: Duration,
+    ) -> Result<(), ArmError> {
+        let (address, shift) = self.rdp_location();
+        let value = memory.read_word_32(address)?;
+        let value = (value & !(0xFF << shift)) | ((rdp as u32) << shift);
+
+        self.write(memory, &[(address, value)], timeout)
+    }
+
+    fn write(
+        &self,
+        memory: &mut dyn ArmProbe,
+        words: &[(u64, u32)],
+        timeout: Duration,
+    ) -> Result<(), ArmError> {
+        match *self {
+            Stm32OptionBytes::Optcr { flash } => Self::write_optcr(memory, flash, words, timeout),
+            Stm32OptionBytes::Optr { flash } => Self::write_optr(memory, flash, words, timeout),
+        }
+    }
+
+    fn wait_for_not_busy(
+        memory: &mut dyn ArmProbe,
+        status_register: u64,
+        timeout: Duration,
+    ) -> Result<u32, ArmError> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let status = memory.read_word_32(status_register)?;
+            if status & SR_BSY == 0 {
+                return Ok(status);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        tracing::error!(
+            "Option bytes programming did not finish within {} seconds",
+            timeout.as_secs()
+        );
+        Err(ArmError::Timeout)
+    }
+
+    fn write_optcr(
+        memory: &mut dyn ArmProbe,
+        flash: u64,
+        words: &[(u64, u32)],
+        timeout: Duration,
+    ) -> Result<(), ArmError> {
+        const OPTKEYR: u64 = 0x08;
+        const SR: u64 = 0x0C;
+        const OPTCR: u64 = 0x14;
+        const OPTCR_OPTLOCK: u32 = 1 << 0;
+        const OPTCR_OPTSTRT: u32 = 1 << 1;
+        // Programming, write protection and operation errors.
+        const SR_ERRORS: u32 = 0xF2;
+
+        if memory.read_word_32(flash + OPTCR)? & OPTCR_OPTLOCK != 0 {
+            for key in OPTKEY {
+                memory.write_word_32(flash + OPTKEYR, key)?;
+            }
+        }
+
+        // Write OPTCR last, since it triggers the programming operation.
+        let mut optcr = None;
+        for &(address, word) in words {
+            if address == flash + OPTCR {
+                optcr = Some(word & !(OPTCR_OPTLOCK | OPTCR_OPTSTRT));
+            } else {
+                memory.write_word_32(address, word)?;
+            }
+        }
+        let optcr = match optcr {
+            Some(optcr) => optcr,
+            None => memory.read_word_32(flash + OPTCR)? & !OPTCR_OPTLOCK,
+        };
+
+        memory.write_word_32(flash + OPTCR, optcr)?;
+        memory.write_word_32(flash + OPTCR, optcr | OPTCR_OPTSTRT)?;
+
+        let status = Self::wait_for_not_busy(memory, flash + SR, timeout)?;
+
+        memory.write_word_32(flash + OPTCR, optcr | OPTCR_OPTLOCK)?;
+
+        if status & SR_ERRORS != 0 {
+            memory.write_word_32(flash + SR, status & SR_ERRORS)?;
+            return Err(ArmDebugSequenceError::custom(format!(
+                "Option bytes programming failed, FLASH_SR = {status:#010x}"
+            ))
+            .into());
+        }
+
+        tracing::info!("Option bytes programmed, they will be loaded on the next reset");
+
+        Ok(())
+    }
+
+    fn write_optr(
+        memory: &mut dyn ArmProbe,
+        flash: u64,
+        words: &[(u64, u32)],
+        timeout: Duration,
+    ) -> Result<(), ArmError> {
+        const KEYR: u64 = 0x08;
+        const OPTKEYR: u64 = 0x0C;
+        const SR: u64 = 0x10;
+        const CR: u64 = 0x14;
+        const CR_OPTSTRT: u32 = 1 << 17;
+        const CR_OBL_LAUNCH: u32 = 1 << 27;
+        const CR_OPTLOCK: u32 = 1 << 30;
+        const CR_LOCK: u32 = 1 << 31;
+        // Operation, programming and option validity errors.
+        const SR_ERRORS: u32 = 0x83FA;
+
+        if memory.read_word_32(flash + CR)? & CR_LOCK != 0 {
+            for key in KEY {
+                memory.write_word_32(flash + KEYR, key)?;
+            }
+        }
+        if memory.read_word_32(flash + CR)? & CR_OPTLOCK != 0 {
+            for key in OPTKEY {
+                memory.write_word_32(flash + OPTKEYR, key)?;
+            }
+        }
+
+        // Clear any stale errors from previous operations.
+        let status = memory.read_word_32(flash + SR)?;
+        memory.write_word_32(flash + SR, status & SR_ERRORS)?;
+
+        for &(address, word) in words {
+            memory.write_word_32(address, word)?;
+        }
+
+        let cr = memory.read_word_32(flash + CR)?;
+        memory.write_word_32(flash + CR, cr | CR_OPTSTRT)?;
+
+        let status = Self::wait_for_not_busy(memory, flash + SR, timeout)?;
+        if status & SR_ERRORS != 0 {
+            memory.write_word_32(flash + SR, status & SR_ERRORS)?;
+            memory.write_word_32(flash + CR, cr | CR_LOCK)?;
+            return Err(ArmDebugSequenceError::custom(format!(
+                "Option bytes programming failed, FLASH_SR = {status:#010x}"
+            ))
+            .into());
+        }
+
+        tracing::info!("Option bytes programmed, reloading them");
+
+        // Launching the option byte loading resets the device, so the write
+        // is usually not acknowledged.
+        if let Err(error) = memory.write_word_32(flash + CR, cr | CR_OBL_LAUNCH) {
+            tracing::debug!("Ignoring error during option bytes reload: {error}");
+        }
+
+        Err(ArmError::ReAttachRequired)
+    }
+}
+
+impl OptionBytesSequence for Stm32OptionBytes {
+    fn write_option_bytes(
+        &self,
+        memory: &mut dyn ArmProbe,
+        region: &OptionBytesRegion,
+        data: &[u8],
+    ) -> Result<(), ArmError> {
+        let words = data
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, chunk)| {
+                (
+                    region.range.start + 4 * index as u64,
+                    u32::from_le_bytes(chunk.try_into().unwrap()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        self.write(memory, &words, PROGRAM_TIMEOUT)
+    }
+}
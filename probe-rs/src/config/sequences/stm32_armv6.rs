@@ -6,9 +6,16 @@ use std::sync::Arc;
 
 use probe_rs_target::CoreType;
 
-use crate::architecture::arm::{
-    ap::MemoryAp, memory::adi_v5_memory_interface::ArmProbe, sequences::ArmDebugSequence, ArmError,
-    ArmProbeInterface,
+use super::stm32::Stm32OptionBytes;
+use crate::{
+    architecture::arm::{
+        ap::MemoryAp,
+        memory::adi_v5_memory_interface::ArmProbe,
+        sequences::{ArmDebugSequence, OptionBytesSequence, ProtectionState},
+        ArmError, ArmProbeInterface,
+    },
+    probe::DebugProbeError,
+    Permissions,
 };
 
 /// Supported families for custom sequences on ARMv6 STM32 devices.
@@ -35,6 +42,22 @@ impl Stm32Armv6 {
     pub fn create(family: Stm32Armv6Family) -> Arc<Self> {
         Arc::new(Self { family })
     }
+
+    fn option_bytes(&self) -> Option<Stm32OptionBytes> {
+        match self.family {
+            // The G0 uses the same flash interface as the STM32L4 for the option bytes.
+            Stm32Armv6Family::G0 => Some(Stm32OptionBytes::Optr { flash: 0x4002_2000 }),
+            // F0 and L0 program the option bytes like regular flash, which is not supported yet.
+            Stm32Armv6Family::F0 | Stm32Armv6Family::L0 => None,
+        }
+    }
+
+    fn readout_protection(&self) -> Result<Stm32OptionBytes, ArmError> {
+        self.option_bytes().ok_or_else(|| {
+            DebugProbeError::NotImplemented("Readout protection is not available on this device")
+                .into()
+        })
+    }
 }
 
 mod rcc {
@@ -173,4 +196,45 @@ impl ArmDebugSequence for Stm32Armv6 {
 
         Ok(())
     }
+
+    fn option_bytes_sequence(&self) -> Option<Arc<dyn OptionBytesSequence>> {
+        self.option_bytes()
+            .map(|option_bytes| Arc::new(option_bytes) as Arc<dyn OptionBytesSequence>)
+    }
+
+    fn protection_state(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+    ) -> Result<ProtectionState, ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.protection_state(&mut *memory)
+    }
+
+    fn lock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.lock(&mut *memory, permanent, permissions)
+    }
+
+    fn unlock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.unlock(&mut *memory, permissions)
+    }
 }
@@ -12,22 +12,44 @@ use std::sync::Arc;
 
 use probe_rs_target::CoreType;
 
-use crate::architecture::arm::{
-    ap::MemoryAp,
-    component::TraceSink,
-    memory::{adi_v5_memory_interface::ArmProbe, CoresightComponent},
-    sequences::ArmDebugSequence,
-    ArmError, ArmProbeInterface,
+use super::stm32::Stm32OptionBytes;
+use crate::{
+    architecture::arm::{
+        ap::MemoryAp,
+        component::TraceSink,
+        memory::{adi_v5_memory_interface::ArmProbe, CoresightComponent},
+        sequences::{ArmDebugSequence, OptionBytesSequence, ProtectionState},
+        ArmError, ArmProbeInterface,
+    },
+    probe::DebugProbeError,
+    Permissions,
 };
 
 /// Marker structure for most ARMv7 STM32 devices.
 #[derive(Debug)]
-pub struct Stm32Armv7 {}
+pub struct Stm32Armv7 {
+    option_bytes: Option<Stm32OptionBytes>,
+}
 
 impl Stm32Armv7 {
     /// Create the sequencer for most ARMv7 STM32 families.
     pub fn create() -> Arc<Self> {
-        Arc::new(Self {})
+        Arc::new(Self { option_bytes: None })
+    }
+
+    /// Create the sequencer for an ARMv7 STM32 family which supports programming
+    /// the option bytes through the given flash interface.
+    pub fn create_with_option_bytes(option_bytes: Stm32OptionBytes) -> Arc<Self> {
+        Arc::new(Self {
+            option_bytes: Some(option_bytes),
+        })
+    }
+
+    fn readout_protection(&self) -> Result<Stm32OptionBytes, ArmError> {
+        self.option_bytes.ok_or_else(|| {
+            DebugProbeError::NotImplemented("Readout protection is not available on this device")
+                .into()
+        })
     }
 }
 
@@ -120,4 +142,45 @@ impl ArmDebugSequence for Stm32Armv7 {
         cr.write(&mut *memory)?;
         Ok(())
     }
+
+    fn option_bytes_sequence(&self) -> Option<Arc<dyn OptionBytesSequence>> {
+        self.option_bytes
+            .map(|option_bytes| Arc::new(option_bytes) as Arc<dyn OptionBytesSequence>)
+    }
+
+    fn protection_state(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+    ) -> Result<ProtectionState, ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.protection_state(&mut *memory)
+    }
+
+    fn lock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.lock(&mut *memory, permanent, permissions)
+    }
+
+    fn unlock(
+        &self,
+        interface: &mut dyn ArmProbeInterface,
+        default_ap: MemoryAp,
+        permissions: &Permissions,
+    ) -> Result<(), ArmError> {
+        let option_bytes = self.readout_protection()?;
+        let mut memory = interface.memory_interface(default_ap)?;
+
+        option_bytes.unlock(&mut *memory, permissions)
+    }
 }
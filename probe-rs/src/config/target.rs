@@ -15,6 +15,7 @@ use super::{
         nrf91::Nrf9160,
         nxp_armv7m::{MIMXRT10xx, MIMXRT11xx},
         nxp_armv8m::{LPC55Sxx, MIMXRT5xxS},
+        stm32::Stm32OptionBytes,
         stm32_armv6::{Stm32Armv6, Stm32Armv6Family},
         stm32_armv7::Stm32Armv7,
         stm32h7::Stm32h7,
@@ -31,7 +32,9 @@ use crate::architecture::{
     xtensa::sequences::{DefaultXtensaSequence, XtensaDebugSequence},
 };
 use crate::flashing::FlashLoader;
-use probe_rs_target::{Architecture, BinaryFormat, ChipFamily, Jtag, MemoryRange};
+use probe_rs_target::{
+    Architecture, BinaryFormat, ChipFamily, Jtag, MemoryRange, OptionBytesRegion, SpiFlash,
+};
 use std::sync::Arc;
 
 /// This describes a complete target with a fixed chip model and variant.
@@ -62,6 +65,10 @@ pub struct Target {
     pub jtag: Option<Jtag>,
     /// The default executable format for the target.
     pub default_format: BinaryFormat,
+    /// Option bytes, UICR, OTP and fuse regions of the target.
+    pub option_bytes: Vec<OptionBytesRegion>,
+    /// External SPI NOR flashes of the target, which are programmed through their controller.
+    pub spi_flash: Vec<SpiFlash>,
 }
 
 impl std::fmt::Debug for Target {
@@ -163,16 +170,24 @@ impl Target {
             DebugSequence::Arm(Stm32Armv6::create(Stm32Armv6Family::L0))
         } else if chip.name.starts_with("STM32G0") {
             DebugSequence::Arm(Stm32Armv6::create(Stm32Armv6Family::G0))
-        } else if chip.name.starts_with("STM32F1")
-            || chip.name.starts_with("STM32F2")
-            || chip.name.starts_with("STM32F3")
+        } else if chip.name.starts_with("STM32F2")
             || chip.name.starts_with("STM32F4")
             || chip.name.starts_with("STM32F7")
-            || chip.name.starts_with("STM32G4")
+        {
+            DebugSequence::Arm(Stm32Armv7::create_with_option_bytes(
+                Stm32OptionBytes::Optcr { flash: 0x4002_3C00 },
+            ))
+        } else if chip.name.starts_with("STM32G4") || chip.name.starts_with("STM32L4") {
+            DebugSequence::Arm(Stm32Armv7::create_with_option_bytes(
+                Stm32OptionBytes::Optr { flash: 0x4002_2000 },
+            ))
+        } else if chip.name.starts_with("STM32WB") || chip.name.starts_with("STM32WL") {
+            DebugSequence::Arm(Stm32Armv7::create_with_option_bytes(
+                Stm32OptionBytes::Optr { flash: 0x5800_4000 },
+            ))
+        } else if chip.name.starts_with("STM32F1")
+            || chip.name.starts_with("STM32F3")
             || chip.name.starts_with("STM32L1")
-            || chip.name.starts_with("STM32L4")
-            || chip.name.starts_with("STM32WB")
-            || chip.name.starts_with("STM32WL")
         {
             DebugSequence::Arm(Stm32Armv7::create())
         } else if chip.name.starts_with("STM32H7") {
@@ -239,6 +254,8 @@ impl Target {
             rtt_scan_regions,
             jtag: chip.jtag.clone(),
             default_format: chip.default_binary_format.clone().unwrap_or_default(),
+            option_bytes: chip.option_bytes.clone(),
+            spi_flash: chip.spi_flash.clone(),
         })
     }
 
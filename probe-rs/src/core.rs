@@ -168,6 +168,11 @@ pub trait CoreInterface: MemoryInterface {
     fn disable_vector_catch(&mut self, _condition: VectorCatchCondition) -> Result<(), Error> {
         Err(Error::NotImplemented("vector catch"))
     }
+
+    /// Samples the program counter without halting the core, see [`Core::read_pc_samples`].
+    fn read_pc_samples(&mut self, _samples: &mut [u64]) -> Result<usize, Error> {
+        Err(Error::NotImplemented("non-halting PC sampling"))
+    }
 }
 
 impl<'probe> MemoryInterface for Core<'probe> {
@@ -295,6 +300,12 @@ pub trait ExceptionInterface {
         memory: &mut dyn MemoryInterface,
         stackframe_registers: &crate::debug::DebugRegisters,
     ) -> Result<String, crate::Error>;
+
+    /// Convert the architecture specific exception number into its name, e.g. for exceptions which were traced,
+    /// rather than read from the core. Returns `None` if the exception numbers of the core are not known.
+    fn exception_name(&self, _exception_number: u32) -> Option<String> {
+        None
+    }
 }
 
 /// Placeholder for exception handling for cores where handling exceptions is not yet supported.
@@ -724,6 +735,18 @@ impl<'probe> Core<'probe> {
         self.inner.disable_vector_catch(condition)
     }
 
+    /// Samples the program counter without halting the core, by reading the PC sample register
+    /// of the debug unit repeatedly, e.g. `DWT_PCSR` on Cortex-M cores.
+    ///
+    /// Up to `samples.len()` samples are read in a single batch. Samples which are not valid,
+    /// e.g. because the core was halted or sleeping, are discarded, and the valid samples are
+    /// moved to the start of `samples`. Returns the number of valid samples.
+    ///
+    /// Returns [`Error::NotImplemented`] if the core does not support PC sampling.
+    pub fn read_pc_samples(&mut self, samples: &mut [u64]) -> Result<usize, Error> {
+        self.inner.read_pc_samples(samples)
+    }
+
     /// Dumps core info with the current state.
     ///
     /// # Arguments
@@ -1,6 +1,6 @@
 use super::{
-    function_die::FunctionDie, get_object_reference, unit_info::UnitInfo, variable::*, DebugError,
-    DebugRegisters, StackFrame, VariableCache,
+    extract_byte_size, function_die::FunctionDie, get_object_reference, unit_info::UnitInfo,
+    variable::*, DebugError, DebugRegisters, StackFrame, VariableCache,
 };
 use crate::core::UnwindRule;
 use crate::debug::stack_frame::StackFrameInfo;
@@ -18,7 +18,14 @@ use probe_rs_target::InstructionSet;
 use typed_path::{TypedPath, TypedPathBuf};
 
 use std::{
-    borrow, cmp::Ordering, num::NonZeroU64, ops::ControlFlow, path::Path, rc::Rc, str::from_utf8,
+    borrow,
+    cmp::Ordering,
+    collections::HashMap,
+    num::NonZeroU64,
+    ops::{ControlFlow, Range},
+    path::Path,
+    rc::Rc,
+    str::from_utf8,
 };
 
 pub(crate) type GimliReader = gimli::EndianReader<gimli::LittleEndian, std::rc::Rc<[u8]>>;
@@ -232,6 +239,137 @@ impl DebugInfo {
         None
     }
 
+    /// Returns the source location of every address range with line information, sorted by
+    /// address.
+    ///
+    /// This is the complete line table of the program, e.g. to determine which source lines
+    /// contain code.
+    pub fn line_table(&self) -> Vec<(Range<u64>, SourceLocation)> {
+        let mut table = Vec::new();
+
+        for unit_info in &self.unit_infos {
+            let unit = &unit_info.unit;
+            let Some(ilnp) = unit.line_program.clone() else {
+                continue;
+            };
+            let (program, sequences) = match ilnp.sequences() {
+                Ok(value) => value,
+                Err(error) => {
+                    tracing::warn!(
+                        "No valid line program found for unit {:?}: {:?}",
+                        unit.dwo_name(),
+                        error
+                    );
+                    continue;
+                }
+            };
+
+            let mut files = HashMap::new();
+            for sequence in &sequences {
+                let mut previous_row: Option<gimli::LineRow> = None;
+                let mut rows = program.resume_from(sequence);
+
+                while let Ok(Some((_, row))) = rows.next_row() {
+                    if let Some(previous_row) = previous_row {
+                        let file = files
+                            .entry(previous_row.file_index())
+                            .or_insert_with(|| {
+                                self.find_file_and_directory(unit, previous_row.file_index())
+                            })
+                            .clone();
+
+                        if let (Some(line), Some((file, directory))) = (previous_row.line(), file) {
+                            if previous_row.address() < row.address() {
+                                table.push((
+                                    previous_row.address()..row.address(),
+                                    SourceLocation {
+                                        line: Some(line.get()),
+                                        column: Some(previous_row.column().into()),
+                                        file,
+                                        directory,
+                                    },
+                                ));
+                            }
+                        }
+                    }
+                    previous_row = (!row.end_sequence()).then_some(*row);
+                }
+            }
+        }
+
+        table.sort_by_key(|(range, _)| range.start);
+        table
+    }
+
+    /// Returns the address and the size in bytes of the static variable `name`.
+    ///
+    /// The name can be the plain name of the variable, e.g. `COUNTER`, or include the namespaces
+    /// it is declared in, e.g. `my_app::COUNTER`. The size is `None` if it can't be determined.
+    pub fn static_variable(&self, name: &str) -> Option<(u64, Option<u64>)> {
+        for unit_info in &self.unit_infos {
+            let unit = &unit_info.unit;
+            let mut entries = unit.entries();
+            let mut depth = 0;
+            let mut namespaces: Vec<(isize, String)> = Vec::new();
+
+            while let Ok(Some((delta, entry))) = entries.next_dfs() {
+                depth += delta;
+                while namespaces.last().is_some_and(|(level, _)| *level >= depth) {
+                    namespaces.pop();
+                }
+
+                let Some(entry_name) = entry
+                    .attr_value(gimli::DW_AT_name)
+                    .ok()
+                    .flatten()
+                    .and_then(|value| self.dwarf.attr_string(unit, value).ok())
+                    .and_then(|value| from_utf8(&value).ok().map(str::to_owned))
+                else {
+                    continue;
+                };
+
+                match entry.tag() {
+                    gimli::DW_TAG_namespace => namespaces.push((depth, entry_name)),
+                    gimli::DW_TAG_variable => {
+                        let path = namespaces
+                            .iter()
+                            .map(|(_, namespace)| namespace.as_str())
+                            .chain(std::iter::once(entry_name.as_str()))
+                            .collect::<Vec<_>>()
+                            .join("::");
+                        if entry_name != name && path != name {
+                            continue;
+                        }
+
+                        let Some(gimli::AttributeValue::Exprloc(expression)) =
+                            entry.attr_value(gimli::DW_AT_location).ok().flatten()
+                        else {
+                            continue;
+                        };
+                        let Ok(Some(gimli::Operation::Address { address })) =
+                            expression.operations(unit.encoding()).next()
+                        else {
+                            continue;
+                        };
+
+                        let byte_size = match entry.attr_value(gimli::DW_AT_type) {
+                            Ok(Some(gimli::AttributeValue::UnitRef(offset))) => unit
+                                .entry(offset)
+                                .ok()
+                                .and_then(|type_entry| extract_byte_size(&type_entry)),
+                            _ => None,
+                        };
+
+                        return Some((address, byte_size));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        None
+    }
+
     /// We do not actually resolve the children of `[VariableName::StaticScope]` automatically, and only create the necessary header in the `VariableCache`.
     /// This allows us to resolve the `[VariableName::StaticScope]` on demand/lazily, when a user requests it from the debug client.
     /// This saves a lot of overhead when a user only wants to see the `[VariableName::LocalScope]` or `[VariableName::Registers]` while stepping through code (the most common use cases)
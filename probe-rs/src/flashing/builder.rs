@@ -163,7 +163,7 @@ impl FlashDataBlockSpan {
 }
 
 /// A helper structure to build a flash layout from a set of data blocks.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub(super) struct FlashBuilder {
     pub(super) data: BTreeMap<u64, Vec<u8>>,
 }
@@ -219,6 +219,51 @@ impl FlashBuilder {
         Ok(())
     }
 
+    /// Overwrites staged data with `data`.
+    ///
+    /// Parts of `data` for which no data was staged yet are staged as new chunks.
+    pub(super) fn patch_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        let end = address + data.len() as u64;
+
+        // Start with the chunk before `address`, if it reaches into the patched range.
+        let start = match self.data.range(..address).next_back() {
+            Some((&prev_addr, prev_data)) if prev_addr + prev_data.len() as u64 > address => {
+                prev_addr
+            }
+            _ => address,
+        };
+
+        let mut gaps = Vec::new();
+        let mut next = address;
+        for (&chunk_addr, chunk) in self.data.range_mut(start..end) {
+            let overlap_start = chunk_addr.max(address);
+            let overlap_end = (chunk_addr + chunk.len() as u64).min(end);
+
+            if overlap_start > next {
+                gaps.push(next..overlap_start);
+            }
+
+            chunk[(overlap_start - chunk_addr) as usize..(overlap_end - chunk_addr) as usize]
+                .copy_from_slice(
+                    &data[(overlap_start - address) as usize..(overlap_end - address) as usize],
+                );
+            next = overlap_end;
+        }
+
+        if next < end {
+            gaps.push(next..end);
+        }
+
+        for gap in gaps {
+            self.add_data(
+                gap.start,
+                &data[(gap.start - address) as usize..(gap.end - address) as usize],
+            )?;
+        }
+
+        Ok(())
+    }
+
     /// Check whether there is staged data for a given address range.
     pub(crate) fn has_data_in_range(&self, range: &Range<u64>) -> bool {
         self.data_in_range(range).next().is_some()
@@ -1145,4 +1190,23 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn patch_staged_and_unstaged_data() {
+        let mut flash_builder = FlashBuilder::new();
+        flash_builder.add_data(0, &[0; 4]).unwrap();
+        flash_builder.add_data(8, &[0; 4]).unwrap();
+
+        flash_builder.patch_data(2, &[1; 8]).unwrap();
+        flash_builder.patch_data(14, &[2; 2]).unwrap();
+
+        assert_eq!(
+            flash_builder.data_in_range(&(0..16)).collect::<Vec<_>>(),
+            vec![
+                (0, &[0, 0, 1, 1, 1, 1, 1, 1][..]),
+                (8, &[1, 1, 0, 0][..]),
+                (14, &[2, 2][..]),
+            ]
+        );
+    }
 }
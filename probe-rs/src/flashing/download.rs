@@ -1,10 +1,10 @@
 use object::{
     elf::FileHeader32, elf::PT_LOAD, read::elf::FileHeader, read::elf::ProgramHeader, Endianness,
-    Object, ObjectSection,
+    Object, ObjectSection, ObjectSymbol,
 };
 use probe_rs_target::MemoryRange;
 
-use std::{fs::File, path::Path, str::FromStr};
+use std::{collections::HashMap, fs::File, ops::Range, path::Path, str::FromStr};
 
 use super::*;
 use crate::session::Session;
@@ -306,6 +306,55 @@ pub(super) fn extract_from_elf<'data>(
     Ok(extracted_sections)
 }
 
+/// Extracts the defined symbols of an ELF file, which are located in loadable segments.
+///
+/// The address of each symbol is translated to the physical address of its segment, which is
+/// where the symbol is programmed to. This is different from the symbol address for e.g.
+/// initialized data, which is copied from flash to RAM at startup.
+pub(super) fn extract_symbols_from_elf(
+    elf_data: &[u8],
+) -> Result<HashMap<String, Range<u64>>, FileDownloadError> {
+    let elf_header = FileHeader32::<Endianness>::parse(elf_data)?;
+    let endian = elf_header.endian()?;
+    let binary = object::read::elf::ElfFile::<FileHeader32<Endianness>>::parse(elf_data)?;
+
+    let segments = elf_header
+        .program_headers(endian, elf_data)?
+        .iter()
+        .filter(|segment| segment.p_type(endian) == PT_LOAD)
+        .map(|segment| {
+            let vaddr: u64 = segment.p_vaddr(endian).into();
+            let paddr: u64 = segment.p_paddr(endian).into();
+            let filesz: u64 = segment.p_filesz(endian).into();
+            (vaddr..vaddr + filesz, paddr)
+        })
+        .collect::<Vec<_>>();
+
+    let mut symbols = HashMap::new();
+    for symbol in binary.symbols() {
+        if symbol.is_undefined() {
+            continue;
+        }
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+
+        let address = symbol.address();
+        let size = symbol.size().max(1);
+        let Some((virtual_range, physical_address)) = segments
+            .iter()
+            .find(|(range, _)| range.contains_range(&(address..address + size)))
+        else {
+            continue;
+        };
+
+        let start = address - virtual_range.start + physical_address;
+        symbols.insert(name.to_string(), start..start + symbol.size());
+    }
+
+    Ok(symbols)
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
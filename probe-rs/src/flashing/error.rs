@@ -1,5 +1,7 @@
 use crate::config::{NvmRegion, RamRegion, TargetDescriptionSource};
-use crate::error;
+use crate::debug::{DebugInfo, DebugRegisters, SourceLocation};
+use crate::{error, exception_handler_for_core, Core};
+use std::fmt::Write;
 use std::ops::Range;
 
 /// Describes any error that happened during the or in preparation for the flashing procedure.
@@ -58,6 +60,17 @@ pub enum FlashError {
         /// The error code the called routine returned.
         error_code: u32,
     },
+    /// A routine of the flash algorithm failed.
+    ///
+    /// The report contains the state of the algorithm when it failed, and its last RTT output.
+    #[error("The flash algorithm failed: {report}")]
+    AlgorithmFailed {
+        /// The state of the flash algorithm when it failed.
+        report: Box<FlashAlgorithmReport>,
+        /// The error which caused the failure.
+        #[source]
+        source: Box<FlashError>,
+    },
     /// The core entered an unexpected status while executing a flashing operation.
     #[error("The core entered an unexpected status: {status:?}.")]
     UnexpectedCoreStatus {
@@ -162,4 +175,227 @@ pub enum FlashError {
     /// The register value supplied for this flash algorithm is out of the supported range.
     #[error("The register value {0:08X?} is out of the supported range.")]
     RegisterValueNotSupported(u64),
+    /// The target description does not contain an option bytes region with the given name.
+    #[error("No option bytes region named '{0}' was found in the target description.")]
+    OptionBytesRegionNotFound(String),
+    /// The option bytes region does not contain a field with the given name.
+    #[error("The option bytes region '{region}' does not contain a field named '{field}'.")]
+    OptionBytesFieldNotFound {
+        /// The name of the option bytes region.
+        region: String,
+        /// The name of the field which was not found.
+        field: String,
+    },
+    /// The value is not valid for the given option bytes field.
+    #[error("'{value}' is not a valid value for the option bytes field '{field}'.")]
+    OptionBytesInvalidValue {
+        /// The name of the field.
+        field: String,
+        /// The value which was tried to be set.
+        value: String,
+    },
+    /// The contents do not have the size of the option bytes region.
+    #[error("The option bytes region '{name}' has a size of {expected} bytes, but {actual} bytes were given.")]
+    OptionBytesSizeMismatch {
+        /// The name of the option bytes region.
+        name: String,
+        /// The size of the region.
+        expected: usize,
+        /// The size of the given contents.
+        actual: usize,
+    },
+    /// Programming option bytes is not supported for this target.
+    #[error("Programming option bytes is not supported for target {name}.")]
+    OptionBytesNotSupported {
+        /// The name of the chip.
+        name: String,
+    },
+    /// The SPI flash did not respond with a valid SFDP header.
+    #[error("No valid SFDP header was found on SPI flash {name}. Is the controller configured correctly?")]
+    SfdpNotFound {
+        /// The name of the SPI flash.
+        name: String,
+    },
+    /// The SFDP parameters of the SPI flash are not supported.
+    #[error("The SFDP parameters of SPI flash {name} are not supported: {reason}")]
+    SfdpNotSupported {
+        /// The name of the SPI flash.
+        name: String,
+        /// Why the parameters are not supported.
+        reason: String,
+    },
+    /// The SPI flash did not finish an operation in time.
+    #[error("SPI flash {name} did not finish the {operation} operation in time.")]
+    SpiFlashTimeout {
+        /// The name of the SPI flash.
+        name: String,
+        /// The operation which timed out.
+        operation: &'static str,
+    },
+    /// The symbol to patch was not found in the loaded ELF file.
+    #[error("The symbol '{0}' was not found in the loaded ELF file.")]
+    SymbolNotFound(String),
+    /// The patched data does not fit into the symbol.
+    #[error("The symbol '{name}' has a size of {size} bytes, but {actual} bytes were given.")]
+    PatchTooLarge {
+        /// The name of the symbol.
+        name: String,
+        /// The size of the symbol.
+        size: u64,
+        /// The size of the patched data.
+        actual: usize,
+    },
+}
+
+/// The state of a flash algorithm after one of its routines failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashAlgorithmReport {
+    /// The name of the routine which failed, e.g. `program_page`.
+    pub routine: &'static str,
+    /// The core registers, if the routine did not return, e.g. because it timed out.
+    ///
+    /// The core is left halted in this state, so the stack of the algorithm can be unwound
+    /// with [`FlashAlgorithmReport::unwind`].
+    pub registers: Vec<(&'static str, u64)>,
+    /// The program counter, if the routine did not return.
+    pub pc: Option<u64>,
+    /// The stack of the algorithm, if it was unwound with [`FlashAlgorithmReport::unwind`].
+    pub stack_frames: Vec<FlashAlgorithmFrame>,
+    /// The last output of the algorithm over RTT, if it supports RTT.
+    pub rtt_output: String,
+}
+
+/// A frame of the unwound stack of a flash algorithm, see [`FlashAlgorithmReport::unwind`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashAlgorithmFrame {
+    /// The name of the function.
+    pub function_name: String,
+    /// The program counter in the function.
+    pub pc: u64,
+    /// Whether the function was inlined into the function of the next frame.
+    pub is_inlined: bool,
+    /// The source location as `path:line`, if the debug info has one.
+    pub source_location: Option<String>,
+}
+
+impl FlashAlgorithmReport {
+    /// Unwinds the stack of the algorithm using the debug info of its ELF file, if the routine
+    /// did not return.
+    ///
+    /// The algorithm is loaded at the addresses of its ELF file, and `core` was left halted where
+    /// the routine stopped, so the stack is unwound like that of a regular program.
+    pub fn unwind(&mut self, core: &mut Core, debug_info: &DebugInfo) -> Result<(), error::Error> {
+        if self.pc.is_none() {
+            return Ok(());
+        }
+
+        let initial_registers = DebugRegisters::from_core(core);
+        let exception_interface = exception_handler_for_core(core.core_type());
+        let instruction_set = core.instruction_set().ok();
+        let stack_frames = debug_info.unwind(
+            core,
+            initial_registers,
+            exception_interface.as_ref(),
+            instruction_set,
+        )?;
+
+        self.stack_frames = stack_frames
+            .into_iter()
+            .map(|frame| {
+                Ok(FlashAlgorithmFrame {
+                    pc: frame.pc.try_into()?,
+                    function_name: frame.function_name,
+                    is_inlined: frame.is_inlined,
+                    source_location: frame.source_location.as_ref().and_then(format_location),
+                })
+            })
+            .collect::<Result<_, error::Error>>()?;
+
+        Ok(())
+    }
+}
+
+/// Formats a source location as `path:line`, or returns `None` if it has no file.
+fn format_location(location: &SourceLocation) -> Option<String> {
+    let file = location.file.as_ref()?;
+    let mut formatted = match &location.directory {
+        Some(directory) => format!("{}/{file}", directory.to_path().display()),
+        None => file.clone(),
+    };
+    if let Some(line) = location.line {
+        write!(formatted, ":{line}").expect("Writing to a String cannot fail");
+    }
+
+    Some(formatted)
+}
+
+impl std::fmt::Display for FlashAlgorithmReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pc {
+            Some(pc) => write!(f, "'{}' stopped at {pc:#010x}", self.routine)?,
+            None => write!(f, "'{}' returned an error", self.routine)?,
+        }
+
+        if !self.registers.is_empty() {
+            write!(f, "\nRegisters:")?;
+            for (name, value) in &self.registers {
+                write!(f, "\n    {name:>4}: {value:#010x}")?;
+            }
+        }
+
+        if !self.stack_frames.is_empty() {
+            write!(f, "\nStack:")?;
+            for (i, frame) in self.stack_frames.iter().enumerate() {
+                write!(
+                    f,
+                    "\n    Frame {i}: {} @ {:#010x}",
+                    frame.function_name, frame.pc
+                )?;
+                if frame.is_inlined {
+                    write!(f, " inline")?;
+                }
+                if let Some(location) = &frame.source_location {
+                    write!(f, "\n        {location}")?;
+                }
+            }
+        }
+
+        if !self.rtt_output.is_empty() {
+            write!(f, "\nLast RTT output:")?;
+            for line in self.rtt_output.lines() {
+                write!(f, "\n    {line}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_shows_registers_stack_and_rtt_output() {
+        let report = FlashAlgorithmReport {
+            routine: "program_page",
+            registers: vec![("R0", 0x2000_0100), ("PC", 0x2000_0042)],
+            pc: Some(0x2000_0042),
+            stack_frames: vec![FlashAlgorithmFrame {
+                function_name: "wait_ready".to_owned(),
+                pc: 0x2000_0042,
+                is_inlined: true,
+                source_location: Some("src/flash.rs:12".to_owned()),
+            }],
+            rtt_output: "erasing\nprogramming\n".to_owned(),
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "'program_page' stopped at 0x20000042\n\
+             Registers:\n      R0: 0x20000100\n      PC: 0x20000042\n\
+             Stack:\n    Frame 0: wait_ready @ 0x20000042 inline\n        src/flash.rs:12\n\
+             Last RTT output:\n    erasing\n    programming"
+        );
+    }
 }
@@ -1,7 +1,10 @@
 use probe_rs_target::{MemoryRegion, RamRegion, RawFlashAlgorithm};
 use tracing::Level;
 
-use super::{FlashAlgorithm, FlashBuilder, FlashError, FlashFill, FlashPage, FlashProgress};
+use super::{
+    FlashAlgorithm, FlashAlgorithmReport, FlashBuilder, FlashError, FlashFill, FlashPage,
+    FlashProgress,
+};
 use crate::config::NvmRegion;
 use crate::flashing::encoder::FlashEncoder;
 use crate::memory::MemoryInterface;
@@ -11,6 +14,10 @@ use std::{
     time::{Duration, Instant},
 };
 
+/// The number of bytes of RTT output kept to report along with a failure of the flash algorithm.
+#[cfg(feature = "rtt")]
+const RTT_OUTPUT_LIMIT: usize = 4096;
+
 pub(super) trait Operation {
     fn operation() -> u32;
     fn operation_name() -> &'static str {
@@ -221,6 +228,8 @@ impl<'session> Flasher<'session> {
             memory_map,
             progress: self.progress.clone(),
             flash_algorithm: self.flash_algorithm.clone(),
+            routine: "unknown",
+            rtt_output: String::new(),
             _operation: core::marker::PhantomData,
         };
 
@@ -489,10 +498,7 @@ impl<'session> Flasher<'session> {
 
                 t = Instant::now();
                 if result != 0 {
-                    return Err(FlashError::RoutineCallFailed {
-                        name: "program_page",
-                        error_code: result,
-                    });
+                    return Err(active.routine_returned_error("program_page", result));
                 }
 
                 // Start the next copy process.
@@ -514,10 +520,7 @@ impl<'session> Flasher<'session> {
                 })?;
 
             if result != 0 {
-                Err(FlashError::RoutineCallFailed {
-                    name: "wait_for_completion",
-                    error_code: result,
-                })
+                Err(active.routine_returned_error("wait_for_completion", result))
             } else {
                 Ok(0)
             }
@@ -569,6 +572,10 @@ pub(super) struct ActiveFlasher<'probe, O: Operation> {
     memory_map: Vec<MemoryRegion>,
     progress: FlashProgress,
     flash_algorithm: FlashAlgorithm,
+    /// The name of the routine which was called last.
+    routine: &'static str,
+    /// The last output of the flash algorithm over RTT.
+    rtt_output: String,
     _operation: core::marker::PhantomData<O>,
 }
 
@@ -596,10 +603,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                 .map_err(|error| FlashError::Init(Box::new(error)))?;
 
             if result != 0 {
-                return Err(FlashError::RoutineCallFailed {
-                    name: "init",
-                    error_code: result,
-                });
+                return Err(self.routine_returned_error("init", result));
             }
         }
 
@@ -630,10 +634,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                 .map_err(|error| FlashError::Uninit(Box::new(error)))?;
 
             if result != 0 {
-                return Err(FlashError::RoutineCallFailed {
-                    name: "uninit",
-                    error_code: result,
-                });
+                return Err(self.routine_returned_error("uninit", result));
             }
         }
         Ok(())
@@ -652,6 +653,8 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
     fn call_function(&mut self, registers: &Registers, init: bool) -> Result<(), FlashError> {
         tracing::debug!("Calling routine {:?}, init={})", &registers, init);
 
+        self.routine = self.routine_name(registers.pc.into());
+
         let algo = &self.flash_algorithm;
         let regs: &'static CoreRegisters = self.core.registers();
 
@@ -722,7 +725,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                 let rtt = match crate::rtt::Rtt::attach_region(
                     &mut self.core,
                     &self.memory_map,
-                    &crate::rtt::ScanRegion::Exact(rtt_address as u32),
+                    &crate::rtt::ScanRegion::Exact(rtt_address),
                 ) {
                     Ok(rtt) => Some(rtt),
                     Err(error) => {
@@ -761,9 +764,11 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                     break;
                 }
                 crate::CoreStatus::LockedUp => {
-                    return Err(FlashError::UnexpectedCoreStatus {
-                        status: crate::CoreStatus::LockedUp,
-                    });
+                    return Err(
+                        self.routine_did_not_return(FlashError::UnexpectedCoreStatus {
+                            status: crate::CoreStatus::LockedUp,
+                        }),
+                    );
                 }
                 _ => {
                     // All other statuses are okay: we'll just keep polling.
@@ -778,7 +783,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         }
 
         if timeout_ocurred {
-            return Err(FlashError::Core(crate::Error::Timeout));
+            return Err(self.routine_did_not_return(FlashError::Core(crate::Error::Timeout)));
         }
 
         let r: u32 = self.core.read_core_reg(regs.result_register(0))?;
@@ -795,6 +800,7 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
                         let message = String::from_utf8_lossy(&buffer[..read]).to_string();
                         let channel = channel.name().unwrap_or("unnamed");
                         tracing::debug!("RTT({channel}): {message}");
+                        keep_last(&mut self.rtt_output, &message, RTT_OUTPUT_LIMIT);
                         self.progress.message(message);
                     }
                     Ok(_) => (),
@@ -804,6 +810,108 @@ impl<'probe, O: Operation> ActiveFlasher<'probe, O> {
         }
         Ok(())
     }
+
+    /// Returns the name of the routine of the flash algorithm which starts at `pc`.
+    fn routine_name(&self, pc: u64) -> &'static str {
+        let algo = &self.flash_algorithm;
+
+        if algo.pc_init == Some(pc) {
+            "init"
+        } else if algo.pc_uninit == Some(pc) {
+            "uninit"
+        } else if algo.pc_erase_all == Some(pc) {
+            "chip_erase"
+        } else if algo.pc_erase_sector == pc {
+            "erase_sector"
+        } else if algo.pc_program_page == pc {
+            "program_page"
+        } else {
+            "unknown"
+        }
+    }
+
+    /// Creates the error for a routine which returned `error_code`.
+    ///
+    /// If the flash algorithm wrote any RTT output, it is reported along with the error.
+    fn routine_returned_error(&self, name: &'static str, error_code: u32) -> FlashError {
+        let error = FlashError::RoutineCallFailed { name, error_code };
+
+        if self.rtt_output.is_empty() {
+            return error;
+        }
+
+        FlashError::AlgorithmFailed {
+            report: Box::new(FlashAlgorithmReport {
+                routine: name,
+                registers: Vec::new(),
+                pc: None,
+                stack_frames: Vec::new(),
+                rtt_output: self.rtt_output.clone(),
+            }),
+            source: Box::new(error),
+        }
+    }
+
+    /// Halts the core after the current routine did not return, and reports the registers and
+    /// the last RTT output along with `error`.
+    fn routine_did_not_return(&mut self, error: FlashError) -> FlashError {
+        let captured = self.capture_registers().and_then(|registers| {
+            let pc = self.core.read_core_reg(self.core.program_counter())?;
+            Ok((registers, pc))
+        });
+        let (registers, pc) = match captured {
+            Ok((registers, pc)) => (registers, Some(pc)),
+            Err(capture_error) => {
+                tracing::warn!(
+                    "Failed to read the registers of the flash algorithm: {capture_error}"
+                );
+                (Vec::new(), None)
+            }
+        };
+
+        // The core is halted now, so all RTT output has been written.
+        #[cfg(feature = "rtt")]
+        if let Err(rtt_error) = self.read_rtt() {
+            tracing::debug!("Reading RTT failed: {rtt_error}");
+        }
+
+        FlashError::AlgorithmFailed {
+            report: Box::new(FlashAlgorithmReport {
+                routine: self.routine,
+                registers,
+                pc,
+                stack_frames: Vec::new(),
+                rtt_output: self.rtt_output.clone(),
+            }),
+            source: Box::new(error),
+        }
+    }
+
+    /// Halts the core, and reads its core registers.
+    fn capture_registers(&mut self) -> Result<Vec<(&'static str, u64)>, crate::Error> {
+        if !self.core.core_halted()? {
+            self.core.halt(Duration::from_millis(100))?;
+        }
+
+        let regs: &'static CoreRegisters = self.core.registers();
+        regs.core_registers()
+            .map(|register| Ok((register.name(), self.core.read_core_reg(register)?)))
+            .collect()
+    }
+}
+
+/// Appends `text` to `output`, and drops the start of `output` so that it is at most `limit` bytes.
+#[cfg(feature = "rtt")]
+fn keep_last(output: &mut String, text: &str, limit: usize) {
+    output.push_str(text);
+
+    if output.len() > limit {
+        let mut start = output.len() - limit;
+        while !output.is_char_boundary(start) {
+            start += 1;
+        }
+        output.drain(..start);
+    }
 }
 
 impl<'probe> ActiveFlasher<'probe, Erase> {
@@ -831,10 +939,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
 
             if result != 0 {
                 Err(FlashError::ChipEraseFailed {
-                    source: Box::new(FlashError::RoutineCallFailed {
-                        name: "chip_erase",
-                        error_code: result,
-                    }),
+                    source: Box::new(flasher.routine_returned_error("chip_erase", result)),
                 })
             } else {
                 Ok(())
@@ -873,10 +978,7 @@ impl<'probe> ActiveFlasher<'probe, Erase> {
         );
 
         if result != 0 {
-            Err(FlashError::RoutineCallFailed {
-                name: "erase_sector",
-                error_code: result,
-            })
+            Err(self.routine_returned_error("erase_sector", result))
         } else {
             Ok(())
         }
@@ -959,10 +1061,7 @@ impl<'p> ActiveFlasher<'p, Program> {
         if result != 0 {
             Err(FlashError::PageWrite {
                 page_address: address,
-                source: Box::new(FlashError::RoutineCallFailed {
-                    name: "program_page",
-                    error_code: result,
-                }),
+                source: Box::new(self.routine_returned_error("program_page", result)),
             })
         } else {
             Ok(())
@@ -1018,3 +1117,20 @@ impl<'p> ActiveFlasher<'p, Program> {
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "rtt"))]
+mod test {
+    use super::keep_last;
+
+    #[test]
+    fn rtt_output_keeps_the_last_characters() {
+        let mut output = String::new();
+        keep_last(&mut output, "erasing\n", 12);
+        keep_last(&mut output, "error\n", 12);
+        assert_eq!(output, "asing\nerror\n");
+
+        // The limit would split `ä`, so the whole character is dropped.
+        keep_last(&mut output, "ä!", 2);
+        assert_eq!(output, "!");
+    }
+}
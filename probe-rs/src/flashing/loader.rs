@@ -1,6 +1,6 @@
 use ihex::Record;
 use probe_rs_target::{
-    MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, TargetDescriptionSource,
+    MemoryRange, MemoryRegion, NvmRegion, RawFlashAlgorithm, SpiFlash, TargetDescriptionSource,
 };
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
@@ -9,8 +9,8 @@ use std::str::FromStr;
 
 use super::builder::FlashBuilder;
 use super::{
-    extract_from_elf, BinOptions, DownloadOptions, FileDownloadError, FlashError, Flasher,
-    IdfOptions,
+    extract_from_elf, extract_symbols_from_elf, BinOptions, DownloadOptions, FileDownloadError,
+    FlashError, Flasher, IdfOptions, SpiFlasher,
 };
 use crate::config::DebugSequence;
 use crate::memory::MemoryInterface;
@@ -23,10 +23,14 @@ use crate::Target;
 /// Once you are done adding all your data, use `commit()` to flash the data.
 /// The flash loader will make sure to select the appropriate flash region for the right data chunks.
 /// Region crossing data chunks are allowed as long as the regions are contiguous.
+#[derive(Clone)]
 pub struct FlashLoader {
     memory_map: Vec<MemoryRegion>,
     builder: FlashBuilder,
 
+    /// Symbols of loaded ELF files, with the address range they are programmed to.
+    symbols: HashMap<String, Range<u64>>,
+
     /// Source of the flash description,
     /// used for diagnostics.
     source: TargetDescriptionSource,
@@ -38,6 +42,7 @@ impl FlashLoader {
         Self {
             memory_map,
             builder: FlashBuilder::new(),
+            symbols: HashMap::new(),
             source,
         }
     }
@@ -76,6 +81,47 @@ impl FlashLoader {
         self.builder.add_data(address, data)
     }
 
+    /// Overwrites staged data with `data`.
+    ///
+    /// This is used to program data which is unique for each device, like serial numbers or
+    /// calibration data, into a loaded image. If no data was staged at the given addresses yet,
+    /// `data` is staged like with [add_data()](FlashLoader::add_data).
+    pub fn patch_data(&mut self, address: u64, data: &[u8]) -> Result<(), FlashError> {
+        tracing::debug!(
+            "Patching data at address {:#010x} with size {} bytes",
+            address,
+            data.len()
+        );
+
+        self.check_data_in_memory_map(address..address + data.len() as u64)?;
+        self.builder.patch_data(address, data)
+    }
+
+    /// The address range a symbol of a loaded ELF file is programmed to.
+    pub fn symbol_range(&self, name: &str) -> Option<Range<u64>> {
+        self.symbols.get(name).cloned()
+    }
+
+    /// Overwrites the staged data of a symbol of a loaded ELF file with `data`.
+    ///
+    /// `data` must not be larger than the symbol, unless the symbol has no size.
+    pub fn patch_symbol(&mut self, name: &str, data: &[u8]) -> Result<(), FlashError> {
+        let range = self
+            .symbol_range(name)
+            .ok_or_else(|| FlashError::SymbolNotFound(name.to_string()))?;
+
+        let size = range.end - range.start;
+        if size != 0 && data.len() as u64 > size {
+            return Err(FlashError::PatchTooLarge {
+                name: name.to_string(),
+                size,
+                actual: data.len(),
+            });
+        }
+
+        self.patch_data(range.start, data)
+    }
+
     pub(super) fn get_region_for_address(
         memory_map: &[MemoryRegion],
         address: u64,
@@ -263,6 +309,8 @@ impl FlashLoader {
             self.add_data(data.address.into(), data.data)?;
         }
 
+        self.symbols.extend(extract_symbols_from_elf(&elf_buffer)?);
+
         Ok(())
     }
 
@@ -330,6 +378,7 @@ impl FlashLoader {
         }
 
         let mut algos: HashMap<(String, String), Vec<NvmRegion>> = HashMap::new();
+        let mut spi_regions: Vec<(SpiFlash, NvmRegion)> = Vec::new();
 
         // Commit NVM first
 
@@ -357,7 +406,24 @@ impl FlashLoader {
                     continue;
                 }
 
-                let algo = Self::get_flash_algorithm_for_region(region, session.target())?;
+                let algo = match Self::get_flash_algorithm_for_region(region, session.target()) {
+                    Ok(algo) => algo,
+                    Err(error) => {
+                        // Without a flash algorithm, an external SPI flash is programmed through its controller.
+                        let Some(spi_flash) = session
+                            .target()
+                            .spi_flash
+                            .iter()
+                            .find(|flash| flash.address_range.contains_range(&region.range))
+                        else {
+                            return Err(error);
+                        };
+
+                        tracing::debug!("     -- using SPI flash: {}", spi_flash.name);
+                        spi_regions.push((spi_flash.clone(), region.clone()));
+                        continue;
+                    }
+                };
 
                 let entry = algos
                     .entry((
@@ -442,6 +508,32 @@ impl FlashLoader {
             }
         }
 
+        for (spi_flash, region) in spi_regions {
+            tracing::debug!(
+                "    programming SPI flash region: {:08x}-{:08x} ({} bytes)",
+                region.range.start,
+                region.range.end,
+                region.range.end - region.range.start
+            );
+
+            let core = session
+                .target()
+                .core_index_by_name(
+                    region
+                        .cores
+                        .first()
+                        .ok_or_else(|| FlashError::NoNvmCoreAccess(region.clone()))?,
+                )
+                .unwrap();
+            let mut flasher = SpiFlasher::new(session, core, &spi_flash, options.progress.clone());
+            flasher.program(
+                &region,
+                &self.builder,
+                options.keep_unwritten_bytes,
+                options.skip_erase,
+            )?;
+        }
+
         tracing::debug!("committing RAM!");
 
         // Commit RAM last, because NVM flashing overwrites RAM
@@ -522,6 +614,50 @@ impl FlashLoader {
         Ok(())
     }
 
+    /// Writes the data chunks of several loaders to the flash of several targets at once.
+    ///
+    /// Each session is programmed with the loader with the same index on its own thread, so that
+    /// each target can receive its own data, e.g. a unique serial number. The `options` closure is
+    /// called on that thread with the index of the session, to create the [`DownloadOptions`] used
+    /// for it, e.g. to report the progress of each device separately.
+    ///
+    /// Returns the result for each session, in the same order as `sessions`.
+    ///
+    /// # Panics
+    /// If the number of loaders and sessions differs.
+    pub fn commit_parallel(
+        loaders: &[FlashLoader],
+        sessions: &mut [Session],
+        options: impl Fn(usize) -> DownloadOptions + Sync,
+    ) -> Vec<Result<(), FlashError>> {
+        assert_eq!(
+            loaders.len(),
+            sessions.len(),
+            "Each session needs its own flash loader"
+        );
+        let options = &options;
+
+        std::thread::scope(|scope| {
+            let threads = loaders
+                .iter()
+                .zip(sessions.iter_mut())
+                .enumerate()
+                .map(|(index, (loader, session))| {
+                    scope.spawn(move || loader.commit(session, options(index)))
+                })
+                .collect::<Vec<_>>();
+
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+
     /// Try to find a flash algorithm for the given NvmRegion.
     /// Errors when:
     /// - there's no algo for the region.
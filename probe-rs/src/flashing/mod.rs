@@ -51,11 +51,14 @@ mod error;
 mod flash_algorithm;
 mod flasher;
 mod loader;
+mod option_bytes;
 mod progress;
+mod spi_flash;
 mod visualizer;
 
 use builder::*;
 use flasher::*;
+use spi_flash::SpiFlasher;
 
 pub use builder::{FlashDataBlockSpan, FlashFill, FlashLayout, FlashPage, FlashSector};
 pub use download::*;
@@ -63,5 +66,6 @@ pub use erase::*;
 pub use error::*;
 pub use flash_algorithm::*;
 pub use loader::*;
+pub use option_bytes::*;
 pub use progress::*;
 pub use visualizer::*;
@@ -0,0 +1,189 @@
+use probe_rs_target::{OptionBytesField, OptionBytesFieldValue, OptionBytesRegion};
+
+use crate::flashing::FlashError;
+use crate::{Error, MemoryInterface, Permissions, Session};
+
+/// The contents of an option bytes, UICR, OTP or fuse region.
+///
+/// The contents can be decoded and modified using the bitfields described
+/// in the target description, and then be programmed using [`write_option_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionBytes {
+    region: OptionBytesRegion,
+    data: Vec<u8>,
+}
+
+/// The decoded value of a single [`OptionBytesField`].
+#[derive(Debug, Clone, Copy)]
+pub struct OptionBytesFieldState<'a> {
+    /// The field description.
+    pub field: &'a OptionBytesField,
+    /// The raw value of the field.
+    pub value: u32,
+    /// The named value matching `value`, if the target description contains one.
+    pub named_value: Option<&'a OptionBytesFieldValue>,
+}
+
+impl OptionBytes {
+    /// Create option bytes for `region` from raw contents.
+    ///
+    /// `data` must have the same size as the region.
+    pub fn new(region: OptionBytesRegion, data: Vec<u8>) -> Result<Self, FlashError> {
+        if data.len() as u64 != region.size() {
+            return Err(FlashError::OptionBytesSizeMismatch {
+                expected: region.size() as usize,
+                actual: data.len(),
+                name: region.name,
+            });
+        }
+
+        Ok(Self { region, data })
+    }
+
+    /// The region these option bytes belong to.
+    pub fn region(&self) -> &OptionBytesRegion {
+        &self.region
+    }
+
+    /// The raw contents of the region.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Decode all fields of the region.
+    pub fn fields(&self) -> impl Iterator<Item = OptionBytesFieldState<'_>> {
+        self.region.fields.iter().filter_map(|field| {
+            let value = field.extract(&self.data)?;
+
+            Some(OptionBytesFieldState {
+                field,
+                value,
+                named_value: field.value_by_raw(value),
+            })
+        })
+    }
+
+    /// Get the raw value of the field `name`.
+    pub fn get(&self, name: &str) -> Result<u32, FlashError> {
+        let field = find_field(&self.region, name)?;
+
+        Ok(field.extract(&self.data).expect(
+            "Fields are validated to be inside the region. This is a bug, please report it.",
+        ))
+    }
+
+    /// Set the field `name` to a raw value.
+    pub fn set(&mut self, name: &str, value: u32) -> Result<(), FlashError> {
+        let field = find_field(&self.region, name)?;
+
+        field
+            .insert(&mut self.data, value)
+            .ok_or_else(|| FlashError::OptionBytesInvalidValue {
+                field: field.name.clone(),
+                value: format!("{value:#x}"),
+            })
+    }
+
+    /// Set the field `name` to one of the named values from the target description.
+    pub fn set_named(&mut self, name: &str, value: &str) -> Result<(), FlashError> {
+        let field = find_field(&self.region, name)?;
+
+        let raw = field
+            .value_by_name(value)
+            .ok_or_else(|| FlashError::OptionBytesInvalidValue {
+                field: field.name.clone(),
+                value: value.to_string(),
+            })?
+            .value as u32;
+
+        self.set(name, raw)
+    }
+}
+
+/// Find the field `name` in the option bytes `region`.
+fn find_field<'a>(
+    region: &'a OptionBytesRegion,
+    name: &str,
+) -> Result<&'a OptionBytesField, FlashError> {
+    region
+        .field(name)
+        .ok_or_else(|| FlashError::OptionBytesFieldNotFound {
+            region: region.name.clone(),
+            field: name.to_string(),
+        })
+}
+
+/// Find the option bytes region `name` of the connected target.
+fn find_region(session: &Session, name: &str) -> Result<OptionBytesRegion, FlashError> {
+    session
+        .target()
+        .option_bytes
+        .iter()
+        .find(|region| region.name.eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| FlashError::OptionBytesRegionNotFound(name.to_string()))
+}
+
+/// Read the current contents of the option bytes region `name`.
+///
+/// If the target has a chip specific option bytes sequence, it is used to read
+/// the region. Otherwise the region is read through the memory of the first core.
+pub fn read_option_bytes(session: &mut Session, name: &str) -> Result<OptionBytes, FlashError> {
+    let region = find_region(session, name)?;
+    let mut data = vec![0; region.size() as usize];
+
+    if session.has_sequence_option_bytes() {
+        session.sequence_read_option_bytes(&region, &mut data)?;
+    } else {
+        let mut words = vec![0u32; data.len() / 4];
+        session.core(0)?.read_32(region.range.start, &mut words)?;
+
+        for (chunk, word) in data.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    OptionBytes::new(region, data)
+}
+
+/// Program the option bytes region with the given contents.
+///
+/// Writes which can not be undone, e.g. to OTP memory or to a field value
+/// marked as irreversible in the target description, are only performed if
+/// `permissions` allows irreversible writes.
+///
+/// Depending on the chip, the new values are either loaded immediately, which
+/// usually resets the device, or only on the next reset.
+pub fn write_option_bytes(
+    session: &mut Session,
+    option_bytes: &OptionBytes,
+    permissions: &Permissions,
+) -> Result<(), FlashError> {
+    let region = option_bytes.region();
+
+    if !session.has_sequence_option_bytes() {
+        return Err(FlashError::OptionBytesNotSupported {
+            name: session.target().name.clone(),
+        });
+    }
+
+    let current = read_option_bytes(session, &region.name)?;
+    if current.data() == option_bytes.data() {
+        tracing::info!("Option bytes region {} is unchanged", region.name);
+        return Ok(());
+    }
+
+    if region.is_irreversible_change(current.data(), option_bytes.data()) {
+        tracing::warn!(
+            "Writing option bytes region {} can not be undone",
+            region.name
+        );
+        permissions
+            .irreversible_writes()
+            .map_err(|error| Error::MissingPermissions(error.0))?;
+    }
+
+    session.sequence_write_option_bytes(region, option_bytes.data())?;
+
+    Ok(())
+}
@@ -0,0 +1,427 @@
+//! A generic flash algorithm for external SPI NOR flashes.
+//!
+//! Instead of running a flash algorithm on the target, the memory mapped SPI flash
+//! controller of the target is driven directly from the debugger. Only the controller
+//! is described in the target description, the layout of the flash itself is discovered
+//! through its SFDP tables. All instructions are sent in single line (1-1-1) mode,
+//! which is supported by all SPI NOR flashes.
+
+mod rp2040_ssi;
+mod sfdp;
+mod stm32_quadspi;
+
+use std::time::{Duration, Instant};
+
+use probe_rs_target::{FlashProperties, SectorDescription, SpiFlash, SpiFlashController};
+
+use super::{FlashAlgorithm, FlashBuilder, FlashError, FlashProgress};
+use crate::config::NvmRegion;
+use crate::{MemoryInterface, Session};
+use sfdp::{AddressMode, EraseType, SfdpParameters};
+
+/// The instruction to read the SFDP tables.
+const READ_SFDP: u8 = 0x5A;
+/// The instruction to read the status register.
+const READ_STATUS: u8 = 0x05;
+/// The instruction to enable writes.
+const WRITE_ENABLE: u8 = 0x06;
+/// The instruction to switch to 4-byte addresses.
+const ENTER_4_BYTE_ADDRESS_MODE: u8 = 0xB7;
+/// The write in progress bit of the status register.
+const STATUS_WIP: u8 = 1 << 0;
+
+/// The maximum time to program a single page.
+const PROGRAM_PAGE_TIMEOUT: Duration = Duration::from_secs(1);
+/// The maximum time to erase a single sector.
+const ERASE_SECTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An instruction sent to the flash, together with its address and dummy phase.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SpiCommand {
+    /// The instruction opcode.
+    pub opcode: u8,
+    /// The address, and the number of bytes used to send it.
+    pub address: Option<(u32, u8)>,
+    /// The number of dummy bytes between the address and the data.
+    pub dummy_bytes: u8,
+}
+
+impl SpiCommand {
+    fn new(opcode: u8) -> Self {
+        Self {
+            opcode,
+            address: None,
+            dummy_bytes: 0,
+        }
+    }
+
+    fn with_address(self, address: u32, address_bytes: u8) -> Self {
+        Self {
+            address: Some((address, address_bytes)),
+            ..self
+        }
+    }
+
+    fn with_dummy_bytes(self, dummy_bytes: u8) -> Self {
+        Self {
+            dummy_bytes,
+            ..self
+        }
+    }
+
+    /// The instruction, address and dummy bytes, as they are sent on the bus.
+    pub(super) fn header(&self) -> Vec<u8> {
+        let mut header = vec![self.opcode];
+        if let Some((address, bytes)) = self.address {
+            header.extend_from_slice(&address.to_be_bytes()[4 - bytes as usize..]);
+        }
+        header.extend(std::iter::repeat(0).take(self.dummy_bytes as usize));
+
+        header
+    }
+}
+
+/// The data phase of a [`SpiCommand`].
+pub(super) enum SpiData<'a> {
+    /// The command has no data phase.
+    None,
+    /// Data is read from the flash.
+    Read(&'a mut [u8]),
+    /// Data is written to the flash.
+    Write(&'a [u8]),
+}
+
+/// A memory mapped SPI flash controller, which can send single line instructions to the flash.
+pub(super) trait SpiNorController {
+    /// Switch the controller to a mode where instructions can be sent to the flash.
+    fn init(&mut self, memory: &mut dyn MemoryInterface) -> Result<(), crate::Error>;
+
+    /// Send a command to the flash, and transfer its data.
+    fn transfer(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        command: &SpiCommand,
+        data: SpiData<'_>,
+    ) -> Result<(), crate::Error>;
+
+    /// Switch the controller back to memory mapped mode, using `read` to read from the flash.
+    fn enable_memory_mapped(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        read: &SpiCommand,
+    ) -> Result<(), crate::Error>;
+}
+
+fn create_controller(flash: &SpiFlash) -> Box<dyn SpiNorController> {
+    match &flash.controller {
+        SpiFlashController::Stm32Quadspi(options) => Box::new(stm32_quadspi::Stm32Quadspi::new(
+            options,
+            flash.address_range.end - flash.address_range.start,
+        )),
+        SpiFlashController::Rp2040Ssi => Box::new(rp2040_ssi::Rp2040Ssi),
+    }
+}
+
+/// Programs an external SPI NOR flash through its memory mapped controller.
+pub(super) struct SpiFlasher<'session> {
+    session: &'session mut Session,
+    core_index: usize,
+    flash: SpiFlash,
+    controller: Box<dyn SpiNorController>,
+    progress: FlashProgress,
+}
+
+impl<'session> SpiFlasher<'session> {
+    pub(super) fn new(
+        session: &'session mut Session,
+        core_index: usize,
+        flash: &SpiFlash,
+        progress: Option<FlashProgress>,
+    ) -> Self {
+        Self {
+            session,
+            core_index,
+            flash: flash.clone(),
+            controller: create_controller(flash),
+            progress: progress.unwrap_or(FlashProgress::new(|_| {})),
+        }
+    }
+
+    /// Program the contents of given `FlashBuilder` to the flash.
+    ///
+    /// If `restore_unwritten_bytes` is `true`, all bytes of a sector,
+    /// that are not to be written during flashing will be read from the flash first
+    /// and written again once the sector is erased.
+    pub(super) fn program(
+        &mut self,
+        region: &NvmRegion,
+        flash_builder: &FlashBuilder,
+        restore_unwritten_bytes: bool,
+        skip_erasing: bool,
+    ) -> Result<(), FlashError> {
+        tracing::debug!("Starting SPI flash program procedure.");
+
+        let description_source = self.session.target().source().clone();
+        let mut core = self.session.core(self.core_index)?;
+        if !core.core_halted()? {
+            core.halt(Duration::from_millis(100))?;
+        }
+
+        for write in &self.flash.init {
+            let value = match write.mask {
+                Some(mask) => (core.read_word_32(write.address)? & !mask) | (write.value & mask),
+                None => write.value,
+            };
+            core.write_word_32(write.address, value)?;
+        }
+
+        let mut spi = ActiveSpiFlash {
+            memory: &mut core,
+            controller: self.controller.as_mut(),
+            name: &self.flash.name,
+        };
+        spi.controller.init(spi.memory)?;
+
+        let parameters = spi.discover()?;
+        tracing::info!(
+            "Found SPI flash {} with {} bytes, {} byte pages, erase types {:x?}",
+            self.flash.name,
+            parameters.size,
+            parameters.page_size,
+            parameters.erase_types
+        );
+
+        let flash_end = self.flash.address_range.start + parameters.size;
+        if let Some((address, data)) = flash_builder
+            .data_in_range(&region.range)
+            .find(|(address, data)| *address + data.len() as u64 > flash_end)
+        {
+            return Err(FlashError::NoSuitableNvm {
+                start: address,
+                end: address + data.len() as u64,
+                description_source,
+            });
+        }
+
+        // Erase with the smallest sector size, to keep as much of the flash untouched as possible.
+        let erase = parameters.erase_types[0];
+        let flash_algorithm = FlashAlgorithm {
+            name: self.flash.name.clone(),
+            flash_properties: FlashProperties {
+                address_range: self.flash.address_range.start
+                    ..flash_end.min(self.flash.address_range.end),
+                page_size: parameters.page_size,
+                erased_byte_value: 0xFF,
+                program_page_timeout: PROGRAM_PAGE_TIMEOUT.as_millis() as u32,
+                erase_sector_timeout: ERASE_SECTOR_TIMEOUT.as_millis() as u32,
+                sectors: vec![SectorDescription {
+                    size: erase.size as u64,
+                    address: 0,
+                }],
+            },
+            ..Default::default()
+        };
+
+        let mut flash_layout = flash_builder.build_sectors_and_pages(
+            region,
+            &flash_algorithm,
+            restore_unwritten_bytes,
+        )?;
+        self.progress.initialized(flash_layout.clone());
+
+        let base = self.flash.address_range.start;
+
+        spi.enter_address_mode(parameters.address_mode)?;
+
+        // Read all fill areas from the flash.
+        self.progress.started_filling();
+        if restore_unwritten_bytes {
+            let fills = flash_layout.fills().to_vec();
+            for fill in fills {
+                let t = Instant::now();
+                let page = &mut flash_layout.pages_mut()[fill.page_index()];
+                let offset = (fill.address() - page.address()) as usize;
+                let data = &mut page.data_mut()[offset..offset + fill.size() as usize];
+
+                let read = SpiCommand::new(parameters.address_mode.read_opcode()).with_address(
+                    (fill.address() - base) as u32,
+                    parameters.address_mode.address_bytes(),
+                );
+                if let Err(error) = spi
+                    .controller
+                    .transfer(spi.memory, &read, SpiData::Read(data))
+                {
+                    self.progress.failed_filling();
+                    return Err(error.into());
+                }
+                self.progress.page_filled(fill.size(), t.elapsed());
+            }
+        }
+        self.progress.finished_filling();
+
+        if !skip_erasing {
+            self.progress.started_erasing();
+            for sector in flash_layout.sectors() {
+                let t = Instant::now();
+                if let Err(error) = spi.erase_sector(&parameters, erase, sector.address() - base) {
+                    self.progress.failed_erasing();
+                    return Err(FlashError::EraseFailed {
+                        sector_address: sector.address(),
+                        source: Box::new(error),
+                    });
+                }
+                self.progress.sector_erased(sector.size(), t.elapsed());
+            }
+            self.progress.finished_erasing();
+        }
+
+        self.progress.started_programming(
+            flash_layout
+                .pages()
+                .iter()
+                .map(|page| page.data().len() as u64)
+                .sum(),
+        );
+        for page in flash_layout.pages() {
+            let t = Instant::now();
+            if let Err(error) = spi.program_page(&parameters, page.address() - base, page.data()) {
+                self.progress.failed_programming();
+                return Err(FlashError::PageWrite {
+                    page_address: page.address(),
+                    source: Box::new(error),
+                });
+            }
+            self.progress.page_programmed(page.size(), t.elapsed());
+        }
+        self.progress.finished_programming();
+
+        let read = SpiCommand::new(parameters.address_mode.read_opcode())
+            .with_address(0, parameters.address_mode.address_bytes());
+        spi.controller.enable_memory_mapped(spi.memory, &read)?;
+
+        Ok(())
+    }
+}
+
+/// The flash controller together with the memory interface used to access it.
+struct ActiveSpiFlash<'a> {
+    memory: &'a mut dyn MemoryInterface,
+    controller: &'a mut dyn SpiNorController,
+    name: &'a str,
+}
+
+impl ActiveSpiFlash<'_> {
+    fn command(&mut self, command: SpiCommand, data: SpiData<'_>) -> Result<(), FlashError> {
+        Ok(self.controller.transfer(self.memory, &command, data)?)
+    }
+
+    /// Read the SFDP tables of the flash.
+    fn discover(&mut self) -> Result<SfdpParameters, FlashError> {
+        let mut header = [0; sfdp::HEADER_SIZE];
+        self.command(
+            SpiCommand::new(READ_SFDP)
+                .with_address(0, 3)
+                .with_dummy_bytes(1),
+            SpiData::Read(&mut header),
+        )?;
+
+        let location = sfdp::parse_header(&header).ok_or_else(|| FlashError::SfdpNotFound {
+            name: self.name.to_string(),
+        })?;
+
+        // Only the DWORDs up to the 4-byte address instructions are used.
+        let mut bfpt = vec![0; location.length.min(16 * 4)];
+        self.command(
+            SpiCommand::new(READ_SFDP)
+                .with_address(location.pointer, 3)
+                .with_dummy_bytes(1),
+            SpiData::Read(&mut bfpt),
+        )?;
+
+        sfdp::parse_bfpt(&bfpt).map_err(|reason| FlashError::SfdpNotSupported {
+            name: self.name.to_string(),
+            reason,
+        })
+    }
+
+    fn enter_address_mode(&mut self, address_mode: AddressMode) -> Result<(), FlashError> {
+        if let AddressMode::EnterFourByte { write_enable } = address_mode {
+            if write_enable {
+                self.command(SpiCommand::new(WRITE_ENABLE), SpiData::None)?;
+            }
+            self.command(SpiCommand::new(ENTER_4_BYTE_ADDRESS_MODE), SpiData::None)?;
+        }
+
+        Ok(())
+    }
+
+    fn erase_sector(
+        &mut self,
+        parameters: &SfdpParameters,
+        erase: EraseType,
+        offset: u64,
+    ) -> Result<(), FlashError> {
+        self.command(SpiCommand::new(WRITE_ENABLE), SpiData::None)?;
+        self.command(
+            SpiCommand::new(erase.opcode)
+                .with_address(offset as u32, parameters.address_mode.address_bytes()),
+            SpiData::None,
+        )?;
+
+        self.wait_ready("erase", ERASE_SECTOR_TIMEOUT)
+    }
+
+    fn program_page(
+        &mut self,
+        parameters: &SfdpParameters,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), FlashError> {
+        // Programming erased bytes has no effect.
+        if data.iter().all(|&byte| byte == 0xFF) {
+            return Ok(());
+        }
+
+        self.command(SpiCommand::new(WRITE_ENABLE), SpiData::None)?;
+        self.command(
+            SpiCommand::new(parameters.address_mode.program_opcode())
+                .with_address(offset as u32, parameters.address_mode.address_bytes()),
+            SpiData::Write(data),
+        )?;
+
+        self.wait_ready("program", PROGRAM_PAGE_TIMEOUT)
+    }
+
+    fn wait_ready(&mut self, operation: &'static str, timeout: Duration) -> Result<(), FlashError> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            let mut status = [0];
+            self.command(SpiCommand::new(READ_STATUS), SpiData::Read(&mut status))?;
+
+            if status[0] & STATUS_WIP == 0 {
+                return Ok(());
+            }
+        }
+
+        Err(FlashError::SpiFlashTimeout {
+            name: self.name.to_string(),
+            operation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpiCommand;
+
+    #[test]
+    fn command_header() {
+        let command = SpiCommand::new(0x5A)
+            .with_address(0x12_3456, 3)
+            .with_dummy_bytes(1);
+        assert_eq!(command.header(), [0x5A, 0x12, 0x34, 0x56, 0x00]);
+
+        let command = SpiCommand::new(0x12).with_address(0x0123_4567, 4);
+        assert_eq!(command.header(), [0x12, 0x01, 0x23, 0x45, 0x67]);
+    }
+}
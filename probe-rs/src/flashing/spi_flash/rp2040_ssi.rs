@@ -0,0 +1,145 @@
+//! The SSI peripheral of the RP2040, which connects the boot flash.
+
+use std::time::{Duration, Instant};
+
+use super::{SpiCommand, SpiData, SpiNorController};
+use crate::MemoryInterface;
+
+/// Base address of the SSI registers.
+const SSI: u64 = 0x1800_0000;
+/// Control register 0.
+const CTRLR0: u64 = SSI;
+/// Control register 1, the number of data frames in receive only mode.
+const CTRLR1: u64 = SSI + 0x04;
+/// SSI enable register.
+const SSIENR: u64 = SSI + 0x08;
+/// Slave enable register.
+const SER: u64 = SSI + 0x10;
+/// Baud rate register.
+const BAUDR: u64 = SSI + 0x14;
+/// Receive FIFO level register.
+const RXFLR: u64 = SSI + 0x24;
+/// Data register. The registers up to `DR35` all access the FIFO.
+const DR0: u64 = SSI + 0x60;
+/// SPI control register, used in memory mapped mode.
+const SPI_CTRLR0: u64 = SSI + 0xF4;
+
+/// The depth of the transmit and receive FIFOs.
+const FIFO_DEPTH: usize = 16;
+
+/// Control of the chip select pin of the flash.
+const GPIO_QSPI_SS_CTRL: u64 = 0x4001_800C;
+const SS_OUTOVER_NORMAL: u32 = 0 << 8;
+const SS_OUTOVER_LOW: u32 = 2 << 8;
+const SS_OUTOVER_HIGH: u32 = 3 << 8;
+
+/// Timeout for the transfer of a single chunk.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// XIP cache flush register.
+const XIP_FLUSH: u64 = 0x1400_0004;
+
+/// The SSI peripheral of the RP2040, using a manually controlled chip select for each command.
+pub(super) struct Rp2040Ssi;
+
+impl Rp2040Ssi {
+    fn set_chip_select(memory: &mut dyn MemoryInterface, outover: u32) -> Result<(), crate::Error> {
+        memory.write_word_32(GPIO_QSPI_SS_CTRL, outover)
+    }
+
+    /// Transmit `tx` while receiving the same amount of bytes, in chunks which fit into the FIFOs.
+    fn exchange(memory: &mut dyn MemoryInterface, tx: &[u8]) -> Result<Vec<u8>, crate::Error> {
+        let mut rx = Vec::with_capacity(tx.len());
+        for chunk in tx.chunks(FIFO_DEPTH) {
+            let words = chunk.iter().map(|&byte| byte as u32).collect::<Vec<_>>();
+            memory.write_32(DR0, &words)?;
+
+            let mut received = [0; FIFO_DEPTH];
+            let mut count = 0;
+            let start = Instant::now();
+            while count < chunk.len() {
+                if start.elapsed() > TIMEOUT {
+                    return Err(crate::Error::Other(anyhow::anyhow!(
+                        "Timeout waiting for data from the SSI"
+                    )));
+                }
+
+                let level = (memory.read_word_32(RXFLR)? as usize).min(chunk.len() - count);
+                if level > 0 {
+                    memory.read_32(DR0, &mut received[count..count + level])?;
+                    count += level;
+                }
+            }
+
+            rx.extend(received[..chunk.len()].iter().map(|&word| word as u8));
+        }
+
+        Ok(rx)
+    }
+}
+
+impl SpiNorController for Rp2040Ssi {
+    fn init(&mut self, memory: &mut dyn MemoryInterface) -> Result<(), crate::Error> {
+        memory.write_word_32(SSIENR, 0)?;
+        // Clock divider of 6, the SSI runs from the system clock.
+        memory.write_word_32(BAUDR, 6)?;
+        // Standard SPI, 8 bit frames, transmit and receive.
+        memory.write_word_32(CTRLR0, 7 << 16)?;
+        memory.write_word_32(SER, 1)?;
+        memory.write_word_32(SSIENR, 1)?;
+
+        // The flash might still be in continuous read mode after running from XIP, which is
+        // exited by sending ones while the mode bits are expected.
+        Self::set_chip_select(memory, SS_OUTOVER_LOW)?;
+        Self::exchange(memory, &[0xFF; 4])?;
+        Self::set_chip_select(memory, SS_OUTOVER_HIGH)
+    }
+
+    fn transfer(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        command: &SpiCommand,
+        data: SpiData<'_>,
+    ) -> Result<(), crate::Error> {
+        let mut tx = command.header();
+        let header_len = tx.len();
+        match &data {
+            SpiData::None => {}
+            SpiData::Read(buffer) => tx.resize(header_len + buffer.len(), 0),
+            SpiData::Write(buffer) => tx.extend_from_slice(buffer),
+        }
+
+        Self::set_chip_select(memory, SS_OUTOVER_LOW)?;
+        let result = Self::exchange(memory, &tx);
+        Self::set_chip_select(memory, SS_OUTOVER_HIGH)?;
+        let rx = result?;
+
+        if let SpiData::Read(buffer) = data {
+            buffer.copy_from_slice(&rx[header_len..]);
+        }
+
+        Ok(())
+    }
+
+    fn enable_memory_mapped(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        read: &SpiCommand,
+    ) -> Result<(), crate::Error> {
+        Self::set_chip_select(memory, SS_OUTOVER_NORMAL)?;
+
+        memory.write_word_32(SSIENR, 0)?;
+        // Standard SPI, 32 bit frames, EEPROM read mode.
+        memory.write_word_32(CTRLR0, 31 << 16 | 3 << 8)?;
+        memory.write_word_32(CTRLR1, 0)?;
+        // 8 bit instruction, the address length is given in units of 4 bits.
+        let address_bits = read.address.map_or(0, |(_, bytes)| bytes as u32 * 2);
+        memory.write_word_32(
+            SPI_CTRLR0,
+            (read.opcode as u32) << 24 | 2 << 8 | address_bits << 2,
+        )?;
+        memory.write_word_32(SSIENR, 1)?;
+
+        memory.write_word_32(XIP_FLUSH, 1)
+    }
+}
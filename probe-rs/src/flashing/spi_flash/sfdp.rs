@@ -0,0 +1,300 @@
+//! Parsing of the Serial Flash Discoverable Parameters (SFDP), as defined in JEDEC JESD216.
+
+/// The `SFDP` signature at the start of the SFDP header.
+const SIGNATURE: u32 = 0x5044_4653;
+
+/// The size of the SFDP header together with the first parameter header.
+pub(super) const HEADER_SIZE: usize = 16;
+
+/// How addresses are transferred to the flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AddressMode {
+    /// 3-byte addresses, with the standard instructions.
+    ThreeByte,
+    /// 4-byte addresses with the standard instructions, the flash always operates in 4-byte mode.
+    FourByte,
+    /// 4-byte addresses with the standard instructions, after the flash has been switched
+    /// to 4-byte mode with the `B7h` instruction, optionally preceded by a write enable.
+    EnterFourByte {
+        /// Whether a write enable is required before `B7h`.
+        write_enable: bool,
+    },
+    /// 4-byte addresses, with the dedicated 4-byte address instructions.
+    FourByteInstructions,
+}
+
+impl AddressMode {
+    /// The number of address bytes sent with each instruction.
+    pub(super) fn address_bytes(&self) -> u8 {
+        match self {
+            AddressMode::ThreeByte => 3,
+            _ => 4,
+        }
+    }
+
+    /// The instruction to read data.
+    pub(super) fn read_opcode(&self) -> u8 {
+        match self {
+            AddressMode::FourByteInstructions => 0x13,
+            _ => 0x03,
+        }
+    }
+
+    /// The instruction to program a page.
+    pub(super) fn program_opcode(&self) -> u8 {
+        match self {
+            AddressMode::FourByteInstructions => 0x12,
+            _ => 0x02,
+        }
+    }
+
+    /// The erase instruction to use for the given 3-byte address erase instruction.
+    fn erase_opcode(&self, opcode: u8) -> Option<u8> {
+        match self {
+            AddressMode::FourByteInstructions => match opcode {
+                0x20 => Some(0x21),
+                0x52 => Some(0x5C),
+                0xD8 => Some(0xDC),
+                _ => None,
+            },
+            _ => Some(opcode),
+        }
+    }
+}
+
+/// An erase instruction of the flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct EraseType {
+    /// The size of the erased sector in bytes.
+    pub size: u32,
+    /// The erase instruction.
+    pub opcode: u8,
+}
+
+/// The parameters of the flash, discovered from the Basic Flash Parameter Table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SfdpParameters {
+    /// The size of the flash in bytes.
+    pub size: u64,
+    /// The size of a page which can be programmed at once.
+    pub page_size: u32,
+    /// The supported erase instructions, ordered from the smallest to the largest sector size.
+    pub erase_types: Vec<EraseType>,
+    /// How addresses are transferred to the flash.
+    pub address_mode: AddressMode,
+}
+
+/// The location of the Basic Flash Parameter Table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct BfptLocation {
+    /// Address of the table in the SFDP address space.
+    pub pointer: u32,
+    /// Length of the table in bytes.
+    pub length: usize,
+}
+
+/// Parse the SFDP header, followed by the first parameter header, which always
+/// describes the Basic Flash Parameter Table.
+///
+/// Returns `None` if the header does not contain the SFDP signature.
+pub(super) fn parse_header(header: &[u8; HEADER_SIZE]) -> Option<BfptLocation> {
+    let signature = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    if signature != SIGNATURE {
+        return None;
+    }
+
+    // The first parameter header starts at byte 8, the BFPT has the ID FF00h.
+    let id = u16::from_le_bytes([header[8], header[15]]);
+    if id != 0xFF00 {
+        return None;
+    }
+
+    Some(BfptLocation {
+        pointer: u32::from_le_bytes([header[12], header[13], header[14], 0]),
+        length: header[11] as usize * 4,
+    })
+}
+
+/// Parse the Basic Flash Parameter Table.
+pub(super) fn parse_bfpt(bfpt: &[u8]) -> Result<SfdpParameters, String> {
+    let dwords = bfpt
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect::<Vec<_>>();
+
+    // JESD216 defines 9 DWORDs, later revisions append more.
+    if dwords.len() < 9 {
+        return Err(format!(
+            "the basic flash parameter table has only {} DWORDs",
+            dwords.len()
+        ));
+    }
+
+    let density = dwords[1];
+    let size = if density & 0x8000_0000 == 0 {
+        (density as u64 + 1) / 8
+    } else {
+        let exponent = density & 0x7FFF_FFFF;
+        if !(3..=63).contains(&exponent) {
+            return Err(format!("invalid flash density {density:#010x}"));
+        }
+        1 << (exponent - 3)
+    };
+
+    let page_size = match dwords.get(10) {
+        Some(dword) => 1 << ((dword >> 4) & 0xF),
+        // JESD216 without revision A does not specify the page size, 256 bytes are used by all flashes.
+        None => 256,
+    };
+
+    let address_bytes = (dwords[0] >> 17) & 0b11;
+    let address_mode = if size <= 1 << 24 && address_bytes != 0b10 {
+        AddressMode::ThreeByte
+    } else {
+        let enter = dwords.get(15).map(|dword| dword >> 24).unwrap_or(0);
+
+        if address_bytes == 0b10 || enter & (1 << 6) != 0 {
+            AddressMode::FourByte
+        } else if enter & (1 << 5) != 0 {
+            AddressMode::FourByteInstructions
+        } else if enter & (1 << 0) != 0 {
+            AddressMode::EnterFourByte {
+                write_enable: false,
+            }
+        } else if enter & (1 << 1) != 0 {
+            AddressMode::EnterFourByte { write_enable: true }
+        } else {
+            return Err("no supported method to use 4-byte addresses".to_string());
+        }
+    };
+
+    let mut erase_types = dwords[7..9]
+        .iter()
+        .flat_map(|dword| [dword & 0xFFFF, dword >> 16])
+        .filter(|erase| erase & 0xFF != 0)
+        .filter_map(|erase| {
+            let size = 1u32.checked_shl(erase & 0xFF)?;
+            let opcode = address_mode.erase_opcode((erase >> 8) as u8)?;
+
+            Some(EraseType { size, opcode })
+        })
+        .filter(|erase| erase.size >= page_size)
+        .collect::<Vec<_>>();
+    erase_types.sort_by_key(|erase| erase.size);
+
+    if erase_types.is_empty() {
+        return Err("no supported erase instruction".to_string());
+    }
+
+    Ok(SfdpParameters {
+        size,
+        page_size,
+        erase_types,
+        address_mode,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_bytes(dwords: &[u32]) -> Vec<u8> {
+        dwords
+            .iter()
+            .flat_map(|dword| dword.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn header() {
+        let header = [
+            0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x01, 0xFF, 0x00, 0x06, 0x01, 0x10, 0x30, 0x00,
+            0x00, 0xFF,
+        ];
+
+        assert_eq!(
+            parse_header(&header),
+            Some(BfptLocation {
+                pointer: 0x30,
+                length: 64,
+            })
+        );
+
+        assert_eq!(parse_header(&[0xFF; HEADER_SIZE]), None);
+    }
+
+    #[test]
+    fn bfpt_16_mbyte() {
+        // Basic flash parameter table of a W25Q128JV.
+        let bfpt = to_bytes(&[
+            0xFFF9_20E5,
+            0x07FF_FFFF,
+            0x6B08_EB44,
+            0xBB42_3B08,
+            0xFFFF_FFFE,
+            0x0000_FFFF,
+            0xEB40_FFFF,
+            0x520F_200C,
+            0x0000_D810,
+            0x0024_4A36,
+            0x33EA_8281,
+        ]);
+
+        let parameters = parse_bfpt(&bfpt).unwrap();
+
+        assert_eq!(
+            parameters,
+            SfdpParameters {
+                size: 16 * 1024 * 1024,
+                page_size: 256,
+                erase_types: vec![
+                    EraseType {
+                        size: 4096,
+                        opcode: 0x20
+                    },
+                    EraseType {
+                        size: 32768,
+                        opcode: 0x52
+                    },
+                    EraseType {
+                        size: 65536,
+                        opcode: 0xD8
+                    },
+                ],
+                address_mode: AddressMode::ThreeByte,
+            }
+        );
+    }
+
+    #[test]
+    fn bfpt_4_byte_instructions() {
+        let mut dwords = [0xFFFF_FFFF; 16];
+        // 3- or 4-byte addresses, 4 KiB erase with 20h.
+        dwords[0] = 0xFFF2_20E5;
+        // 512 Mbit.
+        dwords[1] = 0x8000_001D;
+        dwords[7] = 0xD810_200C;
+        dwords[8] = 0x0000_0000;
+        dwords[10] = 0x0000_0080;
+        // Dedicated 4-byte address instructions, or B7h.
+        dwords[15] = 0x2100_0000;
+
+        let parameters = parse_bfpt(&to_bytes(&dwords)).unwrap();
+
+        assert_eq!(parameters.size, 64 * 1024 * 1024);
+        assert_eq!(parameters.address_mode, AddressMode::FourByteInstructions);
+        assert_eq!(
+            parameters.erase_types,
+            vec![
+                EraseType {
+                    size: 4096,
+                    opcode: 0x21
+                },
+                EraseType {
+                    size: 65536,
+                    opcode: 0xDC
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,471 @@
+//! The QUADSPI peripheral of STM32 devices.
+
+use std::time::{Duration, Instant};
+
+use probe_rs_target::Stm32QuadspiOptions;
+
+use super::{SpiCommand, SpiData, SpiNorController};
+use crate::MemoryInterface;
+
+/// Control register.
+const CR: u64 = 0x00;
+/// Device configuration register.
+const DCR: u64 = 0x04;
+/// Status register.
+const SR: u64 = 0x08;
+/// Flag clear register.
+const FCR: u64 = 0x0C;
+/// Data length register.
+const DLR: u64 = 0x10;
+/// Communication configuration register.
+const CCR: u64 = 0x14;
+/// Address register.
+const AR: u64 = 0x18;
+/// Data register.
+const DR: u64 = 0x20;
+
+const CR_EN: u32 = 1 << 0;
+const CR_ABORT: u32 = 1 << 1;
+const CR_FSEL: u32 = 1 << 7;
+
+const SR_TCF: u32 = 1 << 1;
+const SR_BUSY: u32 = 1 << 5;
+
+/// Single line mode for the instruction, address and data phase.
+const MODE_SINGLE: u32 = 0b01;
+
+const FMODE_INDIRECT_WRITE: u32 = 0b00;
+const FMODE_INDIRECT_READ: u32 = 0b01;
+const FMODE_MEMORY_MAPPED: u32 = 0b11;
+
+/// Timeout for a single transfer, or for an abort to complete.
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The QUADSPI peripheral, operated in indirect mode.
+pub(super) struct Stm32Quadspi {
+    registers: u64,
+    prescaler: u8,
+    flash_select: bool,
+    /// The size of the memory mapped window.
+    size: u64,
+}
+
+impl Stm32Quadspi {
+    pub(super) fn new(options: &Stm32QuadspiOptions, size: u64) -> Self {
+        Self {
+            registers: options.registers,
+            prescaler: options.prescaler,
+            flash_select: options.flash_select,
+            size,
+        }
+    }
+
+    fn read(&self, memory: &mut dyn MemoryInterface, offset: u64) -> Result<u32, crate::Error> {
+        memory.read_word_32(self.registers + offset)
+    }
+
+    fn write(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        offset: u64,
+        value: u32,
+    ) -> Result<(), crate::Error> {
+        memory.write_word_32(self.registers + offset, value)
+    }
+
+    /// Wait until all bits in `mask` of the status register have the given `value`.
+    fn wait_status(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        mask: u32,
+        value: u32,
+    ) -> Result<u32, crate::Error> {
+        let start = Instant::now();
+        loop {
+            let status = self.read(memory, SR)?;
+            if status & mask == value {
+                return Ok(status);
+            }
+
+            if start.elapsed() > TIMEOUT {
+                return Err(crate::Error::Other(anyhow::anyhow!(
+                    "Timeout waiting for the QUADSPI status {status:#010x}"
+                )));
+            }
+        }
+    }
+
+    /// The number of bytes in the FIFO.
+    fn fifo_level(status: u32) -> usize {
+        ((status >> 8) & 0x3F) as usize
+    }
+
+    /// Wait until the FIFO contains data.
+    fn wait_fifo_level(&self, memory: &mut dyn MemoryInterface) -> Result<u32, crate::Error> {
+        let start = Instant::now();
+        loop {
+            let status = self.read(memory, SR)?;
+            if Self::fifo_level(status) > 0 {
+                return Ok(status);
+            }
+
+            if start.elapsed() > TIMEOUT {
+                return Err(crate::Error::Other(anyhow::anyhow!(
+                    "Timeout waiting for data from the QUADSPI"
+                )));
+            }
+        }
+    }
+
+    fn configure(
+        &self,
+        memory: &mut dyn MemoryInterface,
+        command: &SpiCommand,
+        fmode: u32,
+        has_data: bool,
+    ) -> Result<(), crate::Error> {
+        let mut ccr = command.opcode as u32 | MODE_SINGLE << 8 | fmode << 26;
+        if let Some((_, bytes)) = command.address {
+            ccr |= MODE_SINGLE << 10 | (bytes as u32 - 1) << 12;
+        }
+        ccr |= (command.dummy_bytes as u32 * 8) << 18;
+        if has_data {
+            ccr |= MODE_SINGLE << 24;
+        }
+
+        self.write(memory, CCR, ccr)?;
+        if let Some((address, _)) = command.address {
+            self.write(memory, AR, address)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SpiNorController for Stm32Quadspi {
+    fn init(&mut self, memory: &mut dyn MemoryInterface) -> Result<(), crate::Error> {
+        // Abort a memory mapped read which might still be active.
+        let cr = self.read(memory, CR)?;
+        if cr & CR_EN != 0 {
+            self.write(memory, CR, cr | CR_ABORT)?;
+            let start = Instant::now();
+            while self.read(memory, CR)? & CR_ABORT != 0 {
+                if start.elapsed() > TIMEOUT {
+                    return Err(crate::Error::Other(anyhow::anyhow!(
+                        "Timeout aborting the QUADSPI operation"
+                    )));
+                }
+            }
+        }
+        self.wait_status(memory, SR_BUSY, 0)?;
+
+        self.write(memory, CR, 0)?;
+
+        // FSIZE is the number of address bits minus one, CSHT is one cycle more than the minimum.
+        let fsize = self.size.next_power_of_two().trailing_zeros().max(1) - 1;
+        self.write(memory, DCR, fsize << 16 | 1 << 8)?;
+
+        let mut cr = (self.prescaler as u32) << 24 | CR_EN;
+        if self.flash_select {
+            cr |= CR_FSEL;
+        }
+        self.write(memory, CR, cr)
+    }
+
+    fn transfer(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        command: &SpiCommand,
+        data: SpiData<'_>,
+    ) -> Result<(), crate::Error> {
+        self.wait_status(memory, SR_BUSY, 0)?;
+        self.write(memory, FCR, 0x1F)?;
+
+        match data {
+            SpiData::None => {
+                self.configure(memory, command, FMODE_INDIRECT_WRITE, false)?;
+            }
+            SpiData::Read(buffer) => {
+                self.write(memory, DLR, buffer.len() as u32 - 1)?;
+                self.configure(memory, command, FMODE_INDIRECT_READ, true)?;
+
+                let mut received = 0;
+                while received < buffer.len() {
+                    let status = self.wait_fifo_level(memory)?;
+                    let available = Self::fifo_level(status).min(buffer.len() - received);
+
+                    let words = available / 4;
+                    if words > 0 {
+                        for _ in 0..words {
+                            let word = self.read(memory, DR)?;
+                            buffer[received..received + 4].copy_from_slice(&word.to_le_bytes());
+                            received += 4;
+                        }
+                    } else {
+                        for byte in &mut buffer[received..received + available] {
+                            *byte = memory.read_word_8(self.registers + DR)?;
+                        }
+                        received += available;
+                    }
+                }
+            }
+            SpiData::Write(buffer) => {
+                self.write(memory, DLR, buffer.len() as u32 - 1)?;
+                self.configure(memory, command, FMODE_INDIRECT_WRITE, true)?;
+
+                let mut words = buffer.chunks_exact(4);
+                for word in &mut words {
+                    self.write(memory, DR, u32::from_le_bytes(word.try_into().unwrap()))?;
+                }
+                for byte in words.remainder() {
+                    memory.write_word_8(self.registers + DR, *byte)?;
+                }
+            }
+        }
+
+        self.wait_status(memory, SR_TCF, SR_TCF)?;
+        self.write(memory, FCR, SR_TCF)
+    }
+
+    fn enable_memory_mapped(
+        &mut self,
+        memory: &mut dyn MemoryInterface,
+        read: &SpiCommand,
+    ) -> Result<(), crate::Error> {
+        self.wait_status(memory, SR_BUSY, 0)?;
+        self.configure(memory, read, FMODE_MEMORY_MAPPED, true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    const REGISTERS: u64 = 0xA000_1000;
+
+    /// Emulates the registers of the QUADSPI, with a flash which answers with `rx`.
+    #[derive(Default)]
+    struct MockQuadspi {
+        cr: u32,
+        /// The register writes, except for the ones to the data register.
+        writes: Vec<(u64, u32)>,
+        /// The data written to the data register.
+        tx: Vec<u8>,
+        /// The data returned by the data register.
+        rx: VecDeque<u8>,
+    }
+
+    impl MemoryInterface for MockQuadspi {
+        fn supports_native_64bit_access(&mut self) -> bool {
+            false
+        }
+
+        fn read_word_64(&mut self, _address: u64) -> Result<u64, crate::Error> {
+            todo!()
+        }
+
+        fn read_word_32(&mut self, address: u64) -> Result<u32, crate::Error> {
+            Ok(match address - REGISTERS {
+                CR => self.cr,
+                // The transfer completes immediately, and the FIFO holds up to 32 bytes.
+                SR => SR_TCF | (self.rx.len().min(32) as u32) << 8,
+                DR => {
+                    let mut word = [0; 4];
+                    for byte in &mut word {
+                        *byte = self.rx.pop_front().unwrap();
+                    }
+                    u32::from_le_bytes(word)
+                }
+                _ => 0,
+            })
+        }
+
+        fn read_word_16(&mut self, _address: u64) -> Result<u16, crate::Error> {
+            todo!()
+        }
+
+        fn read_word_8(&mut self, address: u64) -> Result<u8, crate::Error> {
+            assert_eq!(address, REGISTERS + DR);
+            Ok(self.rx.pop_front().unwrap())
+        }
+
+        fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn read_32(&mut self, _address: u64, _data: &mut [u32]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn read_16(&mut self, _address: u64, _data: &mut [u16]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_word_64(&mut self, _address: u64, _data: u64) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_word_32(&mut self, address: u64, data: u32) -> Result<(), crate::Error> {
+            match address - REGISTERS {
+                DR => self.tx.extend_from_slice(&data.to_le_bytes()),
+                offset => {
+                    if offset == CR {
+                        // The abort completes immediately.
+                        self.cr = data & !CR_ABORT;
+                    }
+                    self.writes.push((offset, data));
+                }
+            }
+            Ok(())
+        }
+
+        fn write_word_16(&mut self, _address: u64, _data: u16) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_word_8(&mut self, address: u64, data: u8) -> Result<(), crate::Error> {
+            assert_eq!(address, REGISTERS + DR);
+            self.tx.push(data);
+            Ok(())
+        }
+
+        fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_32(&mut self, _address: u64, _data: &[u32]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_16(&mut self, _address: u64, _data: &[u16]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), crate::Error> {
+            todo!()
+        }
+
+        fn supports_8bit_transfers(&self) -> Result<bool, crate::Error> {
+            Ok(true)
+        }
+
+        fn flush(&mut self) -> Result<(), crate::Error> {
+            Ok(())
+        }
+    }
+
+    fn quadspi() -> Stm32Quadspi {
+        let options = Stm32QuadspiOptions {
+            registers: REGISTERS,
+            prescaler: 7,
+            flash_select: false,
+        };
+        Stm32Quadspi::new(&options, 0x100_0000)
+    }
+
+    #[test]
+    fn init_aborts_memory_mapped_mode() {
+        let mut memory = MockQuadspi {
+            cr: CR_EN,
+            ..Default::default()
+        };
+        quadspi().init(&mut memory).unwrap();
+
+        assert_eq!(
+            memory.writes,
+            [
+                (CR, CR_EN | CR_ABORT),
+                (CR, 0),
+                // 24 address bits for 16 MiB, and two cycles of chip select high time
+                (DCR, 23 << 16 | 1 << 8),
+                (CR, 7 << 24 | CR_EN),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_command() {
+        let mut memory = MockQuadspi {
+            rx: VecDeque::from([1, 2, 3, 4, 5, 6]),
+            ..Default::default()
+        };
+        let command = SpiCommand::new(0x5A)
+            .with_address(0x30, 3)
+            .with_dummy_bytes(1);
+        let mut data = [0; 6];
+        quadspi()
+            .transfer(&mut memory, &command, SpiData::Read(&mut data))
+            .unwrap();
+
+        assert_eq!(data, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            memory.writes,
+            [
+                (FCR, 0x1F),
+                (DLR, 5),
+                // Single line instruction, 3 byte address, 8 dummy cycles, single line data
+                (
+                    CCR,
+                    0x5A | 1 << 8 | 1 << 10 | 2 << 12 | 8 << 18 | 1 << 24 | 1 << 26
+                ),
+                (AR, 0x30),
+                (FCR, SR_TCF),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_command() {
+        let mut memory = MockQuadspi::default();
+        let command = SpiCommand::new(0x02).with_address(0x12_3456, 3);
+        quadspi()
+            .transfer(&mut memory, &command, SpiData::Write(&[1, 2, 3, 4, 5, 6]))
+            .unwrap();
+
+        assert_eq!(memory.tx, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            memory.writes,
+            [
+                (FCR, 0x1F),
+                (DLR, 5),
+                (CCR, 0x02 | 1 << 8 | 1 << 10 | 2 << 12 | 1 << 24),
+                (AR, 0x12_3456),
+                (FCR, SR_TCF),
+            ]
+        );
+    }
+
+    #[test]
+    fn command_without_data() {
+        let mut memory = MockQuadspi::default();
+        quadspi()
+            .transfer(&mut memory, &SpiCommand::new(0x06), SpiData::None)
+            .unwrap();
+
+        assert_eq!(
+            memory.writes,
+            [(FCR, 0x1F), (CCR, 0x06 | 1 << 8), (FCR, SR_TCF)]
+        );
+    }
+
+    #[test]
+    fn memory_mapped_mode() {
+        let mut memory = MockQuadspi::default();
+        let read = SpiCommand::new(0x03).with_address(0, 3);
+        quadspi().enable_memory_mapped(&mut memory, &read).unwrap();
+
+        assert_eq!(
+            memory.writes,
+            [
+                (CCR, 0x03 | 1 << 8 | 1 << 10 | 2 << 12 | 1 << 24 | 3 << 26),
+                (AR, 0),
+            ]
+        );
+    }
+}
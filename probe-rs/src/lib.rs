@@ -93,8 +93,8 @@ pub use crate::config::{CoreType, InstructionSet, Target};
 pub use crate::core::{
     dump::{CoreDump, CoreDumpError},
     exception_handler_for_core, Architecture, BreakpointCause, Core, CoreInformation,
-    CoreInterface, CoreRegister, CoreRegisters, CoreState, CoreStatus, HaltReason,
-    MemoryMappedRegister, RegisterId, RegisterRole, RegisterValue, SemihostingCommand,
+    CoreInterface, CoreRegister, CoreRegisters, CoreState, CoreStatus, ExceptionInterface,
+    HaltReason, MemoryMappedRegister, RegisterId, RegisterRole, RegisterValue, SemihostingCommand,
     SpecificCoreState, VectorCatchCondition,
 };
 pub use crate::error::Error;
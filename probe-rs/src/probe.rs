@@ -12,7 +12,7 @@ pub mod list;
 pub mod stlink;
 pub mod wlink;
 
-use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
+use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence, ProtectionState};
 use crate::architecture::arm::ArmError;
 use crate::architecture::arm::{
     communication_interface::{DapProbe, UninitializedArmProbe},
@@ -287,6 +287,43 @@ impl Probe {
         Ok(())
     }
 
+    /// Query the readout protection state of the chip.
+    ///
+    /// Unlike [`Probe::attach`], this does not halt any core and does not unlock a protected chip,
+    /// so it also works for chips which can not be attached to while they are protected.
+    pub fn protection_state(
+        self,
+        target: impl Into<TargetSelector>,
+    ) -> Result<ProtectionState, Error> {
+        Session::protection_state(self, target.into())
+    }
+
+    /// Enable the readout protection of the chip, unless it is already protected.
+    ///
+    /// The protection state is queried before attaching to the chip, so that a protected chip is
+    /// not unlocked. Returns the protection state of the chip after locking.
+    pub fn lock_device(
+        self,
+        target: impl Into<TargetSelector>,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<ProtectionState, Error> {
+        Session::attach_and_lock(self, target.into(), permanent, permissions)
+    }
+
+    /// Remove the readout protection of the chip, which erases it.
+    ///
+    /// The protection is removed before attaching to the chip, so this works for chips
+    /// which can not be attached to while they are protected. Returns the protection state
+    /// of the chip after the unlock.
+    pub fn unlock_device(
+        self,
+        target: impl Into<TargetSelector>,
+        permissions: &Permissions,
+    ) -> Result<ProtectionState, Error> {
+        Session::unlock_device(self, target.into(), permissions)
+    }
+
     /// Attach to the chip under hard-reset.
     ///
     /// This asserts the reset pin via the probe, plays the protocol init routines and deasserts the pin.
@@ -1,5 +1,10 @@
 #![allow(missing_docs)] // Don't require docs for test code
-use std::{cell::RefCell, collections::VecDeque, fmt::Debug, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    sync::Arc,
+};
 
 use probe_rs_target::ScanChainElement;
 
@@ -48,6 +53,9 @@ struct MockCore {
 
     /// Is the core halted?
     is_halted: bool,
+
+    /// The memory written through the core, below the system region. Other addresses read as 0.
+    memory: HashMap<u64, u8>,
 }
 
 impl MockCore {
@@ -55,6 +63,19 @@ impl MockCore {
         Self {
             dhcsr: Dhcsr(0),
             is_halted: false,
+            memory: HashMap::new(),
+        }
+    }
+
+    fn read_word(&self, address: u64) -> u32 {
+        u32::from_le_bytes(std::array::from_fn(|i| {
+            self.memory.get(&(address + i as u64)).copied().unwrap_or(0)
+        }))
+    }
+
+    fn write_word(&mut self, address: u64, word: u32) {
+        for (i, byte) in word.to_le_bytes().into_iter().enumerate() {
+            self.memory.insert(address + i as u64, byte);
         }
     }
 }
@@ -75,14 +96,22 @@ impl SwdSequence for &mut MockCore {
 }
 
 impl ArmProbe for &mut MockCore {
-    fn read_8(&mut self, _address: u64, _data: &mut [u8]) -> Result<(), ArmError> {
-        todo!()
+    fn read_8(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        for (address, value) in (address..).zip(data.iter_mut()) {
+            *value = self.memory.get(&address).copied().unwrap_or(0);
+        }
+
+        Ok(())
     }
 
     fn read_16(&mut self, _address: u64, _data: &mut [u16]) -> Result<(), ArmError> {
         todo!()
     }
 
+    fn read(&mut self, address: u64, data: &mut [u8]) -> Result<(), ArmError> {
+        self.read_8(address, data)
+    }
+
     fn read_32(&mut self, address: u64, data: &mut [u32]) -> Result<(), ArmError> {
         for (i, val) in data.iter_mut().enumerate() {
             let address = address + (i as u64 * 4);
@@ -105,8 +134,8 @@ impl ArmProbe for &mut MockCore {
                 }
 
                 _ => {
-                    *val = 0;
-                    println!("Read {:#010x} = 0", address);
+                    *val = self.read_word(address);
+                    println!("Read {:#010x} = {:#x}", address, val);
                 }
             }
         }
@@ -114,18 +143,30 @@ impl ArmProbe for &mut MockCore {
         Ok(())
     }
 
-    fn read_64(&mut self, _address: u64, _data: &mut [u64]) -> Result<(), ArmError> {
-        todo!()
+    fn read_64(&mut self, address: u64, data: &mut [u64]) -> Result<(), ArmError> {
+        for (address, value) in (address..).step_by(8).zip(data.iter_mut()) {
+            let mut words = [0; 2];
+            self.read_32(address, &mut words)?;
+            *value = u64::from(words[0]) | u64::from(words[1]) << 32;
+        }
+
+        Ok(())
     }
 
-    fn write_8(&mut self, _address: u64, _data: &[u8]) -> Result<(), ArmError> {
-        todo!()
+    fn write_8(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        self.memory.extend((address..).zip(data.iter().copied()));
+
+        Ok(())
     }
 
     fn write_16(&mut self, _address: u64, _data: &[u16]) -> Result<(), ArmError> {
         todo!()
     }
 
+    fn write(&mut self, address: u64, data: &[u8]) -> Result<(), ArmError> {
+        self.write_8(address, data)
+    }
+
     fn write_32(&mut self, address: u64, data: &[u32]) -> Result<(), ArmError> {
         for (i, word) in data.iter().enumerate() {
             let address = address + (i as u64 * 4);
@@ -150,15 +191,26 @@ impl ArmProbe for &mut MockCore {
                         }
                     }
                 }
-                _ => println!("Write {:#010x} = {:#010x}", address, word),
+                // Memory below the system region, i.e. no register with side effects.
+                _ if address < 0xE000_0000 => {
+                    self.write_word(address, *word);
+                    println!("Write {:#010x} = {:#010x}", address, word);
+                }
+                _ => {
+                    println!("Write {:#010x} = {:#010x}", address, word);
+                }
             }
         }
 
         Ok(())
     }
 
-    fn write_64(&mut self, _address: u64, _data: &[u64]) -> Result<(), ArmError> {
-        todo!()
+    fn write_64(&mut self, address: u64, data: &[u64]) -> Result<(), ArmError> {
+        for (address, value) in (address..).step_by(8).zip(data.iter()) {
+            self.write_32(address, &[*value as u32, (*value >> 32) as u32])?;
+        }
+
+        Ok(())
     }
 
     fn flush(&mut self) -> Result<(), ArmError> {
@@ -170,7 +222,7 @@ impl ArmProbe for &mut MockCore {
     }
 
     fn supports_8bit_transfers(&self) -> Result<bool, ArmError> {
-        todo!()
+        Ok(true)
     }
 
     fn ap(&mut self) -> MemoryAp {
@@ -55,7 +55,10 @@ pub use syscall::*;
 pub mod channels;
 pub use channels::Channels;
 
-use crate::{config::MemoryRegion, Core, MemoryInterface};
+mod poller;
+pub use poller::{RttData, RttPoller, RttPollerThread};
+
+use crate::{config::MemoryRegion, Core, InstructionSet, MemoryInterface};
 use scroll::{Pread, LE};
 use std::borrow::Cow;
 use std::collections::BTreeMap;
@@ -91,7 +94,17 @@ use std::ops::Range;
 ///         * RTT Channel names are correct, but no data, or corrupted data, will be reported from RTT, because the buffer sizes are incorrect.
 #[derive(Debug)]
 pub struct Rtt {
-    ptr: u32,
+    ptr: u64,
+
+    /// The region in which the control block was found, used by [`Rtt::reattach`].
+    region: ScanRegion,
+
+    /// The layout of the control block when attaching, used by [`Rtt::control_block_is_valid`].
+    max_up_channels: usize,
+    max_down_channels: usize,
+    pointer_size: usize,
+    /// Buffer address and size of every channel slot, up channels first.
+    channel_buffers: Vec<(u64, u32)>,
 
     /// The detected up (target to host) channels.
     pub up_channels: Channels<UpChannel>,
@@ -112,6 +125,9 @@ pub struct Rtt {
 //     RttChannel up_channels[max_up_channels]; // Array of up (target to host) channels.
 //     RttChannel down_channels[max_down_channels]; // array of down (host to target) channels.
 // }
+//
+// The channel arrays are aligned to the pointer size, which is 8 bytes on 64-bit targets. As the
+// header is 24 bytes long, the offset of the arrays is the same in both cases.
 
 impl Rtt {
     const RTT_ID: [u8; 16] = *b"SEGGER RTT\0\0\0\0\0\0";
@@ -129,7 +145,7 @@ impl Rtt {
         core: &mut Core,
         memory_map: &[MemoryRegion],
         // Pointer from which to scan
-        ptr: u32,
+        ptr: u64,
         // Memory contents read in advance, starting from ptr
         mem_in: Option<&[u8]>,
     ) -> Result<Option<Rtt>, Error> {
@@ -138,7 +154,7 @@ impl Rtt {
             None => {
                 // If memory wasn't passed in, read the minimum header size
                 let mut mem = vec![0u8; Self::MIN_SIZE];
-                core.read(ptr.into(), &mut mem)?;
+                core.read(ptr, &mut mem)?;
                 Cow::Owned(mem)
             }
         };
@@ -166,13 +182,22 @@ impl Rtt {
             )));
         }
 
-        let cb_len = Self::O_CHANNEL_ARRAYS + (max_up_channels + max_down_channels) * Channel::SIZE;
+        // The layout of the channels depends on the pointer width of the target. This can't be
+        // derived from the supported memory accesses, a 32-bit core can be behind an access port
+        // with 64-bit accesses.
+        let pointer_size = match core.instruction_set()? {
+            InstructionSet::A64 => 8,
+            _ => 4,
+        };
+        let channel_size = Channel::size(pointer_size);
+
+        let cb_len = Self::O_CHANNEL_ARRAYS + (max_up_channels + max_down_channels) * channel_size;
 
         if let Cow::Owned(mem) = &mut mem {
             // If memory wasn't passed in, read the rest of the control block
             mem.resize(cb_len, 0);
             core.read(
-                (ptr + Self::MIN_SIZE as u32).into(),
+                ptr + Self::MIN_SIZE as u64,
                 &mut mem[Self::MIN_SIZE..cb_len],
             )?;
         }
@@ -185,32 +210,50 @@ impl Rtt {
 
         let mut up_channels = BTreeMap::new();
         let mut down_channels = BTreeMap::new();
+        let channel_buffers = (0..max_up_channels + max_down_channels)
+            .map(|i| Self::channel_buffer(&mem, i, pointer_size))
+            .collect();
 
         let mut offset = Self::O_CHANNEL_ARRAYS;
         for i in 0..max_up_channels {
-            if let Some(chan) =
-                Channel::from(core, i, memory_map, ptr + offset as u32, &mem[offset..])?
-            {
+            if let Some(chan) = Channel::from(
+                core,
+                i,
+                memory_map,
+                ptr + offset as u64,
+                pointer_size,
+                &mem[offset..],
+            )? {
                 up_channels.insert(i, UpChannel(chan));
             } else {
                 tracing::warn!("Buffer for up channel {} not initialized", i);
             }
-            offset += Channel::SIZE;
+            offset += channel_size;
         }
 
         for i in 0..max_down_channels {
-            if let Some(chan) =
-                Channel::from(core, i, memory_map, ptr + offset as u32, &mem[offset..])?
-            {
+            if let Some(chan) = Channel::from(
+                core,
+                i,
+                memory_map,
+                ptr + offset as u64,
+                pointer_size,
+                &mem[offset..],
+            )? {
                 down_channels.insert(i, DownChannel(chan));
             } else {
                 tracing::warn!("Buffer for down channel {} not initialized", i);
             }
-            offset += Channel::SIZE;
+            offset += channel_size;
         }
 
         Ok(Some(Rtt {
             ptr,
+            region: ScanRegion::Exact(ptr),
+            max_up_channels,
+            max_down_channels,
+            pointer_size,
+            channel_buffers,
             up_channels: Channels(up_channels),
             down_channels: Channels(down_channels),
         }))
@@ -232,6 +275,17 @@ impl Rtt {
         core: &mut Core,
         memory_map: &[MemoryRegion],
         region: &ScanRegion,
+    ) -> Result<Rtt, Error> {
+        let mut rtt = Self::find_control_block(core, memory_map, region)?;
+        rtt.region = region.clone();
+
+        Ok(rtt)
+    }
+
+    fn find_control_block(
+        core: &mut Core,
+        memory_map: &[MemoryRegion],
+        region: &ScanRegion,
     ) -> Result<Rtt, Error> {
         let ranges: Vec<Range<u64>> = match region {
             ScanRegion::Exact(addr) => {
@@ -297,16 +351,6 @@ impl Rtt {
                 match kmp::kmp_find(&Self::RTT_ID, mem.as_slice()) {
                     Some(offset) => {
                         let target_ptr = range.start + (offset as u64);
-                        let target_ptr: u32 = match target_ptr.try_into() {
-                            Ok(v) => v,
-                            Err(_) => {
-                                // FIXME: The RTT API currently supports only
-                                // 32-bit addresses, and so it can't accept
-                                // an RTT block at an address >4GiB.
-                                tracing::warn!("can't use RTT block at {:#010x}; must be at a location reachable by 32-bit addressing", target_ptr);
-                                return None;
-                            },
-                        };
 
                         Rtt::from(
                             core,
@@ -335,10 +379,81 @@ impl Rtt {
     }
 
     /// Returns the memory address of the control block in target memory.
-    pub fn ptr(&self) -> u32 {
+    pub fn ptr(&self) -> u64 {
         self.ptr
     }
 
+    /// Checks whether the control block in target memory still has the layout it had when
+    /// attaching.
+    ///
+    /// This is not the case anymore if the target was reset and the control block was cleared,
+    /// moved or initialized with different channels, in which case all channels of this instance
+    /// access stale memory. Use [`Rtt::reattach`] to attach to the new control block.
+    pub fn control_block_is_valid(&self, core: &mut Core) -> Result<bool, Error> {
+        let channel_size = Channel::size(self.pointer_size);
+        let mut mem = vec![0u8; Self::O_CHANNEL_ARRAYS + self.channel_buffers.len() * channel_size];
+        core.read(self.ptr, &mut mem)?;
+
+        Ok(self.matches_control_block(&mem))
+    }
+
+    /// Checks the memory of the control block against the layout read when attaching.
+    fn matches_control_block(&self, mem: &[u8]) -> bool {
+        if mem[Self::O_ID..][..Self::RTT_ID.len()] != Self::RTT_ID {
+            tracing::debug!("RTT control block ID was overwritten");
+            return false;
+        }
+
+        let max_up_channels = mem.pread_with::<u32>(Self::O_MAX_UP_CHANNELS, LE).unwrap() as usize;
+        let max_down_channels = mem
+            .pread_with::<u32>(Self::O_MAX_DOWN_CHANNELS, LE)
+            .unwrap() as usize;
+        if (max_up_channels, max_down_channels) != (self.max_up_channels, self.max_down_channels) {
+            tracing::debug!("RTT control block has a different number of channels");
+            return false;
+        }
+
+        let channel_size = Channel::size(self.pointer_size);
+        for (i, &(buffer_ptr, size)) in self.channel_buffers.iter().enumerate() {
+            if Self::channel_buffer(mem, i, self.pointer_size) != (buffer_ptr, size) {
+                tracing::debug!("Buffer of RTT channel slot {} was moved", i);
+                return false;
+            }
+
+            if buffer_ptr != 0 {
+                let offset = Self::O_CHANNEL_ARRAYS + i * channel_size + 2 * self.pointer_size;
+                let write = mem.pread_with::<u32>(offset + 4, LE).unwrap();
+                let read = mem.pread_with::<u32>(offset + 8, LE).unwrap();
+                if write >= size || read >= size {
+                    tracing::debug!("Offsets of RTT channel slot {} are out of range", i);
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Reads the buffer address and size of channel slot `i` from the control block memory.
+    fn channel_buffer(mem: &[u8], i: usize, pointer_size: usize) -> (u64, u32) {
+        let offset = Self::O_CHANNEL_ARRAYS + i * Channel::size(pointer_size);
+        let buffer_ptr = read_pointer(mem, offset + pointer_size, pointer_size).unwrap_or(0);
+        let size = mem
+            .pread_with::<u32>(offset + 2 * pointer_size, LE)
+            .unwrap_or(0);
+
+        (buffer_ptr, size)
+    }
+
+    /// Attempts to attach to the control block again, by scanning the region in which it was
+    /// originally found.
+    ///
+    /// This is used after [`Rtt::control_block_is_valid`] detected that the target re-initialized
+    /// RTT. Channel modes set on the previous channels have to be set again.
+    pub fn reattach(&self, core: &mut Core, memory_map: &[MemoryRegion]) -> Result<Rtt, Error> {
+        Self::attach_region(core, memory_map, &self.region)
+    }
+
     /// Gets a mutable reference to the detected up channels.
     pub fn up_channels(&mut self) -> &mut Channels<UpChannel> {
         &mut self.up_channels
@@ -373,7 +488,7 @@ pub enum ScanRegion {
     /// Tries to find the control block starting at this exact address. It is up to the user to
     /// ensure that reading the necessary bytes after the pointer will no read from undefined
     /// memory.
-    Exact(u32),
+    Exact(u64),
 }
 
 /// Error type for RTT operations.
@@ -387,7 +502,7 @@ pub enum Error {
     ControlBlockNotFound,
 
     /// Multiple control blocks found in target memory: {display_list(_0)}.
-    MultipleControlBlocksFound(Vec<u32>),
+    MultipleControlBlocksFound(Vec<u64>),
 
     /// The control block has been corrupted. {0}
     ControlBlockCorrupted(String),
@@ -402,7 +517,7 @@ pub enum Error {
     MemoryRead(String),
 }
 
-fn display_list(list: &[u32]) -> String {
+fn display_list(list: &[u64]) -> String {
     list.iter()
         .map(|x| format!("{:#x}", x))
         .collect::<Vec<_>>()
@@ -419,4 +534,150 @@ mod test {
             "Multiple control blocks found in target memory: 0x2000, 0x3000."
         );
     }
+
+    #[test]
+    fn channel_layout_depends_on_pointer_size() {
+        use super::channel::{read_pointer, Channel};
+
+        assert_eq!(Channel::size(4), 24);
+        assert_eq!(Channel::size(8), 32);
+
+        let mem = [0x00, 0x10, 0x00, 0x20, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(read_pointer(&mem, 0, 4), Some(0x2000_1000));
+        assert_eq!(read_pointer(&mem, 0, 8), Some(0x1_2000_1000));
+        assert_eq!(read_pointer(&mem, 4, 8), None);
+    }
+
+    #[test]
+    fn control_block_changes_are_detected() {
+        use super::{Channels, Rtt, ScanRegion};
+
+        // A control block with one up and one down channel, of which only the up channel is used.
+        let mut mem = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        mem.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0]);
+        // Up channel: name, buffer, size, write, read, flags
+        for word in [0x2000_0100u32, 0x2000_0200, 0x100, 0x10, 0x08, 0] {
+            mem.extend_from_slice(&word.to_le_bytes());
+        }
+        mem.extend_from_slice(&[0; 24]);
+
+        let rtt = Rtt {
+            ptr: 0x2000_0000,
+            region: ScanRegion::Exact(0x2000_0000),
+            max_up_channels: 1,
+            max_down_channels: 1,
+            pointer_size: 4,
+            channel_buffers: (0..2).map(|i| Rtt::channel_buffer(&mem, i, 4)).collect(),
+            up_channels: Channels::default(),
+            down_channels: Channels::default(),
+        };
+        assert_eq!(rtt.channel_buffers, [(0x2000_0200, 0x100), (0, 0)]);
+        assert!(rtt.matches_control_block(&mem));
+
+        let mut cleared = mem.clone();
+        cleared[..16].fill(0);
+        assert!(!rtt.matches_control_block(&cleared));
+
+        let mut more_channels = mem.clone();
+        more_channels[16] = 2;
+        assert!(!rtt.matches_control_block(&more_channels));
+
+        let mut moved_buffer = mem.clone();
+        moved_buffer[28] = 0x04;
+        assert!(!rtt.matches_control_block(&moved_buffer));
+
+        let mut invalid_offset = mem.clone();
+        invalid_offset[36..40].copy_from_slice(&0x100u32.to_le_bytes());
+        assert!(!rtt.matches_control_block(&invalid_offset));
+    }
+
+    #[test]
+    fn control_block_of_32_bit_core_behind_64_bit_access_port() {
+        use super::{Rtt, ScanRegion};
+        use crate::architecture::arm::core::CortexMState;
+        use crate::architecture::arm::{
+            ap::memory_ap::mock::MockMemoryAp, core::armv7m::Armv7m,
+            memory::adi_v5_memory_interface::ADIMemoryInterface, sequences::DefaultArmSequence,
+            ApAddress, DpAddress, MemoryApInformation,
+        };
+        use crate::{Core, MemoryInterface};
+
+        // A control block with one up channel, using 32-bit pointers.
+        let mut mock = MockMemoryAp::with_pattern_and_size(0x100);
+        let mut mem = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        mem.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        // Up channel: name, buffer, size, write, read, flags
+        for word in [0u32, 0x80, 0x40, 0x10, 0x08, 0] {
+            mem.extend_from_slice(&word.to_le_bytes());
+        }
+        mock.memory[..mem.len()].copy_from_slice(&mem);
+
+        let ap_information = MemoryApInformation {
+            address: ApAddress {
+                dp: DpAddress::Default,
+                ap: 0,
+            },
+            supports_only_32bit_data_size: false,
+            supports_hnonsec: false,
+            debug_base_address: 0xf000_0000,
+            has_large_address_extension: false,
+            has_large_data_extension: true,
+            device_enabled: true,
+        };
+        let memory = ADIMemoryInterface::new(&mut mock, ap_information).unwrap();
+
+        let mut state = CortexMState::new();
+        state.initialize();
+        let armv7m =
+            Armv7m::new(Box::new(memory), &mut state, DefaultArmSequence::create()).unwrap();
+        let mut core = Core::new(0, "core", &[], armv7m);
+        assert!(core.supports_native_64bit_access());
+
+        let mut rtt = Rtt::attach_region(&mut core, &[], &ScanRegion::Exact(0))
+            .expect("control block should be found");
+        assert_eq!(rtt.pointer_size, 4);
+        assert_eq!(rtt.channel_buffers, [(0x80, 0x40)]);
+        let channel = rtt.up_channels.get(0).expect("up channel should be found");
+        assert_eq!(channel.buffer_size(), 0x40);
+    }
+
+    #[test]
+    fn reinitialized_channels_are_detected() {
+        use super::{ChannelMode, Rtt, ScanRegion};
+        use crate::probe::fake_probe::FakeProbe;
+        use crate::{MemoryInterface, Permissions};
+
+        let probe = FakeProbe::with_mocked_core().into_probe();
+        let mut session = probe
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+        let mut core = session.core(0).unwrap();
+
+        // A control block with one up channel, which the target initialized to block if full.
+        let mut control_block = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        control_block.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        // Up channel: name, buffer, size, write, read, flags
+        for word in [0u32, 0x2000_0100, 0x40, 0, 0, 2] {
+            control_block.extend_from_slice(&word.to_le_bytes());
+        }
+        core.write_8(0x2000_0000, &control_block).unwrap();
+
+        let mut rtt = Rtt::attach_region(&mut core, &[], &ScanRegion::Exact(0x2000_0000)).unwrap();
+        let channel = rtt.up_channels().take(0).unwrap();
+        assert!(!channel.was_reinitialized(&mut core).unwrap());
+
+        // The target writes data, which the host reads.
+        core.write_8(0x2000_0100, b"hello").unwrap();
+        core.write_word_32(0x2000_0024, 5).unwrap();
+        channel.read(&mut core, &mut [0; 0x40]).unwrap();
+        channel
+            .set_mode(&mut core, ChannelMode::NoBlockSkip)
+            .unwrap();
+        assert!(!channel.was_reinitialized(&mut core).unwrap());
+
+        // The target is reset and initializes the control block again, with the same layout.
+        core.write_8(0x2000_0000, &control_block).unwrap();
+        assert!(rtt.control_block_is_valid(&mut core).unwrap());
+        assert!(channel.was_reinitialized(&mut core).unwrap());
+    }
 }
@@ -2,6 +2,7 @@ use crate::rtt::Error;
 use crate::{config::MemoryRegion, Core, MemoryInterface};
 use scroll::{Pread, LE};
 use std::cmp::min;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 /// Trait for channel information shared between up and down channels.
 pub trait RttChannel {
@@ -20,10 +21,18 @@ pub trait RttChannel {
 pub(crate) struct Channel {
     number: usize,
     core_id: usize,
-    ptr: u32,
+    ptr: u64,
     name: Option<String>,
-    buffer_ptr: u32,
+    buffer_ptr: u64,
     size: u32,
+    pointer_size: usize,
+    /// The fields of the header which only the host changes after the target initialized the
+    /// channel, i.e. the read offset of up channels, the write offset of down channels and the
+    /// flags, as read when attaching or last written by the host. See
+    /// [`UpChannel::was_reinitialized`].
+    host_read: AtomicU32,
+    host_write: AtomicU32,
+    host_flags: AtomicU32,
 }
 
 // Chanels must follow this data layout when reading/writing memory in order to be compatible with
@@ -38,40 +47,56 @@ pub(crate) struct Channel {
 //     // The low 2 bits of flags are used for blocking/non blocking modes, the rest are ignored.
 //     unsigned int flags;
 // }
+//
+// The size of the pointers depends on the target, so the offsets of all fields after them do too.
 
 impl Channel {
-    // Size of the Channel struct in target memory in bytes
-    pub(crate) const SIZE: usize = 24;
-
-    // Offsets of fields in target memory in bytes
+    // Offset of the name pointer in target memory in bytes. All other offsets depend on the pointer size.
     const O_NAME: usize = 0;
-    const O_BUFFER_PTR: usize = 4;
-    const O_SIZE: usize = 8;
-    const O_WRITE: usize = 12;
-    const O_READ: usize = 16;
-    const O_FLAGS: usize = 20;
+
+    /// Size of the Channel struct in target memory in bytes, for pointers of `pointer_size` bytes.
+    pub(crate) const fn size(pointer_size: usize) -> usize {
+        2 * pointer_size + 16
+    }
+
+    const fn o_buffer_ptr(pointer_size: usize) -> usize {
+        pointer_size
+    }
+
+    const fn o_size(pointer_size: usize) -> usize {
+        2 * pointer_size
+    }
+
+    fn o_write(&self) -> u64 {
+        (2 * self.pointer_size + 4) as u64
+    }
+
+    fn o_read(&self) -> u64 {
+        (2 * self.pointer_size + 8) as u64
+    }
+
+    fn o_flags(&self) -> u64 {
+        (2 * self.pointer_size + 12) as u64
+    }
 
     pub(crate) fn from(
         core: &mut Core,
         number: usize,
         memory_map: &[MemoryRegion],
-        ptr: u32,
+        ptr: u64,
+        pointer_size: usize,
         mem: &[u8],
     ) -> Result<Option<Channel>, Error> {
-        let buffer_ptr: u32 = match mem.pread_with(Self::O_BUFFER_PTR, LE) {
-            Ok(buffer_ptr) => buffer_ptr,
-            Err(_error) => return Err(Error::MemoryRead("RTT channel address".to_string())),
-        };
+        let buffer_ptr = read_pointer(mem, Self::o_buffer_ptr(pointer_size), pointer_size)
+            .ok_or_else(|| Error::MemoryRead("RTT channel address".to_string()))?;
 
         if buffer_ptr == 0 {
             // This buffer isn't in use
             return Ok(None);
         }
 
-        let name_ptr: u32 = match mem.pread_with(Self::O_NAME, LE) {
-            Ok(name_ptr) => name_ptr,
-            Err(_error) => return Err(Error::MemoryRead("RTT channel name".to_string())),
-        };
+        let name_ptr = read_pointer(mem, Self::O_NAME, pointer_size)
+            .ok_or_else(|| Error::MemoryRead("RTT channel name".to_string()))?;
 
         let name = if name_ptr == 0 {
             None
@@ -85,10 +110,31 @@ impl Channel {
             ptr,
             name,
             buffer_ptr,
-            size: mem.pread_with(Self::O_SIZE, LE).unwrap(),
+            size: mem.pread_with(Self::o_size(pointer_size), LE).unwrap(),
+            pointer_size,
+            host_write: AtomicU32::new(mem.pread_with(2 * pointer_size + 4, LE).unwrap()),
+            host_read: AtomicU32::new(mem.pread_with(2 * pointer_size + 8, LE).unwrap()),
+            host_flags: AtomicU32::new(mem.pread_with(2 * pointer_size + 12, LE).unwrap()),
         }))
     }
 
+    /// Creates an unnamed channel of a 32-bit target with its header at `ptr`.
+    #[cfg(test)]
+    pub(crate) fn for_test(number: usize, ptr: u64, buffer_ptr: u64, size: u32) -> Self {
+        Channel {
+            number,
+            core_id: 0,
+            ptr,
+            name: None,
+            buffer_ptr,
+            size,
+            pointer_size: 4,
+            host_read: AtomicU32::new(0),
+            host_write: AtomicU32::new(0),
+            host_flags: AtomicU32::new(0),
+        }
+    }
+
     /// Validate that the Core id of a request is the same as the Core id against which the Channel was created.
     pub(crate) fn validate_core_id(&self, core: &mut Core) -> Result<(), Error> {
         if core.id() == self.core_id {
@@ -106,14 +152,40 @@ impl Channel {
         self.size as usize
     }
 
-    fn read_pointers(&self, core: &mut Core, dir: &'static str) -> Result<(u32, u32), Error> {
+    /// Address of the write offset in target memory, which is directly followed by the read offset.
+    pub(crate) fn pointers_address(&self) -> u64 {
+        self.ptr + self.o_write()
+    }
+
+    /// Reads the write and read offsets and the flags.
+    fn read_header(&self, core: &mut Core) -> Result<[u32; 3], Error> {
         self.validate_core_id(core)?;
+
+        let mut header = [0u32; 3];
+        core.read_32(self.pointers_address(), &mut header)?;
+        Ok(header)
+    }
+
+    /// Reads the write and read offsets. The caller checks that `memory` belongs to the channel's
+    /// core.
+    pub(crate) fn read_pointers(
+        &self,
+        memory: &mut impl MemoryInterface,
+        dir: &'static str,
+    ) -> Result<(u32, u32), Error> {
         let mut block = [0u32; 2];
-        core.read_32((self.ptr + Self::O_WRITE as u32).into(), block.as_mut())?;
+        memory.read_32(self.pointers_address(), block.as_mut())?;
 
-        let write: u32 = block[0];
-        let read: u32 = block[1];
+        self.validate_pointers(block[0], block[1], dir)
+    }
 
+    /// Checks that the write and read offsets read from the target are within the buffer.
+    pub(crate) fn validate_pointers(
+        &self,
+        write: u32,
+        read: u32,
+        dir: &'static str,
+    ) -> Result<(u32, u32), Error> {
         let validate = |which, value| {
             if value >= self.size {
                 Err(Error::ControlBlockCorrupted(format!(
@@ -164,7 +236,7 @@ impl UpChannel {
     pub fn mode(&self, core: &mut Core) -> Result<ChannelMode, Error> {
         self.0.validate_core_id(core)?;
 
-        let flags = core.read_word_32((self.0.ptr + Channel::O_FLAGS as u32).into())?;
+        let flags = core.read_word_32(self.0.ptr + self.0.o_flags())?;
 
         match flags & 0x3 {
             0 => Ok(ChannelMode::NoBlockSkip),
@@ -181,18 +253,46 @@ impl UpChannel {
     /// See [`ChannelMode`] for more information on what the modes mean.
     pub fn set_mode(&self, core: &mut Core, mode: ChannelMode) -> Result<(), Error> {
         self.0.validate_core_id(core)?;
-        let flags = core.read_word_32((self.0.ptr + Channel::O_FLAGS as u32).into())?;
+        let flags = core.read_word_32(self.0.ptr + self.0.o_flags())?;
 
         let new_flags = (flags & !3) | (mode as u32);
-        core.write_word_32((self.0.ptr + Channel::O_FLAGS as u32).into(), new_flags)?;
+        core.write_word_32(self.0.ptr + self.0.o_flags(), new_flags)?;
+        self.0.host_flags.store(new_flags, Ordering::Relaxed);
 
         Ok(())
     }
 
-    fn read_core(&self, core: &mut Core, mut buf: &mut [u8]) -> Result<(u32, usize), Error> {
+    /// Checks whether the target initialized the channel again since attaching, e.g. after a
+    /// reset.
+    ///
+    /// Only the host changes the read offset and the flags of an up channel after the target
+    /// initialized it, so the channel was initialized again if they differ from the values the
+    /// host last wrote. This detects a control block which was initialized again with the same
+    /// layout, which [`Rtt::control_block_is_valid`](crate::rtt::Rtt::control_block_is_valid)
+    /// can't distinguish from the old one.
+    pub fn was_reinitialized(&self, core: &mut Core) -> Result<bool, Error> {
+        let [_, read, flags] = self.0.read_header(core)?;
+
+        Ok(read != self.0.host_read.load(Ordering::Relaxed)
+            || flags != self.0.host_flags.load(Ordering::Relaxed))
+    }
+
+    fn read_core(&self, core: &mut Core, buf: &mut [u8]) -> Result<(u32, usize), Error> {
         self.0.validate_core_id(core)?;
-        let (write, mut read) = self.0.read_pointers(core, "up")?;
+        let (write, read) = self.0.read_pointers(core, "up")?;
+
+        self.read_region(core, write, read, buf)
+    }
 
+    /// Reads the data between the `read` and `write` offsets into `buf`, and returns the new read
+    /// offset and how many bytes were read.
+    pub(crate) fn read_region(
+        &self,
+        core: &mut Core,
+        write: u32,
+        mut read: u32,
+        mut buf: &mut [u8],
+    ) -> Result<(u32, usize), Error> {
         let mut total = 0;
 
         // Read while buffer contains data and output buffer has space (maximum of two iterations)
@@ -202,7 +302,7 @@ impl UpChannel {
                 break;
             }
 
-            core.read((self.0.buffer_ptr + read).into(), &mut buf[..count])?;
+            core.read(self.0.buffer_ptr + read as u64, &mut buf[..count])?;
 
             total += count;
             read += count as u32;
@@ -229,12 +329,19 @@ impl UpChannel {
 
         if total > 0 {
             // Write read pointer back to target if something was read
-            core.write_word_32((self.0.ptr + Channel::O_READ as u32).into(), read)?;
+            self.write_read_pointer(core, read)?;
         }
 
         Ok(total)
     }
 
+    /// Writes the read offset back to the target, which frees the space of the data read so far.
+    pub(crate) fn write_read_pointer(&self, core: &mut Core, read: u32) -> Result<(), Error> {
+        core.write_word_32(self.0.ptr + self.0.o_read(), read)?;
+        self.0.host_read.store(read, Ordering::Relaxed);
+        Ok(())
+    }
+
     /// Peeks at the current data in the channel buffer, copies data into the specified buffer and
     /// returns how many bytes were read.
     ///
@@ -312,7 +419,7 @@ impl DownChannel {
                 break;
             }
 
-            core.write_8((self.0.buffer_ptr + write).into(), &buf[..count])?;
+            core.write_8(self.0.buffer_ptr + write as u64, &buf[..count])?;
 
             total += count;
             write += count as u32;
@@ -326,11 +433,24 @@ impl DownChannel {
         }
 
         // Write write pointer back to target
-        core.write_word_32((self.0.ptr + Channel::O_WRITE as u32).into(), write)?;
+        core.write_word_32(self.0.ptr + self.0.o_write(), write)?;
+        self.0.host_write.store(write, Ordering::Relaxed);
 
         Ok(total)
     }
 
+    /// Checks whether the target initialized the channel again since attaching, e.g. after a
+    /// reset.
+    ///
+    /// Like [`UpChannel::was_reinitialized`], but with the write offset, which only the host
+    /// changes for a down channel.
+    pub fn was_reinitialized(&self, core: &mut Core) -> Result<bool, Error> {
+        let [write, _, flags] = self.0.read_header(core)?;
+
+        Ok(write != self.0.host_write.load(Ordering::Relaxed)
+            || flags != self.0.host_flags.load(Ordering::Relaxed))
+    }
+
     /// Calculates amount of contiguous space available for writing
     fn writable_contiguous(&self, write: u32, read: u32) -> usize {
         (if read > write {
@@ -357,11 +477,19 @@ impl RttChannel for DownChannel {
     }
 }
 
+/// Reads a little endian pointer of `pointer_size` bytes at `offset`.
+pub(crate) fn read_pointer(mem: &[u8], offset: usize, pointer_size: usize) -> Option<u64> {
+    match pointer_size {
+        8 => mem.pread_with::<u64>(offset, LE).ok(),
+        _ => mem.pread_with::<u32>(offset, LE).ok().map(u64::from),
+    }
+}
+
 /// Reads a null-terminated string from target memory. Lossy UTF-8 decoding is used.
 fn read_c_string(
     core: &mut Core,
     memory_map: &[MemoryRegion],
-    ptr: u32,
+    ptr: u64,
 ) -> Result<Option<String>, Error> {
     // Find out which memory range contains the pointer
     let range = memory_map
@@ -371,7 +499,7 @@ fn read_c_string(
             MemoryRegion::Ram(r) => Some(&r.range),
             _ => None,
         })
-        .find(|r| r.contains(&(ptr as u64)));
+        .find(|r| r.contains(&ptr));
 
     // If the pointer is not within any valid range, return None.
     let range = match range {
@@ -380,8 +508,8 @@ fn read_c_string(
     };
 
     // Read up to 128 bytes not going past the end of the region
-    let mut bytes = vec![0u8; min(128, (range.end - ptr as u64) as usize)];
-    core.read(ptr.into(), bytes.as_mut())?;
+    let mut bytes = vec![0u8; min(128, (range.end - ptr) as usize)];
+    core.read(ptr, bytes.as_mut())?;
 
     let return_value = bytes
         .iter()
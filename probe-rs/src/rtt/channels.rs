@@ -37,6 +37,14 @@ impl<T: RttChannel> Channels<T> {
     }
 }
 
+impl<T: RttChannel> Default for Channels<T> {
+    /// Returns an empty list, e.g. to take all channels out of an [`Rtt`](crate::rtt::Rtt) with
+    /// [`std::mem::take`].
+    fn default() -> Self {
+        Self(BTreeMap::new())
+    }
+}
+
 impl<T: RttChannel> IntoIterator for Channels<T> {
     type Item = T;
     type IntoIter = IntoValues<usize, T>;
@@ -0,0 +1,351 @@
+//! Batched polling of RTT up channels.
+
+use crate::rtt::{Error, UpChannel};
+use crate::{Core, MemoryInterface, Session};
+use scroll::{Pread, LE};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// The largest memory range read at once to snapshot the channel headers. Channels further apart,
+/// e.g. because they belong to different control blocks, are read one by one.
+const MAX_SNAPSHOT_SIZE: u64 = 0x1000;
+
+/// Polls RTT up channels with as few memory transactions as possible.
+///
+/// Each poll reads the write and read offsets of all channels in a single transaction, and only
+/// reads the ring buffers of the channels which contain new data. The delay until the next poll,
+/// see [`RttPoller::next_poll_delay`], grows while no data arrives, to reduce the load on the
+/// probe and the intrusion into the target.
+#[derive(Debug)]
+pub struct RttPoller {
+    min_interval: Duration,
+    max_interval: Duration,
+    interval: Duration,
+    /// Holds the channel headers read by the last poll.
+    snapshot: Vec<u8>,
+    /// Holds the data of the channel which is currently being read.
+    data: Vec<u8>,
+}
+
+impl Default for RttPoller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RttPoller {
+    /// Creates a poller which polls every millisecond while data is arriving, and backs off to
+    /// every 100 milliseconds when idle.
+    pub fn new() -> Self {
+        Self::with_poll_interval(Duration::from_millis(1), Duration::from_millis(100))
+    }
+
+    /// Creates a poller which polls every `min` while data is arriving, and backs off to every
+    /// `max` when idle.
+    pub fn with_poll_interval(min: Duration, max: Duration) -> Self {
+        Self {
+            min_interval: min,
+            max_interval: max.max(min),
+            interval: min,
+            snapshot: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Returns how long to wait before the next call to [`RttPoller::poll`].
+    pub fn next_poll_delay(&self) -> Duration {
+        self.interval
+    }
+
+    /// Reads all new data of `channels`, and calls `on_data` with the data of every channel which
+    /// had any. Returns the total number of bytes read.
+    pub fn poll(
+        &mut self,
+        core: &mut Core,
+        channels: &[&UpChannel],
+        mut on_data: impl FnMut(&UpChannel, &[u8]),
+    ) -> Result<usize, Error> {
+        for channel in channels {
+            channel.0.validate_core_id(core)?;
+        }
+
+        let pointers = self.snapshot_pointers(core, channels)?;
+
+        let mut total = 0;
+        for (channel, (write, read)) in channels.iter().zip(pointers) {
+            if write == read {
+                continue;
+            }
+
+            self.data.resize(channel.buffer_size(), 0);
+            let (read, count) = channel.read_region(core, write, read, &mut self.data)?;
+            if count > 0 {
+                channel.write_read_pointer(core, read)?;
+                on_data(channel, &self.data[..count]);
+                total += count;
+            }
+        }
+
+        self.update_interval(total > 0);
+
+        Ok(total)
+    }
+
+    /// Reads the write and read offsets of all channels, if possible in a single transaction.
+    fn snapshot_pointers(
+        &mut self,
+        memory: &mut impl MemoryInterface,
+        channels: &[&UpChannel],
+    ) -> Result<Vec<(u32, u32)>, Error> {
+        let addresses = channels.iter().map(|channel| channel.0.pointers_address());
+        let (Some(start), Some(end)) = (addresses.clone().min(), addresses.max().map(|a| a + 8))
+        else {
+            return Ok(Vec::new());
+        };
+
+        let batched = end - start <= MAX_SNAPSHOT_SIZE;
+        if batched {
+            self.snapshot.resize((end - start) as usize, 0);
+            memory.read(start, &mut self.snapshot)?;
+        }
+
+        channels
+            .iter()
+            .map(|channel| {
+                if !batched {
+                    return channel.0.read_pointers(memory, "up");
+                }
+
+                let offset = (channel.0.pointers_address() - start) as usize;
+                let write = self.snapshot.pread_with::<u32>(offset, LE).unwrap();
+                let read = self.snapshot.pread_with::<u32>(offset + 4, LE).unwrap();
+
+                channel.0.validate_pointers(write, read, "up")
+            })
+            .collect()
+    }
+
+    /// Polls again right away while data is arriving, and doubles the delay while idle.
+    fn update_interval(&mut self, had_data: bool) {
+        self.interval = if had_data {
+            self.min_interval
+        } else {
+            (self.interval * 2).clamp(self.min_interval, self.max_interval)
+        };
+    }
+
+    /// Moves the poller and `channels` to a background thread, which polls the channels of core
+    /// `core_index` until the returned [`RttPollerThread`] is stopped or dropped.
+    ///
+    /// The session is only locked while polling, so it can be used by other threads in between.
+    pub fn spawn(
+        mut self,
+        session: Arc<Mutex<Session>>,
+        core_index: usize,
+        channels: Vec<UpChannel>,
+    ) -> RttPollerThread {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let channels = channels.iter().collect::<Vec<_>>();
+
+            while !thread_stop.load(Ordering::Relaxed) {
+                let mut disconnected = false;
+                {
+                    let mut session = session.lock().unwrap();
+                    let mut core = session.core(core_index)?;
+
+                    self.poll(&mut core, &channels, |channel, data| {
+                        disconnected |= sender
+                            .send(RttData {
+                                channel: channel.number(),
+                                data: data.to_vec(),
+                            })
+                            .is_err();
+                    })?;
+                }
+
+                if disconnected {
+                    break;
+                }
+
+                std::thread::sleep(self.next_poll_delay());
+            }
+
+            Ok(())
+        });
+
+        RttPollerThread {
+            receiver,
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// Data read from an up channel by an [`RttPollerThread`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RttData {
+    /// The number of the up channel.
+    pub channel: usize,
+    /// The data read from the channel.
+    pub data: Vec<u8>,
+}
+
+/// A background thread polling RTT up channels, created by [`RttPoller::spawn`].
+///
+/// The thread stops when this is dropped.
+#[derive(Debug)]
+pub struct RttPollerThread {
+    receiver: Receiver<RttData>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Result<(), Error>>>,
+}
+
+impl RttPollerThread {
+    /// Returns the receiver for the data read by the thread.
+    ///
+    /// The receiver disconnects when the thread stops, e.g. after an error.
+    pub fn receiver(&self) -> &Receiver<RttData> {
+        &self.receiver
+    }
+
+    /// Stops the thread, and returns the error which stopped it early, if any.
+    pub fn stop(mut self) -> Result<(), Error> {
+        self.stop.store(true, Ordering::Relaxed);
+
+        match self.handle.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(panic)) => std::panic::resume_unwind(panic),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RttPollerThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::probe::fake_probe::FakeProbe;
+    use crate::rtt::channel::Channel;
+    use crate::rtt::{Rtt, ScanRegion};
+    use crate::test::MockMemory;
+    use crate::Permissions;
+
+    #[test]
+    fn poll_interval_backs_off_while_idle() {
+        let mut poller =
+            RttPoller::with_poll_interval(Duration::from_millis(1), Duration::from_millis(10));
+        assert_eq!(poller.next_poll_delay(), Duration::from_millis(1));
+
+        let delays = (0..5)
+            .map(|_| {
+                poller.update_interval(false);
+                poller.next_poll_delay().as_millis()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [2, 4, 8, 10, 10]);
+
+        poller.update_interval(true);
+        assert_eq!(poller.next_poll_delay(), Duration::from_millis(1));
+    }
+
+    /// Adds the header of a 64 byte channel at `address`, and returns the channel.
+    fn add_channel(memory: &mut MockMemory, number: usize, address: u64, write: u32) -> UpChannel {
+        let buffer = 0x2001_0000 + number as u32 * 0x40;
+        memory.add_word_range(address, &[0, buffer, 0x40, write, number as u32, 0]);
+
+        UpChannel(Channel::for_test(number, address, u64::from(buffer), 0x40))
+    }
+
+    fn snapshot_matches_separate_reads(second_channel: u64) {
+        let mut memory = MockMemory::new();
+        let channels = [
+            add_channel(&mut memory, 0, 0x2000_0018, 10),
+            add_channel(&mut memory, 1, second_channel, 20),
+        ];
+        let channels = channels.iter().collect::<Vec<_>>();
+
+        let separate = channels
+            .iter()
+            .map(|channel| channel.0.read_pointers(&mut memory, "up").unwrap())
+            .collect::<Vec<_>>();
+        let snapshot = RttPoller::new()
+            .snapshot_pointers(&mut memory, &channels)
+            .unwrap();
+
+        assert_eq!(snapshot, separate);
+        assert_eq!(snapshot, [(10, 0), (20, 1)]);
+    }
+
+    #[test]
+    fn batched_snapshot_matches_separate_reads() {
+        snapshot_matches_separate_reads(0x2000_0030);
+    }
+
+    #[test]
+    fn distant_channels_are_read_separately() {
+        snapshot_matches_separate_reads(0x2000_0018 + 2 * MAX_SNAPSHOT_SIZE);
+    }
+
+    #[test]
+    fn thread_delivers_data() {
+        let probe = FakeProbe::with_mocked_core().into_probe();
+        let mut session = probe
+            .attach("nrf51822_xxAC", Permissions::default())
+            .unwrap();
+
+        // A control block with one up channel, whose buffer contains "hello".
+        let mut control_block = b"SEGGER RTT\0\0\0\0\0\0".to_vec();
+        control_block.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        // Up channel: name, buffer, size, write, read, flags
+        for word in [0u32, 0x2000_0100, 0x40, 5, 0, 0] {
+            control_block.extend_from_slice(&word.to_le_bytes());
+        }
+
+        let channel = {
+            let mut core = session.core(0).unwrap();
+            core.write_8(0x2000_0000, &control_block).unwrap();
+            core.write_8(0x2000_0100, b"hello").unwrap();
+
+            let mut rtt =
+                Rtt::attach_region(&mut core, &[], &ScanRegion::Exact(0x2000_0000)).unwrap();
+            rtt.up_channels().take(0).unwrap()
+        };
+
+        let session = Arc::new(Mutex::new(session));
+        let thread = RttPoller::new().spawn(session.clone(), 0, vec![channel]);
+
+        let data = thread
+            .receiver()
+            .recv_timeout(Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(
+            data,
+            RttData {
+                channel: 0,
+                data: b"hello".to_vec(),
+            }
+        );
+        thread.stop().unwrap();
+
+        // The data was consumed, i.e. the read offset moved to the write offset.
+        let mut session = session.lock().unwrap();
+        let mut core = session.core(0).unwrap();
+        assert_eq!(core.read_word_32(0x2000_0028).unwrap(), 5);
+    }
+}
@@ -1,6 +1,8 @@
-use crate::architecture::arm::ap::AccessPort;
+use crate::architecture::arm::ap::{AccessPort, MemoryAp};
 use crate::architecture::arm::component::get_arm_components;
-use crate::architecture::arm::sequences::{ArmDebugSequence, DefaultArmSequence};
+use crate::architecture::arm::sequences::{
+    ArmDebugSequence, DefaultArmSequence, OptionBytesSequence, ProtectionState,
+};
 use crate::architecture::arm::{ArmError, DpAddress};
 use crate::architecture::riscv::communication_interface::RiscvError;
 use crate::architecture::xtensa::communication_interface::{
@@ -12,8 +14,10 @@ use crate::probe::fake_probe::FakeProbe;
 use crate::{
     architecture::{
         arm::{
-            communication_interface::ArmProbeInterface, component::TraceSink,
-            memory::CoresightComponent, SwoReader,
+            communication_interface::ArmProbeInterface,
+            component::{TraceSink, ITM_TRACE_ID},
+            memory::CoresightComponent,
+            SwoReader,
         },
         riscv::communication_interface::RiscvCommunicationInterface,
     },
@@ -24,6 +28,7 @@ use crate::{
     Core, CoreType, Error,
 };
 use anyhow::anyhow;
+use probe_rs_target::OptionBytesRegion;
 use std::ops::DerefMut;
 use std::{fmt, sync::Arc, time::Duration};
 
@@ -108,19 +113,7 @@ impl Session {
     ) -> Result<Self, Error> {
         let (probe, target) = get_target_from_selector(target, attach_method, probe)?;
 
-        let cores = target
-            .cores
-            .iter()
-            .enumerate()
-            .map(|(id, core)| {
-                Core::create_state(
-                    id,
-                    core.core_access_options.clone(),
-                    &target,
-                    core.core_type,
-                )
-            })
-            .collect();
+        let cores = Self::create_core_states(&target);
 
         let mut session = match target.architecture() {
             Architecture::Arm => {
@@ -139,6 +132,17 @@ impl Session {
         Ok(session)
     }
 
+    fn create_core_states(target: &Target) -> Vec<CombinedCoreState> {
+        target
+            .cores
+            .iter()
+            .enumerate()
+            .map(|(id, core)| {
+                Core::create_state(id, core.core_access_options.clone(), target, core.core_type)
+            })
+            .collect()
+    }
+
     fn attach_arm(
         mut probe: Probe,
         target: Target,
@@ -146,15 +150,7 @@ impl Session {
         permissions: Permissions,
         cores: Vec<CombinedCoreState>,
     ) -> Result<Self, Error> {
-        let default_core = target.default_core();
-
-        let default_memory_ap = default_core.memory_ap().ok_or_else(|| {
-            Error::Other(anyhow::anyhow!(
-                "Unable to connect to core {default_core:?}, no memory AP configured"
-            ))
-        })?;
-
-        let default_dp = default_memory_ap.ap_address().dp;
+        let default_memory_ap = default_arm_memory_ap(&target)?;
 
         let sequence_handle = match &target.debug_sequence {
             DebugSequence::Arm(sequence) => sequence.clone(),
@@ -177,6 +173,31 @@ impl Session {
             }
         }
 
+        let interface = Self::initialize_arm_interface(
+            probe,
+            &target,
+            &sequence_handle,
+            default_memory_ap.ap_address().dp,
+        )?;
+
+        Self::attach_arm_interface(
+            interface,
+            target,
+            sequence_handle,
+            default_memory_ap,
+            attach_method,
+            permissions,
+            cores,
+        )
+    }
+
+    /// Connects to the debug port `dp` of `target`, without accessing any access port or core.
+    fn initialize_arm_interface(
+        mut probe: Probe,
+        target: &Target,
+        sequence_handle: &Arc<dyn ArmDebugSequence>,
+        dp: DpAddress,
+    ) -> Result<Box<dyn ArmProbeInterface>, Error> {
         if let Some(jtag) = target.jtag.as_ref() {
             if let Some(scan_chain) = jtag.scan_chain.clone() {
                 probe.set_scan_chain(scan_chain)?;
@@ -186,9 +207,23 @@ impl Session {
 
         let interface = probe.try_into_arm_interface().map_err(|(_, err)| err)?;
 
-        let mut interface = interface
-            .initialize(sequence_handle.clone(), default_dp)
+        let interface = interface
+            .initialize(sequence_handle.clone(), dp)
             .map_err(|(_interface, e)| e)?;
+
+        Ok(interface)
+    }
+
+    /// Unlocks the device behind the initialized `interface` and enables debugging of its cores.
+    fn attach_arm_interface(
+        mut interface: Box<dyn ArmProbeInterface>,
+        target: Target,
+        sequence_handle: Arc<dyn ArmDebugSequence>,
+        default_memory_ap: MemoryAp,
+        attach_method: AttachMethod,
+        permissions: Permissions,
+        cores: Vec<CombinedCoreState>,
+    ) -> Result<Self, Error> {
         let unlock_span = tracing::debug_span!("debug_device_unlock").entered();
 
         // Enable debug mode
@@ -423,6 +458,15 @@ impl Session {
     /// return [ArmError::ArchitectureRequired] otherwise.
     #[tracing::instrument(skip(self))]
     pub fn read_trace_data(&mut self) -> Result<Vec<u8>, ArmError> {
+        self.read_trace_source(ITM_TRACE_ID)
+    }
+
+    /// Read available trace data of the trace source with the trace bus ID `source`, e.g. an ETM.
+    ///
+    /// For the SWO sink, which only carries ITM data, `source` is ignored. This method is only
+    /// supported for ARM-based targets, and will return [ArmError::ArchitectureRequired] otherwise.
+    #[tracing::instrument(skip(self))]
+    pub fn read_trace_source(&mut self, source: u8) -> Result<Vec<u8>, ArmError> {
         let sink = self
             .configured_trace_sink
             .as_ref()
@@ -441,7 +485,11 @@ impl Session {
             TraceSink::TraceMemory => {
                 let components = self.get_arm_components(DpAddress::Default)?;
                 let interface = self.get_arm_interface()?;
-                crate::architecture::arm::component::read_trace_memory(interface, &components)
+                crate::architecture::arm::component::read_trace_memory(
+                    interface,
+                    &components,
+                    source,
+                )
             }
         }
     }
@@ -581,6 +629,283 @@ impl Session {
         Ok(())
     }
 
+    /// Check if the connected device has an option bytes sequence defined
+    pub fn has_sequence_option_bytes(&self) -> bool {
+        match &self.target.debug_sequence {
+            DebugSequence::Arm(seq) => seq.option_bytes_sequence().is_some(),
+            // Currently, option_bytes_sequence is ARM specific
+            _ => false,
+        }
+    }
+
+    /// Read an option bytes region using the Device's Option Bytes Sequence
+    ///
+    /// # Errors
+    /// NotImplemented if no option bytes sequence exists
+    pub(crate) fn sequence_read_option_bytes(
+        &mut self,
+        region: &OptionBytesRegion,
+        data: &mut [u8],
+    ) -> Result<(), Error> {
+        let option_bytes_sequence = self.option_bytes_sequence()?;
+        let memory_ap = self.default_memory_ap()?;
+
+        let interface = self.get_arm_interface()?;
+        let mut memory = interface.memory_interface(memory_ap)?;
+
+        option_bytes_sequence.read_option_bytes(&mut *memory, region, data)?;
+
+        Ok(())
+    }
+
+    /// Program an option bytes region using the Device's Option Bytes Sequence
+    ///
+    /// All cores are halted while the region is programmed. If the device resets
+    /// to load the new option bytes, the probe is re-attached.
+    ///
+    /// # Errors
+    /// NotImplemented if no option bytes sequence exists
+    /// Err(e) if the option bytes sequence failed
+    pub(crate) fn sequence_write_option_bytes(
+        &mut self,
+        region: &OptionBytesRegion,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let option_bytes_sequence = self.option_bytes_sequence()?;
+        let memory_ap = self.default_memory_ap()?;
+
+        self.halted_access(|session| {
+            let ArchitectureInterface::Arm(ref mut interface) = session.interface else {
+                unreachable!("This should never happen. Please file a bug if it does.");
+            };
+
+            let DebugSequence::Arm(ref debug_sequence) = session.target.debug_sequence else {
+                unreachable!("This should never happen. Please file a bug if it does.");
+            };
+
+            let write_result = {
+                let mut memory = interface.memory_interface(memory_ap)?;
+                option_bytes_sequence.write_option_bytes(&mut *memory, region, data)
+            };
+
+            match write_result {
+                Ok(()) => Ok(()),
+                // The device was reset to load the new option bytes. Try to re-attach the probe once.
+                Err(ArmError::ReAttachRequired) => {
+                    Self::reattach_arm_interface(interface, debug_sequence)?;
+                    // For re-setup debugging on all cores
+                    for core_state in &session.cores {
+                        core_state.enable_arm_debug(interface.deref_mut())?;
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(Error::Arm(e)),
+            }
+        })
+    }
+
+    /// Enable the readout protection of the device using the Device's Debug Sequence
+    ///
+    /// If `permanent` is set, the strongest protection level of the device is enabled,
+    /// which can not be removed anymore.
+    ///
+    /// # Errors
+    /// NotImplemented if the device does not support readout protection
+    /// MissingPermissions if `permissions` does not allow locking the device
+    pub fn lock_device(&mut self, permanent: bool, permissions: &Permissions) -> Result<(), Error> {
+        tracing::info!("Enabling readout protection");
+        self.sequence_protection(|sequence, interface, memory_ap| {
+            sequence.lock(interface, memory_ap, permanent, permissions)
+        })
+    }
+
+    /// Enable the readout protection of a device, unless it is already protected.
+    ///
+    /// The state is queried before attaching to the device, so that a protected device is not
+    /// unlocked as it would be by [`Session::new`]. Returns the protection state after locking.
+    ///
+    /// # Errors
+    /// NotImplemented if the device does not support readout protection
+    /// MissingPermissions if `permissions` does not allow locking the device
+    pub(crate) fn attach_and_lock(
+        probe: Probe,
+        target: TargetSelector,
+        permanent: bool,
+        permissions: &Permissions,
+    ) -> Result<ProtectionState, Error> {
+        let UnattachedArm {
+            target,
+            sequence,
+            memory_ap,
+            mut interface,
+        } = Self::before_arm_attach(probe, target)?;
+
+        let state = sequence.protection_state(interface.deref_mut(), memory_ap)?;
+        if state == ProtectionState::PermanentlyProtected
+            || (state == ProtectionState::Protected && !permanent)
+        {
+            return Ok(state);
+        }
+
+        let cores = Self::create_core_states(&target);
+        let mut session = Self::attach_arm_interface(
+            interface,
+            target,
+            sequence,
+            memory_ap,
+            AttachMethod::Normal,
+            permissions.clone(),
+            cores,
+        )?;
+        session.lock_device(permanent, permissions)?;
+
+        let ArchitectureInterface::Arm(ref mut interface) = session.interface else {
+            unreachable!("This should never happen. Please file a bug if it does.");
+        };
+        let DebugSequence::Arm(ref sequence) = session.target.debug_sequence else {
+            unreachable!("This should never happen. Please file a bug if it does.");
+        };
+        Ok(sequence.protection_state(interface.deref_mut(), memory_ap)?)
+    }
+
+    /// Query the readout protection state of a device, without attaching to it.
+    ///
+    /// The state is read through the access ports only, e.g. the nRF CTRL-AP. No core is halted,
+    /// and a protected device is not unlocked as it would be by [`Session::new`].
+    ///
+    /// # Errors
+    /// NotImplemented if the device does not support readout protection
+    pub(crate) fn protection_state(
+        probe: Probe,
+        target: TargetSelector,
+    ) -> Result<ProtectionState, Error> {
+        let UnattachedArm {
+            sequence,
+            memory_ap,
+            mut interface,
+            ..
+        } = Self::before_arm_attach(probe, target)?;
+
+        Ok(sequence.protection_state(interface.deref_mut(), memory_ap)?)
+    }
+
+    /// Remove the readout protection of a device, without attaching to it.
+    ///
+    /// This erases the device. Like [`Session::protection_state`], the protection is removed
+    /// through the access ports, before any core is halted. Returns the protection state
+    /// after the unlock.
+    ///
+    /// # Errors
+    /// NotImplemented if the device does not support readout protection
+    /// MissingPermissions if `permissions` does not allow erasing the device
+    pub(crate) fn unlock_device(
+        probe: Probe,
+        target: TargetSelector,
+        permissions: &Permissions,
+    ) -> Result<ProtectionState, Error> {
+        tracing::info!("Removing readout protection");
+        let UnattachedArm {
+            sequence,
+            memory_ap,
+            mut interface,
+            ..
+        } = Self::before_arm_attach(probe, target)?;
+
+        match sequence.unlock(interface.deref_mut(), memory_ap, permissions) {
+            Ok(()) => (),
+            // The device was reset to apply the new protection state. Try to re-attach the probe once.
+            Err(ArmError::ReAttachRequired) => {
+                Self::reattach_arm_interface(&mut interface, &sequence)?;
+            }
+            Err(e) => return Err(Error::Arm(e)),
+        }
+
+        Ok(sequence.protection_state(interface.deref_mut(), memory_ap)?)
+    }
+
+    /// Connects to the debug port of the default core of `target`, like [`Session::new`], but
+    /// stops before the device is unlocked and debugging of the cores is enabled.
+    fn before_arm_attach(probe: Probe, target: TargetSelector) -> Result<UnattachedArm, Error> {
+        let (probe, target) = get_target_from_selector(target, AttachMethod::Normal, probe)?;
+
+        let DebugSequence::Arm(ref sequence) = target.debug_sequence else {
+            return Err(Error::Probe(DebugProbeError::NotImplemented(
+                "Readout Protection",
+            )));
+        };
+        let sequence = sequence.clone();
+        let memory_ap = default_arm_memory_ap(&target)?;
+
+        let interface =
+            Self::initialize_arm_interface(probe, &target, &sequence, memory_ap.ap_address().dp)?;
+
+        Ok(UnattachedArm {
+            target,
+            sequence,
+            memory_ap,
+            interface,
+        })
+    }
+
+    fn sequence_protection(
+        &mut self,
+        operation: impl FnOnce(
+            &dyn ArmDebugSequence,
+            &mut dyn ArmProbeInterface,
+            MemoryAp,
+        ) -> Result<(), ArmError>,
+    ) -> Result<(), Error> {
+        if !matches!(self.interface, ArchitectureInterface::Arm(_)) {
+            return Err(Error::Probe(DebugProbeError::NotImplemented(
+                "Readout Protection",
+            )));
+        }
+
+        let memory_ap = self.default_memory_ap()?;
+
+        self.halted_access(|session| {
+            let ArchitectureInterface::Arm(ref mut interface) = session.interface else {
+                unreachable!("This should never happen. Please file a bug if it does.");
+            };
+
+            let DebugSequence::Arm(ref debug_sequence) = session.target.debug_sequence else {
+                unreachable!("This should never happen. Please file a bug if it does.");
+            };
+
+            match operation(debug_sequence.as_ref(), interface.deref_mut(), memory_ap) {
+                Ok(()) => Ok(()),
+                // The device was reset to apply the new protection state. Try to re-attach the probe once.
+                Err(ArmError::ReAttachRequired) => {
+                    Self::reattach_arm_interface(interface, debug_sequence)?;
+                    // For re-setup debugging on all cores
+                    for core_state in &session.cores {
+                        core_state.enable_arm_debug(interface.deref_mut())?;
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(Error::Arm(e)),
+            }
+        })
+    }
+
+    fn option_bytes_sequence(&self) -> Result<Arc<dyn OptionBytesSequence>, Error> {
+        let DebugSequence::Arm(ref debug_sequence) = self.target.debug_sequence else {
+            return Err(Error::Probe(DebugProbeError::NotImplemented(
+                "Option Bytes Sequence",
+            )));
+        };
+
+        debug_sequence
+            .option_bytes_sequence()
+            .ok_or(Error::Probe(DebugProbeError::NotImplemented(
+                "Option Bytes Sequence",
+            )))
+    }
+
+    fn default_memory_ap(&self) -> Result<MemoryAp, Error> {
+        default_arm_memory_ap(&self.target)
+    }
+
     /// Reads all the available ARM CoresightComponents of the currently attached target.
     ///
     /// This will recursively parse the Romtable of the attached target
@@ -712,6 +1037,26 @@ impl Drop for Session {
 /// If the selector is [TargetSelector::Unspecified], the target will be looked up in the registry.
 /// If it its [TargetSelector::Auto], probe-rs will try to determine the target automatically, based on
 /// information read from the chip.
+/// An ARM target whose debug port is connected, before the device is unlocked and debugging of
+/// the cores is enabled.
+struct UnattachedArm {
+    target: Target,
+    sequence: Arc<dyn ArmDebugSequence>,
+    memory_ap: MemoryAp,
+    interface: Box<dyn ArmProbeInterface>,
+}
+
+/// Returns the memory AP of the default core of `target`.
+fn default_arm_memory_ap(target: &Target) -> Result<MemoryAp, Error> {
+    let default_core = target.default_core();
+
+    default_core.memory_ap().ok_or_else(|| {
+        Error::Other(anyhow!(
+            "Unable to connect to core {default_core:?}, no memory AP configured"
+        ))
+    })
+}
+
 fn get_target_from_selector(
     target: TargetSelector,
     attach_method: AttachMethod,
@@ -814,6 +1159,10 @@ fn get_target_from_selector(
 pub struct Permissions {
     /// When set to true, all memory of the chip may be erased or reset to factory default
     erase_all: bool,
+    /// When set to true, writes which can not be undone (e.g. OTP, fuses) may be performed
+    irreversible_writes: bool,
+    /// When set to true, the readout protection of the chip may be enabled
+    device_lock: bool,
 }
 
 impl Permissions {
@@ -842,6 +1191,48 @@ impl Permissions {
             Err(MissingPermissions("erase_all".into()))
         }
     }
+
+    /// Allow the session to perform writes which can not be undone, such as programming
+    /// OTP memory, fuses or permanent readout protection levels.
+    ///
+    /// # Warning
+    /// This may permanently change the behaviour of the device, or even render it unusable for debugging.
+    #[must_use]
+    pub fn allow_irreversible_writes(self) -> Self {
+        Self {
+            irreversible_writes: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn irreversible_writes(&self) -> Result<(), MissingPermissions> {
+        if self.irreversible_writes {
+            Ok(())
+        } else {
+            Err(MissingPermissions("irreversible_writes".into()))
+        }
+    }
+
+    /// Allow the session to enable the readout protection of the chip.
+    ///
+    /// # Warning
+    /// A protected chip can usually only be unlocked by erasing it completely, and some
+    /// protection levels can not be removed at all.
+    #[must_use]
+    pub fn allow_device_lock(self) -> Self {
+        Self {
+            device_lock: true,
+            ..self
+        }
+    }
+
+    pub(crate) fn device_lock(&self) -> Result<(), MissingPermissions> {
+        if self.device_lock {
+            Ok(())
+        } else {
+            Err(MissingPermissions("device_lock".into()))
+        }
+    }
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
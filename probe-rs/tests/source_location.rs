@@ -112,3 +112,34 @@ fn find_non_existing_unit_by_path() {
         .get_breakpoint_location(&unit_path, 14, None)
         .is_err());
 }
+
+#[test]
+fn line_table_matches_source_locations() {
+    let di = DebugInfo::from_file("tests/probe-rs-debugger-test").unwrap();
+
+    let table = di.line_table();
+    assert!(table.windows(2).all(|w| w[0].0.start <= w[1].0.start));
+
+    for (addr, line, _) in TEST_DATA.iter() {
+        let (_, location) = table
+            .iter()
+            .find(|(range, _)| range.contains(addr))
+            .expect("Address missing from the line table.");
+
+        assert_eq!(location.line, Some(*line));
+        assert_eq!(Some(location), di.get_source_location(*addr).as_ref());
+    }
+}
+
+#[test]
+fn static_variable_address() {
+    let di = DebugInfo::from_file("tests/probe-rs-debugger-test").unwrap();
+
+    assert_eq!(di.static_variable("U32"), Some((0x2000_0020, Some(4))));
+    assert_eq!(
+        di.static_variable("probe_rs_debugger_test::I16"),
+        Some((0x2000_000A, Some(2)))
+    );
+    assert_eq!(di.static_variable("other_crate::I16"), None);
+    assert_eq!(di.static_variable("DOES_NOT_EXIST"), None);
+}
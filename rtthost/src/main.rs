@@ -49,7 +49,7 @@ fn parse_scan_region(
         .collect::<Result<Vec<_>, _>>()?;
 
     match *parts.as_slice() {
-        [addr] => Ok(ScanRegion::Exact(addr)),
+        [addr] => Ok(ScanRegion::Exact(addr.into())),
         [start, end] => Ok(ScanRegion::Range(start..end)),
         _ => Err("Invalid range: multiple '..'s".into()),
     }
@@ -108,6 +108,8 @@ pub fn cmd_elf(
                 rtt_scan_ranges: None,
                 jtag: None,
                 default_binary_format: None,
+                option_bytes: vec![],
+                spi_flash: vec![],
             }],
             flash_algorithms: vec![algorithm],
             source: BuiltIn,
@@ -6,7 +6,8 @@ use std::time::Instant;
 
 use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
-use probe_rs::flashing::erase_all;
+use probe_rs::debug::DebugInfo;
+use probe_rs::flashing::{erase_all, FlashAlgorithmReport, FlashError};
 use probe_rs::MemoryInterface;
 use probe_rs::{
     flashing::{erase_sectors, DownloadOptions, FlashLoader, FlashProgress},
@@ -99,6 +100,23 @@ pub fn cmd_test(
         }
     });
 
+    let result = run_tests(&mut session, progress, test_start_sector_address);
+
+    if let Err(error) = &result {
+        if let Some(report) = algorithm_report(error) {
+            print_algorithm_report(&mut session, report, target_artifact)?;
+        }
+    }
+
+    result
+}
+
+/// Erases and programs the flash with the test algorithm in all supported ways.
+fn run_tests(
+    session: &mut Session,
+    progress: FlashProgress,
+    test_start_sector_address: Option<u64>,
+) -> Result<()> {
     let flash_algorithm = if let Some(test_start_sector_address) = test_start_sector_address {
         let predicate = |x: &&RawFlashAlgorithm| {
             x.flash_properties.address_range.start <= test_start_sector_address
@@ -136,7 +154,7 @@ pub fn cmd_test(
     let test = "Test".green();
     println!("{test}: Erasing sectorwise and writing two pages ...");
     run_flash_erase(
-        &mut session,
+        session,
         progress.clone(),
         EraseSectors(test_start_sector_index, 2),
     )?;
@@ -152,7 +170,7 @@ pub fn cmd_test(
     let mut loader = session.target().flash_loader();
     let data = (0..data_size).map(|n| (n % 256) as u8).collect::<Vec<_>>();
     loader.add_data(test_start_sector_address + 1, &data)?;
-    run_flash_download(&mut session, loader, progress.clone(), true)?;
+    run_flash_download(session, loader, progress.clone(), true)?;
     let mut readback = vec![0; data_size as usize];
     session
         .core(0)?
@@ -160,7 +178,7 @@ pub fn cmd_test(
     assert_eq!(readback, data);
 
     println!("{test}: Erasing the entire chip and writing two pages ...");
-    run_flash_erase(&mut session, progress.clone(), EraseAll)?;
+    run_flash_erase(session, progress.clone(), EraseAll)?;
     let mut readback = vec![0; (sector_size * 2) as usize];
     session
         .core(0)?
@@ -173,7 +191,7 @@ pub fn cmd_test(
     let mut loader = session.target().flash_loader();
     let data = (0..data_size).map(|n| (n % 256) as u8).collect::<Vec<_>>();
     loader.add_data(test_start_sector_address + 1, &data)?;
-    run_flash_download(&mut session, loader, progress.clone(), true)?;
+    run_flash_download(session, loader, progress.clone(), true)?;
     let mut readback = vec![0; data_size as usize];
     session
         .core(0)?
@@ -182,7 +200,7 @@ pub fn cmd_test(
 
     println!("{test}: Erasing sectorwise and writing two pages double buffered ...");
     run_flash_erase(
-        &mut session,
+        session,
         progress.clone(),
         EraseSectors(test_start_sector_index, 2),
     )?;
@@ -198,7 +216,7 @@ pub fn cmd_test(
     let mut loader = session.target().flash_loader();
     let data = (0..data_size).map(|n| (n % 256) as u8).collect::<Vec<_>>();
     loader.add_data(test_start_sector_address + 1, &data)?;
-    run_flash_download(&mut session, loader, progress, false)?;
+    run_flash_download(session, loader, progress, false)?;
     let mut readback = vec![0; data_size as usize];
     session
         .core(0)?
@@ -208,6 +226,38 @@ pub fn cmd_test(
     Ok(())
 }
 
+/// Returns the report of the flash algorithm, if it caused `error`.
+fn algorithm_report(error: &anyhow::Error) -> Option<&FlashAlgorithmReport> {
+    error
+        .chain()
+        .find_map(|cause| match cause.downcast_ref::<FlashError>() {
+            Some(FlashError::AlgorithmFailed { report, .. }) => Some(report.as_ref()),
+            _ => None,
+        })
+}
+
+/// Prints the state of the flash algorithm when it failed, and unwinds its stack using the debug
+/// info of `target_artifact`, if the algorithm did not return.
+fn print_algorithm_report(
+    session: &mut Session,
+    report: &FlashAlgorithmReport,
+    target_artifact: &Path,
+) -> Result<()> {
+    let mut report = report.clone();
+    if report.pc.is_some() {
+        match DebugInfo::from_file(target_artifact) {
+            Ok(debug_info) => report.unwind(&mut session.core(0)?, &debug_info)?,
+            Err(error) => {
+                println!("Failed to read the debug info of the flash algorithm: {error}")
+            }
+        }
+    }
+
+    println!("{}: {report}", "Flash algorithm failed".red());
+
+    Ok(())
+}
+
 fn ensure_is_file(file_path: &Path) -> Result<()> {
     anyhow::ensure!(
         file_path.is_file(),
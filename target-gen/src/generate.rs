@@ -166,6 +166,8 @@ where
             rtt_scan_ranges: None,
             jtag: None, // TODO, parse scan chain from sdf
             default_binary_format: None,
+            option_bytes: vec![],
+            spi_flash: vec![],
         });
     }
 